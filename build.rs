@@ -11,6 +11,79 @@ const COMMANDS: &[&str] = &[
     "close_connection",
     "get_connection_state",
     "start_camera_webrtc_session",
+    "set_focus_point",
+    "set_white_balance",
+    "get_white_balance",
+    "get_stream_stats",
+    "save_profile",
+    "list_profiles",
+    "delete_profile",
+    "apply_profile",
+    "export_config",
+    "import_config",
+    "run_diagnostics",
+    "start_native_preview",
+    "set_native_preview_rect",
+    "stop_native_preview",
+    "open_pip_preview",
+    "close_preview_window",
+    "open_overlay_preview",
+    "set_overlay_options",
+    "get_monitors",
+    "start_kiosk_preview",
+    "exit_kiosk_preview",
+    "start_pipeline",
+    "attach_sink",
+    "detach_sink",
+    "reattach_stream",
+    "get_or_start_stream",
+    "get_resource_usage",
+    "start_frame_dump",
+    "stop_frame_dump",
+    "replay_frame_dump",
+    "inject_faults",
+    "get_camera_access",
+    "reset_camera_permission",
+    "add_screen_sources",
+    "set_event_log",
+    "probe_bandwidth",
+    "capture_photo",
+    "get_device_capabilities",
+    "pause_streaming",
+    "resume_streaming",
+    "capture_burst",
+    "start_timelapse",
+    "stop_timelapse",
+    "capture_frame_from_stream",
+    "get_default_camera",
+    "set_default_camera",
+    "get_cameras_by_facing",
+    "switch_stream_device",
+    "capture_photo_with_timer",
+    "set_exposure",
+    "get_exposure",
+    "set_focus_mode",
+    "set_focus_distance",
+    "trigger_autofocus",
+    "set_zoom",
+    "get_zoom_range",
+    "set_torch",
+    "get_camera_controls",
+    "set_camera_control",
+    "lock_auto_controls",
+    "unlock_auto_controls",
+    "save_control_profile",
+    "apply_control_profile",
+    "start_recording",
+    "stop_recording",
+    "attach_recorder",
+    "pause_recording",
+    "resume_recording",
+    "list_microphones",
+    "enable_preroll",
+    "disable_preroll",
+    "start_recording_with_preroll",
+    "request_keyframe",
 ];
 
 fn main() {
@@ -0,0 +1,134 @@
+import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { CropRect } from './streaming'
+
+// Types mirrored from Rust (src/models.rs)
+export interface FocusLockedEvent {
+  deviceId: string
+  x: number
+  y: number
+  locked: boolean
+}
+
+/** Focus on a point of the preview. `x` and `y` are normalized (0..1, origin top-left). Mobile only. */
+export async function setFocusPoint(deviceId: string, x: number, y: number): Promise<void> {
+  await invoke('plugin:camera|set_focus_point', { deviceId, x, y })
+}
+
+export async function onFocusLocked(handler: (event: FocusLockedEvent) => void): Promise<UnlistenFn> {
+  return listen<FocusLockedEvent>('camera://focus-locked', (event) => handler(event.payload))
+}
+
+/** `auto` focuses when `triggerAutofocus` is called, then holds. */
+export type FocusMode = 'auto' | 'manual' | 'continuous'
+
+export async function setFocusMode(deviceId: string, mode: FocusMode): Promise<void> {
+  await invoke('plugin:camera|set_focus_mode', { deviceId, mode })
+}
+
+/** Focus manually, from 0 (infinity) to 1 (closest). */
+export async function setFocusDistance(deviceId: string, distance: number): Promise<void> {
+  await invoke('plugin:camera|set_focus_distance', { deviceId, distance })
+}
+
+/**
+ * Run one autofocus pass and hold the result, on a normalized region of the preview (mobile only) or the whole
+ * frame. `onFocusLocked` reports when focus settles.
+ */
+export async function triggerAutofocus(deviceId: string, roi?: CropRect): Promise<void> {
+  await invoke('plugin:camera|trigger_autofocus', { deviceId, roi })
+}
+
+/** Automatic adjustments: exposure (`ae`), focus (`af`) and white balance (`awb`). */
+export interface AutoControls {
+  ae?: boolean
+  af?: boolean
+  awb?: boolean
+}
+
+/** Freeze the selected automatic adjustments at their current values ("long press to lock"). */
+export async function lockAutoControls(deviceId: string, controls: AutoControls): Promise<void> {
+  await invoke('plugin:camera|lock_auto_controls', { deviceId, controls })
+}
+
+/** Resume the selected automatic adjustments. */
+export async function unlockAutoControls(deviceId: string, controls: AutoControls): Promise<void> {
+  await invoke('plugin:camera|unlock_auto_controls', { deviceId, controls })
+}
+
+export type CameraControl = 'brightness' | 'contrast' | 'saturation' | 'sharpness' | 'gain' | 'gamma'
+
+export interface CameraControlInfo {
+  control: CameraControl
+  value: number
+  min: number
+  max: number
+  step: number
+  default: number
+}
+
+/** Image controls the device supports, with their current value and range. */
+export async function getCameraControls(deviceId: string): Promise<CameraControlInfo[]> {
+  return invoke<CameraControlInfo[]>('plugin:camera|get_camera_controls', { deviceId })
+}
+
+/** Set one image control, within the range reported by `getCameraControls`. */
+export async function setCameraControl(deviceId: string, control: CameraControl, value: number): Promise<void> {
+  await invoke('plugin:camera|set_camera_control', { deviceId, control, value })
+}
+
+/** Turn the torch (continuous flash) on or off. Mobile only: desktop rejects it with an `Unsupported` error. */
+export async function setTorch(deviceId: string, on: boolean): Promise<void> {
+  await invoke('plugin:camera|set_torch', { deviceId, on })
+}
+
+export interface ZoomRange {
+  min: number
+  max: number
+  zoom: number
+  /** Frames are cropped and scaled instead of using the camera's own zoom */
+  digital: boolean
+}
+
+/** Zoom by `factor`, within `getZoomRange()`. */
+export async function setZoom(deviceId: string, factor: number): Promise<void> {
+  await invoke('plugin:camera|set_zoom', { deviceId, factor })
+}
+
+export async function getZoomRange(deviceId: string): Promise<ZoomRange> {
+  return invoke<ZoomRange>('plugin:camera|get_zoom_range', { deviceId })
+}
+
+export type WhiteBalance =
+  | { mode: 'auto' | 'daylight' | 'tungsten' | 'fluorescent' }
+  | { mode: 'manual'; kelvin: number }
+
+/** Apply a white balance preset or a manual color temperature (2000-10000K). */
+export async function setWhiteBalance(deviceId: string, whiteBalance: WhiteBalance): Promise<void> {
+  await invoke('plugin:camera|set_white_balance', { deviceId, whiteBalance })
+}
+
+export async function getWhiteBalance(deviceId: string): Promise<WhiteBalance> {
+  return invoke<WhiteBalance>('plugin:camera|get_white_balance', { deviceId })
+}
+
+export type ExposureMode = 'auto' | 'manual' | 'locked'
+
+export interface Exposure {
+  mode: ExposureMode
+  /** EV compensation in `auto` mode, exposure time in milliseconds otherwise */
+  value?: number | null
+}
+
+/**
+ * Set exposure: `auto` with an optional EV compensation (-3 to 3, mobile only), `manual` with an exposure time in
+ * milliseconds, or `locked` to hold the current exposure.
+ */
+export async function setExposure(deviceId: string, mode: ExposureMode, value?: number): Promise<void> {
+  await invoke('plugin:camera|set_exposure', { deviceId, mode, value })
+}
+
+/** The exposure applied to a device. On desktop, a locked exposure reads back as `manual`. */
+export async function getExposure(deviceId: string): Promise<Exposure> {
+  return invoke<Exposure>('plugin:camera|get_exposure', { deviceId })
+}
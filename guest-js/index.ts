@@ -1,5 +1,11 @@
 // Re-export everything from core
 export * from './core'
 export * from './webrtc'
+export * from './controls'
+export * from './streaming'
 
 
+export * from './profiles'
+export * from './recording'
+export * from './preview'
+export * from './pipeline'
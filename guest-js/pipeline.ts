@@ -0,0 +1,76 @@
+import { Channel, invoke } from '@tauri-apps/api/core'
+import { getStreamToken, setStreamToken, type FrameEvent, type StreamOptions } from './streaming'
+import type { OverlayOptions, PipOptions, PreviewRect } from './preview'
+import type { RecordingOptions } from './recording'
+
+// Types mirrored from Rust (src/models.rs)
+export type SinkDesc =
+  /** Frames delivered to the `onFrame` callback of `startPipeline` */
+  | { type: 'channel' }
+  | { type: 'webRtc'; connectionId: string }
+  /** Native preview behind a webview, the `main` window by default */
+  | { type: 'nativePreview'; windowLabel?: string; rect: PreviewRect }
+  | ({ type: 'pip' } & PipOptions)
+  | ({ type: 'overlay' } & OverlayOptions)
+  | { type: 'kiosk'; monitor?: string }
+  /** MP4 file inside the allowed output directories; `stopRecording(sinkId)` returns its summary */
+  | ({ type: 'recording'; path: string } & RecordingOptions)
+
+export interface PipelineDesc {
+  /** Device ID of a camera or of a frame source registered by the app */
+  source: string
+  /** Built-in processing; the options of the profile applied to the source by default */
+  options?: StreamOptions
+  /** Names of filters registered on the Rust side, applied in order */
+  filters?: string[]
+  sinks?: SinkDesc[]
+}
+
+export interface PipelineInfo {
+  streamId: string
+  /** Access token of the stream, also kept by this module */
+  token: string
+  /** ID of each sink, in order, for `detachSink` (connection, preview or window ID for those sinks) */
+  sinks: string[]
+}
+
+/**
+ * Start a stream from a pipeline description: source → [filters] → {sinks}.
+ * Stop it with `stopStreaming(info.streamId)`.
+ */
+export async function startPipeline(
+  pipeline: PipelineDesc,
+  onFrame?: (frame: FrameEvent) => void,
+  token?: string
+): Promise<PipelineInfo> {
+  let channel: Channel<FrameEvent> | undefined
+  if (onFrame) {
+    channel = new Channel<FrameEvent>()
+    channel.onmessage = onFrame
+  }
+  const info = await invoke<PipelineInfo>('plugin:camera|start_pipeline', { pipeline, onFrame: channel, token })
+  setStreamToken(info.streamId, info.token)
+  return info
+}
+
+/**
+ * Attach a sink to a running stream without interrupting the capture or the other sinks.
+ * `onFrame` is required for a `channel` sink. Returns the sink ID.
+ */
+export async function attachSink(
+  streamId: string,
+  sink: SinkDesc,
+  onFrame?: (frame: FrameEvent) => void
+): Promise<string> {
+  let channel: Channel<FrameEvent> | undefined
+  if (onFrame) {
+    channel = new Channel<FrameEvent>()
+    channel.onmessage = onFrame
+  }
+  return invoke<string>('plugin:camera|attach_sink', { streamId, sink, onFrame: channel, token: getStreamToken(streamId) })
+}
+
+/** Detach a sink from its stream; preview windows are closed, the stream keeps running. */
+export async function detachSink(streamId: string, sinkId: string): Promise<void> {
+  await invoke('plugin:camera|detach_sink', { streamId, sinkId, token: getStreamToken(streamId) })
+}
@@ -0,0 +1,104 @@
+import { invoke } from '@tauri-apps/api/core'
+import { getStreamToken } from './streaming'
+
+// Types mirrored from Rust (src/models.rs)
+/** Region of a window in physical pixels, from its top-left corner. */
+export interface PreviewRect {
+  x: number
+  y: number
+  width: number
+  height: number
+}
+
+/** Physical-pixel rect of an element, for placing a native preview over it. */
+export function elementPreviewRect(element: Element): PreviewRect {
+  const bounds = element.getBoundingClientRect()
+  const scale = window.devicePixelRatio
+  return {
+    x: Math.round(bounds.left * scale),
+    y: Math.round(bounds.top * scale),
+    width: Math.round(bounds.width * scale),
+    height: Math.round(bounds.height * scale)
+  }
+}
+
+/**
+ * Desktop only: render a stream directly into the native window behind the webview,
+ * without sending frames over IPC. The page must be transparent over `rect`
+ * (transparent window and no background behind that region).
+ */
+export async function startNativePreview(streamId: string, rect: PreviewRect, windowLabel?: string): Promise<string> {
+  return invoke<string>('plugin:camera|start_native_preview', { streamId, rect, windowLabel, token: getStreamToken(streamId) })
+}
+
+export async function setNativePreviewRect(previewId: string, rect: PreviewRect): Promise<void> {
+  await invoke('plugin:camera|set_native_preview_rect', { previewId, rect })
+}
+
+export async function stopNativePreview(previewId: string): Promise<void> {
+  await invoke('plugin:camera|stop_native_preview', { previewId })
+}
+
+export interface PipOptions {
+  /** Logical size, defaults to 320x180. */
+  size?: { width: number; height: number }
+  /** Logical position, defaults to the bottom-right corner of the primary monitor. */
+  position?: { x: number; y: number }
+  /** Defaults to `true`. */
+  alwaysOnTop?: boolean
+}
+
+/** Desktop only: show a stream in a small frameless window managed by the plugin. Returns the window ID. */
+export async function openPipPreview(streamId: string, options?: PipOptions): Promise<string> {
+  return invoke<string>('plugin:camera|open_pip_preview', { streamId, options, token: getStreamToken(streamId) })
+}
+
+/** Close a preview window opened by the plugin. */
+export async function closePreviewWindow(windowId: string): Promise<void> {
+  await invoke('plugin:camera|close_preview_window', { windowId })
+}
+
+export interface OverlayOptions {
+  /** Logical size, defaults to 480x270. */
+  size?: { width: number; height: number }
+  /** Logical position, centered by default. */
+  position?: { x: number; y: number }
+  /** Let mouse events pass through to the windows below. */
+  clickThrough?: boolean
+  /** From 0 (invisible) to 1 (opaque, the default). */
+  opacity?: number
+}
+
+/** Desktop only: show a stream in a borderless always-on-top window. Returns the window ID. */
+export async function openOverlayPreview(streamId: string, options?: OverlayOptions): Promise<string> {
+  return invoke<string>('plugin:camera|open_overlay_preview', { streamId, options, token: getStreamToken(streamId) })
+}
+
+/** Change the click-through and opacity of an overlay preview; omitted fields are kept. */
+export async function setOverlayOptions(
+  windowId: string,
+  options: Pick<OverlayOptions, 'clickThrough' | 'opacity'>
+): Promise<void> {
+  await invoke('plugin:camera|set_overlay_options', { windowId, options })
+}
+
+export interface MonitorInfo {
+  name: string | null
+  position: { x: number; y: number }
+  size: { width: number; height: number }
+  scaleFactor: number
+  primary: boolean
+}
+
+export async function getMonitors(): Promise<MonitorInfo[]> {
+  return invoke<MonitorInfo[]>('plugin:camera|get_monitors')
+}
+
+/** Desktop only: show a stream fullscreen on a monitor (by name, primary by default). Returns the window ID. */
+export async function startKioskPreview(streamId: string, monitor?: string): Promise<string> {
+  return invoke<string>('plugin:camera|start_kiosk_preview', { streamId, monitor, token: getStreamToken(streamId) })
+}
+
+export async function exitKioskPreview(windowId: string): Promise<void> {
+  await invoke('plugin:camera|exit_kiosk_preview', { windowId })
+}
@@ -0,0 +1,108 @@
+import { invoke } from '@tauri-apps/api/core'
+import type { CameraControl, Exposure, FocusMode, WhiteBalance } from './controls'
+import type { StreamOptions } from './streaming'
+
+// Types mirrored from Rust (src/models.rs)
+export interface ProfileFormat {
+  width: number
+  height: number
+  fps: number
+  format?: string | null
+}
+
+/** H.264 profile; `baseline` is decoded by every WebRTC peer. */
+export type H264Profile = 'baseline' | 'main' | 'high'
+
+export type H264Level =
+  | '1' | '1b' | '1.1' | '1.2' | '1.3'
+  | '2' | '2.1' | '2.2'
+  | '3' | '3.1' | '3.2'
+  | '4' | '4.1' | '4.2'
+  | '5' | '5.1' | '5.2'
+
+/**
+ * `quality` keeps the picture quality constant, `bitrate` holds the target bitrate, `buffer` holds it over
+ * a buffer window, `timestamp` paces it by frame timestamps (variable frame rates), `off` disables rate control.
+ */
+export type RateControl = 'quality' | 'bitrate' | 'buffer' | 'timestamp' | 'off'
+
+export interface EncoderSettings {
+  bitrateKbps?: number | null
+  /** Name of an encoder registered by the host app, the built-in OpenH264 encoder by default. */
+  encoder?: string | null
+  /** B-frames between reference frames; 0 by default for WebRTC sinks and `realtime` streams. */
+  bFrames?: number | null
+  /** Frames of rate control lookahead; 0 by default for WebRTC sinks and `realtime` streams. */
+  lookahead?: number | null
+  /** Slices per frame. */
+  slices?: number | null
+  /** Highest encoded frame rate, faster frames are skipped; the stream's `maxFps` by default. */
+  maxFps?: number | null
+  profile?: H264Profile | null
+  /** Derived from the frame size and rate by default. */
+  level?: H264Level | null
+  /** Frames between keyframes; two seconds of frames by default. */
+  keyframeInterval?: number | null
+  rateControl?: RateControl | null
+  /** AV1 speed preset, 0 (slowest, smallest files) to 10 (fastest); 10 by default. */
+  speedPreset?: number | null
+}
+
+/** Controls of a device, saved with `saveControlProfile`. */
+export interface ControlSettings {
+  exposure?: Exposure | null
+  whiteBalance?: WhiteBalance | null
+  focusMode?: FocusMode | null
+  /** Manual focus distance, 0.0 (infinity) to 1.0 (closest). */
+  focusDistance?: number | null
+  zoom?: number | null
+  image?: Partial<Record<CameraControl, number>>
+}
+
+export interface CameraProfile {
+  /** Capture format used instead of the recommended one. */
+  format?: ProfileFormat | null
+  whiteBalance?: WhiteBalance | null
+  /** Options of streams started without explicit options. */
+  stream?: StreamOptions
+  encoder?: EncoderSettings
+  controls?: ControlSettings | null
+}
+
+/** Save a named profile for a device, replacing any profile with the same name. */
+export async function saveProfile(deviceId: string, name: string, profile: CameraProfile): Promise<void> {
+  await invoke('plugin:camera|save_profile', { deviceId, name, profile })
+}
+
+export async function listProfiles(deviceId: string): Promise<Record<string, CameraProfile>> {
+  return invoke<Record<string, CameraProfile>>('plugin:camera|list_profiles', { deviceId })
+}
+
+export async function deleteProfile(deviceId: string, name: string): Promise<void> {
+  await invoke('plugin:camera|delete_profile', { deviceId, name })
+}
+
+/** Apply controls now; format, stream options and encoder settings are used by the next stream of the device. */
+export async function applyProfile(deviceId: string, name: string): Promise<void> {
+  await invoke('plugin:camera|apply_profile', { deviceId, name })
+}
+
+/** Save the controls currently applied to a device (exposure, white balance, focus, zoom, image controls) in a profile, creating it if needed. */
+export async function saveControlProfile(deviceId: string, name: string): Promise<void> {
+  await invoke('plugin:camera|save_control_profile', { deviceId, name })
+}
+
+/** Restore the controls saved with `saveControlProfile`. */
+export async function applyControlProfile(deviceId: string, name: string): Promise<void> {
+  await invoke('plugin:camera|apply_control_profile', { deviceId, name })
+}
+
+/** Export the plugin configuration (saved profiles) as a JSON document. */
+export async function exportConfig(): Promise<string> {
+  return invoke<string>('plugin:camera|export_config')
+}
+
+/** Import a configuration produced by `exportConfig`. Existing profiles are kept unless `replace` is set. */
+export async function importConfig(config: string, replace = false): Promise<void> {
+  await invoke('plugin:camera|import_config', { config, replace })
+}
@@ -0,0 +1,176 @@
+import { invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import { getStreamToken, type StreamOptions } from './streaming'
+
+// Types mirrored from Rust (src/models.rs)
+/** File format of a recording. WebM needs a VP8, VP9 or AV1 encoder; H.264 goes to MP4 or MKV. */
+export type RecordingContainer = 'mp4' | 'mkv' | 'webm'
+
+export interface RecordingOptions {
+  /** File format, `mp4` by default */
+  container?: RecordingContainer
+  /** Options of the stream started when none runs on the device, the applied profile's by default. Leave `colorSpace` unset. */
+  stream?: StreamOptions
+  /** Go on in a new file after this many seconds, e.g. for dashcam-style continuous capture. */
+  segmentDurationSecs?: number
+  /** Go on in a new file once this many bytes are written. */
+  segmentMaxBytes?: number
+  /** Record sound from a microphone along with the video (AAC, `mp4` or `mkv` only). */
+  audio?: AudioOptions
+  /** Write a JPEG thumbnail next to the recording when it stops (H.264 only). */
+  thumbnail?: ThumbnailOptions
+}
+
+export interface ThumbnailOptions {
+  /** Time in the recording to show, pauses left out; the latest keyframe up to it is used. The first keyframe by default. */
+  atMs?: number
+  /** Longest side of the thumbnail in pixels, 320 by default */
+  maxSize?: number
+}
+
+export interface AudioOptions {
+  /** Name of the microphone, as listed by `listMicrophones`; the default input device otherwise */
+  device?: string
+  /** AAC bitrate in bits per second, 128 kbps by default */
+  bitrate?: number
+}
+
+export interface RecordingSummary {
+  recordingId: string
+  /** The recorded file, the last segment of a segmented recording */
+  path: string
+  frames: number
+  durationMs: number
+  /** Files of a segmented recording, in order */
+  segments: string[]
+  /** JPEG thumbnail written next to the recording, when asked for */
+  thumbnail: string | null
+}
+
+/** Emitted on `camera://segment-finalized` once a segment of a recording is complete. */
+export interface SegmentFinalizedEvent {
+  recordingId: string
+  /** Number of the segment, from 0, as in its file name */
+  index: number
+  path: string
+  frames: number
+  durationMs: number
+}
+
+/**
+ * Desktop only: record a device to an MP4 file at `path` (inside the allowed output directories),
+ * starting a stream if none runs on it. Recording a device that is already streaming requires
+ * that stream's `token`. Returns the recording ID.
+ */
+export async function startRecording(
+  deviceId: string,
+  path: string,
+  options?: RecordingOptions,
+  token?: string
+): Promise<string> {
+  return invoke<string>('plugin:camera|start_recording', { deviceId, path, options, token })
+}
+
+/**
+ * Desktop only: record a running stream to an MP4 file at `path` without interrupting its preview or
+ * other sinks; the camera and the encoder are shared. Returns the recording ID, also its sink ID.
+ */
+export async function attachRecorder(streamId: string, path: string, options?: RecordingOptions): Promise<string> {
+  return invoke<string>('plugin:camera|attach_recorder', {
+    streamId,
+    path,
+    options,
+    token: getStreamToken(streamId)
+  })
+}
+
+/**
+ * Desktop only: keep the last `seconds` (up to 60) of a stream's encoded frames in memory, so
+ * `startRecordingWithPreroll` can include what happened before it was called. The stream is
+ * encoded continuously while enabled.
+ */
+export async function enablePreroll(streamId: string, seconds: number): Promise<void> {
+  await invoke('plugin:camera|enable_preroll', { streamId, seconds, token: getStreamToken(streamId) })
+}
+
+/** Stop keeping the pre-roll of a stream, releasing its frames. */
+export async function disablePreroll(streamId: string): Promise<void> {
+  await invoke('plugin:camera|disable_preroll', { streamId, token: getStreamToken(streamId) })
+}
+
+/**
+ * Desktop only: record a running stream like `attachRecorder`, starting `prerollSecs` before the
+ * call with the frames kept since `enablePreroll`. Returns the recording ID.
+ */
+export async function startRecordingWithPreroll(
+  streamId: string,
+  prerollSecs: number,
+  path: string,
+  options?: RecordingOptions
+): Promise<string> {
+  return invoke<string>('plugin:camera|start_recording_with_preroll', {
+    streamId,
+    prerollSecs,
+    path,
+    options,
+    token: getStreamToken(streamId)
+  })
+}
+
+/** Pause a recording, keeping its file open. */
+export async function pauseRecording(recordingId: string): Promise<void> {
+  await invoke('plugin:camera|pause_recording', { recordingId })
+}
+
+/** Resume a paused recording in the same file; playback goes straight on from the frame before the pause. */
+export async function resumeRecording(recordingId: string): Promise<void> {
+  await invoke('plugin:camera|resume_recording', { recordingId })
+}
+
+/** Names of the microphones recordings can take sound from. */
+export async function listMicrophones(): Promise<string[]> {
+  return invoke<string[]>('plugin:camera|list_microphones')
+}
+
+/** Stop a recording once its file is finalized. */
+export async function stopRecording(recordingId: string): Promise<RecordingSummary> {
+  return invoke<RecordingSummary>('plugin:camera|stop_recording', { recordingId })
+}
+
+/** Emitted on `camera://recording-progress` every second while a recording runs. */
+export interface RecordingProgressEvent {
+  recordingId: string
+  /** Recorded time, pauses left out, across all segments */
+  elapsedMs: number
+  /** Bytes of the recorded frames and sound so far, across all segments */
+  bytes: number
+  frames: number
+  /** Frames lost because the recorder fell behind, and left out until the next keyframe after a gap or a resume */
+  droppedFrames: number
+  /** Bits per second written since the previous event */
+  bitrate: number
+  paused: boolean
+}
+
+export async function onRecordingProgress(handler: (event: RecordingProgressEvent) => void): Promise<UnlistenFn> {
+  return listen<RecordingProgressEvent>('camera://recording-progress', (event) => handler(event.payload))
+}
+
+/** Why a recording ended without `stopRecording` */
+export type RecordingStopReason = 'disk_full' | 'stream_ended' | 'frame_size_changed' | 'failed'
+
+/** Emitted on `camera://recording-stopped` once the file of a recording that ended on its own is complete. */
+export interface RecordingStoppedEvent {
+  recordingId: string
+  reason: RecordingStopReason
+  /** What failed, for `failed` and for a write that hit a full disk */
+  error: string | null
+}
+
+export async function onRecordingStopped(handler: (event: RecordingStoppedEvent) => void): Promise<UnlistenFn> {
+  return listen<RecordingStoppedEvent>('camera://recording-stopped', (event) => handler(event.payload))
+}
+
+export async function onSegmentFinalized(handler: (event: SegmentFinalizedEvent) => void): Promise<UnlistenFn> {
+  return listen<SegmentFinalizedEvent>('camera://segment-finalized', (event) => handler(event.payload))
+}
@@ -0,0 +1,286 @@
+import { Channel, invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { EncoderSettings } from './profiles'
+import type { Photo, PhotoOptions } from './webrtc'
+
+// Types mirrored from Rust (src/models.rs)
+export type ColorSpace = 'bt601' | 'bt709' | 'bt2020' | 'srgb' | 'displayP3'
+
+export type HdrTransfer = 'hlg' | 'pq'
+
+/** `low` captures at 15fps or less, skips gamut mapping and deduplicates static frames. */
+export type PowerMode = 'normal' | 'low'
+
+/**
+ * `realtime` tunes the encoder for zero latency, keeps no frames queued and lets a lagging
+ * WebRTC sink jump to the newest frame; `quality` spends encoder time on the picture and
+ * queues frames so recordings don't skip any.
+ */
+export type LatencyMode = 'realtime' | 'balanced' | 'quality'
+
+export interface StreamOptions {
+  /** Convert frames to RGBA in this color space. Raw frames are tagged with their source space. */
+  colorSpace?: ColorSpace
+  /** Transfer function of 10-bit HDR frames (defaults to `hlg`). */
+  hdrTransfer?: HdrTransfer
+  /** Keep HDR frames untouched instead of tone mapping them to SDR. */
+  hdrPassthrough?: boolean
+  /** Report unchanged frames as `repeated` (with empty data) when their mean difference is at most this value (0-255). */
+  dedupTolerance?: number
+  /** Lower the delivered frame rate (30→15→5fps) while the webview lags behind. Defaults to `true`. */
+  adaptiveFps?: boolean
+  powerMode?: PowerMode
+  /** Defaults to `balanced`. */
+  latencyMode?: LatencyMode
+  /** Only convert and deliver this region of the frames, e.g. `{ x: 0.2, y: 0.2, width: 0.6, height: 0.6 }`. */
+  crop?: CropRect
+  /** Scale frames down to fit this width, keeping their aspect ratio; never scales up. */
+  targetWidth?: number
+  /** Scale frames down to fit this height, keeping their aspect ratio; never scales up. */
+  targetHeight?: number
+  /**
+   * Reopen the capture when frames stop, and as soon as an unplugged camera is back, keeping the
+   * stream ID. Defaults to `true`; with `false` a stalled stream is stopped after `onStreamError`.
+   */
+  autoReconnect?: boolean
+  /** Mirror frames left to right (selfie previews); recordings and WebRTC tracks are mirrored too. */
+  mirror?: boolean
+  /** Flip frames upside down. */
+  flipVertical?: boolean
+  /** Rotate frames clockwise, e.g. for sideways mounted sensors; applied before `mirror` and `flipVertical`. */
+  rotation?: 0 | 90 | 180 | 270
+  /** Deliver at most this many frames per second, evenly spaced, while the camera keeps its capture rate. */
+  maxFps?: number
+  /** Encoder settings of the stream's WebRTC tracks and recordings, instead of the profile's. */
+  encoder?: EncoderSettings
+}
+
+/** Region of a frame, normalized to its size (0-1, origin top-left). */
+export interface CropRect {
+  x: number
+  y: number
+  width: number
+  height: number
+}
+
+export interface FrameEvent {
+  data: Uint8Array
+  width: number
+  height: number
+  format: string
+  colorSpace: ColorSpace
+  /** Present when the frame still carries an HDR signal (passthrough mode). */
+  hdr?: HdrTransfer
+  /** Content is identical to the previous frame; `data` is empty, keep showing the last one. */
+  repeated: boolean
+  /** Presentation timestamp: microseconds on a monotonic clock started with the stream, taken at frame arrival. Strictly increasing. */
+  ptsUs: number
+  /** Wall-clock arrival time, milliseconds since the Unix epoch. */
+  captureTimeMs: number
+}
+
+export interface StreamStats {
+  streamId: string
+  deviceId: string
+  uptimeMs: number
+  framesDelivered: number
+  framesSkipped: number
+  sendErrors: number
+  /** Frame rate cap applied by adaptive delivery, `null` when running at capture rate. */
+  targetFps: number | null
+  effectiveFps: number
+  /** Memory held by the stream's frame buffers and queues */
+  bufferBytes: number
+  /** Frames dropped because the plugin's memory budget (`memoryBudgetMb`) was exhausted */
+  framesOverBudget: number
+  /** Paused with `pauseStreaming` */
+  paused: boolean
+}
+
+/** Capture format requested when starting a stream; unset fields pick the largest and fastest match. */
+export interface StreamFormat {
+  width?: number
+  height?: number
+  fps?: number
+}
+
+export interface StreamSession {
+  streamId: string
+  /** Required to control the stream; kept by this module for the streams this page started. */
+  token: string
+}
+
+// Tokens of the streams this page may control, by stream ID
+const streamTokens = new Map<string, string>()
+
+/** Access token of a stream known to this page, e.g. to persist it across a reload. */
+export function getStreamToken(streamId: string): string | undefined {
+  return streamTokens.get(streamId)
+}
+
+/** Make a stream's token known to this page, e.g. after a reload or when handed over by another window. */
+export function setStreamToken(streamId: string, token: string): void {
+  streamTokens.set(streamId, token)
+}
+
+/**
+ * Start capturing from a device. Returns the stream session ID (reused if the device is already streaming,
+ * which requires that stream's `token`). Pass `onFrame` to receive frames in the webview.
+ * `format` picks one of the device's formats (see `getDeviceCapabilities`) instead of the profile's.
+ */
+export async function startStreaming(
+  deviceId: string,
+  options?: StreamOptions,
+  onFrame?: (frame: FrameEvent) => void,
+  token?: string,
+  format?: StreamFormat
+): Promise<string> {
+  let channel: Channel<FrameEvent> | undefined
+  if (onFrame) {
+    channel = new Channel<FrameEvent>()
+    channel.onmessage = onFrame
+  }
+  const session = await invoke<StreamSession>('plugin:camera|start_streaming', {
+    deviceId,
+    options,
+    onFrame: channel,
+    token,
+    width: format?.width,
+    height: format?.height,
+    fps: format?.fps
+  })
+  setStreamToken(session.streamId, session.token)
+  return session.streamId
+}
+
+/**
+ * Receive the frames of the device's running stream on `onFrame`, or start a stream if there is none,
+ * e.g. after an unclean frontend restart. `options` only apply to a newly started stream. Returns the session ID.
+ */
+export async function getOrStartStream(
+  deviceId: string,
+  onFrame: (frame: FrameEvent) => void,
+  options?: StreamOptions,
+  token?: string
+): Promise<string> {
+  const channel = new Channel<FrameEvent>()
+  channel.onmessage = onFrame
+  const session = await invoke<StreamSession>('plugin:camera|get_or_start_stream', {
+    deviceId,
+    options,
+    onFrame: channel,
+    token
+  })
+  setStreamToken(session.streamId, session.token)
+  return session.streamId
+}
+
+/**
+ * Receive the frames of a running stream on a new callback, e.g. after a page reload.
+ * Frontend callbacks registered by the previous page are dropped. Returns the sink ID.
+ */
+export async function reattachStream(streamId: string, onFrame: (frame: FrameEvent) => void): Promise<string> {
+  const channel = new Channel<FrameEvent>()
+  channel.onmessage = onFrame
+  return invoke<string>('plugin:camera|reattach_stream', { streamId, onFrame: channel, token: getStreamToken(streamId) })
+}
+
+export async function stopStreaming(streamId: string): Promise<void> {
+  await invoke('plugin:camera|stop_streaming', { streamId, token: getStreamToken(streamId) })
+  streamTokens.delete(streamId)
+}
+
+/**
+ * Stop delivering a stream's frames, e.g. while its tab is hidden. The camera stays open and the
+ * stream's sinks stay attached, so `resumeStreaming` is instant.
+ */
+export async function pauseStreaming(streamId: string): Promise<void> {
+  await invoke('plugin:camera|pause_streaming', { streamId, token: getStreamToken(streamId) })
+}
+
+export async function resumeStreaming(streamId: string): Promise<void> {
+  await invoke('plugin:camera|resume_streaming', { streamId, token: getStreamToken(streamId) })
+}
+
+/**
+ * Move a stream to another camera, e.g. to toggle front/back during a call. The stream ID, channels
+ * and WebRTC tracks stay the same, so the remote peer needs no new offer/answer.
+ */
+export async function switchStreamDevice(streamId: string, deviceId: string): Promise<void> {
+  await invoke('plugin:camera|switch_stream_device', { streamId, deviceId, token: getStreamToken(streamId) })
+}
+
+/**
+ * Encode the latest frame of a stream as JPEG or PNG, as the stream delivers it (cropped, scaled
+ * and filtered). The stream and its WebRTC sessions are not interrupted.
+ */
+export async function captureFrameFromStream(streamId: string, options?: PhotoOptions): Promise<Photo> {
+  return invoke<Photo>('plugin:camera|capture_frame_from_stream', {
+    streamId,
+    options,
+    token: getStreamToken(streamId)
+  })
+}
+
+export async function getStreamStats(streamId: string): Promise<StreamStats> {
+  return invoke<StreamStats>('plugin:camera|get_stream_stats', { streamId })
+}
+
+/**
+ * Record the raw capture frames of a device to `path` (inside the configured output directories),
+ * e.g. to reproduce a conversion bug reported by a user. Dumping a device that is already streaming
+ * requires that stream's `token`. Returns the dump ID.
+ */
+export async function startFrameDump(deviceId: string, path: string, token?: string): Promise<string> {
+  return invoke<string>('plugin:camera|start_frame_dump', { deviceId, path, token })
+}
+
+export async function stopFrameDump(dumpId: string): Promise<void> {
+  await invoke('plugin:camera|stop_frame_dump', { dumpId })
+}
+
+/** Register a frame dump as a device replaying it in a loop; returns the device ID to stream from. */
+export async function replayFrameDump(path: string): Promise<string> {
+  return invoke<string>('plugin:camera|replay_frame_dump', { path })
+}
+
+export interface StreamStalledEvent {
+  streamId: string
+  deviceId: string
+  /** Time since the last frame */
+  idleMs: number
+}
+
+export interface StreamRecoveredEvent {
+  streamId: string
+  deviceId: string
+  /** Time without frames */
+  stalledMs: number
+  /** Times the capture was reopened */
+  restarts: number
+}
+
+export interface StreamErrorEvent {
+  streamId: string
+  deviceId: string
+  error: string
+  /** The camera is still listed by the system */
+  deviceConnected: boolean
+  /** The stream waits for the camera and keeps trying to reopen it; otherwise it was stopped */
+  reconnecting: boolean
+}
+
+/** Desktop only: a stream stopped receiving frames (e.g. after sleep/resume); its capture is being reopened. */
+export async function onStreamStalled(handler: (event: StreamStalledEvent) => void): Promise<UnlistenFn> {
+  return listen<StreamStalledEvent>('camera://stream-stalled', (event) => handler(event.payload))
+}
+
+/** Desktop only: frames flow again after a stall. */
+export async function onStreamRecovered(handler: (event: StreamRecoveredEvent) => void): Promise<UnlistenFn> {
+  return listen<StreamRecoveredEvent>('camera://stream-recovered', (event) => handler(event.payload))
+}
+
+/** Desktop only: a stalled stream's camera was unplugged or couldn't be reopened. */
+export async function onStreamError(handler: (event: StreamErrorEvent) => void): Promise<UnlistenFn> {
+  return listen<StreamErrorEvent>('camera://stream-error', (event) => handler(event.payload))
+}
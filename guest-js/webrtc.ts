@@ -1,4 +1,6 @@
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
+import { listen, type UnlistenFn } from '@tauri-apps/api/event'
+import type { EncoderSettings, ProfileFormat } from './profiles'
 
 // Types mirrored from CrabCamera (crabcamera::CameraDeviceInfo)
 export interface CameraFormat {
@@ -8,6 +10,9 @@ export interface CameraFormat {
   format_type: string
 }
 
+/** Where a camera faces; guessed from the device name on desktop. */
+export type CameraFacing = 'front' | 'back' | 'external' | 'unknown'
+
 export interface CameraDeviceInfo {
   id: string
   name: string
@@ -15,6 +20,9 @@ export interface CameraDeviceInfo {
   is_available: boolean
   supports_formats: CameraFormat[]
   platform: string
+  facing: CameraFacing
+  /** Physical location reported by the system, when it reports one; never set on desktop. */
+  position: string | null
 }
 
 // Types mirrored from Rust (src/webrtc.rs)
@@ -40,6 +48,39 @@ export async function getAvailableCameras(): Promise<CameraDeviceInfo[]> {
   return invoke<CameraDeviceInfo[]>('plugin:camera|get_available_cameras')
 }
 
+/** Connected cameras facing `facing`, in enumeration order. */
+export async function getCamerasByFacing(facing: CameraFacing): Promise<CameraDeviceInfo[]> {
+  return invoke<CameraDeviceInfo[]>('plugin:camera|get_cameras_by_facing', { facing })
+}
+
+/**
+ * Camera to use when none is specified, or `null` without any camera connected: the one set with
+ * `setDefaultCamera`, else the last used one, else the best match of the `defaultCamera` config.
+ */
+export async function getDefaultCamera(): Promise<CameraDeviceInfo | null> {
+  return invoke<CameraDeviceInfo | null>('plugin:camera|get_default_camera')
+}
+
+/** Make a device the default camera across launches; `null` goes back to the selection policy. */
+export async function setDefaultCamera(deviceId: string | null): Promise<void> {
+  await invoke('plugin:camera|set_default_camera', { deviceId })
+}
+
+/** Formats a device can capture in, highest resolution first and fastest first among equal sizes. */
+export async function getDeviceCapabilities(deviceId: string): Promise<ProfileFormat[]> {
+  return invoke<ProfileFormat[]>('plugin:camera|get_device_capabilities', { deviceId })
+}
+
+/** Desktop only: a camera was plugged in. */
+export async function onDeviceAdded(handler: (device: CameraDeviceInfo) => void): Promise<UnlistenFn> {
+  return listen<CameraDeviceInfo>('camera://device-added', (event) => handler(event.payload))
+}
+
+/** Desktop only: a camera was unplugged. Streams of the device stall and are recovered when it comes back. */
+export async function onDeviceRemoved(handler: (device: CameraDeviceInfo) => void): Promise<UnlistenFn> {
+  return listen<CameraDeviceInfo>('camera://device-removed', (event) => handler(event.payload))
+}
+
 export async function initialize(): Promise<string> {
   return invoke<string>('plugin:camera|initialize')
 }
@@ -63,11 +104,32 @@ export async function createOffer(iceServers: IceServer[] = []): Promise<{ offer
   return { offer, connectionId }
 }
 
+/** Codec of a WebRTC video track; VP8 and VP9 need the plugin's `vpx` feature, AV1 its `av1` feature. */
+export type VideoCodec = 'h264' | 'vp8' | 'vp9' | 'av1'
+
+/** Capture format, codec and encoder settings of a WebRTC session's video. */
+export interface VideoConfig {
+  width?: number
+  height?: number
+  fps?: number
+  /** H.264 when unset */
+  codec?: VideoCodec | null
+  encoder?: EncoderSettings
+}
+
 // Composite: initialize camera, create connection, attach track, start streaming, and return offer + connectionId
-export async function startCameraWebRTCSesion(deviceId: string, iceServers: IceServer[] = []): Promise<{ offer: SessionDescription; connectionId: string }> {
+// Sending a device that is already streaming requires that stream's `token`
+export async function startCameraWebRTCSesion(
+  deviceId: string,
+  iceServers: IceServer[] = [],
+  video?: VideoConfig,
+  token?: string
+): Promise<{ offer: SessionDescription; connectionId: string }> {
   const [sdpData, connectionId] = await invoke<[SessionDescription, string]>('plugin:camera|start_camera_webrtc_session', {
     deviceId,
     iceServers: iceServers,
+    video,
+    token,
   })
   const offer: SessionDescription = { type: sdpData.type as 'offer', sdp: sdpData.sdp }
   return { offer, connectionId }
@@ -99,3 +161,334 @@ export async function closeConnection(connectionId: string): Promise<void> {
 export async function getConnectionState(connectionId: string): Promise<string> {
   return invoke<string>('plugin:camera|get_connection_state', { connectionId })
 }
+
+export interface BandwidthEstimate {
+  connectionId: string
+  /** Rate the peer received without noticeable loss */
+  availableKbps: number
+  /** Share of packets lost at the last rate tried, 0..1 */
+  loss: number
+  /** The probe stopped on loss before reaching `maxKbps` */
+  limited: boolean
+  durationMs: number
+}
+
+/**
+ * Make the next video frame sent to a connection a keyframe, so a remote decoder showing a frozen or corrupted
+ * picture recovers. Picture loss reports (PLI/FIR) of the peer's browser do the same automatically.
+ */
+export async function requestKeyframe(connectionId: string): Promise<void> {
+  await invoke('plugin:camera|request_keyframe', { connectionId })
+}
+
+/**
+ * Desktop only: estimate the uplink of a connected peer by sending padding at rising rates
+ * (up to `maxKbps`, 8000 by default) until the peer reports loss. Takes a few seconds.
+ */
+export async function probeBandwidth(connectionId: string, maxKbps?: number): Promise<BandwidthEstimate> {
+  return invoke<BandwidthEstimate>('plugin:camera|probe_bandwidth', { connectionId, maxKbps })
+}
+
+export interface IceRestartEvent {
+  connectionId: string
+  /** New offer to relay to the peer; pass its answer to `setRemoteDescription` */
+  offer: SessionDescription
+}
+
+/** Desktop only: the network changed and ICE was restarted on a connection. */
+export async function onIceRestart(handler: (event: IceRestartEvent) => void): Promise<UnlistenFn> {
+  return listen<IceRestartEvent>('camera://ice-restart', (event) => handler(event.payload))
+}
+
+export interface MediaLivenessEvent {
+  connectionId: string
+  /** `outbound`: no video sample sent; `inbound`: no RTCP report received from the peer */
+  direction: 'outbound' | 'inbound'
+  stalled: boolean
+  /** Time without media in that direction */
+  idleMs: number
+}
+
+/** Desktop only: a connection is still connected but its media went quiet in one direction. */
+export async function onMediaStalled(handler: (event: MediaLivenessEvent) => void): Promise<UnlistenFn> {
+  return listen<MediaLivenessEvent>('camera://media-stalled', (event) => handler(event.payload))
+}
+
+export interface BitrateChangedEvent {
+  connectionId: string
+  bitrateKbps: number
+  previousKbps: number
+  /** Share of packets lost in the peer's latest receiver report, 0..1 */
+  loss: number
+  /** Bandwidth estimate of the peer (REMB), if it sends one */
+  estimateKbps: number | null
+}
+
+/** Desktop only: the encoder bitrate of a connection adapted to its network. */
+export async function onBitrateChanged(handler: (event: BitrateChangedEvent) => void): Promise<UnlistenFn> {
+  return listen<BitrateChangedEvent>('camera://bitrate-changed', (event) => handler(event.payload))
+}
+
+/** Desktop only: media flows again on a connection after `onMediaStalled`. */
+export async function onMediaResumed(handler: (event: MediaLivenessEvent) => void): Promise<UnlistenFn> {
+  return listen<MediaLivenessEvent>('camera://media-resumed', (event) => handler(event.payload))
+}
+
+// Types mirrored from Rust (src/models.rs)
+export interface DiagnosticStep {
+  name: string
+  ok: boolean
+  durationMs: number
+  detail?: string
+  error?: string
+}
+
+export interface DiagnosticsReport {
+  platform: string
+  passed: boolean
+  totalMs: number
+  steps: DiagnosticStep[]
+}
+
+export type PhotoFormat = 'jpeg' | 'png'
+
+export interface PhotoOptions {
+  /** Defaults to `jpeg`. */
+  format?: PhotoFormat
+  /** JPEG quality, 1-100 (90 by default). */
+  quality?: number
+  /** Write the photo to this file, within the allowed output directories, instead of returning its bytes. */
+  path?: string
+  /** EXIF metadata embedded in JPEG photos. */
+  metadata?: CaptureOptions
+  /** Defaults to `off`. Desktop cameras have no flash and refuse `on`. */
+  flash?: FlashMode
+}
+
+export type FlashMode = 'off' | 'auto' | 'on'
+
+/** EXIF metadata of a JPEG photo. The capture time and camera name are always filled in. */
+export interface CaptureOptions {
+  /** Embed EXIF metadata (default `true`). */
+  exif?: boolean
+  /** EXIF orientation, 1-8 (default 1, upright). */
+  orientation?: number
+  /** Stored as the image description. */
+  description?: string
+  /** Where the photo was taken, e.g. from `navigator.geolocation`. */
+  gps?: GpsLocation
+}
+
+export interface GpsLocation {
+  /** Degrees, north positive */
+  latitude: number
+  /** Degrees, east positive */
+  longitude: number
+  /** Meters above sea level */
+  altitude?: number
+}
+
+export interface Photo {
+  width: number
+  height: number
+  format: PhotoFormat
+  /** Encoded bytes, unless the photo was written to `path` */
+  data?: number[]
+  path?: string
+}
+
+/**
+ * Desktop only: capture a still photo, from the running stream of the device or at its highest resolution.
+ * Taking it from a running stream requires that stream's `token`.
+ */
+export async function capturePhoto(deviceId: string, options?: PhotoOptions, token?: string): Promise<Photo> {
+  return invoke<Photo>('plugin:camera|capture_photo', { deviceId, options, token })
+}
+
+/**
+ * Desktop only: capture `count` photos `intervalMs` apart, e.g. for best shot selection. The device's running
+ * stream is reused; with `options.path`, shot `n` is written to `<name>-n.<ext>`.
+ */
+export interface CountdownEvent {
+  /** Seconds left before the shot, 0 as the frame is taken */
+  remainingSeconds: number
+}
+
+/**
+ * Desktop only: capture a photo after `delaySeconds` (up to 60). `onCountdown` receives the seconds left every
+ * second, then 0 as the frame is taken. The camera opens when the countdown starts so the shot isn't delayed.
+ * Taking it from a running stream requires that stream's `token`.
+ */
+export async function capturePhotoWithTimer(
+  deviceId: string,
+  delaySeconds: number,
+  onCountdown: (event: CountdownEvent) => void,
+  options?: PhotoOptions,
+  token?: string
+): Promise<Photo> {
+  const channel = new Channel<CountdownEvent>()
+  channel.onmessage = onCountdown
+  return invoke<Photo>('plugin:camera|capture_photo_with_timer', {
+    deviceId,
+    delaySeconds,
+    options,
+    onCountdown: channel,
+    token
+  })
+}
+
+/** Desktop only: capture `count` photos `intervalMs` apart. Using a running stream requires that stream's `token`. */
+export async function captureBurst(
+  deviceId: string,
+  count: number,
+  intervalMs: number,
+  options?: PhotoOptions,
+  token?: string
+): Promise<Photo[]> {
+  return invoke<Photo[]>('plugin:camera|capture_burst', { deviceId, count, intervalMs, options, token })
+}
+
+export interface TimelapseOptions {
+  /** JPEG quality of the shots, 1-100 (90 by default). */
+  quality?: number
+  /** Assemble the shots into `timelapse.h264` when the time-lapse stops. */
+  assembleVideo?: boolean
+}
+
+export interface TimelapseProgressEvent {
+  timelapseId: string
+  /** Shots written so far */
+  frames: number
+  /** File of this shot, null when it failed */
+  path: string | null
+  error: string | null
+}
+
+export interface TimelapseSummary {
+  timelapseId: string
+  frames: number
+  outputDir: string
+  /** Raw H.264 (Annex B) video of the shots, when assembled */
+  video: string | null
+}
+
+/**
+ * Desktop only: write a JPEG of the device to `outputDir` (inside the allowed output directories) every
+ * `intervalSeconds`, as `frame-00000.jpg`, `frame-00001.jpg`, ... Returns the time-lapse ID.
+ * Shooting a device that is already streaming requires that stream's `token`.
+ */
+export async function startTimelapse(
+  deviceId: string,
+  intervalSeconds: number,
+  outputDir: string,
+  options?: TimelapseOptions,
+  token?: string
+): Promise<string> {
+  return invoke<string>('plugin:camera|start_timelapse', { deviceId, intervalSeconds, outputDir, options, token })
+}
+
+export async function stopTimelapse(timelapseId: string): Promise<TimelapseSummary> {
+  return invoke<TimelapseSummary>('plugin:camera|stop_timelapse', { timelapseId })
+}
+
+export async function onTimelapseProgress(handler: (event: TimelapseProgressEvent) => void): Promise<UnlistenFn> {
+  return listen<TimelapseProgressEvent>('camera://timelapse-progress', (event) => handler(event.payload))
+}
+
+/** Self-test for remote support: permission, devices, one-frame capture, conversion and encode. */
+export async function runDiagnostics(): Promise<DiagnosticsReport> {
+  return invoke<DiagnosticsReport>('plugin:camera|run_diagnostics')
+}
+
+export interface SessionUsage {
+  streamId: string
+  deviceId: string
+  uptimeMs: number
+  bufferBytes: number
+  /** Channel and WebRTC sinks attached to the stream */
+  sinks: number
+  encoding: boolean
+}
+
+export interface ResourceUsage {
+  sessions: SessionUsage[]
+  /** Camera devices held open by the plugin */
+  openDevices: string[]
+  webrtcConnections: number
+  nativePreviews: number
+  frameConsumers: number
+  memoryUsedBytes: number
+  memoryBudgetBytes: number | null
+  runtimeWorkers: number
+  /** Threads of the whole process, `null` where the platform doesn't report it */
+  processThreads: number | null
+}
+
+/** Sessions, buffers, threads, devices and connections held by the plugin, to spot leaks during development. */
+export async function getResourceUsage(): Promise<ResourceUsage> {
+  return invoke<ResourceUsage>('plugin:camera|get_resource_usage')
+}
+
+/**
+ * Desktop only: turn the JSON event log of the frame pipeline on or off. Each line records one stage
+ * (capture, convert, encode, send) of one frame with its timing. Returns the path of the log file.
+ */
+export async function setEventLog(enabled: boolean): Promise<string> {
+  return invoke<string>('plugin:camera|set_event_log', { enabled })
+}
+
+/**
+ * Linux only (`pipewire-portal` feature): let the user pick screens or windows to share through the
+ * ScreenCast portal, on Wayland as well as X11. Returns device IDs to stream from like cameras.
+ */
+export async function addScreenSources(): Promise<string[]> {
+  return invoke<string[]>('plugin:camera|add_screen_sources')
+}
+
+export interface CameraAccess {
+  /** 'NotDetermined' (not asked yet), 'Denied', 'Restricted' (MDM or Screen Time) or 'Granted' */
+  status: 'NotDetermined' | 'Denied' | 'Restricted' | 'Granted'
+  /** NSCameraUsageDescription is in Info.plist; without it the prompt never appears */
+  usageDescription: boolean
+  signed: boolean
+  /** macOS forgets the permission whenever an ad-hoc signed binary changes */
+  adHoc: boolean
+  hardenedRuntime: boolean
+  sandboxed: boolean
+  cameraEntitlement: boolean
+  canPrompt: boolean
+  /** Steps to get camera access, most important first */
+  guidance: string[]
+}
+
+/** macOS only: camera authorization status and what keeps the permission prompt from appearing. */
+export async function getCameraAccess(): Promise<CameraAccess> {
+  return invoke<CameraAccess>('plugin:camera|get_camera_access')
+}
+
+/**
+ * macOS only: forget the app's camera decision so the next request shows the prompt again.
+ * Requires the `camera:allow-reset-camera-permission` permission.
+ */
+export async function resetCameraPermission(): Promise<void> {
+  await invoke('plugin:camera|reset_camera_permission')
+}
+
+export interface FaultInjection {
+  /** Stop delivering frames, as if the camera was unplugged, after this many more frames */
+  deviceLostAfterFrames?: number
+  /** Fail the processing (color conversion, filters) of every frame */
+  conversionErrors?: boolean
+  /** Fail every frame sent to frontend callbacks */
+  channelSendErrors?: boolean
+  /** Panic in the encoder on the next encoded frame */
+  encoderPanic?: boolean
+}
+
+/**
+ * Simulate camera failures on a device to test error handling; call without `faults` to clear them.
+ * Requires the `fault-injection` cargo feature and the `camera:allow-inject-faults` permission.
+ */
+export async function injectFaults(deviceId: string, faults?: FaultInjection): Promise<void> {
+  await invoke('plugin:camera|inject_faults', { deviceId, faults })
+}
@@ -0,0 +1,328 @@
+use crate::error::{Error, Result};
+use crate::models::AudioOptions;
+use crate::recording::{AudioPacket, AudioTrack, AAC_FRAME};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use fdk_aac::enc::{
+    AudioObjectType, BitRate, ChannelMode, Encoder as FdkEncoder, EncoderParams, Transport,
+};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Samples fdk-aac's AAC-LC encoder delays its output by, trimmed from the start
+const ENCODER_DELAY: u64 = 2048;
+const DEFAULT_BITRATE: u32 = 128_000;
+/// Sampling frequencies with an index in the AAC configuration
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+/// Capture timestamps further than this from the sample count restart the timeline,
+/// e.g. after the device dropped buffers
+const RESYNC_US: u64 = 100_000;
+/// How often the capture thread checks whether the recording ended
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+/// Interleaved samples as delivered by the device, at most stereo
+struct AudioChunk {
+    samples: Vec<i16>,
+    capture_time_us: u64,
+}
+
+/// Names of the audio input devices, for `AudioOptions::device`
+pub fn list_microphones() -> Result<Vec<String>> {
+    let devices = cpal::default_host()
+        .input_devices()
+        .map_err(|e| Error::CameraError(format!("Failed to list microphones: {}", e)))?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+/// Microphone captured and encoded to AAC on its own thread, until dropped
+/// The device stream lives on that thread as some platforms can't move it across threads.
+pub struct AudioCapture {
+    track: AudioTrack,
+    pub packets: mpsc::UnboundedReceiver<AudioPacket>,
+    stop: Arc<AtomicBool>,
+}
+
+impl AudioCapture {
+    pub fn start(options: &AudioOptions) -> Result<Self> {
+        let options = options.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (packets_tx, packets) = mpsc::unbounded_channel();
+        let (started_tx, started_rx) = std_mpsc::sync_channel(1);
+        let thread_stop = stop.clone();
+        std::thread::Builder::new()
+            .name("camera-audio".to_string())
+            .spawn(move || capture(options, packets_tx, thread_stop, started_tx))?;
+        let track = started_rx
+            .recv()
+            .map_err(|_| Error::CameraError("The audio thread stopped".to_string()))??;
+        Ok(Self {
+            track,
+            packets,
+            stop,
+        })
+    }
+
+    /// Track the packets are encoded for
+    pub fn track(&self) -> &AudioTrack {
+        &self.track
+    }
+}
+
+impl Drop for AudioCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn capture(
+    options: AudioOptions,
+    packets: mpsc::UnboundedSender<AudioPacket>,
+    stop: Arc<AtomicBool>,
+    started: std_mpsc::SyncSender<Result<AudioTrack>>,
+) {
+    let (chunks_tx, chunks) = std_mpsc::channel();
+    let opened = open_stream(&options, chunks_tx).and_then(|(stream, sample_rate, channels)| {
+        let encoder = AacEncoder::new(sample_rate, channels, options.bitrate)?;
+        Ok((stream, encoder))
+    });
+    let (_stream, mut encoder) = match opened {
+        Ok(opened) => opened,
+        Err(e) => {
+            let _ = started.send(Err(e));
+            return;
+        }
+    };
+    let _ = started.send(Ok(encoder.track.clone()));
+
+    while !stop.load(Ordering::Relaxed) {
+        let chunk = match chunks.recv_timeout(STOP_POLL) {
+            Ok(chunk) => chunk,
+            Err(std_mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(std_mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        match encoder.push(chunk) {
+            Ok(encoded) => {
+                for packet in encoded {
+                    if packets.send(packet).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Audio encoding failed: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Open the microphone; returns its stream, sample rate and recorded channel count
+fn open_stream(
+    options: &AudioOptions,
+    chunks: std_mpsc::Sender<AudioChunk>,
+) -> Result<(cpal::Stream, u32, u16)> {
+    let host = cpal::default_host();
+    let device = match &options.device {
+        Some(name) => host
+            .input_devices()
+            .map_err(|e| Error::CameraError(format!("Failed to list microphones: {}", e)))?
+            .find(|device| device.name().is_ok_and(|device_name| &device_name == name)),
+        None => host.default_input_device(),
+    }
+    .ok_or_else(|| {
+        Error::DeviceNotFound(
+            options
+                .device
+                .clone()
+                .unwrap_or_else(|| "default microphone".to_string()),
+        )
+    })?;
+    let supported = device
+        .default_input_config()
+        .map_err(|e| Error::CameraError(format!("Failed to configure the microphone: {}", e)))?;
+    let format = supported.sample_format();
+    let config: cpal::StreamConfig = supported.into();
+    let stream = match format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, chunks),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, chunks),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, chunks),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, chunks),
+        format => {
+            return Err(Error::CameraError(format!(
+                "Unsupported microphone sample format: {}",
+                format
+            )))
+        }
+    }?;
+    stream
+        .play()
+        .map_err(|e| Error::CameraError(format!("Failed to start the microphone: {}", e)))?;
+    Ok((stream, config.sample_rate.0, config.channels.min(2)))
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    chunks: std_mpsc::Sender<AudioChunk>,
+) -> Result<cpal::Stream>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    let kept = channels.min(2);
+    let sample_rate = config.sample_rate.0 as u64;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                let frames = (data.len() / channels) as u64;
+                // The buffer was captured over its own length before this callback
+                let captured =
+                    SystemTime::now() - Duration::from_micros(frames * 1_000_000 / sample_rate);
+                let samples = data
+                    .chunks_exact(channels)
+                    .flat_map(|frame| frame[..kept].iter().map(|sample| i16::from_sample(*sample)))
+                    .collect();
+                let _ = chunks.send(AudioChunk {
+                    samples,
+                    capture_time_us: captured
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_micros() as u64,
+                });
+            },
+            |e| log::warn!("Microphone error: {}", e),
+            None,
+        )
+        .map_err(|e| Error::CameraError(format!("Failed to open the microphone: {}", e)))
+}
+
+/// AAC-LC encoder timestamping its packets from the capture times of their samples
+struct AacEncoder {
+    encoder: FdkEncoder,
+    track: AudioTrack,
+    /// Samples not encoded yet, less than a frame
+    pending: Vec<i16>,
+    /// Capture time of the first sample since the last resync, and samples per channel
+    /// taken in since
+    base_us: Option<u64>,
+    base_frames: u64,
+    /// Capture times of the frames given to the encoder, whose packets didn't come out yet
+    in_flight: VecDeque<u64>,
+    output: Vec<u8>,
+}
+
+impl AacEncoder {
+    fn new(sample_rate: u32, channels: u16, bitrate: Option<u32>) -> Result<Self> {
+        let freq_index = AAC_SAMPLE_RATES
+            .iter()
+            .position(|rate| *rate == sample_rate)
+            .ok_or_else(|| {
+                Error::CameraError(format!(
+                    "Unsupported microphone sample rate: {}",
+                    sample_rate
+                ))
+            })? as u8;
+        let bitrate = bitrate.unwrap_or(DEFAULT_BITRATE);
+        let encoder = FdkEncoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(bitrate),
+            sample_rate,
+            transport: Transport::Raw,
+            channels: if channels == 1 {
+                ChannelMode::Mono
+            } else {
+                ChannelMode::Stereo
+            },
+            audio_object_type: AudioObjectType::Mpeg4LowComplexity,
+        })
+        .map_err(|e| Error::CameraError(format!("Failed to create the AAC encoder: {}", e)))?;
+        Ok(Self {
+            encoder,
+            track: AudioTrack {
+                sample_rate,
+                channels,
+                bitrate,
+                freq_index,
+                config: audio_specific_config(freq_index, channels),
+            },
+            pending: Vec::new(),
+            base_us: None,
+            base_frames: 0,
+            in_flight: VecDeque::new(),
+            output: vec![0; 8192],
+        })
+    }
+
+    fn frames_us(&self, frames: u64) -> u64 {
+        frames * 1_000_000 / self.track.sample_rate as u64
+    }
+
+    /// Encode the complete frames available; returns the packets that came out
+    fn push(&mut self, chunk: AudioChunk) -> Result<Vec<AudioPacket>> {
+        let channels = self.track.channels as usize;
+        // Sample counting keeps timestamps free of callback jitter, unless they drift apart
+        let buffered = (self.pending.len() / channels) as u64;
+        let expected = self
+            .base_us
+            .map(|base| base + self.frames_us(self.base_frames + buffered));
+        if expected.map_or(true, |expected| {
+            expected.abs_diff(chunk.capture_time_us) > RESYNC_US
+        }) {
+            self.pending.clear();
+            self.base_us = Some(chunk.capture_time_us);
+            self.base_frames = 0;
+        }
+        self.pending.extend(chunk.samples);
+
+        let mut packets = Vec::new();
+        let frame_len = AAC_FRAME * channels;
+        while self.pending.len() >= frame_len {
+            let frame: Vec<i16> = self.pending.drain(..frame_len).collect();
+            let base_us = self.base_us.unwrap_or_default();
+            self.in_flight
+                .push_back(base_us + self.frames_us(self.base_frames));
+            self.base_frames += AAC_FRAME as u64;
+
+            let info = self
+                .encoder
+                .encode(&frame, &mut self.output)
+                .map_err(|e| Error::CameraError(format!("AAC encoding failed: {}", e)))?;
+            if info.output_size == 0 {
+                continue;
+            }
+            // Packets come out in order; each decodes to the samples given
+            // `ENCODER_DELAY` before its frame
+            let frame_us = self.in_flight.pop_front().unwrap_or(base_us);
+            packets.push(AudioPacket {
+                capture_time_us: frame_us.saturating_sub(self.frames_us(ENCODER_DELAY)),
+                data: self.output[..info.output_size].to_vec(),
+            });
+        }
+        Ok(packets)
+    }
+}
+
+/// `AudioSpecificConfig` of an AAC-LC stream
+fn audio_specific_config(freq_index: u8, channels: u16) -> Vec<u8> {
+    const AAC_LC: u16 = 2;
+    let config = AAC_LC << 11 | (freq_index as u16) << 7 | channels << 3;
+    config.to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_specific_config() {
+        // 48 kHz stereo and 44.1 kHz mono, as written by common encoders
+        assert_eq!(audio_specific_config(3, 2), [0x11, 0x90]);
+        assert_eq!(audio_specific_config(4, 1), [0x12, 0x08]);
+    }
+}
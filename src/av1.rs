@@ -0,0 +1,189 @@
+use crate::encoder::{EncodedFrame, Encoder, EncoderParams};
+use crate::error::{Error, Result};
+use crate::models::FrameEvent;
+use crate::utils::nv12_to_i420;
+use rav1e::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Name of the rav1e encoder in the encoder registry
+pub const AV1_ENCODER: &str = "av1";
+/// Speed preset when the settings have none: the fastest, as cameras encode live
+const DEFAULT_SPEED_PRESET: u8 = 10;
+/// Seconds between keyframes when the settings don't set an interval
+const DEFAULT_KEYFRAME_INTERVAL_SECS: f64 = 2.0;
+
+/// Timestamps of a frame given to rav1e, carried through to its packet
+struct FrameTimes {
+    pts_us: u64,
+    capture_time_ms: u64,
+    width: u32,
+    height: u32,
+}
+
+/// AV1 encoder backed by rav1e
+/// Without B-frames in the settings, rav1e runs in low latency mode: no frame reordering,
+/// so packets come out in capture order, a few frames late at most. rav1e can't change
+/// the bitrate of a running encoder, so `set_bitrate` restarts it, on a keyframe.
+#[derive(Default)]
+pub struct Av1Encoder {
+    params: Option<EncoderParams>,
+    /// Started on the first frame after `configure` or `set_bitrate`
+    context: Option<Context<u8>>,
+    /// Packets received beyond the one returned, handed out on the next frames
+    pending: VecDeque<EncodedFrame>,
+    force_keyframe: bool,
+}
+
+impl Av1Encoder {
+    fn create_context(params: &EncoderParams, width: u32, height: u32) -> Result<Context<u8>> {
+        let mut speed_settings =
+            SpeedSettings::from_preset(params.speed_preset.unwrap_or(DEFAULT_SPEED_PRESET));
+        if let Some(lookahead) = params.lookahead {
+            speed_settings.rdo_lookahead_frames = lookahead.max(1) as usize;
+        }
+        let keyframe_interval = params.keyframe_interval.map(u64::from).unwrap_or(
+            (params.fps * DEFAULT_KEYFRAME_INTERVAL_SECS)
+                .round()
+                .max(1.0) as u64,
+        );
+        let config = EncoderConfig {
+            width: width as usize,
+            height: height as usize,
+            // One tick per frame, so the rate control spreads the bitrate over the frames
+            time_base: Rational::new(1000, (params.fps * 1000.0).round().max(1.0) as u64),
+            bitrate: params.bitrate_kbps.map_or(0, |kbps| {
+                kbps.saturating_mul(1000).min(i32::MAX as u32) as i32
+            }),
+            low_latency: params.b_frames.unwrap_or(0) == 0,
+            min_key_frame_interval: keyframe_interval,
+            max_key_frame_interval: keyframe_interval,
+            speed_settings,
+            ..Default::default()
+        };
+        Config::new()
+            .with_encoder_config(config)
+            .new_context()
+            .map_err(|e| Error::CameraError(format!("Failed to create AV1 encoder: {}", e)))
+    }
+}
+
+impl Encoder for Av1Encoder {
+    fn mime_type(&self) -> &str {
+        "video/av1"
+    }
+
+    fn configure(&mut self, params: &EncoderParams) -> Result<()> {
+        if params.speed_preset.is_some_and(|preset| preset > 10) {
+            return Err(Error::CameraError(
+                "AV1 speed presets go from 0 to 10".to_string(),
+            ));
+        }
+        self.params = Some(params.clone());
+        self.context = None;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: &FrameEvent) -> Result<EncodedFrame> {
+        let params = self
+            .params
+            .as_ref()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        if !frame.format.eq_ignore_ascii_case("NV12") {
+            return Err(Error::CameraError(format!(
+                "Cannot encode {} frames to AV1, start the stream without color conversion",
+                frame.format
+            )));
+        }
+        let i420 = nv12_to_i420(&frame.data, frame.width, frame.height)?;
+
+        let context = match self.context.take() {
+            Some(context) => context,
+            None => Self::create_context(params, frame.width, frame.height)?,
+        };
+        let context = self.context.insert(context);
+
+        let width = frame.width as usize;
+        let luma = width * frame.height as usize;
+        let (y, chroma) = i420.split_at(luma);
+        let (u, v) = chroma.split_at(luma / 4);
+        let mut picture = context.new_frame();
+        picture.planes[0].copy_from_raw_u8(y, width, 1);
+        picture.planes[1].copy_from_raw_u8(u, width / 2, 1);
+        picture.planes[2].copy_from_raw_u8(v, width / 2, 1);
+        let frame_params = FrameParameters {
+            frame_type_override: if std::mem::take(&mut self.force_keyframe) {
+                FrameTypeOverride::Key
+            } else {
+                FrameTypeOverride::No
+            },
+            opaque: Some(Opaque::new(FrameTimes {
+                pts_us: frame.pts_us,
+                capture_time_ms: frame.capture_time_ms,
+                width: frame.width,
+                height: frame.height,
+            })),
+            ..Default::default()
+        };
+        context
+            .send_frame((Arc::new(picture), frame_params))
+            .map_err(|e| Error::CameraError(format!("Failed to encode frame: {:?}", e)))?;
+
+        loop {
+            match context.receive_packet() {
+                Ok(packet) => {
+                    let Some(times) = packet
+                        .opaque
+                        .and_then(|opaque| opaque.downcast::<FrameTimes>())
+                    else {
+                        continue;
+                    };
+                    self.pending.push_back(EncodedFrame {
+                        data: packet.data,
+                        keyframe: packet.frame_type == FrameType::KEY,
+                        pts_us: times.pts_us,
+                        capture_time_ms: times.capture_time_ms,
+                        width: times.width,
+                        height: times.height,
+                    });
+                }
+                Err(EncoderStatus::Encoded) => {}
+                Err(EncoderStatus::NeedMoreData) => break,
+                Err(e) => {
+                    return Err(Error::CameraError(format!(
+                        "Failed to encode frame: {:?}",
+                        e
+                    )))
+                }
+            }
+        }
+
+        // Nothing out yet while rav1e looks ahead: an empty frame skips this one
+        Ok(self.pending.pop_front().unwrap_or_else(|| EncodedFrame {
+            data: Vec::new(),
+            keyframe: false,
+            pts_us: frame.pts_us,
+            capture_time_ms: frame.capture_time_ms,
+            width: frame.width,
+            height: frame.height,
+        }))
+    }
+
+    fn force_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        let params = self
+            .params
+            .as_mut()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        if params.bitrate_kbps != Some(bitrate_kbps) {
+            params.bitrate_kbps = Some(bitrate_kbps);
+            self.context = None;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
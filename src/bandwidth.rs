@@ -0,0 +1,239 @@
+use crate::error::{Error, Result};
+use crate::webrtc::{BandwidthEstimate, MediaActivity, PeerConnection};
+use bytes::Bytes;
+use std::time::{Instant, SystemTime};
+use tokio::time::Duration;
+use webrtc::media::Sample;
+
+/// Rate the probe starts at, doubled at every step
+const FIRST_STEP_KBPS: u32 = 250;
+const DEFAULT_MAX_KBPS: u32 = 8000;
+/// Time each rate is held
+const STEP_DURATION: Duration = Duration::from_secs(1);
+/// Longest wait for the receiver report covering a step; browsers send one every second
+const REPORT_TIMEOUT: Duration = Duration::from_secs(2);
+const PADDING_INTERVAL: Duration = Duration::from_millis(20);
+/// RTP header and the 255 bytes of padding of one padding packet
+const PADDING_PACKET_BYTES: u64 = 12 + 255;
+/// Loss above which a rate is considered over the available bandwidth
+const MAX_LOSS: f32 = 0.05;
+/// Loss below which an adapting bitrate grows, and above which it drops
+const LOW_LOSS: f32 = 0.02;
+const HIGH_LOSS: f32 = 0.10;
+/// Growth of an adapting bitrate per receiver report with low loss
+const INCREASE: f32 = 1.08;
+/// Bitrate changes smaller than this share aren't worth reconfiguring the encoder
+const MIN_CHANGE: f32 = 0.05;
+
+/// Rates tried in turn, doubling up to `max_kbps`
+fn probe_steps(max_kbps: u32) -> Vec<u32> {
+    let mut steps = Vec::new();
+    let mut kbps = FIRST_STEP_KBPS;
+    while kbps < max_kbps {
+        steps.push(kbps);
+        kbps = kbps.saturating_mul(2);
+    }
+    steps.push(max_kbps);
+    steps
+}
+
+/// Padding packets needed for `sent` bytes to reach `kbps` after `elapsed`
+fn padding_due(kbps: u32, elapsed: Duration, sent: u64) -> u16 {
+    let due = kbps as u64 * 125 * elapsed.as_millis() as u64 / 1000;
+    due.saturating_sub(sent)
+        .div_ceil(PADDING_PACKET_BYTES)
+        .min(u16::MAX as u64) as u16
+}
+
+/// Wait for a receiver report newer than the `seen` first ones, returning its loss
+async fn next_report(activity: &MediaActivity, seen: u64) -> Option<f32> {
+    let deadline = Instant::now() + REPORT_TIMEOUT;
+    while Instant::now() < deadline {
+        if activity.loss_reports() > seen {
+            return Some(activity.fraction_lost());
+        }
+        tokio::time::sleep(PADDING_INTERVAL).await;
+    }
+    None
+}
+
+/// Pad the connection's video track at doubling rates, on top of the media already sent,
+/// until the peer reports loss or `max_kbps` is reached
+pub async fn probe(conn: &PeerConnection, max_kbps: Option<u32>) -> Result<BandwidthEstimate> {
+    let track = conn
+        .video_track
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| Error::CameraError("No video track attached".to_string()))?;
+    let activity = &conn.activity;
+    let max_kbps = max_kbps.unwrap_or(DEFAULT_MAX_KBPS).max(FIRST_STEP_KBPS);
+
+    let started = Instant::now();
+    let mut available_kbps = None;
+    let mut loss = 0.0;
+    let mut limited = false;
+    for kbps in probe_steps(max_kbps) {
+        let step_started = Instant::now();
+        let media_before = activity.sent_bytes();
+        let reports_before = activity.loss_reports();
+        let mut padding_bytes = 0;
+        let mut tick = tokio::time::interval(PADDING_INTERVAL);
+        while step_started.elapsed() < STEP_DURATION {
+            tick.tick().await;
+            let sent = activity.sent_bytes() - media_before + padding_bytes;
+            let packets = padding_due(kbps, step_started.elapsed(), sent);
+            if packets == 0 {
+                continue;
+            }
+            // An empty sample only carries the padding packets sent before it
+            let sample = Sample {
+                data: Bytes::new(),
+                timestamp: SystemTime::now(),
+                prev_padding_packets: packets,
+                ..Default::default()
+            };
+            track
+                .write_sample(&sample)
+                .await
+                .map_err(|e| Error::CameraError(format!("Failed to send padding: {}", e)))?;
+            padding_bytes += packets as u64 * PADDING_PACKET_BYTES;
+        }
+        let sent = activity.sent_bytes() - media_before + padding_bytes;
+        let achieved_kbps = (sent * 8 / step_started.elapsed().as_millis().max(1) as u64) as u32;
+
+        let Some(fraction_lost) = next_report(activity, reports_before).await else {
+            if available_kbps.is_none() {
+                return Err(Error::CameraError(
+                    "The peer sent no receiver report during the probe".to_string(),
+                ));
+            }
+            log::warn!("No receiver report for the {} kbps probe step", kbps);
+            break;
+        };
+        loss = fraction_lost;
+        if fraction_lost > MAX_LOSS {
+            // Over the limit on the first step: what got through is the estimate
+            if available_kbps.is_none() {
+                available_kbps = Some((achieved_kbps as f32 * (1.0 - fraction_lost)) as u32);
+            }
+            limited = true;
+            break;
+        }
+        available_kbps = Some(achieved_kbps);
+    }
+
+    let available_kbps = available_kbps.unwrap_or_default();
+    log::info!(
+        "Bandwidth probe on {}: {} kbps ({:.1}% loss)",
+        conn.id,
+        available_kbps,
+        loss * 100.0
+    );
+    Ok(BandwidthEstimate {
+        connection_id: conn.id.clone(),
+        available_kbps,
+        loss,
+        limited,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Encoder bitrate of a connection following its network conditions
+/// Works like the loss-based part of Google Congestion Control: the rate drops in
+/// proportion to the loss of the peer's receiver reports above 10%, grows by 8% per report
+/// below 2% and holds in between, without exceeding the peer's REMB estimate if it sends one.
+#[cfg_attr(mobile, allow(dead_code))]
+pub struct BitrateController {
+    min_kbps: u32,
+    max_kbps: u32,
+    kbps: u32,
+    /// Rate the reports lead to, `kbps` only follows changes large enough
+    target: f32,
+    reports_seen: u64,
+}
+
+#[cfg_attr(mobile, allow(dead_code))]
+impl BitrateController {
+    pub fn new(start_kbps: u32, min_kbps: u32, max_kbps: u32) -> Self {
+        let max_kbps = max_kbps.max(min_kbps);
+        let kbps = start_kbps.clamp(min_kbps, max_kbps);
+        Self {
+            min_kbps,
+            max_kbps,
+            kbps,
+            target: kbps as f32,
+            reports_seen: 0,
+        }
+    }
+
+    pub fn kbps(&self) -> u32 {
+        self.kbps
+    }
+
+    /// Follow the reports received since the last call; returns the new bitrate when it
+    /// changed
+    pub fn update(&mut self, activity: &MediaActivity) -> Option<u32> {
+        let reports = activity.loss_reports();
+        if reports == self.reports_seen {
+            return None;
+        }
+        self.reports_seen = reports;
+        self.adjust(activity.fraction_lost(), activity.remb_kbps())
+    }
+
+    fn adjust(&mut self, loss: f32, estimate_kbps: Option<u32>) -> Option<u32> {
+        if loss > HIGH_LOSS {
+            self.target *= 1.0 - 0.5 * loss;
+        } else if loss < LOW_LOSS {
+            self.target *= INCREASE;
+        }
+        if let Some(estimate_kbps) = estimate_kbps {
+            self.target = self.target.min(estimate_kbps as f32);
+        }
+        self.target = self
+            .target
+            .clamp(self.min_kbps as f32, self.max_kbps as f32);
+
+        let kbps = self.target.round() as u32;
+        let change = kbps.abs_diff(self.kbps) as f32 / self.kbps.max(1) as f32;
+        // The bounds are always reached, small steps towards them included
+        let bound = kbps == self.min_kbps || kbps == self.max_kbps;
+        if kbps == self.kbps || (change < MIN_CHANGE && !bound) {
+            return None;
+        }
+        self.kbps = kbps;
+        Some(kbps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_steps_and_padding() {
+        assert_eq!(probe_steps(3000), vec![250, 500, 1000, 2000, 3000]);
+        assert_eq!(probe_steps(250), vec![250]);
+
+        // 1000 kbps for 100ms is 12500 bytes, 10000 of them already sent as media
+        assert_eq!(padding_due(1000, Duration::from_millis(100), 10_000), 10);
+        assert_eq!(padding_due(1000, Duration::from_millis(100), 20_000), 0);
+    }
+
+    #[test]
+    fn test_bitrate_controller() {
+        let mut controller = BitrateController::new(1000, 200, 1100);
+        assert_eq!(controller.adjust(0.0, None), Some(1080));
+        // Capped by the maximum, then holding there
+        assert_eq!(controller.adjust(0.0, None), Some(1100));
+        assert_eq!(controller.adjust(0.0, None), None);
+        // Moderate loss holds, heavy loss drops in proportion
+        assert_eq!(controller.adjust(0.05, None), None);
+        assert_eq!(controller.adjust(0.5, None), Some(825));
+        // The peer's estimate caps the rate
+        assert_eq!(controller.adjust(0.0, Some(400)), Some(400));
+        // Changes under 5% wait until they add up
+        assert_eq!(controller.adjust(0.0, Some(410)), None);
+    }
+}
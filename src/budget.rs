@@ -0,0 +1,114 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Memory cap shared by the frame buffers and queues of every stream, so a stalled
+/// consumer can't grow the process without bound (`Config::memory_budget_mb`)
+#[derive(Debug, Default)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    used: AtomicUsize,
+}
+
+/// Buffer memory held by one stream, within the shared budget
+#[derive(Debug, Default)]
+pub struct StreamMemory {
+    used: AtomicUsize,
+    dropped_frames: AtomicU64,
+}
+
+impl StreamMemory {
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Frames dropped because the budget was exhausted
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Account `bytes` of frame data held by a stream until the reservation is dropped
+    /// Returns `None`, counted as a dropped frame, when the budget would be exceeded
+    pub fn reserve(
+        self: &Arc<Self>,
+        stream: &Arc<StreamMemory>,
+        bytes: usize,
+    ) -> Option<Reservation> {
+        let reserved = self
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                let next = used + bytes;
+                match self.limit {
+                    Some(limit) if next > limit => None,
+                    _ => Some(next),
+                }
+            })
+            .is_ok();
+        if !reserved {
+            stream.dropped_frames.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        stream.used.fetch_add(bytes, Ordering::Relaxed);
+        Some(Reservation {
+            budget: self.clone(),
+            stream: stream.clone(),
+            bytes,
+        })
+    }
+}
+
+/// Memory accounted to the budget, released on drop
+#[derive(Debug)]
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    stream: Arc<StreamMemory>,
+    bytes: usize,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.used.fetch_sub(self.bytes, Ordering::AcqRel);
+        self.stream.used.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// A value whose memory stays accounted as long as it is alive
+pub struct Budgeted<T> {
+    value: T,
+    _reservation: Reservation,
+}
+
+impl<T> Budgeted<T> {
+    pub fn new(value: T, reservation: Reservation) -> Self {
+        Self {
+            value,
+            _reservation: reservation,
+        }
+    }
+}
+
+impl<T> Deref for Budgeted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
@@ -1,23 +1,225 @@
+use super::streaming::authorize;
+use crate::error::{Error, Result};
+use crate::models::{
+    CameraAccess, CameraFacing, CameraFormat, CameraInfo, CountdownEvent, DiagnosticsReport,
+    FaultInjection, Photo, PhotoOptions, ResourceUsage, StreamScope, TimelapseOptions,
+    TimelapseSummary,
+};
 use crate::CameraExt;
-use crate::Result;
 use crabcamera::permissions::PermissionInfo;
-use tauri::{command, AppHandle, Runtime};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{
+    command,
+    ipc::{Channel, CommandScope},
+    AppHandle, Runtime,
+};
 
 #[command]
-pub async fn request_camera_permission<R: Runtime>(
-    app: AppHandle<R>,
-) -> Result<PermissionInfo> {
+pub async fn request_camera_permission<R: Runtime>(app: AppHandle<R>) -> Result<PermissionInfo> {
     app.camera().request_permission().await
 }
 
 #[command]
-pub async fn get_available_cameras<R: Runtime>(
+pub async fn get_available_cameras<R: Runtime>(app: AppHandle<R>) -> Result<Vec<CameraInfo>> {
+    app.camera().list_cameras().await
+}
+
+/// Connected cameras facing `facing` (front, back, external)
+#[command]
+pub async fn get_cameras_by_facing<R: Runtime>(
+    app: AppHandle<R>,
+    facing: CameraFacing,
+) -> Result<Vec<CameraInfo>> {
+    app.camera().get_cameras_by_facing(facing).await
+}
+
+/// Camera to use when none is specified, `None` without any camera connected
+#[command]
+pub async fn get_default_camera<R: Runtime>(app: AppHandle<R>) -> Result<Option<CameraInfo>> {
+    app.camera().get_default_camera().await
+}
+
+/// Make a device the default camera across launches, or clear it with `None`
+#[command]
+pub async fn set_default_camera<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: Option<String>,
+) -> Result<()> {
+    app.camera().set_default_camera(device_id).await
+}
+
+/// Resolutions, frame rates and pixel formats a device can capture in, largest first
+#[command]
+pub async fn get_device_capabilities<R: Runtime>(
     app: AppHandle<R>,
-) -> Result<Vec<crabcamera::CameraDeviceInfo>> {
-    app.camera().get_available_cameras().await
+    device_id: String,
+) -> Result<Vec<CameraFormat>> {
+    app.camera().get_device_capabilities(&device_id).await
+}
+
+/// macOS: camera authorization status and what keeps the permission prompt from appearing
+/// (missing usage description, unsigned binary, missing entitlement)
+#[command]
+pub async fn get_camera_access<R: Runtime>(app: AppHandle<R>) -> Result<CameraAccess> {
+    app.camera().get_camera_access()
+}
+
+/// macOS: reset the app's camera permission so the next request prompts again
+#[command]
+pub async fn reset_camera_permission<R: Runtime>(app: AppHandle<R>) -> Result<()> {
+    app.camera().reset_camera_permission()
+}
+
+/// Linux: pick screens or windows to share through the ScreenCast portal; returns their device IDs
+#[command]
+pub async fn add_screen_sources<R: Runtime>(app: AppHandle<R>) -> Result<Vec<String>> {
+    app.camera().add_screen_sources().await
+}
+
+/// Capture a still photo from a device as JPEG or PNG, returning its bytes or writing it
+/// to `options.path`
+/// Taking it from a stream already running on the device requires its `token`
+#[command]
+pub async fn capture_photo<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    options: Option<PhotoOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<Photo> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera
+        .capture_photo(&device_id, options.unwrap_or_default())
+        .await
+}
+
+/// Capture a photo after `delay_seconds`, counting down on `on_countdown`
+/// Taking it from a stream already running on the device requires its `token`
+#[command]
+pub async fn capture_photo_with_timer<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    delay_seconds: u32,
+    options: Option<PhotoOptions>,
+    on_countdown: Channel<CountdownEvent>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<Photo> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera
+        .capture_photo_with_timer(
+            &device_id,
+            delay_seconds,
+            options.unwrap_or_default(),
+            on_countdown,
+        )
+        .await
+}
+
+/// Capture `count` photos `interval_ms` apart, e.g. for best shot selection; a stream
+/// running on the device is reused, which requires its `token`
+#[command]
+pub async fn capture_burst<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    count: u32,
+    interval_ms: u64,
+    options: Option<PhotoOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<Vec<Photo>> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera
+        .capture_burst(
+            &device_id,
+            count,
+            Duration::from_millis(interval_ms),
+            options.unwrap_or_default(),
+        )
+        .await
+}
+
+/// Write a JPEG of the device to `output_dir` every `interval_seconds`, reporting each
+/// shot on `camera://timelapse-progress`; returns the time-lapse ID
+/// Shooting from a stream already running on the device requires its `token`
+#[command]
+pub async fn start_timelapse<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    interval_seconds: f64,
+    output_dir: PathBuf,
+    options: Option<TimelapseOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    let interval = Duration::try_from_secs_f64(interval_seconds).map_err(|_| {
+        Error::InvalidConfig(format!("Invalid time-lapse interval: {}", interval_seconds))
+    })?;
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera
+        .start_timelapse(
+            &device_id,
+            interval,
+            &output_dir,
+            options.unwrap_or_default(),
+        )
+        .await
+}
+
+/// Stop a time-lapse, assembling its shots into an H.264 video if asked at start
+#[command]
+pub async fn stop_timelapse<R: Runtime>(
+    app: AppHandle<R>,
+    timelapse_id: String,
+) -> Result<TimelapseSummary> {
+    app.camera().stop_timelapse(&timelapse_id).await
 }
 
 #[command]
 pub async fn initialize<R: Runtime>(app: AppHandle<R>) -> Result<String> {
     app.camera().initialize().await
 }
+
+/// Self-test for remote support: checks permission, enumerates devices, captures one frame
+/// from the default camera, converts and encodes it, and reports timings and failures
+#[command]
+pub async fn run_diagnostics<R: Runtime>(app: AppHandle<R>) -> Result<DiagnosticsReport> {
+    app.camera().run_diagnostics().await
+}
+
+/// Sessions, buffer memory, threads, open devices and connections held by the plugin,
+/// to spot leaks during development
+#[command]
+pub async fn get_resource_usage<R: Runtime>(app: AppHandle<R>) -> Result<ResourceUsage> {
+    app.camera().get_resource_usage().await
+}
+
+/// Turn the JSON event log of the frame pipeline on or off; returns the log file path
+#[command]
+pub async fn set_event_log<R: Runtime>(app: AppHandle<R>, enabled: bool) -> Result<PathBuf> {
+    app.camera().set_event_log(enabled)
+}
+
+/// Simulate failures on a device to test the app's error handling (`fault-injection` feature)
+#[command]
+pub async fn inject_faults<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    faults: Option<FaultInjection>,
+) -> Result<()> {
+    app.camera()
+        .inject_faults(&device_id, faults.unwrap_or_default())
+}
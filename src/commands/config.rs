@@ -0,0 +1,25 @@
+use crate::error::{Error, Result};
+use crate::CameraExt;
+use tauri::{command, AppHandle, Runtime};
+
+/// Export the plugin configuration (saved profiles) as a JSON document
+#[command]
+pub async fn export_config<R: Runtime>(app: AppHandle<R>) -> Result<String> {
+    Ok(serde_json::to_string_pretty(
+        &app.camera().profiles.export(),
+    )?)
+}
+
+/// Import a configuration produced by `export_config`, e.g. to provision another machine
+/// Existing profiles are kept unless `replace` is set
+#[command]
+pub async fn import_config<R: Runtime>(
+    app: AppHandle<R>,
+    config: String,
+    replace: Option<bool>,
+) -> Result<()> {
+    let config = serde_json::from_str(&config).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+    app.camera()
+        .profiles
+        .import(config, replace.unwrap_or(false))
+}
@@ -0,0 +1,151 @@
+use crate::error::Result;
+use crate::models::{
+    AutoControls, CameraControl, CameraControlInfo, CropRect, Exposure, ExposureMode, FocusMode,
+    WhiteBalance, ZoomRange,
+};
+use crate::CameraExt;
+use tauri::{command, AppHandle, Runtime};
+
+/// Focus the lens on a normalized point of the preview (tap-to-focus)
+/// Emits `camera://focus-locked` once focus settles
+#[command]
+pub async fn set_focus_point<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    x: f64,
+    y: f64,
+) -> Result<()> {
+    app.camera().set_focus_point(device_id, x, y).await
+}
+
+/// Switch between triggered (`auto`), `manual` and `continuous` focus
+#[command]
+pub async fn set_focus_mode<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    mode: FocusMode,
+) -> Result<()> {
+    app.camera().set_focus_mode(device_id, mode).await
+}
+
+/// Focus manually, from 0.0 (infinity) to 1.0 (closest)
+#[command]
+pub async fn set_focus_distance<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    distance: f32,
+) -> Result<()> {
+    app.camera().set_focus_distance(device_id, distance).await
+}
+
+/// Run one autofocus pass, on a normalized region of the preview (tap-to-focus) or the
+/// whole frame; emits `camera://focus-locked` once focus settles
+#[command]
+pub async fn trigger_autofocus<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    roi: Option<CropRect>,
+) -> Result<()> {
+    app.camera().trigger_autofocus(device_id, roi).await
+}
+
+/// Freeze automatic exposure (`ae`), focus (`af`) and/or white balance (`awb`) at their
+/// current values
+#[command]
+pub async fn lock_auto_controls<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    controls: AutoControls,
+) -> Result<()> {
+    app.camera().lock_auto_controls(device_id, controls).await
+}
+
+/// Resume the automatic adjustments set in `controls`
+#[command]
+pub async fn unlock_auto_controls<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    controls: AutoControls,
+) -> Result<()> {
+    app.camera().unlock_auto_controls(device_id, controls).await
+}
+
+/// List the image controls of a device (brightness, contrast, saturation, sharpness, gain,
+/// gamma) it supports, with their current value and min/max/step/default
+#[command]
+pub async fn get_camera_controls<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+) -> Result<Vec<CameraControlInfo>> {
+    app.camera().get_camera_controls(device_id).await
+}
+
+/// Set one image control of a device
+#[command]
+pub async fn set_camera_control<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    control: CameraControl,
+    value: f32,
+) -> Result<()> {
+    app.camera()
+        .set_camera_control(device_id, control, value)
+        .await
+}
+
+/// Turn the torch (continuous flash) on or off; mobile only
+#[command]
+pub async fn set_torch<R: Runtime>(app: AppHandle<R>, device_id: String, on: bool) -> Result<()> {
+    app.camera().set_torch(device_id, on).await
+}
+
+/// Zoom by `factor`, with the camera's own zoom or digitally
+#[command]
+pub async fn set_zoom<R: Runtime>(app: AppHandle<R>, device_id: String, factor: f32) -> Result<()> {
+    app.camera().set_zoom(device_id, factor).await
+}
+
+/// Get the zoom factors a device supports and its current zoom
+#[command]
+pub async fn get_zoom_range<R: Runtime>(app: AppHandle<R>, device_id: String) -> Result<ZoomRange> {
+    app.camera().get_zoom_range(device_id).await
+}
+
+/// Apply a white balance preset (auto, daylight, tungsten, fluorescent) or a manual Kelvin value
+#[command]
+pub async fn set_white_balance<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    white_balance: WhiteBalance,
+) -> Result<()> {
+    app.camera()
+        .set_white_balance(device_id, white_balance)
+        .await
+}
+
+/// Get the white balance currently applied to a device
+#[command]
+pub async fn get_white_balance<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+) -> Result<WhiteBalance> {
+    app.camera().get_white_balance(device_id).await
+}
+
+/// Set auto exposure with an optional EV compensation, a manual exposure time in
+/// milliseconds, or lock the current exposure
+#[command]
+pub async fn set_exposure<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    mode: ExposureMode,
+    value: Option<f32>,
+) -> Result<()> {
+    app.camera().set_exposure(device_id, mode, value).await
+}
+
+/// Get the exposure currently applied to a device
+#[command]
+pub async fn get_exposure<R: Runtime>(app: AppHandle<R>, device_id: String) -> Result<Exposure> {
+    app.camera().get_exposure(device_id).await
+}
@@ -0,0 +1,35 @@
+use super::streaming::authorize;
+use crate::error::Result;
+use crate::models::StreamScope;
+use crate::CameraExt;
+use std::path::PathBuf;
+use tauri::{command, ipc::CommandScope, AppHandle, Runtime};
+
+/// Record the raw capture frames of a device to a dump file inside the output directories
+/// Dumping a stream already running on the device requires its `token`. Returns the dump ID.
+#[command]
+pub async fn start_frame_dump<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    path: PathBuf,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera.start_frame_dump(&device_id, &path).await
+}
+
+#[command]
+pub async fn stop_frame_dump<R: Runtime>(app: AppHandle<R>, dump_id: String) -> Result<()> {
+    app.camera().stop_frame_dump(&dump_id)
+}
+
+/// Make a frame dump available as a device replaying the recorded frames
+/// Returns the device ID to stream from
+#[command]
+pub async fn replay_frame_dump<R: Runtime>(app: AppHandle<R>, path: PathBuf) -> Result<String> {
+    app.camera().replay_frame_dump(&path)
+}
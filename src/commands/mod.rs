@@ -1,8 +1,22 @@
 pub mod camera;
+pub mod config;
+pub mod controls;
+pub mod dump;
+pub mod pipeline;
+pub mod preview;
+pub mod profiles;
+pub mod recording;
 pub mod streaming;
 pub mod webrtc;
 
 // Re-export WebRTCManager for state management
 pub use camera::*;
+pub use config::*;
+pub use controls::*;
+pub use dump::*;
+pub use pipeline::*;
+pub use preview::*;
+pub use profiles::*;
+pub use recording::*;
 pub use streaming::*;
 pub use webrtc::*;
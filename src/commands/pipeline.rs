@@ -0,0 +1,55 @@
+use super::streaming::authorize;
+use crate::error::Result;
+use crate::models::{FrameEvent, PipelineDesc, PipelineInfo, SinkDesc, StreamScope};
+use crate::CameraExt;
+use tauri::{
+    command,
+    ipc::{Channel, CommandScope},
+    AppHandle, Runtime,
+};
+
+/// Start a stream from a pipeline description (source, filters, sinks)
+/// `on_frame` feeds the pipeline's `channel` sink; stop it with `stop_streaming`
+/// Joining a stream already running on the source requires its `token`
+#[command]
+pub async fn start_pipeline<R: Runtime>(
+    app: AppHandle<R>,
+    pipeline: PipelineDesc,
+    on_frame: Option<Channel<FrameEvent>>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<PipelineInfo> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&pipeline.source).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera.start_pipeline(pipeline, on_frame).await
+}
+
+/// Attach a sink to a running stream without interrupting the capture or the other sinks
+/// `on_frame` is required for a `channel` sink. Returns the sink ID.
+#[command]
+pub async fn attach_sink<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    sink: SinkDesc,
+    on_frame: Option<Channel<FrameEvent>>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().attach_sink(&stream_id, sink, on_frame).await
+}
+
+/// Detach a sink from its stream, e.g. stop sending to a preview; the stream keeps running
+#[command]
+pub async fn detach_sink<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    sink_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().detach_sink(&stream_id, &sink_id).await
+}
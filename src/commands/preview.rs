@@ -0,0 +1,118 @@
+use super::streaming::authorize;
+use crate::error::{Error, Result};
+use crate::models::{
+    MonitorInfo, OverlayOptions, OverlayUpdate, PipOptions, PreviewRect, StreamScope,
+};
+use crate::CameraExt;
+use tauri::{command, ipc::CommandScope, AppHandle, Manager, Runtime};
+
+/// Render a stream straight into a native window surface behind the webview
+/// `window_label` defaults to the `main` window; `rect` is in physical pixels
+#[command]
+pub async fn start_native_preview<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    window_label: Option<String>,
+    rect: PreviewRect,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    let label = window_label.unwrap_or_else(|| "main".to_string());
+    let window = app
+        .get_webview_window(&label)
+        .ok_or(Error::WindowNotFound(label))?;
+    app.camera()
+        .start_native_preview(&stream_id, window, rect)
+        .await
+}
+
+/// Move or resize a native preview, e.g. when the page layout changes
+#[command]
+pub async fn set_native_preview_rect<R: Runtime>(
+    app: AppHandle<R>,
+    preview_id: String,
+    rect: PreviewRect,
+) -> Result<()> {
+    app.camera().set_native_preview_rect(&preview_id, rect)
+}
+
+/// Stop rendering a native preview; the stream itself keeps running
+#[command]
+pub async fn stop_native_preview<R: Runtime>(app: AppHandle<R>, preview_id: String) -> Result<()> {
+    app.camera().stop_native_preview(&preview_id)
+}
+
+/// Show a stream in a small frameless window managed by the plugin (picture-in-picture)
+/// Returns the window ID, to pass to `close_preview_window`
+#[command]
+pub async fn open_pip_preview<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    options: Option<PipOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .open_pip_preview(&stream_id, options.unwrap_or_default())
+        .await
+}
+
+/// Show a stream in a borderless always-on-top window, optionally click-through and translucent
+/// Returns the window ID, to pass to `set_overlay_options` and `close_preview_window`
+#[command]
+pub async fn open_overlay_preview<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    options: Option<OverlayOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .open_overlay_preview(&stream_id, options.unwrap_or_default())
+        .await
+}
+
+/// Change the click-through and opacity of an overlay preview
+#[command]
+pub async fn set_overlay_options<R: Runtime>(
+    app: AppHandle<R>,
+    window_id: String,
+    options: OverlayUpdate,
+) -> Result<()> {
+    app.camera().set_overlay_options(&window_id, options)
+}
+
+/// Close a preview window opened by the plugin
+#[command]
+pub async fn close_preview_window<R: Runtime>(app: AppHandle<R>, window_id: String) -> Result<()> {
+    app.camera().close_preview_window(&window_id)
+}
+
+/// List the displays available for kiosk previews
+#[command]
+pub async fn get_monitors<R: Runtime>(app: AppHandle<R>) -> Result<Vec<MonitorInfo>> {
+    app.camera().get_monitors()
+}
+
+/// Show a stream fullscreen on a monitor (by name, the primary one by default),
+/// with no webview content. Returns the window ID, to pass to `exit_kiosk_preview`
+#[command]
+pub async fn start_kiosk_preview<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    monitor: Option<String>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().start_kiosk_preview(&stream_id, monitor).await
+}
+
+/// Leave a kiosk preview, closing its window
+#[command]
+pub async fn exit_kiosk_preview<R: Runtime>(app: AppHandle<R>, window_id: String) -> Result<()> {
+    app.camera().close_preview_window(&window_id)
+}
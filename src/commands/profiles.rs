@@ -0,0 +1,67 @@
+use crate::error::Result;
+use crate::models::CameraProfile;
+use crate::CameraExt;
+use std::collections::HashMap;
+use tauri::{command, AppHandle, Runtime};
+
+/// Save a named profile for a device, replacing any profile with the same name
+#[command]
+pub async fn save_profile<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    name: String,
+    profile: CameraProfile,
+) -> Result<()> {
+    app.camera().profiles.save(device_id, name, profile)
+}
+
+/// List the profiles saved for a device, by name
+#[command]
+pub async fn list_profiles<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+) -> Result<HashMap<String, CameraProfile>> {
+    Ok(app.camera().profiles.list(&device_id))
+}
+
+/// Delete a saved profile
+#[command]
+pub async fn delete_profile<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    name: String,
+) -> Result<()> {
+    app.camera().profiles.delete(&device_id, &name)
+}
+
+/// Apply a saved profile: controls are set immediately, format, stream options and
+/// encoder settings are used by the next stream started on the device
+#[command]
+pub async fn apply_profile<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    name: String,
+) -> Result<()> {
+    app.camera().apply_profile(device_id, name).await
+}
+
+/// Save the controls currently applied to a device (exposure, white balance, focus, zoom,
+/// image controls) in a profile, creating it if needed
+#[command]
+pub async fn save_control_profile<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    name: String,
+) -> Result<()> {
+    app.camera().save_control_profile(device_id, name).await
+}
+
+/// Restore the controls saved in a profile with `save_control_profile`
+#[command]
+pub async fn apply_control_profile<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    name: String,
+) -> Result<()> {
+    app.camera().apply_control_profile(device_id, name).await
+}
@@ -0,0 +1,114 @@
+use super::streaming::authorize;
+use crate::error::Result;
+use crate::models::{RecordingOptions, RecordingSummary, StreamScope};
+use crate::CameraExt;
+use std::path::PathBuf;
+use tauri::{command, ipc::CommandScope, AppHandle, Runtime};
+
+/// Record a device to an MP4 file inside the output directories
+/// Recording a stream already running on the device requires its `token`. Returns the
+/// recording ID.
+#[command]
+pub async fn start_recording<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    path: PathBuf,
+    options: Option<RecordingOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+    camera
+        .start_recording(&device_id, &path, options.unwrap_or_default())
+        .await
+}
+
+/// Stop a recording once its file is finalized
+#[command]
+pub async fn stop_recording<R: Runtime>(
+    app: AppHandle<R>,
+    recording_id: String,
+) -> Result<RecordingSummary> {
+    app.camera().stop_recording(&recording_id).await
+}
+
+/// Record a running stream to an MP4 file without interrupting its preview or other sinks
+/// Returns the recording ID.
+#[command]
+pub async fn attach_recorder<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    path: PathBuf,
+    options: Option<RecordingOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .attach_recorder(&stream_id, &path, options.unwrap_or_default())
+        .await
+}
+
+/// Keep the last `seconds` of a stream's encoded frames for `start_recording_with_preroll`
+#[command]
+pub async fn enable_preroll<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    seconds: u32,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().enable_preroll(&stream_id, seconds).await
+}
+
+/// Stop keeping the pre-roll of a stream
+#[command]
+pub async fn disable_preroll<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().disable_preroll(&stream_id).await
+}
+
+/// Record a running stream starting `preroll_secs` before the call, from its pre-roll
+/// Returns the recording ID.
+#[command]
+pub async fn start_recording_with_preroll<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    preroll_secs: u32,
+    path: PathBuf,
+    options: Option<RecordingOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .start_recording_with_preroll(&stream_id, preroll_secs, &path, options.unwrap_or_default())
+        .await
+}
+
+/// Pause a recording, keeping its file open
+#[command]
+pub async fn pause_recording<R: Runtime>(app: AppHandle<R>, recording_id: String) -> Result<()> {
+    app.camera().pause_recording(&recording_id)
+}
+
+/// Names of the microphones `RecordingOptions::audio` can record from
+#[command]
+pub async fn list_microphones<R: Runtime>(app: AppHandle<R>) -> Result<Vec<String>> {
+    app.camera().list_microphones().await
+}
+
+/// Resume a paused recording in the same file, without a gap in its timeline
+#[command]
+pub async fn resume_recording<R: Runtime>(app: AppHandle<R>, recording_id: String) -> Result<()> {
+    app.camera().resume_recording(&recording_id)
+}
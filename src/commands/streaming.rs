@@ -1,19 +1,194 @@
 use crate::error::Result;
+use crate::models::{
+    FrameEvent, Photo, PhotoOptions, StartStreamRequest, StreamOptions, StreamScope, StreamSession,
+    StreamStats,
+};
 use crate::CameraExt;
-use tauri::{command, AppHandle, Runtime};
+use tauri::{
+    command,
+    ipc::{Channel, CommandScope},
+    AppHandle, Runtime,
+};
+
+/// Let a webview control a stream if it presents the stream's token,
+/// or if its capability grants `anyStream` for the command
+pub(crate) async fn authorize<R: Runtime>(
+    app: &AppHandle<R>,
+    stream_id: &str,
+    token: Option<&str>,
+    scope: &CommandScope<StreamScope>,
+) -> Result<()> {
+    if scope.allows().iter().any(|entry| entry.any_stream) {
+        return Ok(());
+    }
+    app.camera().authorize_stream(stream_id, token).await
+}
 
 /// Start a video stream from a camera device
 /// The Camera handles capture, encoding to H.264, and WebRTC integration
+/// Frames are forwarded to `on_frame` when a channel is provided
+/// `width`, `height` and `fps` select one of the device's formats instead of the profile's
+/// Joining a stream already running on the device requires its `token`
 #[command]
-pub async fn start_streaming<R: Runtime>(app: AppHandle<R>, device_id: String) -> Result<String> {
+#[allow(clippy::too_many_arguments)]
+pub async fn start_streaming<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    options: Option<StreamOptions>,
+    on_frame: Option<Channel<FrameEvent>>,
+    token: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    fps: Option<f64>,
+    scope: CommandScope<StreamScope>,
+) -> Result<StreamSession> {
     let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
 
-    camera.start_streaming(device_id).await
+    // Without explicit options, use the ones of the profile applied to the device
+    let options = options
+        .or_else(|| {
+            camera
+                .profiles
+                .active(&device_id)
+                .map(|profile| profile.stream)
+        })
+        .unwrap_or_default();
+    let request = StartStreamRequest {
+        device_id,
+        width,
+        height,
+        fps,
+    };
+    let stream_id = camera.start_streaming_with_format(request, options).await?;
+    if let Some(on_frame) = on_frame {
+        camera.subscribe(&stream_id, on_frame).await?;
+    }
+    let token = camera.stream_token(&stream_id).await?;
+    Ok(StreamSession { stream_id, token })
 }
 
 /// Stop a video stream
 #[command]
-pub async fn stop_streaming<R: Runtime>(app: AppHandle<R>, stream_id: String) -> Result<()> {
+pub async fn stop_streaming<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
     let camera = app.camera();
     camera.stop_streaming(stream_id).await
 }
+
+/// Stop delivering a stream's frames without closing the camera; `resume_streaming`
+/// restarts delivery instantly
+#[command]
+pub async fn pause_streaming<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().pause_streaming(&stream_id).await
+}
+
+/// Resume delivering the frames of a paused stream
+#[command]
+pub async fn resume_streaming<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().resume_streaming(&stream_id).await
+}
+
+/// Move a stream to another device, keeping its channels and WebRTC tracks
+#[command]
+pub async fn switch_stream_device<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    device_id: String,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<()> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .switch_stream_device(&stream_id, device_id)
+        .await
+}
+
+/// Encode the latest frame of a stream as JPEG or PNG without interrupting it
+#[command]
+pub async fn capture_frame_from_stream<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    options: Option<PhotoOptions>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<Photo> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera()
+        .capture_frame_from_stream(&stream_id, options.unwrap_or_default())
+        .await
+}
+
+/// Get runtime statistics of a stream (delivered frames, effective frame rate, ...)
+#[command]
+pub async fn get_stream_stats<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+) -> Result<StreamStats> {
+    app.camera().get_stream_stats(&stream_id).await
+}
+
+/// Reattach `on_frame` to the device's running stream, or start one if there is none
+/// `options` (the applied profile's by default) only apply to a newly started stream
+/// Reattaching requires the running stream's `token`
+#[command]
+pub async fn get_or_start_stream<R: Runtime>(
+    app: AppHandle<R>,
+    device_id: String,
+    options: Option<StreamOptions>,
+    on_frame: Channel<FrameEvent>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<StreamSession> {
+    let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
+
+    let options = options
+        .or_else(|| {
+            camera
+                .profiles
+                .active(&device_id)
+                .map(|profile| profile.stream)
+        })
+        .unwrap_or_default();
+    let stream_id = camera
+        .get_or_start_stream(device_id, options, on_frame)
+        .await?;
+    let token = camera.stream_token(&stream_id).await?;
+    Ok(StreamSession { stream_id, token })
+}
+
+/// Resume receiving the frames of a running stream on a new channel, e.g. after a
+/// webview reload; the stream's previous frontend channels are dropped
+#[command]
+pub async fn reattach_stream<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    on_frame: Channel<FrameEvent>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
+) -> Result<String> {
+    authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    app.camera().reattach_stream(&stream_id, on_frame).await
+}
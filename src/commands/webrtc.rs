@@ -1,8 +1,13 @@
+use super::streaming::authorize;
 use crate::error::{Error, Result};
-use crate::webrtc::{CreatePeerConnectionRequest, IceCandidateData, SessionDescriptionData};
+use crate::models::{StartStreamRequest, StreamOptions, StreamScope};
+use crate::webrtc::{
+    BandwidthEstimate, CreatePeerConnectionRequest, IceCandidateData, SessionDescriptionData,
+    VideoConfig,
+};
 use crate::CameraExt;
 
-use tauri::{command, AppHandle, Runtime};
+use tauri::{command, ipc::CommandScope, AppHandle, Runtime};
 use webrtc::ice_transport::ice_candidate::RTCIceCandidateInit;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
@@ -90,6 +95,8 @@ pub async fn set_remote_description<R: Runtime>(
     let manager = &app.camera().webrtc_manager;
     let conn = manager.get_connection(&connection_id).await?;
 
+    let answer = description.sdp_type.eq_ignore_ascii_case("answer");
+    let sdp_text = description.sdp.clone();
     // Parse based on provided type
     let sdp = match description.sdp_type.to_lowercase().as_str() {
         "offer" => RTCSessionDescription::offer(description.sdp)
@@ -109,6 +116,13 @@ pub async fn set_remote_description<R: Runtime>(
         .await
         .map_err(|e| Error::CameraError(format!("Failed to set remote description: {}", e)))?;
 
+    // Encode what the peer actually negotiated
+    if answer {
+        app.camera()
+            .negotiate_video(&connection_id, &sdp_text)
+            .await?;
+    }
+
     Ok(())
 }
 
@@ -140,21 +154,7 @@ pub async fn add_ice_candidate<R: Runtime>(
 /// Close peer connection
 #[command]
 pub async fn close_connection<R: Runtime>(app: AppHandle<R>, connection_id: String) -> Result<()> {
-    let camera = app.camera();
-    let manager = &camera.webrtc_manager;
-
-    // Find and stop the stream linked to this connection
-    if let Some(stream_id) = manager.get_stream_for_connection(&connection_id).await {
-        log::info!(
-            "Closing connection {} with linked stream {}",
-            connection_id,
-            stream_id
-        );
-        camera.stop_streaming(stream_id).await?;
-    }
-
-    // Close the peer connection
-    manager.remove_connection(&connection_id).await
+    app.camera().close_connection(&connection_id).await
 }
 
 /// Get peer connection state
@@ -169,14 +169,44 @@ pub async fn get_connection_state<R: Runtime>(
     Ok(conn.pc.connection_state().to_string())
 }
 
+/// Make the next video frame sent to a connection a keyframe, so its decoder can recover
+#[command]
+pub async fn request_keyframe<R: Runtime>(app: AppHandle<R>, connection_id: String) -> Result<()> {
+    app.camera()
+        .webrtc_manager
+        .request_keyframe(&connection_id)
+        .await
+}
+
+/// Estimate the uplink available to a connection, to pick an initial resolution and bitrate
+#[command]
+pub async fn probe_bandwidth<R: Runtime>(
+    app: AppHandle<R>,
+    connection_id: String,
+    max_kbps: Option<u32>,
+) -> Result<BandwidthEstimate> {
+    app.camera()
+        .webrtc_manager
+        .probe_bandwidth(&connection_id, max_kbps)
+        .await
+}
+
 /// Composite command: initialize camera, attach track, create connection, and return offer
+/// `video` selects the capture format and the encoder settings of the track
+/// Sending a stream already running on the device requires its `token`.
 #[command]
 pub async fn start_camera_webrtc_session<R: Runtime>(
     app: AppHandle<R>,
     device_id: String,
     ice_servers: Vec<RTCIceServer>,
+    video: Option<VideoConfig>,
+    token: Option<String>,
+    scope: CommandScope<StreamScope>,
 ) -> Result<(SessionDescriptionData, String)> {
     let camera = app.camera();
+    if let Some(stream_id) = camera.stream_id_by_device_id(&device_id).await {
+        authorize(&app, &stream_id, token.as_deref(), &scope).await?;
+    }
     // Initialize camera system (idempotent)
     camera.initialize().await?;
 
@@ -188,9 +218,22 @@ pub async fn start_camera_webrtc_session<R: Runtime>(
         .register_device_for_connection(connection_id.clone(), device_id.clone())
         .await?;
 
-    // Attach H.264 video track so SDP advertises video
-    manager.attach_h264_video_track(&connection_id).await?;
-    let stream_id = camera.start_streaming(device_id.clone()).await?;
+    // Attach the video track so SDP advertises video
+    let video = video.unwrap_or_default();
+    manager
+        .attach_video_track(&connection_id, video.codec.unwrap_or_default())
+        .await?;
+    let options = StreamOptions {
+        encoder: video.encoder,
+        ..Default::default()
+    };
+    let request = StartStreamRequest {
+        device_id: device_id.clone(),
+        width: video.width,
+        height: video.height,
+        fps: video.fps,
+    };
+    let stream_id = camera.start_streaming_with_format(request, options).await?;
 
     // Register stream_id for this connection (for cleanup on close)
     manager
@@ -0,0 +1,173 @@
+use crate::error::{Error, Result};
+use crate::models::FrameEvent;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{oneshot, watch};
+
+/// Receives the frames of a camera stream inside the app, e.g. from another plugin
+/// (OCR, ML, printing), so it doesn't have to open the device itself.
+///
+/// Frames are delivered on a blocking thread, one at a time; while `on_frame` runs,
+/// newer frames replace each other and only the latest one is delivered next.
+/// Frames with `repeated` set carry no data (the picture did not change).
+///
+/// Closures implement this trait:
+///
+/// ```ignore
+/// use tauri_plugin_camera::CameraExt;
+///
+/// app.camera()
+///     .register_consumer("ocr", "0", Box::new(|frame: &FrameEvent| {
+///         println!("{}x{} {}", frame.width, frame.height, frame.format);
+///     }))
+///     .await?;
+/// ```
+pub trait FrameConsumer: Send + 'static {
+    fn on_frame(&mut self, frame: &FrameEvent);
+
+    /// Receive the original capture buffers through `on_raw_frame` instead of `on_frame`:
+    /// no dedup, HDR or color conversion, and no copy. The stream's processing still runs
+    /// for the other consumers of the device.
+    fn raw(&self) -> bool {
+        false
+    }
+
+    /// Called for every captured frame when `raw` returns true
+    #[cfg(desktop)]
+    fn on_raw_frame(&mut self, _frame: &crabcamera::CameraFrame) {}
+
+    /// Called once when the consumer is unregistered or its stream stops
+    fn on_stop(&mut self) {}
+}
+
+impl<F> FrameConsumer for F
+where
+    F: FnMut(&FrameEvent) + Send + 'static,
+{
+    fn on_frame(&mut self, frame: &FrameEvent) {
+        self(frame)
+    }
+}
+
+/// Frames a consumer is fed with
+pub enum FrameFeed {
+    Converted(watch::Receiver<Option<FrameEvent>>),
+    #[cfg(desktop)]
+    Raw(watch::Receiver<Option<Arc<crabcamera::CameraFrame>>>),
+}
+
+/// Registered consumers, by ID
+#[derive(Default)]
+pub struct Consumers {
+    stops: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+}
+
+impl Consumers {
+    /// Feed the frames of `feed` to `consumer` until it is unregistered or the stream stops
+    pub fn register(
+        &self,
+        id: String,
+        feed: FrameFeed,
+        consumer: Box<dyn FrameConsumer>,
+    ) -> Result<()> {
+        let mut stops = self.stops.lock().unwrap();
+        if stops.contains_key(&id) {
+            return Err(Error::CameraError(format!(
+                "Frame consumer already registered: {}",
+                id
+            )));
+        }
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        stops.insert(id.clone(), stop_tx);
+        let stops = self.stops.clone();
+        match feed {
+            FrameFeed::Converted(receiver) => {
+                tokio::spawn(run_consumer(
+                    id,
+                    receiver,
+                    consumer,
+                    stop_rx,
+                    stops,
+                    |c, f| c.on_frame(f),
+                ));
+            }
+            #[cfg(desktop)]
+            FrameFeed::Raw(receiver) => {
+                tokio::spawn(run_consumer(
+                    id,
+                    receiver,
+                    consumer,
+                    stop_rx,
+                    stops,
+                    |c, f| c.on_raw_frame(f),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of registered consumers
+    pub fn count(&self) -> usize {
+        self.stops.lock().unwrap().len()
+    }
+
+    pub fn unregister(&self, id: &str) -> Result<()> {
+        let stop = self
+            .stops
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| Error::CameraError(format!("Frame consumer not found: {}", id)))?;
+        let _ = stop.send(());
+        Ok(())
+    }
+}
+
+async fn run_consumer<T: Clone + Send + Sync + 'static>(
+    id: String,
+    mut receiver: watch::Receiver<Option<T>>,
+    mut consumer: Box<dyn FrameConsumer>,
+    mut stop: oneshot::Receiver<()>,
+    stops: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    deliver: fn(&mut dyn FrameConsumer, &T),
+) {
+    log::info!("Frame consumer {} started", id);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            changed = receiver.changed() => {
+                if changed.is_err() {
+                    // Stream stopped
+                    break;
+                }
+                let Some(frame) = receiver.borrow_and_update().clone() else {
+                    continue;
+                };
+                // Consumers may do heavy work; keep it off the async runtime
+                let delivered = tokio::task::spawn_blocking(move || {
+                    deliver(consumer.as_mut(), &frame);
+                    consumer
+                })
+                .await;
+                consumer = match delivered {
+                    Ok(consumer) => consumer,
+                    Err(e) => {
+                        log::error!("Frame consumer {} panicked: {}", id, e);
+                        stops.lock().unwrap().remove(&id);
+                        return;
+                    }
+                };
+            }
+        }
+    }
+
+    consumer.on_stop();
+    // Forget the consumer if it ended with its stream rather than being unregistered
+    drop(stop);
+    let mut stops = stops.lock().unwrap();
+    if stops.get(&id).is_some_and(|stop| stop.is_closed()) {
+        stops.remove(&id);
+    }
+    log::info!("Frame consumer {} stopped", id);
+}
@@ -0,0 +1,144 @@
+use crate::error::Result;
+use crate::facing::{facing, label};
+use crate::models::{CameraFacing, CameraPreference, DefaultCameraConfig};
+use crabcamera::CameraDeviceInfo;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime};
+
+const DEFAULT_CAMERA_FILE: &str = "camera-default.json";
+
+/// Devices chosen by the user and by use, persisted in the app data directory
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Choices {
+    /// Set with `set_default_camera`, wins while connected
+    default: Option<String>,
+    /// Device of the last started stream
+    last_used: Option<String>,
+}
+
+/// Picks the camera used when none is specified
+pub struct DefaultCamera {
+    path: PathBuf,
+    choices: Mutex<Choices>,
+    prefer: CameraPreference,
+    remember_last_used: bool,
+}
+
+impl DefaultCamera {
+    pub fn load<R: Runtime>(app: &AppHandle<R>, config: &DefaultCameraConfig) -> Result<Self> {
+        let path = app.path().app_data_dir()?.join(DEFAULT_CAMERA_FILE);
+        let choices = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable default camera {:?}: {}", path, e);
+                Choices::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Choices::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            choices: Mutex::new(choices),
+            prefer: config.prefer.unwrap_or(CameraPreference::External),
+            remember_last_used: config.remember_last_used.unwrap_or(true),
+        })
+    }
+
+    /// The default camera among connected `devices`: the one set by the app, else the last
+    /// used one, else the best match of the preference, in enumeration order on ties
+    pub fn select<'a>(&self, devices: &'a [CameraDeviceInfo]) -> Option<&'a CameraDeviceInfo> {
+        let choices = self.choices.lock().unwrap().clone();
+        let connected = |id: &Option<String>| {
+            devices
+                .iter()
+                .find(|device| Some(&device.id) == id.as_ref())
+        };
+        connected(&choices.default)
+            .or_else(|| {
+                self.remember_last_used
+                    .then(|| connected(&choices.last_used))
+                    .flatten()
+            })
+            .or_else(|| {
+                devices
+                    .iter()
+                    .min_by_key(|device| rank(self.prefer, device))
+            })
+    }
+
+    /// Set the default camera, or clear it to fall back to the policy
+    pub fn set(&self, device_id: Option<String>) -> Result<()> {
+        let mut choices = self.choices.lock().unwrap();
+        choices.default = device_id;
+        self.persist(&choices)
+    }
+
+    /// Remember the device of a started stream
+    pub fn record_use(&self, device_id: &str) {
+        if !self.remember_last_used {
+            return;
+        }
+        let mut choices = self.choices.lock().unwrap();
+        if choices.last_used.as_deref() == Some(device_id) {
+            return;
+        }
+        choices.last_used = Some(device_id.to_string());
+        if let Err(e) = self.persist(&choices) {
+            log::warn!("Failed to remember the last used camera: {}", e);
+        }
+    }
+
+    fn persist(&self, choices: &Choices) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(choices)?)?;
+        Ok(())
+    }
+}
+
+/// How well a device matches the preference, lower is better
+fn rank(prefer: CameraPreference, device: &CameraDeviceInfo) -> u8 {
+    let facing = facing(device);
+    match prefer {
+        CameraPreference::External if label(device).contains("usb") => 0,
+        CameraPreference::External if facing == CameraFacing::External => 1,
+        CameraPreference::Front if facing == CameraFacing::Front => 0,
+        CameraPreference::Back if facing == CameraFacing::Back => 0,
+        CameraPreference::First => 0,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, name: &str) -> CameraDeviceInfo {
+        CameraDeviceInfo::new(id.to_string(), name.to_string())
+    }
+
+    #[test]
+    fn test_rank_by_preference() {
+        let devices = [
+            device("0", "OBS Virtual Camera"),
+            device("1", "Integrated Webcam"),
+            device("2", "Logitech BRIO"),
+            device("3", "USB Video Device"),
+            device("4", "Rear Camera"),
+        ];
+        let best = |prefer| {
+            devices
+                .iter()
+                .min_by_key(|device| rank(prefer, device))
+                .map(|device| device.id.as_str())
+        };
+        assert_eq!(best(CameraPreference::External), Some("3"));
+        assert_eq!(best(CameraPreference::Front), Some("1"));
+        assert_eq!(best(CameraPreference::Back), Some("4"));
+        assert_eq!(best(CameraPreference::First), Some("0"));
+    }
+}
@@ -1,159 +1,2725 @@
+use crate::bandwidth::BitrateController;
+use crate::budget::{MemoryBudget, Reservation, StreamMemory};
+use crate::consumers::{Consumers, FrameConsumer, FrameFeed};
+use crate::default_camera::DefaultCamera;
+use crate::dump::{DumpWriter, ReplaySource};
+use crate::encoder::{
+    EncodedFeed, EncoderFactory, EncoderRegistry, SharedEncodedFrame, DEFAULT_ENCODER,
+};
 use crate::error::{Error, Result};
-use crate::models::FrameEvent;
-use crate::utils::yuv_nv12_to_h264;
+use crate::eventlog::{elapsed_us, EventLog, Stage, StageLog, StageRecord};
+use crate::faults::{FaultState, Faults};
+use crate::models::{
+    AutoControls, BitrateAdaptationConfig, BitrateChangedEvent, CameraAccess, CameraControl,
+    CameraControlInfo, CameraFacing, CameraFormat, CameraInfo, Config, ControlSettings,
+    CountdownEvent, CropRect, DiagnosticsReport, EncoderSettings, Exposure, ExposureMode,
+    FaultInjection, FlashMode, FocusLockedEvent, FocusMode, FrameEvent, LatencyMode,
+    MediaLivenessEvent, MonitorInfo, OverlayOptions, OverlayUpdate, Photo, PhotoOptions,
+    PipOptions, PipelineDesc, PipelineInfo, PowerMode, PreviewRect, RawFrame, RecordingOptions,
+    RecordingSummary, ResourceUsage, SessionUsage, SinkDesc, StartStreamRequest, StreamOptions,
+    StreamStats, TimelapseOptions, TimelapseSummary, VideoCodec, WhiteBalance, WindowPosition,
+    WindowSize, ZoomRange,
+};
+use crate::photo::Shot;
+use crate::pipeline::{FilterFactory, FilterRegistry, FrameFilter, FrameProcessor};
+use crate::portal::Portal;
+use crate::preroll::{PrerollBuffer, MAX_PREROLL_SECS};
+use crate::preview::{
+    preview_window_builder, set_window_opacity, NativePreviews, PREVIEW_WINDOW_PREFIX,
+};
+use crate::profiles::ProfileStore;
+use crate::recording::{
+    check_container, check_free_space, AudioCapture, RecordingOutput, Recordings, SegmentLimits,
+};
+use crate::scope::OutputScope;
+use crate::session_controls::SessionControls;
+use crate::sinks::{spawn_channel_sink, SinkStats};
+use crate::sources::{FrameCallback, FrameSource, Sources};
+use crate::telemetry::{Telemetry, TelemetryEvent};
+use crate::thumbnail::Thumbnail;
+use crate::timelapse::Timelapses;
+use crate::watchdog::Heartbeat;
+use crate::workdir::WorkDir;
+use crate::zoom::{Zooms, MAX_DIGITAL_ZOOM};
+use crate::CameraExt;
+use crabcamera::commands::advanced::{get_camera_controls, set_camera_controls};
 use crabcamera::init::initialize_camera_system;
-use crabcamera::permissions::PermissionInfo;
+use crabcamera::permissions::{PermissionInfo, PermissionStatus};
+use crabcamera::types::{CameraControls, WhiteBalance as CrabWhiteBalance};
 use crabcamera::{get_available_cameras, request_camera_permission};
-use crabcamera::{get_recommended_format, set_callback, start_camera_preview, CameraDeviceInfo};
-use serde::de::DeserializeOwned;
-use std::collections::HashMap;
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
-use tokio::sync::watch;
+use crabcamera::{
+    get_recommended_format, set_callback, start_camera_preview, CameraDeviceInfo, CameraFrame,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{
+    ipc::Channel, plugin::PluginApi, AppHandle, Emitter, LogicalPosition, Manager, Runtime,
+    WebviewWindow, WindowEvent,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::{oneshot, watch};
 use tokio::time::Instant;
-pub fn init<R: Runtime, C: DeserializeOwned>(
+/// Distance between a picture-in-picture window and the screen edges
+const PIP_MARGIN: f64 = 24.0;
+/// Prefix of the IDs of frontend channel sinks
+const CHANNEL_SINK_PREFIX: &str = "channel-";
+/// Prefix of the IDs of frame dump consumers
+const DUMP_PREFIX: &str = "dump-";
+/// Prefix of the IDs of recordings, which are also their sink IDs
+const RECORDING_PREFIX: &str = "recording-";
+/// Prefix of the device IDs of replayed frame dumps
+const REPLAY_PREFIX: &str = "replay-";
+/// Default time without frames after which a stream's capture is reopened
+const DEFAULT_STALL_TIMEOUT_SECS: u64 = 5;
+/// Default free disk space recordings leave
+const DEFAULT_RECORDING_MIN_FREE_MB: u64 = 500;
+/// Default time without media after which a WebRTC connection is reported stalled
+const DEFAULT_MEDIA_TIMEOUT_SECS: u64 = 10;
+/// Default bounds of adapting WebRTC bitrates, and where they start without a configured one
+const DEFAULT_MIN_BITRATE_KBPS: u32 = 150;
+const DEFAULT_MAX_BITRATE_KBPS: u32 = 4000;
+const DEFAULT_START_BITRATE_KBPS: u32 = 1000;
+/// How often adapting WebRTC bitrates follow the receiver reports of their peer
+const BITRATE_ADAPTATION_INTERVAL: Duration = Duration::from_secs(1);
+/// Frame rates closer than this are the same rate (drivers report 29.97 for 30)
+const FPS_TOLERANCE: f64 = 0.5;
+/// Time crabcamera needs to fully release a closed camera
+const CAMERA_RELEASE_DELAY: tokio::time::Duration = tokio::time::Duration::from_millis(500);
+/// Duration of the first sample of a WebRTC sink, before there is a previous frame to compare
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_micros(33_333);
+/// Time autofocus runs for a triggered focus pass before the lens is held
+const AUTOFOCUS_SETTLE: Duration = Duration::from_millis(1500);
+
+/// Capture callback of a stream, registered again when its capture is reopened
+type SharedCallback = Arc<Mutex<FrameCallback>>;
+
+/// Capture callback forwarding to the stream's shared one
+fn forward(callback: &SharedCallback) -> impl FnMut(CameraFrame) + Send + Sync + 'static {
+    let callback = callback.clone();
+    move |frame| (callback.lock().unwrap())(frame)
+}
+
+pub fn init<R: Runtime>(
     app: &AppHandle<R>,
-    _api: PluginApi<R, C>,
+    api: PluginApi<R, Option<Config>>,
+    telemetry: Telemetry,
 ) -> Result<Camera<R>> {
-    let webrtc_manager = crate::webrtc::WebRTCManager::new();
+    let config = api.config().clone().unwrap_or_default();
+    let mut webrtc_manager = crate::webrtc::WebRTCManager::new(telemetry.clone());
+    let media_timeout = config
+        .media_liveness
+        .timeout_secs
+        .unwrap_or(DEFAULT_MEDIA_TIMEOUT_SECS);
+    if media_timeout > 0 {
+        let app = app.clone();
+        let close_stalled = config.media_liveness.close_stalled;
+        webrtc_manager = webrtc_manager.with_liveness(
+            std::time::Duration::from_secs(media_timeout),
+            Arc::new(move |event: MediaLivenessEvent| {
+                let name = if event.stalled {
+                    "camera://media-stalled"
+                } else {
+                    "camera://media-resumed"
+                };
+                if let Err(e) = app.emit(name, &event) {
+                    log::warn!("Failed to emit {}: {}", name, e);
+                }
+                if event.stalled && close_stalled {
+                    let app = app.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = app.camera().close_connection(&event.connection_id).await {
+                            log::warn!(
+                                "Failed to close stalled connection {}: {}",
+                                event.connection_id,
+                                e
+                            );
+                        }
+                    });
+                }
+            }),
+        );
+    }
+
+    let camera = Camera {
+        app: app.clone(),
+        webrtc_manager,
+        active_streams: AsyncMutex::new(HashMap::new()),
+        profiles: ProfileStore::load(app)?,
+        default_camera: DefaultCamera::load(app, &config.default_camera)?,
+        native_previews: NativePreviews::default(),
+        consumers: Consumers::default(),
+        encoders: EncoderRegistry::default(),
+        sources: Sources::default(),
+        filters: FilterRegistry::default(),
+        faults: Faults::default(),
+        busy_devices: Mutex::new(HashSet::new()),
+        output_scope: OutputScope::new(app, &config),
+        memory: Arc::new(MemoryBudget::new(
+            config.memory_budget_mb.map(|mb| mb as usize * 1024 * 1024),
+        )),
+        work_dir: WorkDir::new(app, &config)?,
+        portal: Portal::new(config.capture_backend)?,
+        stall_timeout: match config
+            .stall_timeout_secs
+            .unwrap_or(DEFAULT_STALL_TIMEOUT_SECS)
+        {
+            0 => None,
+            secs => Some(std::time::Duration::from_secs(secs)),
+        },
+        telemetry,
+        event_log: Arc::new(EventLog::new(
+            &match &config.event_log.dir {
+                Some(dir) => dir.clone(),
+                None => app.path().app_log_dir()?,
+            },
+            &config.event_log,
+        )),
+        timelapses: Timelapses::default(),
+        recordings: Recordings::default(),
+        bitrate_adaptation: config.bitrate_adaptation.clone(),
+        recording_min_free: config
+            .recording_min_free_mb
+            .unwrap_or(DEFAULT_RECORDING_MIN_FREE_MB)
+            * 1024
+            * 1024,
+        session_controls: SessionControls::default(),
+        zooms: Zooms::default(),
+    };
+    if config.auto_ice_restart.unwrap_or(true) {
+        crate::network::spawn_monitor(app.clone());
+    }
+    if config.watch_devices.unwrap_or(true) {
+        crate::devices::spawn_monitor(app.clone());
+    }
+    #[cfg(feature = "mock-camera")]
+    camera.register_source(Arc::new(crate::mock::MockCamera::new(
+        crate::mock::MOCK_DEVICE_ID,
+    )));
+    Ok(camera)
+}
+
+struct ActiveStream {
+    camera_id: String,
+    /// Required from webviews to control the stream
+    token: String,
+    start_time: Instant,
+    rx: watch::Receiver<Option<FrameEvent>>,
+    /// Unprocessed capture frames, only sent while raw consumers are subscribed
+    raw_tx: watch::Sender<Option<Arc<CameraFrame>>>,
+    /// Custom source feeding the stream instead of a crabcamera device
+    source: Option<Arc<dyn FrameSource>>,
+    /// Format the crabcamera device was opened with
+    capture_format: Option<crabcamera::CameraFormat>,
+    callback: SharedCallback,
+    /// Stops the stall watchdog when the stream is dropped
+    _watchdog: Option<oneshot::Sender<()>>,
+    /// Time of the latest frame, watched by the stall watchdog
+    heartbeat: Heartbeat,
+    /// Encoding passes shared by the stream's encoding sinks, by encoder name; each one
+    /// is started by its first sink
+    encoded: HashMap<String, EncodedFeed>,
+    /// Last seconds of encoded frames, kept for recordings started with a pre-roll
+    preroll: Option<PrerollBuffer>,
+    /// Stop handles of the channel and WebRTC sinks, by sink ID
+    sinks: HashMap<String, oneshot::Sender<()>>,
+    /// Frame buffers held by the stream, within the plugin's memory budget
+    memory: Arc<StreamMemory>,
+    /// Failures injected on the device
+    faults: Arc<FaultState>,
+    /// Event log records of the device's pipeline stages
+    stages: StageLog,
+    options: StreamOptions,
+    sink_stats: Arc<Mutex<SinkStats>>,
+    /// Set while the stream is paused: the camera stays open but frames aren't processed
+    paused: Arc<AtomicBool>,
+}
+/// Access to the camera APIs.
+pub struct Camera<R: Runtime> {
+    app: AppHandle<R>,
+    pub webrtc_manager: crate::webrtc::WebRTCManager,
+    active_streams: AsyncMutex<HashMap<String, ActiveStream>>,
+    pub profiles: ProfileStore,
+    default_camera: DefaultCamera,
+    native_previews: NativePreviews,
+    consumers: Consumers,
+    encoders: EncoderRegistry,
+    sources: Sources,
+    filters: FilterRegistry,
+    faults: Faults,
+    /// Devices with a start or stop in progress
+    busy_devices: Mutex<HashSet<String>>,
+    output_scope: OutputScope,
+    memory: Arc<MemoryBudget>,
+    /// Temporary encoding artifacts, removed on exit
+    pub(crate) work_dir: WorkDir,
+    /// PipeWire camera portal, used instead of direct device access when enabled
+    portal: Portal,
+    /// Time without frames after which a stream's capture is reopened, `None` to never
+    stall_timeout: Option<std::time::Duration>,
+    /// Lifecycle hooks of the host app
+    telemetry: Telemetry,
+    /// Diagnostic JSON log of the frame pipeline
+    event_log: Arc<EventLog>,
+    timelapses: Timelapses,
+    recordings: Recordings,
+    /// Free disk space recordings leave, in bytes; 0 disables the guard
+    recording_min_free: u64,
+    /// Bounds of the bitrate of WebRTC sinks following their network
+    bitrate_adaptation: BitrateAdaptationConfig,
+    /// Controls applied to each device, restored when it is reopened
+    session_controls: SessionControls,
+    /// Zoom of each device, digital unless the device zooms itself
+    zooms: Zooms,
+}
+
+impl<R: Runtime> Camera<R> {
+    /// Request camera permission from the system
+    /// Reported as denied, with what to change, when an OS privacy switch blocks the cameras
+    /// or, on macOS, when the permission prompt can't appear
+    pub async fn request_permission(&self) -> Result<PermissionInfo> {
+        let mut info = request_camera_permission().await.map_err(|e| {
+            Error::CameraError(format!("Failed to request camera permission: {}", e))
+        })?;
+        if let Some(reason) = crate::privacy::blocked_reason() {
+            if !matches!(info.status, PermissionStatus::Restricted) {
+                info.status = PermissionStatus::Denied;
+            }
+            info.message = reason;
+            info.can_request = false;
+        }
+        Ok(info)
+    }
+
+    /// macOS: authorization status, and the Info.plist and signing checks the prompt depends on
+    pub fn get_camera_access(&self) -> Result<CameraAccess> {
+        crate::privacy::camera_access()
+    }
+
+    /// macOS: forget the app's camera decision so the next request shows the prompt again
+    pub fn reset_camera_permission(&self) -> Result<()> {
+        crate::privacy::reset_camera_access(&self.app.config().identifier)
+    }
+
+    pub async fn initialize(&self) -> Result<String> {
+        initialize_camera_system()
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to initialize camera system: {}", e)))
+    }
+
+    /// List all available camera devices, followed by the sources registered by the app
+    /// With the `mock-camera` feature, real devices are hidden behind the mock one
+    /// With the PipeWire portal, the cameras it shares are listed instead of the V4L2 devices
+    pub async fn get_available_cameras(&self) -> Result<Vec<CameraDeviceInfo>> {
+        let mut devices = if cfg!(feature = "mock-camera") || self.portal.enabled() {
+            Vec::new()
+        } else {
+            get_available_cameras()
+                .await
+                .map_err(|e| Error::CameraError(format!("Failed to list devices: {}", e)))?
+        };
+        self.portal.refresh(&self.sources).await?;
+        devices.extend(self.sources.infos());
+
+        Ok(devices)
+    }
+
+    /// Camera to use when none is specified, `None` without any camera connected
+    /// The device set with `set_default_camera` wins, then the device of the last started
+    /// stream, then the best match of `Config::default_camera`.
+    pub async fn get_default_camera(&self) -> Result<Option<CameraInfo>> {
+        let devices: Vec<_> = self
+            .get_available_cameras()
+            .await?
+            .into_iter()
+            .filter(|device| !device.id.starts_with(REPLAY_PREFIX))
+            .collect();
+        Ok(self
+            .default_camera
+            .select(&devices)
+            .cloned()
+            .map(Into::into))
+    }
+
+    /// Connected cameras with where they face
+    pub async fn list_cameras(&self) -> Result<Vec<CameraInfo>> {
+        Ok(self
+            .get_available_cameras()
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Connected cameras facing `facing`, in enumeration order, e.g. to switch between the
+    /// front and back cameras
+    pub async fn get_cameras_by_facing(&self, facing: CameraFacing) -> Result<Vec<CameraInfo>> {
+        Ok(self
+            .list_cameras()
+            .await?
+            .into_iter()
+            .filter(|camera| camera.facing == facing)
+            .collect())
+    }
+
+    /// Make a device the default camera, persisted across launches; `None` goes back to
+    /// the selection policy
+    pub async fn set_default_camera(&self, device_id: Option<String>) -> Result<()> {
+        if let Some(device_id) = &device_id {
+            if !self
+                .get_available_cameras()
+                .await?
+                .iter()
+                .any(|device| &device.id == device_id)
+            {
+                return Err(Error::DeviceNotFound(device_id.clone()));
+            }
+        }
+        self.default_camera.set(device_id)
+    }
+
+    /// Capture formats a device supports (resolution, frame rate, pixel format), from the
+    /// highest resolution down, the fastest rate first among equal sizes
+    pub async fn get_device_capabilities(&self, device_id: &str) -> Result<Vec<CameraFormat>> {
+        let device = self
+            .get_available_cameras()
+            .await?
+            .into_iter()
+            .find(|device| device.id == device_id)
+            .ok_or_else(|| Error::DeviceNotFound(device_id.to_string()))?;
+        Ok(device_formats(&device.supports_formats))
+    }
+
+    /// Self-test: permission, device enumeration, one-frame capture from the default camera,
+    /// conversion and encode, with per-step timings and failures
+    pub async fn run_diagnostics(&self) -> Result<DiagnosticsReport> {
+        Ok(crate::diagnostics::run(self).await)
+    }
+
+    /// Capture a still photo, encoded as JPEG or PNG
+    /// A stream already running on the device is read without interruption, at its
+    /// resolution; otherwise the device is opened at its highest resolution for the shot.
+    pub async fn capture_photo(&self, device_id: &str, options: PhotoOptions) -> Result<Photo> {
+        let path = self.check_photo_options(&options)?;
+        let frame = self
+            .capture_frames(device_id, 1, Duration::ZERO)
+            .await?
+            .remove(0);
+        let camera = self.device_name(device_id).await;
+        let zoom = self.zooms.digital(device_id).get();
+        crate::photo::save(Shot::Raw { frame, zoom }, &options, path, camera).await
+    }
+
+    /// Capture a single frame for Rust code that processes it natively (OCR, upload, ...),
+    /// without going through a webview channel
+    /// Like `capture_photo`, a running stream is read without interruption.
+    pub async fn capture_raw_frame(&self, device_id: &str) -> Result<RawFrame> {
+        let frame = self
+            .capture_frames(device_id, 1, Duration::ZERO)
+            .await?
+            .remove(0);
+        let zoom = self.zooms.digital(device_id).get();
+        let event = tokio::task::spawn_blocking(move || crate::photo::to_srgb(frame, zoom))
+            .await
+            .map_err(|e| Error::CameraError(format!("Frame conversion failed: {}", e)))??;
+        Ok(RawFrame {
+            device_id: device_id.to_string(),
+            data: event.data,
+            width: event.width,
+            height: event.height,
+            capture_time_ms: event.capture_time_ms,
+        })
+    }
+
+    /// Capture a photo after a countdown of `delay_seconds`, sending the seconds left on
+    /// `on_countdown` every second, then 0 as the frame is taken
+    /// The camera opens when the countdown starts, so its warm-up doesn't delay the shot.
+    pub async fn capture_photo_with_timer(
+        &self,
+        device_id: &str,
+        delay_seconds: u32,
+        options: PhotoOptions,
+        on_countdown: Channel<CountdownEvent>,
+    ) -> Result<Photo> {
+        if delay_seconds > crate::photo::MAX_TIMER_SECONDS {
+            return Err(Error::InvalidConfig(format!(
+                "A photo timer lasts up to {} seconds, not {}",
+                crate::photo::MAX_TIMER_SECONDS,
+                delay_seconds
+            )));
+        }
+        let path = self.check_photo_options(&options)?;
+        let opened = match self.stream_id_by_device_id(device_id).await {
+            Some(_) => None,
+            // Only a stream this call started is stopped after the shot
+            None => match self.open_for_capture(device_id).await? {
+                (stream_id, true) => Some(stream_id),
+                (_, false) => None,
+            },
+        };
+
+        let shot = async {
+            let mut tick = tokio::time::interval(Duration::from_secs(1));
+            for remaining_seconds in (0..=delay_seconds).rev() {
+                tick.tick().await;
+                on_countdown
+                    .send(CountdownEvent { remaining_seconds })
+                    .map_err(|_| Error::ChannelSendError)?;
+            }
+            let stream_id = match &opened {
+                Some(stream_id) => stream_id.clone(),
+                None => self
+                    .stream_id_by_device_id(device_id)
+                    .await
+                    .ok_or_else(|| Error::StreamNotFound(device_id.to_string()))?,
+            };
+            // Without a countdown, a camera just opened still has to settle
+            let skip = if opened.is_some() && delay_seconds == 0 {
+                crate::photo::WARMUP_FRAMES
+            } else {
+                0
+            };
+            let receiver = self.raw_receiver(&stream_id).await?;
+            crate::photo::next_frames(receiver, skip, 1, Duration::ZERO).await
+        }
+        .await;
+        if let Some(stream_id) = opened {
+            self.stop_streaming(stream_id).await?;
+        }
+
+        let frame = shot?.remove(0);
+        let camera = self.device_name(device_id).await;
+        let zoom = self.zooms.digital(device_id).get();
+        crate::photo::save(Shot::Raw { frame, zoom }, &options, path, camera).await
+    }
+
+    /// Reject photo options that can't be honored before opening the camera; returns the
+    /// checked output path, if any
+    /// Desktop cameras have no flash: `On` is refused, `Auto` never fires.
+    fn check_photo_options(&self, options: &PhotoOptions) -> Result<Option<PathBuf>> {
+        if options.flash == FlashMode::On {
+            return Err(Error::Unsupported(
+                "Flash is only available on mobile".to_string(),
+            ));
+        }
+        crate::photo::check_metadata(&options.metadata)?;
+        options
+            .path
+            .as_deref()
+            .map(|path| self.check_output_path(path))
+            .transpose()
+    }
+
+    /// Name of a connected device, written to the EXIF metadata of its photos
+    async fn device_name(&self, device_id: &str) -> Option<String> {
+        self.get_available_cameras()
+            .await
+            .ok()?
+            .into_iter()
+            .find(|device| device.id == device_id)
+            .map(|device| device.name)
+    }
+
+    /// Encode the latest frame of a running stream as JPEG or PNG, as the stream delivers it
+    /// (cropped, scaled and filtered); the stream and its WebRTC sessions carry on untouched
+    pub async fn capture_frame_from_stream(
+        &self,
+        stream_id: &str,
+        options: PhotoOptions,
+    ) -> Result<Photo> {
+        let path = self.check_photo_options(&options)?;
+        let (receiver, device_id) = {
+            let streams = self.active_streams.lock().await;
+            let stream = streams.get(stream_id).ok_or_else(|| {
+                Error::StreamNotFound(format!("No active stream with ID: {}", stream_id))
+            })?;
+            (stream.rx.clone(), stream.camera_id.clone())
+        };
+        let event = crate::photo::latest_event(receiver).await?;
+        let camera = self.device_name(&device_id).await;
+        crate::photo::save(Shot::Delivered(event), &options, path, camera).await
+    }
+
+    /// Capture `count` photos `interval` apart, e.g. to pick the best shot; with `options.path`,
+    /// shot `n` is written next to it with `-n` appended to the file name
+    /// Like `capture_photo`, a running stream is reused instead of reopening the device.
+    pub async fn capture_burst(
+        &self,
+        device_id: &str,
+        count: u32,
+        interval: Duration,
+        options: PhotoOptions,
+    ) -> Result<Vec<Photo>> {
+        if count == 0 || count > crate::photo::MAX_BURST_SHOTS {
+            return Err(Error::InvalidConfig(format!(
+                "A burst takes 1 to {} shots, not {}",
+                crate::photo::MAX_BURST_SHOTS,
+                count
+            )));
+        }
+        let path = self.check_photo_options(&options)?;
+        let frames = self.capture_frames(device_id, count, interval).await?;
+        let camera = self.device_name(device_id).await;
+        let zoom = self.zooms.digital(device_id).get();
+
+        let mut photos = Vec::with_capacity(frames.len());
+        for (index, frame) in frames.into_iter().enumerate() {
+            let path = path
+                .as_deref()
+                .map(|path| crate::photo::burst_path(path, index));
+            photos.push(
+                crate::photo::save(Shot::Raw { frame, zoom }, &options, path, camera.clone())
+                    .await?,
+            );
+        }
+        Ok(photos)
+    }
+
+    /// Write a JPEG of the device to `output_dir` every `interval` until `stop_timelapse`,
+    /// emitting `camera://timelapse-progress` after each shot; returns the time-lapse ID
+    pub async fn start_timelapse(
+        &self,
+        device_id: &str,
+        interval: Duration,
+        output_dir: &Path,
+        options: TimelapseOptions,
+    ) -> Result<String> {
+        if interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "The time-lapse interval must be positive".to_string(),
+            ));
+        }
+        let output_dir = self.check_output_path(output_dir)?;
+        tokio::fs::create_dir_all(&output_dir).await?;
+        let timelapse_id = self.timelapses.start(
+            self.app.clone(),
+            device_id.to_string(),
+            interval,
+            output_dir,
+            options,
+        );
+        log::info!(
+            "Time-lapse {} of {} started, one shot every {:?}",
+            timelapse_id,
+            device_id,
+            interval
+        );
+        Ok(timelapse_id)
+    }
+
+    /// Stop a time-lapse, assembling its shots into a video if it was asked to
+    pub async fn stop_timelapse(&self, timelapse_id: &str) -> Result<TimelapseSummary> {
+        self.timelapses.stop(timelapse_id).await
+    }
+
+    /// Record a device to an MP4 file, starting a stream if none runs on it
+    /// The recording shares the stream's encoding pass with its other encoding sinks; its ID
+    /// is also its sink ID. Returns the recording ID.
+    pub async fn start_recording(
+        &self,
+        device_id: &str,
+        path: &Path,
+        options: RecordingOptions,
+    ) -> Result<String> {
+        let path = self.check_output_path(path)?;
+        SegmentLimits::new(&options)?;
+        let owned_stream = match self.stream_id_by_device_id(device_id).await {
+            Some(_) => None,
+            None => {
+                let options = options
+                    .stream
+                    .clone()
+                    .or_else(|| {
+                        self.profiles
+                            .active(device_id)
+                            .map(|profile| profile.stream)
+                    })
+                    .unwrap_or_default();
+                if options.color_space.is_some() {
+                    return Err(Error::InvalidConfig(
+                        "Recordings encode raw frames, leave color_space unset".to_string(),
+                    ));
+                }
+                Some(self.start_streaming(device_id.to_string(), options).await?)
+            }
+        };
+
+        let recording_id = format!("{}{}", RECORDING_PREFIX, uuid::Uuid::new_v4());
+        if let Err(e) = self
+            .spawn_recorder(
+                &recording_id,
+                device_id,
+                &path,
+                &options,
+                owned_stream.clone(),
+                None,
+            )
+            .await
+        {
+            if let Some(stream_id) = owned_stream {
+                if let Err(stop_error) = self.stop_streaming(stream_id).await {
+                    log::warn!("Failed to stop recording stream: {}", stop_error);
+                }
+            }
+            return Err(e);
+        }
+        log::info!("Recording {} to {}", device_id, path.display());
+        Ok(recording_id)
+    }
+
+    /// Record a running stream to an MP4 file without interrupting it, e.g. to keep its
+    /// preview on screen; the camera callback and the encoding pass are shared with the
+    /// stream's other sinks. `options.stream` doesn't apply. Returns the recording ID, also
+    /// its sink ID.
+    pub async fn attach_recorder(
+        &self,
+        stream_id: &str,
+        path: &Path,
+        options: RecordingOptions,
+    ) -> Result<String> {
+        let path = self.check_output_path(path)?;
+        let device_id = self.stream_device_id(stream_id).await?;
+        let recording_id = format!("{}{}", RECORDING_PREFIX, uuid::Uuid::new_v4());
+        self.spawn_recorder(&recording_id, &device_id, &path, &options, None, None)
+            .await?;
+        log::info!("Recording stream {} to {}", stream_id, path.display());
+        Ok(recording_id)
+    }
+
+    /// Keep the last `seconds` of a stream's encoded frames in memory, so recordings can
+    /// include what happened before they were started (`start_recording_with_preroll`).
+    /// The stream is encoded continuously from then on.
+    pub async fn enable_preroll(&self, stream_id: &str, seconds: u32) -> Result<()> {
+        if seconds == 0 || seconds > MAX_PREROLL_SECS {
+            return Err(Error::InvalidConfig(format!(
+                "The pre-roll must last 1 to {} seconds",
+                MAX_PREROLL_SECS
+            )));
+        }
+        let device_id = self.stream_device_id(stream_id).await?;
+        let (feed, receiver) = self.subscribe_encoded(&device_id, false, None).await?;
+        let buffer = PrerollBuffer::spawn(feed, receiver, seconds);
+        self.active_streams
+            .lock()
+            .await
+            .get_mut(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?
+            .preroll = Some(buffer);
+        Ok(())
+    }
+
+    /// Stop keeping the pre-roll of a stream, releasing its frames
+    pub async fn disable_preroll(&self, stream_id: &str) -> Result<()> {
+        self.active_streams
+            .lock()
+            .await
+            .get_mut(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?
+            .preroll = None;
+        Ok(())
+    }
+
+    /// Record a running stream like `attach_recorder`, from `preroll_secs` before now: the
+    /// file starts with the frames kept by the stream's pre-roll (see `enable_preroll`),
+    /// at the latest keyframe before that time. Audio only starts with the call.
+    pub async fn start_recording_with_preroll(
+        &self,
+        stream_id: &str,
+        preroll_secs: u32,
+        path: &Path,
+        options: RecordingOptions,
+    ) -> Result<String> {
+        let path = self.check_output_path(path)?;
+        let device_id = self.stream_device_id(stream_id).await?;
+        let recording_id = format!("{}{}", RECORDING_PREFIX, uuid::Uuid::new_v4());
+        self.spawn_recorder(
+            &recording_id,
+            &device_id,
+            &path,
+            &options,
+            None,
+            Some(preroll_secs),
+        )
+        .await?;
+        log::info!(
+            "Recording stream {} to {} with {}s of pre-roll",
+            stream_id,
+            path.display(),
+            preroll_secs
+        );
+        Ok(recording_id)
+    }
+
+    async fn spawn_recorder(
+        &self,
+        recording_id: &str,
+        device_id: &str,
+        path: &Path,
+        options: &RecordingOptions,
+        owned_stream: Option<String>,
+        preroll_secs: Option<u32>,
+    ) -> Result<()> {
+        let limits = SegmentLimits::new(options)?;
+        check_free_space(path, self.recording_min_free)?;
+        // Everything is checked before the encoding pass is started or joined
+        let (mime_type, b_frames) = self.peek_encoded(device_id, false, None).await?;
+        check_container(options.container, &mime_type, options.audio.is_some())?;
+        if b_frames {
+            return Err(Error::CameraError(
+                "Recordings can't carry B-frames, set b_frames to 0 in the encoder settings"
+                    .to_string(),
+            ));
+        }
+        if options.thumbnail.is_some() && mime_type != "video/h264" {
+            return Err(Error::InvalidConfig(
+                "Thumbnails are only made from H.264 recordings".to_string(),
+            ));
+        }
+        if preroll_secs.is_some() && !self.keeps_preroll(device_id).await {
+            return Err(no_preroll(device_id));
+        }
+        let audio = options
+            .audio
+            .as_ref()
+            .map(AudioCapture::start)
+            .transpose()?;
+        let (feed, receiver) = self.subscribe_encoded(device_id, false, None).await?;
+        let mut output = RecordingOutput::create(
+            path,
+            options.container,
+            feed.mime_type(),
+            audio.as_ref().map(|audio| audio.track().clone()),
+            limits,
+        )?
+        .with_disk_guard(self.recording_min_free);
+        if let Some(thumbnail) = &options.thumbnail {
+            output = output.with_thumbnail(Thumbnail::new(thumbnail.clone()));
+        }
+        // Taken after subscribing, so no frame falls between the pre-roll and the live ones
+        if let Some(seconds) = preroll_secs {
+            let frames = self
+                .active_streams
+                .lock()
+                .await
+                .values()
+                .find(|stream| stream.camera_id == device_id)
+                .and_then(|stream| stream.preroll.as_ref())
+                .map(|preroll| preroll.snapshot(seconds));
+            // The file is already there: it goes if the recording doesn't start
+            let written = frames
+                .ok_or_else(|| no_preroll(device_id))
+                .and_then(|frames| output.write_preroll(&frames));
+            if let Err(e) = written {
+                output.discard();
+                return Err(e);
+            }
+        }
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        if let Some(stream) = self
+            .active_streams
+            .lock()
+            .await
+            .values_mut()
+            .find(|stream| stream.camera_id == device_id)
+        {
+            stream.sinks.insert(recording_id.to_string(), stop_tx);
+        }
+        self.recordings.start(
+            self.app.clone(),
+            recording_id.to_string(),
+            device_id.to_string(),
+            owned_stream,
+            output,
+            feed,
+            receiver,
+            audio,
+            stop_rx,
+        );
+        Ok(())
+    }
+
+    /// Whether the stream of a device keeps a pre-roll, see `enable_preroll`
+    async fn keeps_preroll(&self, device_id: &str) -> bool {
+        self.active_streams
+            .lock()
+            .await
+            .values()
+            .any(|stream| stream.camera_id == device_id && stream.preroll.is_some())
+    }
+
+    /// Names of the microphones recordings can take sound from
+    #[cfg(feature = "audio")]
+    pub async fn list_microphones(&self) -> Result<Vec<String>> {
+        tokio::task::spawn_blocking(crate::audio::list_microphones)
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to list microphones: {}", e)))?
+    }
+
+    /// Names of the microphones recordings can take sound from
+    #[cfg(not(feature = "audio"))]
+    pub async fn list_microphones(&self) -> Result<Vec<String>> {
+        Err(Error::Unsupported(
+            "Microphones need the plugin built with the audio feature".to_string(),
+        ))
+    }
+
+    /// Stop writing frames to a recording, keeping its file open
+    pub fn pause_recording(&self, recording_id: &str) -> Result<()> {
+        self.recordings.set_paused(recording_id, true)
+    }
+
+    /// Resume a paused recording in the same file; playback goes straight from the last
+    /// frame before the pause to the first one after it
+    pub fn resume_recording(&self, recording_id: &str) -> Result<()> {
+        self.recordings.set_paused(recording_id, false)
+    }
+
+    /// Stop a recording and finalize its file; a stream started for it is stopped too,
+    /// unless other sinks were attached to it since
+    pub async fn stop_recording(&self, recording_id: &str) -> Result<RecordingSummary> {
+        let recording = self.recordings.remove(recording_id)?;
+        let remaining_sinks = self
+            .active_streams
+            .lock()
+            .await
+            .values_mut()
+            .find(|stream| stream.camera_id == recording.device_id)
+            .map(|stream| {
+                // The recording may already have ended with the stream
+                if let Some(stop) = stream.sinks.remove(recording_id) {
+                    let _ = stop.send(());
+                }
+                stream.sinks.len()
+            });
+        let summary = recording
+            .task
+            .await
+            .map_err(|e| Error::CameraError(format!("Recording task failed: {}", e)))?;
+
+        if let (Some(stream_id), Some(0)) = (recording.owned_stream, remaining_sinks) {
+            if let Err(e) = self.stop_streaming(stream_id).await {
+                log::warn!("Failed to stop recording stream: {}", e);
+            }
+        }
+        summary
+    }
+
+    /// Read `count` raw frames of a device, `interval` apart, from its running stream, or
+    /// else from a stream opened at the device's highest resolution for the capture
+    async fn capture_frames(
+        &self,
+        device_id: &str,
+        count: u32,
+        interval: Duration,
+    ) -> Result<Vec<Arc<CameraFrame>>> {
+        if let Some(stream_id) = self.stream_id_by_device_id(device_id).await {
+            let receiver = self.raw_receiver(&stream_id).await?;
+            return crate::photo::next_frames(receiver, 0, count, interval).await;
+        }
+
+        // Another caller may have started the stream in the meantime: it is left running
+        let (stream_id, created) = self.open_for_capture(device_id).await?;
+        let warmup = if created {
+            crate::photo::WARMUP_FRAMES
+        } else {
+            0
+        };
+        let frames = match self.raw_receiver(&stream_id).await {
+            Ok(receiver) => crate::photo::next_frames(receiver, warmup, count, interval).await,
+            Err(e) => Err(e),
+        };
+        if created {
+            self.stop_streaming(stream_id).await?;
+        }
+        frames
+    }
+
+    /// Start a stream at the device's highest resolution for a capture; returns its ID, and
+    /// whether this call started it
+    async fn open_for_capture(&self, device_id: &str) -> Result<(String, bool)> {
+        let format = self
+            .get_device_capabilities(device_id)
+            .await?
+            .into_iter()
+            .next();
+        self.start_stream(
+            device_id.to_string(),
+            StreamOptions::default(),
+            Vec::new(),
+            format,
+        )
+        .await
+    }
+
+    /// Unprocessed frames of a stream, as they come from the device
+    async fn raw_receiver(
+        &self,
+        stream_id: &str,
+    ) -> Result<watch::Receiver<Option<Arc<CameraFrame>>>> {
+        self.active_streams
+            .lock()
+            .await
+            .get(stream_id)
+            .map(|stream| stream.raw_tx.subscribe())
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))
+    }
+
+    /// Turn the torch (continuous flash) on or off
+    /// Desktop cameras have no torch.
+    pub async fn set_torch(&self, _device_id: String, _on: bool) -> Result<()> {
+        Err(Error::Unsupported(
+            "Torch is only available on mobile".to_string(),
+        ))
+    }
+
+    /// Focus on a point of the preview (tap-to-focus)
+    /// Desktop capture backends do not expose a focus point of interest.
+    pub async fn set_focus_point(&self, _device_id: String, _x: f64, _y: f64) -> Result<()> {
+        Err(Error::Unsupported(
+            "Focus point of interest is only available on mobile".to_string(),
+        ))
+    }
+
+    /// Switch between triggered, manual and continuous focus
+    /// UVC cameras only turn autofocus on or off: `Auto` and `Continuous` both turn it on, and
+    /// `Auto` holds focus once `trigger_autofocus` is called.
+    pub async fn set_focus_mode(&self, device_id: String, mode: FocusMode) -> Result<()> {
+        let controls = CameraControls {
+            auto_focus: Some(mode != FocusMode::Manual),
+            ..Default::default()
+        };
+        self.apply_controls(&device_id, controls, "focus mode")
+            .await?;
+
+        Ok(())
+    }
+
+    /// Focus manually, from 0.0 (infinity) to 1.0 (closest); turns autofocus off
+    pub async fn set_focus_distance(&self, device_id: String, distance: f32) -> Result<()> {
+        if !(0.0..=1.0).contains(&distance) {
+            return Err(Error::CameraError(format!(
+                "Focus distance must be normalized to 0..1, got {}",
+                distance
+            )));
+        }
+
+        let controls = CameraControls {
+            auto_focus: Some(false),
+            focus_distance: Some(distance),
+            ..Default::default()
+        };
+        self.apply_controls(&device_id, controls, "focus distance")
+            .await?;
+
+        Ok(())
+    }
+
+    /// Run one autofocus pass and hold the result, emitting `camera://focus-locked`
+    /// Desktop capture backends have no focus region: autofocus runs for `AUTOFOCUS_SETTLE`
+    /// on the whole frame, then is turned off so the lens stays put.
+    pub async fn trigger_autofocus(&self, device_id: String, roi: Option<CropRect>) -> Result<()> {
+        if roi.is_some() {
+            return Err(Error::Unsupported(
+                "Focus regions are only available on mobile".to_string(),
+            ));
+        }
+
+        for auto_focus in [true, false] {
+            if !auto_focus {
+                tokio::time::sleep(AUTOFOCUS_SETTLE).await;
+            }
+            let controls = CameraControls {
+                auto_focus: Some(auto_focus),
+                ..Default::default()
+            };
+            self.apply_controls(&device_id, controls, "autofocus")
+                .await?;
+        }
+
+        let event = FocusLockedEvent {
+            device_id,
+            x: 0.5,
+            y: 0.5,
+            locked: true,
+        };
+        if let Err(e) = self.app.emit("camera://focus-locked", event) {
+            log::error!("Failed to emit focus-locked event: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Zoom by `factor`, from 1.0 (no zoom) to `MAX_DIGITAL_ZOOM`
+    /// The device's own zoom is used when it takes the factor; otherwise the streams of the
+    /// device, and its photos, show the center of the frame scaled back up.
+    pub async fn set_zoom(&self, device_id: String, factor: f32) -> Result<()> {
+        if !(1.0..=MAX_DIGITAL_ZOOM).contains(&factor) {
+            return Err(Error::InvalidConfig(format!(
+                "Zoom factor must be 1 to {}, got {}",
+                MAX_DIGITAL_ZOOM, factor
+            )));
+        }
+
+        let controls = CameraControls {
+            zoom: Some(factor),
+            ..Default::default()
+        };
+        // Drivers may accept a zoom they don't apply: only trust one that reads back
+        let platform = match self.apply_controls(&device_id, controls, "zoom").await {
+            Ok(()) => get_camera_controls(device_id.clone())
+                .await
+                .ok()
+                .and_then(|controls| controls.zoom)
+                .is_some_and(|zoom| (zoom - factor).abs() < 0.01),
+            Err(e) => {
+                log::debug!("Zooming {} digitally: {}", device_id, e);
+                false
+            }
+        };
+        self.zooms.set(&device_id, factor, platform);
+        Ok(())
+    }
+
+    /// Zoom factors a device supports and its current zoom
+    pub async fn get_zoom_range(&self, device_id: String) -> Result<ZoomRange> {
+        Ok(self.zooms.range(&device_id))
+    }
+
+    /// Freeze automatic exposure, focus and/or white balance at their current values, e.g.
+    /// on a long press in the preview
+    /// crabcamera can't read the color temperature auto white balance settled on: `awb`
+    /// only holds a white balance that is already fixed, and is refused while it is auto.
+    pub async fn lock_auto_controls(&self, device_id: String, locks: AutoControls) -> Result<()> {
+        let current = get_camera_controls(device_id.clone())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to get camera controls: {}", e)))?;
+        if locks.awb && matches!(current.white_balance, None | Some(CrabWhiteBalance::Auto)) {
+            return Err(Error::Unsupported(
+                "Locking auto white balance is only available on mobile".to_string(),
+            ));
+        }
+
+        let mut controls = CameraControls::default();
+        if locks.ae {
+            controls.auto_exposure = Some(false);
+            controls.exposure_time = current.exposure_time;
+        }
+        if locks.af {
+            controls.auto_focus = Some(false);
+            controls.focus_distance = current.focus_distance;
+        }
+        self.apply_controls(&device_id, controls, "auto control locks")
+            .await
+    }
+
+    /// Resume automatic exposure, focus and/or white balance
+    pub async fn unlock_auto_controls(&self, device_id: String, locks: AutoControls) -> Result<()> {
+        let controls = CameraControls {
+            auto_exposure: locks.ae.then_some(true),
+            auto_focus: locks.af.then_some(true),
+            white_balance: locks.awb.then_some(CrabWhiteBalance::Auto),
+            ..Default::default()
+        };
+        self.apply_controls(&device_id, controls, "auto controls")
+            .await
+    }
+
+    /// Image controls of a device (brightness, contrast, ...) with their current values
+    /// crabcamera neither reports control ranges nor exposes gamma: the ranges are the ones
+    /// it accepts, and gamma is left out.
+    pub async fn get_camera_controls(&self, device_id: String) -> Result<Vec<CameraControlInfo>> {
+        let controls = match get_camera_controls(device_id.clone()).await {
+            Ok(controls) => controls,
+            Err(e) => self.session_controls.get(&device_id).ok_or_else(|| {
+                Error::CameraError(format!("Failed to get camera controls: {}", e))
+            })?,
+        };
+
+        Ok(IMAGE_CONTROLS
+            .iter()
+            .filter_map(|&control| {
+                let mut info = control_range(control)?;
+                info.value = read_control(&controls, control).unwrap_or(info.default);
+                Some(info)
+            })
+            .collect())
+    }
+
+    /// Set one image control, within the range reported by `get_camera_controls`
+    pub async fn set_camera_control(
+        &self,
+        device_id: String,
+        control: CameraControl,
+        value: f32,
+    ) -> Result<()> {
+        let range = control_range(control).ok_or_else(|| {
+            Error::Unsupported(format!("{:?} is only available on mobile", control))
+        })?;
+        if !(range.min..=range.max).contains(&value) {
+            return Err(Error::CameraError(format!(
+                "{:?} {} is outside the supported range {}..={}",
+                control, value, range.min, range.max
+            )));
+        }
+
+        self.apply_controls(&device_id, control_update(control, value), "camera control")
+            .await
+    }
+
+    /// Apply a white balance preset or a manual color temperature
+    pub async fn set_white_balance(
+        &self,
+        device_id: String,
+        white_balance: WhiteBalance,
+    ) -> Result<()> {
+        if let WhiteBalance::Manual { kelvin } = white_balance {
+            if !WhiteBalance::KELVIN_RANGE.contains(&kelvin) {
+                return Err(Error::CameraError(format!(
+                    "Color temperature {}K is outside the supported range {:?}",
+                    kelvin,
+                    WhiteBalance::KELVIN_RANGE
+                )));
+            }
+        }
+
+        let controls = CameraControls {
+            white_balance: Some(to_crabcamera_white_balance(white_balance)),
+            ..Default::default()
+        };
+        self.apply_controls(&device_id, controls, "white balance")
+            .await?;
+
+        Ok(())
+    }
+
+    /// Read the white balance currently applied to a device, or the one it gets when opened
+    pub async fn get_white_balance(&self, device_id: String) -> Result<WhiteBalance> {
+        let controls = match get_camera_controls(device_id.clone()).await {
+            Ok(controls) => controls,
+            Err(e) => self.session_controls.get(&device_id).ok_or_else(|| {
+                Error::CameraError(format!("Failed to get camera controls: {}", e))
+            })?,
+        };
+
+        Ok(controls
+            .white_balance
+            .map(from_crabcamera_white_balance)
+            .unwrap_or(WhiteBalance::Auto))
+    }
+
+    /// Set auto, manual or locked exposure
+    /// Desktop capture backends have no EV compensation: auto mode only accepts a value of 0.
+    /// Locking reads the exposure time the camera metered last and holds it.
+    pub async fn set_exposure(
+        &self,
+        device_id: String,
+        mode: ExposureMode,
+        value: Option<f32>,
+    ) -> Result<()> {
+        let controls = match mode {
+            ExposureMode::Auto => {
+                if value.is_some_and(|compensation| compensation != 0.0) {
+                    return Err(Error::Unsupported(
+                        "Exposure compensation is only available on mobile".to_string(),
+                    ));
+                }
+                CameraControls {
+                    auto_exposure: Some(true),
+                    ..Default::default()
+                }
+            }
+            ExposureMode::Manual => {
+                let time_ms = value.ok_or_else(|| {
+                    Error::CameraError("Manual exposure needs an exposure time".to_string())
+                })?;
+                if !Exposure::TIME_RANGE_MS.contains(&time_ms) {
+                    return Err(Error::CameraError(format!(
+                        "Exposure time {}ms is outside the supported range {:?}",
+                        time_ms,
+                        Exposure::TIME_RANGE_MS
+                    )));
+                }
+                CameraControls {
+                    auto_exposure: Some(false),
+                    exposure_time: Some(time_ms / 1000.0),
+                    ..Default::default()
+                }
+            }
+            ExposureMode::Locked => {
+                let current = get_camera_controls(device_id.clone()).await.map_err(|e| {
+                    Error::CameraError(format!("Failed to get camera controls: {}", e))
+                })?;
+                CameraControls {
+                    auto_exposure: Some(false),
+                    exposure_time: current.exposure_time,
+                    ..Default::default()
+                }
+            }
+        };
+        self.apply_controls(&device_id, controls, "exposure")
+            .await?;
+
+        Ok(())
+    }
+
+    /// Read the exposure currently applied to a device; a locked exposure reads as manual
+    pub async fn get_exposure(&self, device_id: String) -> Result<Exposure> {
+        let controls = get_camera_controls(device_id)
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to get camera controls: {}", e)))?;
+
+        Ok(match controls.auto_exposure {
+            Some(false) => Exposure {
+                mode: ExposureMode::Manual,
+                value: controls.exposure_time.map(|time| time * 1000.0),
+            },
+            _ => Exposure {
+                mode: ExposureMode::Auto,
+                value: None,
+            },
+        })
+    }
+
+    /// Apply camera controls to a device and remember them for the session, so they survive
+    /// the device being reopened
+    async fn apply_controls(
+        &self,
+        device_id: &str,
+        controls: CameraControls,
+        what: &str,
+    ) -> Result<()> {
+        set_camera_controls(device_id.to_string(), controls.clone())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to set {}: {}", what, e)))?;
+        self.session_controls.remember(device_id, controls);
+        Ok(())
+    }
+
+    /// Apply a saved profile to a device
+    /// Controls are set right away; the format, stream options and encoder settings
+    /// are used by streams started on the device afterwards
+    pub async fn apply_profile(&self, device_id: String, name: String) -> Result<()> {
+        let profile = self.profiles.get(&device_id, &name)?;
+        if let Some(white_balance) = profile.white_balance {
+            self.set_white_balance(device_id.clone(), white_balance)
+                .await?;
+        }
+        if let Some(controls) = &profile.controls {
+            self.apply_control_settings(device_id.clone(), controls)
+                .await?;
+        }
+        self.profiles.set_active(device_id, profile);
+        Ok(())
+    }
+
+    /// Save the controls currently applied to a device (exposure, white balance, focus,
+    /// zoom, image controls) in the profile `name`, creating it if needed
+    pub async fn save_control_profile(&self, device_id: String, name: String) -> Result<()> {
+        let controls = self.get_control_settings(device_id.clone()).await?;
+        let mut profile = self
+            .profiles
+            .list(&device_id)
+            .remove(&name)
+            .unwrap_or_default();
+        profile.controls = Some(controls);
+        self.profiles.save(device_id, name, profile)
+    }
+
+    /// Restore the controls saved in the profile `name`, e.g. calibrated settings at startup
+    pub async fn apply_control_profile(&self, device_id: String, name: String) -> Result<()> {
+        let controls = self
+            .profiles
+            .get(&device_id, &name)?
+            .controls
+            .ok_or_else(|| {
+                Error::ProfileNotFound(format!(
+                    "{} has no saved controls (device {})",
+                    name, device_id
+                ))
+            })?;
+        self.apply_control_settings(device_id, &controls).await
+    }
+
+    /// Controls currently applied to a device
+    async fn get_control_settings(&self, device_id: String) -> Result<ControlSettings> {
+        let controls = get_camera_controls(device_id.clone())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to get camera controls: {}", e)))?;
+        let focus_mode = match controls.auto_focus {
+            Some(false) => FocusMode::Manual,
+            _ => FocusMode::Continuous,
+        };
+        let exposure = self.get_exposure(device_id.clone()).await?;
+        let image = self
+            .get_camera_controls(device_id.clone())
+            .await?
+            .into_iter()
+            .map(|info| (info.control, info.value))
+            .collect();
+
+        Ok(ControlSettings {
+            // A manual exposure the driver doesn't report can't be restored
+            exposure: Some(exposure)
+                .filter(|exposure| exposure.mode == ExposureMode::Auto || exposure.value.is_some()),
+            white_balance: Some(self.get_white_balance(device_id.clone()).await?),
+            focus_mode: Some(focus_mode),
+            focus_distance: controls
+                .focus_distance
+                .filter(|_| focus_mode == FocusMode::Manual),
+            zoom: Some(self.zooms.range(&device_id).zoom),
+            image,
+        })
+    }
+
+    /// Apply saved controls to a device
+    async fn apply_control_settings(
+        &self,
+        device_id: String,
+        settings: &ControlSettings,
+    ) -> Result<()> {
+        if let Some(white_balance) = settings.white_balance {
+            self.set_white_balance(device_id.clone(), white_balance)
+                .await?;
+        }
+        if let Some(exposure) = settings.exposure {
+            self.set_exposure(device_id.clone(), exposure.mode, exposure.value)
+                .await?;
+        }
+        match (settings.focus_mode, settings.focus_distance) {
+            (Some(FocusMode::Manual), Some(distance)) => {
+                self.set_focus_distance(device_id.clone(), distance).await?
+            }
+            (Some(mode), _) => self.set_focus_mode(device_id.clone(), mode).await?,
+            (None, _) => {}
+        }
+        if let Some(zoom) = settings.zoom {
+            self.set_zoom(device_id.clone(), zoom).await?;
+        }
+        for (&control, &value) in &settings.image {
+            self.set_camera_control(device_id.clone(), control, value)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn start_streaming(
+        &self,
+        device_id: String,
+        options: StreamOptions,
+    ) -> Result<String> {
+        self.start_stream(device_id, options, Vec::new(), None)
+            .await
+            .map(|(stream_id, _)| stream_id)
+    }
+
+    /// Start streaming in the device format matching the requested size and rate, instead
+    /// of the profile's; unspecified fields pick the largest and fastest matching format
+    pub async fn start_streaming_with_format(
+        &self,
+        request: StartStreamRequest,
+        options: StreamOptions,
+    ) -> Result<String> {
+        if request.width.is_none() && request.height.is_none() && request.fps.is_none() {
+            return self.start_streaming(request.device_id, options).await;
+        }
+        let formats = self.get_device_capabilities(&request.device_id).await?;
+        let format = select_format(&formats, &request).ok_or_else(|| {
+            let supported: Vec<String> = formats.iter().map(format_label).collect();
+            Error::InvalidConfig(format!(
+                "{}x{}@{} is not supported by {} (supported: {})",
+                request.width.map_or("*".to_string(), |w| w.to_string()),
+                request.height.map_or("*".to_string(), |h| h.to_string()),
+                request.fps.map_or("*".to_string(), |fps| fps.to_string()),
+                request.device_id,
+                if supported.is_empty() {
+                    "none reported".to_string()
+                } else {
+                    supported.join(", ")
+                }
+            ))
+        })?;
+        self.start_stream(request.device_id, options, Vec::new(), Some(format))
+            .await
+            .map(|(stream_id, _)| stream_id)
+    }
+
+    /// Start a capture session whose frames go through `options` and then `filters`,
+    /// capturing in `format` if set, else in the format of the device's profile
+    /// An existing stream of the device is reused as is, unless it captures in another `format`.
+    /// Returns the stream ID, and whether this call started the stream.
+    async fn start_stream(
+        &self,
+        device_id: String,
+        options: StreamOptions,
+        filters: Vec<Box<dyn FrameFilter>>,
+        format: Option<CameraFormat>,
+    ) -> Result<(String, bool)> {
+        self.open_stream(device_id.clone(), options, filters, format)
+            .await
+            .inspect_err(|e| {
+                self.telemetry
+                    .error(Some(&device_id), "start_stream", e.to_string())
+            })
+    }
+
+    async fn open_stream(
+        &self,
+        device_id: String,
+        options: StreamOptions,
+        filters: Vec<Box<dyn FrameFilter>>,
+        format: Option<CameraFormat>,
+    ) -> Result<(String, bool)> {
+        if let Some(crop) = &options.crop {
+            crate::pipeline::check_crop(crop)?;
+        }
+        if options.target_width == Some(0) || options.target_height == Some(0) {
+            return Err(Error::InvalidConfig(
+                "Target width and height must be positive".to_string(),
+            ));
+        }
+        if options
+            .max_fps
+            .is_some_and(|fps| !fps.is_finite() || fps <= 0.0)
+        {
+            return Err(Error::InvalidConfig(
+                "Maximum frame rate must be positive".to_string(),
+            ));
+        }
+        if let Some(encoder) = &options.encoder {
+            if encoder
+                .max_fps
+                .is_some_and(|fps| !fps.is_finite() || fps <= 0.0)
+            {
+                return Err(Error::InvalidConfig(
+                    "Encoder frame rate must be positive".to_string(),
+                ));
+            }
+            if encoder.keyframe_interval == Some(0) {
+                return Err(Error::InvalidConfig(
+                    "Keyframe interval must be at least one frame".to_string(),
+                ));
+            }
+            if encoder.speed_preset.is_some_and(|preset| preset > 10) {
+                return Err(Error::InvalidConfig(
+                    "Speed preset must be between 0 and 10".to_string(),
+                ));
+            }
+        }
+        if options.rotation % 90 != 0 || options.rotation >= 360 {
+            return Err(Error::InvalidConfig(format!(
+                "Rotation must be 0, 90, 180 or 270 degrees, not {}",
+                options.rotation
+            )));
+        }
+        // Check if streaming is already active for this device
+        {
+            let streams = self.active_streams.lock().await;
+            for (session_id, active_stream) in streams.iter() {
+                if active_stream.camera_id == device_id {
+                    if let (Some(requested), Some(current)) =
+                        (&format, &active_stream.capture_format)
+                    {
+                        if (requested.width, requested.height) != (current.width, current.height)
+                            || (requested.fps - current.fps as f64).abs() >= FPS_TOLERANCE
+                        {
+                            return Err(Error::StreamingAlreadyActive(format!(
+                                "{} at {}x{}@{}, stop it to change the format",
+                                device_id, current.width, current.height, current.fps
+                            )));
+                        }
+                    }
+                    return Ok((session_id.clone(), false));
+                }
+            }
+        }
+        let _operation = DeviceOperation::begin(&self.busy_devices, &device_id)?;
+
+        let profile_format =
+            format.or_else(|| self.profiles.active(&device_id).and_then(|p| p.format));
+        let source = self.sources.get(&device_id);
+
+        // Create watch channel for frame events
+        let (tx, rx) = watch::channel(None);
+
+        let (raw_tx, _) = watch::channel(None);
+
+        let tx_clone = tx.clone();
+        let raw_tx_clone = raw_tx.clone();
+        let mut processor =
+            FrameProcessor::new(options.clone(), filters).with_zoom(self.zooms.digital(&device_id));
+        let memory = Arc::new(StreamMemory::default());
+        let faults = self.faults.state(&device_id);
+        let stream_faults = faults.clone();
+        let stages = self.event_log.for_device(&device_id);
+        let stream_stages = stages.clone();
+        let budget = self.memory.clone();
+        let stream_memory = memory.clone();
+        // Accounts the frame held by the channel until the next one replaces it
+        let mut held: Option<Reservation> = None;
+        let mut last_capture: Option<std::time::Instant> = None;
+        let heartbeat = Heartbeat::new();
+        let stream_heartbeat = heartbeat.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let stream_paused = paused.clone();
+        let callback = move |frame: CameraFrame| {
+            if stream_faults.device_lost() {
+                // Frames stop arriving, as when the camera is unplugged
+                return;
+            }
+            stream_heartbeat.beat();
+            let captured = std::time::Instant::now();
+            stream_stages.record(
+                Stage::Capture,
+                StageRecord {
+                    interval_us: last_capture.map(elapsed_us),
+                    bytes: Some(frame.data.len()),
+                    width: Some(frame.width),
+                    height: Some(frame.height),
+                    format: Some(&frame.format),
+                    ..Default::default()
+                },
+            );
+            last_capture = Some(captured);
+
+            let frame = Arc::new(frame);
+            if raw_tx_clone.receiver_count() > 0 {
+                raw_tx_clone.send_replace(Some(frame.clone()));
+            }
+            // Raw consumers (photos, frame dumps) keep receiving frames while paused
+            if stream_paused.load(Ordering::Relaxed) || !processor.admit(captured) {
+                return;
+            }
+            let processed = if stream_faults.conversion_error() {
+                Err(Error::CameraError("Injected conversion error".to_string()))
+            } else {
+                processor.process(frame, captured)
+            };
+            let event = match processed {
+                Ok(event) => event,
+                Err(e) => {
+                    log::error!("Failed to process frame: {}", e);
+                    stream_stages.record(
+                        Stage::Convert,
+                        StageRecord {
+                            duration_us: Some(elapsed_us(captured)),
+                            error: Some(e.to_string()),
+                            ..Default::default()
+                        },
+                    );
+                    return;
+                }
+            };
+            stream_stages.record(
+                Stage::Convert,
+                StageRecord {
+                    duration_us: Some(elapsed_us(captured)),
+                    bytes: Some(event.data.len()),
+                    width: Some(event.width),
+                    height: Some(event.height),
+                    format: Some(&event.format),
+                    ..Default::default()
+                },
+            );
+
+            // The frame replaces the previous one in the channel
+            held.take();
+            let Some(reservation) = budget.reserve(&stream_memory, event.data.len()) else {
+                // Over budget: drop the frame, consumers keep the previous one
+                return;
+            };
+            held = Some(reservation);
+
+            if let Err(e) = tx_clone.send(Some(event)) {
+                log::error!("Failed to send frame event: {}", e);
+            }
+        };
+
+        let callback: SharedCallback = Arc::new(Mutex::new(Box::new(callback)));
+        let capture_format = self
+            .open_capture(
+                &device_id,
+                source.as_ref(),
+                profile_format,
+                options.power_mode,
+                &callback,
+            )
+            .await?;
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let watchdog = self.spawn_watchdog(
+            &session_id,
+            &device_id,
+            source.as_ref(),
+            &heartbeat,
+            &options,
+        );
+        let active_stream = ActiveStream {
+            camera_id: device_id.clone(),
+            token: uuid::Uuid::new_v4().to_string(),
+            start_time: Instant::now(),
+            rx,
+            raw_tx,
+            source,
+            capture_format,
+            callback,
+            _watchdog: watchdog,
+            heartbeat,
+            encoded: HashMap::new(),
+            preroll: None,
+            sinks: HashMap::new(),
+            memory,
+            faults,
+            stages,
+            options,
+            sink_stats: Arc::new(Mutex::new(SinkStats::default())),
+            paused,
+        };
+
+        self.active_streams
+            .lock()
+            .await
+            .insert(session_id.clone(), active_stream);
+        if !device_id.starts_with(REPLAY_PREFIX) {
+            self.default_camera.record_use(&device_id);
+        }
+        self.telemetry.emit(TelemetryEvent::StreamStarted {
+            stream_id: session_id.clone(),
+            device_id,
+        });
+
+        Ok((session_id, true))
+    }
+
+    /// Open the capture of a device (or start its custom source) feeding `callback`, in
+    /// `format` or else the recommended format; returns the format a device was opened with
+    async fn open_capture(
+        &self,
+        device_id: &str,
+        source: Option<&Arc<dyn FrameSource>>,
+        profile_format: Option<CameraFormat>,
+        power_mode: PowerMode,
+        callback: &SharedCallback,
+    ) -> Result<Option<crabcamera::CameraFormat>> {
+        if let Some(source) = source {
+            source.start(profile_format.as_ref(), Box::new(forward(callback)))?;
+            return Ok(None);
+        }
+        let mut format = get_recommended_format()
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to get recommended format : {}", e)))?;
+        // The recommended format is the same for every device: with several cameras,
+        // fall back to the device's own best format when it doesn't support it
+        let profile_format = match profile_format {
+            Some(profile_format) => Some(profile_format),
+            None => {
+                let formats = self
+                    .get_device_capabilities(device_id)
+                    .await
+                    .unwrap_or_default();
+                let recommended = StartStreamRequest {
+                    device_id: device_id.to_string(),
+                    width: Some(format.width),
+                    height: Some(format.height),
+                    fps: Some(format.fps as f64),
+                };
+                if select_format(&formats, &recommended).is_none() {
+                    formats.into_iter().next()
+                } else {
+                    None
+                }
+            }
+        };
+        if let Some(profile_format) = profile_format {
+            format.width = profile_format.width;
+            format.height = profile_format.height;
+            format.fps = profile_format.fps as _;
+            if let Some(format_type) = profile_format.format {
+                format.format_type = format_type;
+            }
+        }
+        if power_mode == PowerMode::Low {
+            // The driver picks the closest rate it supports
+            format.fps = format.fps.min(15.0);
+        }
+        let _camera = start_camera_preview(device_id.to_string(), Some(format.clone()))
+            .await
+            .map_err(crate::privacy::open_error)?;
+
+        if let Err(e) = set_callback(device_id.to_string(), forward(callback)).await {
+            // Don't leave the camera open without a stream to stop it
+            let _ = crabcamera::commands::capture::stop_camera_preview(device_id.to_string()).await;
+            let _ = crabcamera::commands::capture::release_camera(device_id.to_string()).await;
+            return Err(Error::CameraError(format!("Failed to set callback: {}", e)));
+        }
+        self.session_controls.restore(device_id).await;
+        Ok(Some(format))
+    }
+
+    /// Watch a stream for stalls unless disabled or its source only sends frames on change;
+    /// returns the handle stopping the watchdog when dropped
+    fn spawn_watchdog(
+        &self,
+        stream_id: &str,
+        device_id: &str,
+        source: Option<&Arc<dyn FrameSource>>,
+        heartbeat: &Heartbeat,
+        options: &StreamOptions,
+    ) -> Option<oneshot::Sender<()>> {
+        let continuous = source.map_or(true, |source| source.continuous());
+        self.stall_timeout.filter(|_| continuous).map(|timeout| {
+            let (stop_tx, stop_rx) = oneshot::channel();
+            crate::watchdog::spawn(
+                self.app.clone(),
+                stream_id.to_string(),
+                device_id.to_string(),
+                heartbeat.clone(),
+                timeout,
+                options.auto_reconnect.unwrap_or(true),
+                self.telemetry.clone(),
+                stop_rx,
+            );
+            stop_tx
+        })
+    }
+
+    /// Close the capture of a device (or stop its custom source)
+    async fn close_capture(
+        &self,
+        device_id: &str,
+        source: Option<&Arc<dyn FrameSource>>,
+    ) -> Result<()> {
+        if let Some(source) = source {
+            return source.stop();
+        }
+
+        // First, clear the callback to stop receiving frames
+        log::info!(" Clearing callback for camera: {}", device_id);
+        set_callback(device_id.to_string(), |_| {})
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to clear callback: {}", e)))?;
+
+        // Then stop the camera preview
+        log::info!(" Stopping camera preview for device: {}", device_id);
+        crabcamera::commands::capture::stop_camera_preview(device_id.to_string())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to stop camera: {}", e)))?;
+
+        // WORKAROUND: Give more time for camera to fully release
+        // TODO: This should be fixed in crabcamera by properly closing/dropping the camera
+        log::warn!(" Waiting 500ms for camera to fully release (crabcamera limitation)");
+        tokio::time::sleep(CAMERA_RELEASE_DELAY).await;
+        crabcamera::commands::capture::release_camera(device_id.to_string())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to release camera: {}", e)))?;
+        // When stream is dropped here, the threadpool will be dropped too
+        log::info!(" Stream resources cleaned up for camera: {}", device_id);
+
+        Ok(())
+    }
+
+    pub async fn stop_streaming(&self, stream_id: String) -> Result<()> {
+        log::info!(" Stopping stream with stream_id: {}", stream_id);
+
+        let device_id = self
+            .active_streams
+            .lock()
+            .await
+            .get(&stream_id)
+            .map(|stream| stream.camera_id.clone())
+            .ok_or_else(|| Error::StreamNotFound(stream_id.clone()))?;
+        let _operation = DeviceOperation::begin(&self.busy_devices, &device_id)?;
+
+        // First, signal the callback to stop processing frames
+        let stream = self
+            .active_streams
+            .lock()
+            .await
+            .remove(&stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.clone()))?;
+
+        log::info!(
+            " Stream stopped for camera: {} (ran for {:?})",
+            stream.camera_id.clone(),
+            stream.start_time.elapsed()
+        );
+        self.telemetry.emit(TelemetryEvent::StreamStopped {
+            stream_id: stream_id.clone(),
+            device_id: stream.camera_id.clone(),
+            duration: stream.start_time.elapsed(),
+        });
+
+        self.close_capture(&stream.camera_id, stream.source.as_ref())
+            .await
+    }
+
+    /// Move a running stream to another device, e.g. to toggle between the front and back
+    /// cameras during a call. The stream keeps its ID, sinks and consumers: channels keep
+    /// receiving frames and WebRTC tracks keep sending, the encoder restarting from a keyframe
+    /// at the new device's size, so peers need no new offer/answer. If the new device can't
+    /// be opened, the stream goes back to the previous one.
+    pub async fn switch_stream_device(&self, stream_id: &str, device_id: String) -> Result<()> {
+        let (previous, previous_source, callback, options) = {
+            let streams = self.active_streams.lock().await;
+            if let Some(other) = streams
+                .iter()
+                .find(|(id, stream)| stream.camera_id == device_id && *id != stream_id)
+                .map(|(id, _)| id)
+            {
+                return Err(Error::StreamingAlreadyActive(format!(
+                    "{} in stream {}",
+                    device_id, other
+                )));
+            }
+            let stream = streams
+                .get(stream_id)
+                .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+            if stream.camera_id == device_id {
+                return Ok(());
+            }
+            (
+                stream.camera_id.clone(),
+                stream.source.clone(),
+                stream.callback.clone(),
+                stream.options.clone(),
+            )
+        };
+        if !self
+            .get_available_cameras()
+            .await?
+            .iter()
+            .any(|device| device.id == device_id)
+        {
+            return Err(Error::DeviceNotFound(device_id));
+        }
+        let _previous_operation = DeviceOperation::begin(&self.busy_devices, &previous)?;
+        let _operation = DeviceOperation::begin(&self.busy_devices, &device_id)?;
+
+        // Close the current device first: some systems can't run both cameras at once
+        if let Err(e) = self
+            .close_capture(&previous, previous_source.as_ref())
+            .await
+        {
+            log::warn!("Failed to close {} before switching: {}", previous, e);
+        }
+        let source = self.sources.get(&device_id);
+        let format = self.profiles.active(&device_id).and_then(|p| p.format);
+        let capture_format = match self
+            .open_capture(
+                &device_id,
+                source.as_ref(),
+                format,
+                options.power_mode,
+                &callback,
+            )
+            .await
+        {
+            Ok(capture_format) => capture_format,
+            Err(e) => {
+                log::warn!("Failed to open {}, back to {}: {}", device_id, previous, e);
+                let format = self.profiles.active(&previous).and_then(|p| p.format);
+                if let Err(e) = self
+                    .open_capture(
+                        &previous,
+                        previous_source.as_ref(),
+                        format,
+                        options.power_mode,
+                        &callback,
+                    )
+                    .await
+                {
+                    log::error!("Failed to reopen {}: {}", previous, e);
+                }
+                return Err(e);
+            }
+        };
+
+        {
+            let mut streams = self.active_streams.lock().await;
+            let stream = streams
+                .get_mut(stream_id)
+                .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+            // The previous watchdog watched the previous device
+            stream._watchdog = self.spawn_watchdog(
+                stream_id,
+                &device_id,
+                source.as_ref(),
+                &stream.heartbeat,
+                &options,
+            );
+            stream.camera_id = device_id.clone();
+            stream.source = source;
+            stream.capture_format = capture_format;
+            for encoded in stream.encoded.values() {
+                encoded.request_keyframe();
+            }
+        }
+        for connection_id in self.webrtc_manager.connections_for_stream(stream_id).await {
+            self.webrtc_manager
+                .register_device_for_connection(connection_id, device_id.clone())
+                .await?;
+        }
+        if !device_id.starts_with(REPLAY_PREFIX) {
+            self.default_camera.record_use(&device_id);
+        }
+        log::info!(
+            "Stream {} switched from {} to {}",
+            stream_id,
+            previous,
+            device_id
+        );
+        Ok(())
+    }
+
+    /// Close and reopen the capture of a stream, e.g. after its device stopped delivering
+    /// frames; sinks and consumers keep running and get the frames of the new capture
+    pub(crate) async fn restart_capture(&self, stream_id: &str) -> Result<()> {
+        let (device_id, source, format, callback) = {
+            let streams = self.active_streams.lock().await;
+            let stream = streams
+                .get(stream_id)
+                .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+            (
+                stream.camera_id.clone(),
+                stream.source.clone(),
+                stream.capture_format.clone(),
+                stream.callback.clone(),
+            )
+        };
+        let _operation = DeviceOperation::begin(&self.busy_devices, &device_id)?;
+
+        if let Some(source) = source {
+            if let Err(e) = source.stop() {
+                log::warn!("Failed to stop source {}: {}", device_id, e);
+            }
+            let format = self.profiles.active(&device_id).and_then(|p| p.format);
+            return source.start(format.as_ref(), Box::new(forward(&callback)));
+        }
+
+        // The device may be wedged: release it whatever fails, then open it again
+        if let Err(e) = set_callback(device_id.clone(), |_| {}).await {
+            log::warn!("Failed to clear callback of {}: {}", device_id, e);
+        }
+        if let Err(e) = crabcamera::commands::capture::stop_camera_preview(device_id.clone()).await
+        {
+            log::warn!("Failed to stop camera {}: {}", device_id, e);
+        }
+        if let Err(e) = crabcamera::commands::capture::release_camera(device_id.clone()).await {
+            log::warn!("Failed to release camera {}: {}", device_id, e);
+        }
+        tokio::time::sleep(CAMERA_RELEASE_DELAY).await;
+
+        start_camera_preview(device_id.clone(), format)
+            .await
+            .map_err(crate::privacy::open_error)?;
+        set_callback(device_id.clone(), forward(&callback))
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to set callback: {}", e)))?;
+        self.session_controls.restore(&device_id).await;
+        Ok(())
+    }
+
+    /// Forward the frames of a stream to a frontend channel; returns the sink ID
+    /// The delivered frame rate adapts to how fast the webview consumes frames
+    /// unless the stream was started with `adaptive_fps: false`
+    pub async fn subscribe(
+        &self,
+        stream_id: &str,
+        on_frame: Channel<FrameEvent>,
+    ) -> Result<String> {
+        let mut streams = self.active_streams.lock().await;
+        let stream = streams
+            .get_mut(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let sink_id = format!("{}{}", CHANNEL_SINK_PREFIX, uuid::Uuid::new_v4());
+        spawn_channel_sink(
+            stream.rx.clone(),
+            on_frame,
+            stream.options.adaptive_fps.unwrap_or(true),
+            stream.sink_stats.clone(),
+            stream.faults.clone(),
+            stream.stages.clone(),
+            sink_id.clone(),
+            stop_rx,
+        );
+        stream.sinks.insert(sink_id.clone(), stop_tx);
+        Ok(sink_id)
+    }
+
+    /// ID of the stream running on a device, if any
+    pub async fn stream_id_by_device_id(&self, device_id: &str) -> Option<String> {
+        self.active_streams
+            .lock()
+            .await
+            .iter()
+            .find(|(_, stream)| stream.camera_id == device_id)
+            .map(|(stream_id, _)| stream_id.clone())
+    }
+
+    /// Access token of a stream, to hand to the webview allowed to control it
+    pub async fn stream_token(&self, stream_id: &str) -> Result<String> {
+        self.active_streams
+            .lock()
+            .await
+            .get(stream_id)
+            .map(|stream| stream.token.clone())
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))
+    }
+
+    /// Check the token presented by a webview for a stream
+    pub async fn authorize_stream(&self, stream_id: &str, token: Option<&str>) -> Result<()> {
+        let streams = self.active_streams.lock().await;
+        let stream = streams
+            .get(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+        if token != Some(stream.token.as_str()) {
+            return Err(Error::AccessDenied(format!(
+                "invalid token for stream {}",
+                stream_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Point a running stream at a new frontend channel, e.g. after the webview reloaded:
+    /// channel sinks of the previous page are detached, other sinks keep running.
+    /// Returns the new sink ID.
+    pub async fn reattach_stream(
+        &self,
+        stream_id: &str,
+        on_frame: Channel<FrameEvent>,
+    ) -> Result<String> {
+        {
+            let mut streams = self.active_streams.lock().await;
+            let stream = streams
+                .get_mut(stream_id)
+                .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+            let stale: Vec<String> = stream
+                .sinks
+                .keys()
+                .filter(|id| id.starts_with(CHANNEL_SINK_PREFIX))
+                .cloned()
+                .collect();
+            for id in stale {
+                if let Some(stop) = stream.sinks.remove(&id) {
+                    let _ = stop.send(());
+                }
+            }
+        }
+        self.subscribe(stream_id, on_frame).await
+    }
+
+    /// Resume the device's stream on `on_frame` if one is running (see `reattach_stream`),
+    /// otherwise start one with `options`. Returns the stream ID.
+    pub async fn get_or_start_stream(
+        &self,
+        device_id: String,
+        options: StreamOptions,
+        on_frame: Channel<FrameEvent>,
+    ) -> Result<String> {
+        match self.stream_id_by_device_id(&device_id).await {
+            Some(stream_id) => {
+                self.reattach_stream(&stream_id, on_frame).await?;
+                Ok(stream_id)
+            }
+            None => {
+                let stream_id = self.start_streaming(device_id, options).await?;
+                self.subscribe(&stream_id, on_frame).await?;
+                Ok(stream_id)
+            }
+        }
+    }
+
+    /// Start a stream from a pipeline description and attach its sinks
+    /// `on_frame` feeds the `Channel` sink. If a sink fails to attach, the stream is stopped.
+    /// The source must not be streaming already, as its stream wouldn't take `desc`.
+    pub async fn start_pipeline(
+        &self,
+        desc: PipelineDesc,
+        on_frame: Option<Channel<FrameEvent>>,
+    ) -> Result<PipelineInfo> {
+        let options = desc
+            .options
+            .or_else(|| {
+                self.profiles
+                    .active(&desc.source)
+                    .map(|profile| profile.stream)
+            })
+            .unwrap_or_default();
+        let filters = desc
+            .filters
+            .iter()
+            .map(|name| self.filters.create(name))
+            .collect::<Result<Vec<_>>>()?;
+        let (stream_id, created) = self
+            .start_stream(desc.source.clone(), options, filters, None)
+            .await?;
+        if !created {
+            return Err(Error::StreamingAlreadyActive(format!(
+                "{} is streaming already, stop it to start a pipeline on it",
+                desc.source
+            )));
+        }
+
+        let mut sinks = Vec::new();
+        for sink in desc.sinks {
+            match self.attach_sink(&stream_id, sink, on_frame.clone()).await {
+                Ok(sink_id) => sinks.push(sink_id),
+                Err(e) => {
+                    if let Err(stop_error) = self.stop_streaming(stream_id.clone()).await {
+                        log::warn!("Failed to stop pipeline stream: {}", stop_error);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let token = self.stream_token(&stream_id).await?;
+        Ok(PipelineInfo {
+            stream_id,
+            token,
+            sinks,
+        })
+    }
+
+    /// Attach a sink to a running stream without interrupting its capture or other sinks
+    /// `on_frame` is required for a `Channel` sink. Returns the sink ID, for `detach_sink`.
+    pub async fn attach_sink(
+        &self,
+        stream_id: &str,
+        sink: SinkDesc,
+        on_frame: Option<Channel<FrameEvent>>,
+    ) -> Result<String> {
+        match sink {
+            SinkDesc::Channel => {
+                let on_frame = on_frame.ok_or_else(|| {
+                    Error::InvalidConfig("channel sink requires an on_frame channel".to_string())
+                })?;
+                self.subscribe(stream_id, on_frame).await
+            }
+            SinkDesc::WebRtc { connection_id } => {
+                let device_id = self.stream_device_id(stream_id).await?;
+                self.connect_camera_to_webrtc(device_id, connection_id.clone())
+                    .await?;
+                Ok(connection_id)
+            }
+            SinkDesc::NativePreview { window_label, rect } => {
+                let label = window_label.unwrap_or_else(|| "main".to_string());
+                let window = self
+                    .app
+                    .get_webview_window(&label)
+                    .ok_or(Error::WindowNotFound(label))?;
+                self.start_native_preview(stream_id, window, rect).await
+            }
+            SinkDesc::Pip(options) => self.open_pip_preview(stream_id, options).await,
+            SinkDesc::Overlay(options) => self.open_overlay_preview(stream_id, options).await,
+            SinkDesc::Kiosk { monitor } => self.start_kiosk_preview(stream_id, monitor).await,
+            SinkDesc::Recording { path, options } => {
+                self.attach_recorder(stream_id, &path, options).await
+            }
+        }
+    }
+
+    /// Device a stream captures from
+    async fn stream_device_id(&self, stream_id: &str) -> Result<String> {
+        self.active_streams
+            .lock()
+            .await
+            .get(stream_id)
+            .map(|stream| stream.camera_id.clone())
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))
+    }
+
+    /// Detach a sink from its stream; the capture and the other sinks keep running
+    /// Preview windows are closed, native previews stopped
+    pub async fn detach_sink(&self, stream_id: &str, sink_id: &str) -> Result<()> {
+        if sink_id.starts_with(RECORDING_PREFIX) {
+            return self.stop_recording(sink_id).await.map(|_| ());
+        }
+        let stop = self
+            .active_streams
+            .lock()
+            .await
+            .get_mut(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?
+            .sinks
+            .remove(sink_id);
+
+        match stop {
+            Some(stop) => {
+                // The sink may already have ended on its own
+                let _ = stop.send(());
+                Ok(())
+            }
+            None if sink_id.starts_with(PREVIEW_WINDOW_PREFIX) => {
+                self.close_preview_window(sink_id)
+            }
+            None => self.stop_native_preview(sink_id),
+        }
+    }
+
+    /// Make a custom filter (blur, text overlay, ...) available to pipelines by name
+    pub fn register_filter(&self, name: impl Into<String>, factory: FilterFactory) {
+        self.filters.register(name, factory);
+    }
+
+    /// Hand the frames of a device to Rust code in the app or another plugin
+    /// Consumers whose `raw` flag is set get the original `crabcamera::CameraFrame`
+    /// The device is shared: an existing stream is reused, otherwise one is started
+    /// (with the options of the profile applied to the device, if any).
+    /// Returns the stream ID; stopping that stream also stops the consumer.
+    pub async fn register_consumer(
+        &self,
+        id: impl Into<String>,
+        device_id: &str,
+        consumer: Box<dyn FrameConsumer>,
+    ) -> Result<String> {
+        let options = self
+            .profiles
+            .active(device_id)
+            .map(|profile| profile.stream)
+            .unwrap_or_default();
+        let stream_id = self.start_streaming(device_id.to_string(), options).await?;
+        let feed = if consumer.raw() {
+            FrameFeed::Raw(self.raw_receiver(&stream_id).await?)
+        } else {
+            FrameFeed::Converted(self.get_receiver_by_stream_id(&stream_id).await?)
+        };
+        self.consumers.register(id.into(), feed, consumer)?;
+        Ok(stream_id)
+    }
+
+    /// Stop delivering frames to a consumer; the stream keeps running
+    pub fn unregister_consumer(&self, id: &str) -> Result<()> {
+        self.consumers.unregister(id)
+    }
+
+    /// Check a path recordings, snapshots or frame dumps are about to be written to
+    /// against the allowed output directories (`Config::output_dirs`)
+    pub fn check_output_path(&self, path: &Path) -> Result<PathBuf> {
+        self.output_scope.check(path)
+    }
+
+    /// Path for a temporary encoding artifact in the plugin's work directory (`Config::temp_dir`)
+    pub fn temp_file(&self, name: &str) -> Result<PathBuf> {
+        self.work_dir.file(name)
+    }
+
+    /// Simulate failures on a device (lost device, conversion errors, channel send failures,
+    /// encoder panic) to test the app's error handling; an empty `FaultInjection` clears them.
+    /// Requires the `fault-injection` feature.
+    pub fn inject_faults(&self, device_id: &str, faults: FaultInjection) -> Result<()> {
+        #[cfg(feature = "fault-injection")]
+        {
+            log::warn!("Injecting faults on {}: {:?}", device_id, faults);
+            self.faults.state(device_id).set(&faults);
+            Ok(())
+        }
+        #[cfg(not(feature = "fault-injection"))]
+        {
+            let _ = (device_id, faults);
+            Err(Error::Unsupported(
+                "Fault injection requires the fault-injection feature".to_string(),
+            ))
+        }
+    }
+
+    /// Write the raw capture frames of a device (format, bytes and capture times) to `path`,
+    /// starting a stream if none runs on it. Returns the dump ID to stop it with.
+    pub async fn start_frame_dump(&self, device_id: &str, path: &Path) -> Result<String> {
+        let path = self.check_output_path(path)?;
+        let dump_id = format!("{}{}", DUMP_PREFIX, uuid::Uuid::new_v4());
+        let writer = DumpWriter::create(&path)?;
+        self.register_consumer(dump_id.clone(), device_id, Box::new(writer))
+            .await?;
+        log::info!("Dumping frames of {} to {}", device_id, path.display());
+        Ok(dump_id)
+    }
+
+    /// Stop a frame dump and flush its file
+    pub fn stop_frame_dump(&self, dump_id: &str) -> Result<()> {
+        if !dump_id.starts_with(DUMP_PREFIX) {
+            return Err(Error::CameraError(format!(
+                "Frame dump not found: {}",
+                dump_id
+            )));
+        }
+        self.unregister_consumer(dump_id)
+    }
+
+    /// Register a frame dump as a source replaying it in a loop with its recorded timing
+    /// Returns the device ID to stream from; remove it with `unregister_source`
+    pub fn replay_frame_dump(&self, path: &Path) -> Result<String> {
+        let path = self.check_output_path(path)?;
+        let device_id = format!("{}{}", REPLAY_PREFIX, uuid::Uuid::new_v4());
+        let source = ReplaySource::open(device_id.clone(), &path)?;
+        self.register_source(Arc::new(source));
+        Ok(device_id)
+    }
+
+    /// Make a custom frame source available as a device
+    /// It is listed by `get_available_cameras` and streams through every sink
+    pub fn register_source(&self, source: Arc<dyn FrameSource>) {
+        self.sources.register(source);
+    }
 
-    Ok(Camera {
-        _app: app.clone(),
-        webrtc_manager,
-        active_streams: AsyncMutex::new(HashMap::new()),
-    })
-}
+    /// Linux: let the user pick screens or windows through the ScreenCast portal (Wayland and X11)
+    /// and register each one as a device; returns their device IDs. Stream them like cameras and
+    /// remove them with `unregister_source`, which ends the sharing once all are removed.
+    pub async fn add_screen_sources(&self) -> Result<Vec<String>> {
+        self.portal.add_screen_sources(&self.sources).await
+    }
 
-struct ActiveStream {
-    camera_id: String,
-    start_time: Instant,
-    rx: watch::Receiver<Option<FrameEvent>>,
-}
-/// Access to the camera APIs.
-pub struct Camera<R: Runtime> {
-    _app: AppHandle<R>,
-    pub webrtc_manager: crate::webrtc::WebRTCManager,
-    active_streams: AsyncMutex<HashMap<String, ActiveStream>>,
-}
+    /// Remove a custom source; a running stream from it keeps going until stopped
+    pub fn unregister_source(&self, device_id: &str) -> Result<()> {
+        self.sources
+            .unregister(device_id)
+            .map(|_| ())
+            .ok_or_else(|| Error::DeviceNotFound(device_id.to_string()))
+    }
 
-impl<R: Runtime> Camera<R> {
-    /// Request camera permission from the system
-    pub async fn request_permission(&self) -> Result<PermissionInfo> {
-        request_camera_permission()
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to request camera permission: {}", e)))
+    /// Make a custom encoder (e.g. a hardware SDK) available to the encoding sinks
+    /// Select it with `EncoderSettings::encoder`; registering `"openh264"` replaces the built-in one
+    pub fn register_encoder(&self, name: impl Into<String>, factory: EncoderFactory) {
+        self.encoders.register(name, factory);
     }
 
-    pub async fn initialize(&self) -> Result<String> {
-        initialize_camera_system()
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to initialize camera system: {}", e)))
+    /// Get runtime statistics of a stream, including its effective delivered frame rate
+    pub async fn get_stream_stats(&self, stream_id: &str) -> Result<StreamStats> {
+        let streams = self.active_streams.lock().await;
+        let stream = streams
+            .get(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+        let sink = stream.sink_stats.lock().unwrap().clone();
+
+        Ok(StreamStats {
+            stream_id: stream_id.to_string(),
+            device_id: stream.camera_id.clone(),
+            uptime_ms: stream.start_time.elapsed().as_millis() as u64,
+            frames_delivered: sink.frames_delivered,
+            frames_skipped: sink.frames_skipped,
+            send_errors: sink.send_errors,
+            target_fps: sink.target_fps,
+            effective_fps: sink.effective_fps,
+            buffer_bytes: stream.memory.used() as u64,
+            frames_over_budget: stream.memory.dropped_frames(),
+            paused: stream.paused.load(Ordering::Relaxed),
+        })
     }
 
-    /// List all available camera devices
-    pub async fn get_available_cameras(&self) -> Result<Vec<CameraDeviceInfo>> {
-        let devices = get_available_cameras()
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to list devices: {}", e)))?;
+    /// Stop delivering a stream's frames to all its sinks (events, channels, previews,
+    /// WebRTC) while the camera stays open and the sinks attached, so resuming is instant
+    pub async fn pause_streaming(&self, stream_id: &str) -> Result<()> {
+        self.set_paused(stream_id, true).await
+    }
 
-        Ok(devices)
+    pub async fn resume_streaming(&self, stream_id: &str) -> Result<()> {
+        self.set_paused(stream_id, false).await
     }
 
-    pub async fn start_streaming(&self, device_id: String) -> Result<String> {
-        // Check if streaming is already active for this device
-        {
-            let streams = self.active_streams.lock().await;
-            for (session_id, active_stream) in streams.iter() {
-                if active_stream.camera_id == device_id {
-                    return Ok(session_id.clone());
-                }
+    async fn set_paused(&self, stream_id: &str, paused: bool) -> Result<()> {
+        let streams = self.active_streams.lock().await;
+        let stream = streams
+            .get(stream_id)
+            .ok_or_else(|| Error::StreamNotFound(stream_id.to_string()))?;
+        if stream.paused.swap(paused, Ordering::Relaxed) == paused {
+            return Ok(());
+        }
+        log::info!(
+            "Stream {} {}",
+            stream_id,
+            if paused { "paused" } else { "resumed" }
+        );
+        if !paused {
+            // Decoders of the encoding sinks restart from a keyframe
+            for encoded in stream.encoded.values() {
+                encoded.request_keyframe();
             }
         }
+        Ok(())
+    }
 
-        let format = get_recommended_format()
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to get recommended format : {}", e)))?;
-        let _camera = start_camera_preview(device_id.clone(), Some(format))
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to start camera preview: {}", e)))?;
+    /// Start or stop writing the frame pipeline's event log; returns the log file
+    pub fn set_event_log(&self, enabled: bool) -> Result<PathBuf> {
+        self.event_log.set_enabled(enabled)?;
+        Ok(self.event_log.path().to_path_buf())
+    }
 
-        // Create watch channel for frame events
-        let (tx, rx) = watch::channel(None);
+    /// Buffers, threads, devices and connections held by the plugin, to spot leaks
+    pub async fn get_resource_usage(&self) -> Result<ResourceUsage> {
+        let (sessions, open_devices) = {
+            let streams = self.active_streams.lock().await;
+            let sessions = streams
+                .iter()
+                .map(|(stream_id, stream)| SessionUsage {
+                    stream_id: stream_id.clone(),
+                    device_id: stream.camera_id.clone(),
+                    uptime_ms: stream.start_time.elapsed().as_millis() as u64,
+                    buffer_bytes: stream.memory.used() as u64,
+                    sinks: stream.sinks.len(),
+                    encoding: stream.encoded.values().any(|feed| feed.is_running()),
+                })
+                .collect();
+            // Custom sources manage their own devices
+            let open_devices = streams
+                .values()
+                .filter(|stream| stream.source.is_none())
+                .map(|stream| stream.camera_id.clone())
+                .collect();
+            (sessions, open_devices)
+        };
 
-        let tx_clone = tx.clone();
-        let callback = move |frame: crabcamera::CameraFrame| {
-            let event = FrameEvent {
-                width: frame.width,
-                height: frame.height,
-                data: frame.data,
-                format: frame.format,
-            };
+        Ok(ResourceUsage {
+            sessions,
+            open_devices,
+            webrtc_connections: self.webrtc_manager.connection_count().await,
+            native_previews: self.native_previews.count(),
+            frame_consumers: self.consumers.count(),
+            memory_used_bytes: self.memory.used() as u64,
+            memory_budget_bytes: self.memory.limit().map(|limit| limit as u64),
+            runtime_workers: tokio::runtime::Handle::try_current()
+                .map(|handle| handle.metrics().num_workers())
+                .unwrap_or(0),
+            process_threads: process_thread_count(),
+        })
+    }
 
-            if let Err(e) = tx_clone.send(Some(event)) {
-                log::error!("Failed to send frame event: {}", e);
+    /// Render a stream directly into a native window surface behind its webview,
+    /// avoiding the IPC cost of sending frames to the frontend
+    /// `rect` is in physical pixels; the page must be transparent over it
+    pub async fn start_native_preview(
+        &self,
+        stream_id: &str,
+        window: WebviewWindow<R>,
+        rect: PreviewRect,
+    ) -> Result<String> {
+        let receiver = self.get_receiver_by_stream_id(stream_id).await?;
+        self.native_previews.start(window, receiver, rect).await
+    }
+
+    /// Move or resize a native preview
+    pub fn set_native_preview_rect(&self, preview_id: &str, rect: PreviewRect) -> Result<()> {
+        self.native_previews.set_rect(preview_id, rect)
+    }
+
+    pub fn stop_native_preview(&self, preview_id: &str) -> Result<()> {
+        self.native_previews.stop(&self.app, preview_id)
+    }
+
+    /// Show a stream in a small frameless window managed by the plugin (picture-in-picture),
+    /// visible while the user works in other apps. Returns the window ID.
+    pub async fn open_pip_preview(&self, stream_id: &str, options: PipOptions) -> Result<String> {
+        let size = options.size.unwrap_or(WindowSize {
+            width: 320.0,
+            height: 180.0,
+        });
+        let (label, builder) = preview_window_builder(&self.app, "Camera");
+        let mut builder = builder
+            .inner_size(size.width, size.height)
+            .always_on_top(options.always_on_top.unwrap_or(true))
+            .skip_taskbar(true);
+        if let Some(position) = options.position {
+            builder = builder.position(position.x, position.y);
+        }
+        let window = builder.build()?;
+
+        if options.position.is_none() {
+            // Bottom-right corner of the primary monitor
+            if let Ok(Some(monitor)) = window.primary_monitor() {
+                let scale = monitor.scale_factor();
+                let area = monitor.size().to_logical::<f64>(scale);
+                let origin = monitor.position().to_logical::<f64>(scale);
+                let position = LogicalPosition::new(
+                    origin.x + area.width - size.width - PIP_MARGIN,
+                    origin.y + area.height - size.height - PIP_MARGIN,
+                );
+                if let Err(e) = window.set_position(position) {
+                    log::warn!("Failed to place picture-in-picture window: {}", e);
+                }
             }
+        }
+
+        self.show_in_window(stream_id, window).await?;
+        Ok(label)
+    }
+
+    /// Turn a stream into a borderless always-on-top window, optionally click-through and
+    /// translucent, for teleprompter and monitoring overlays. Returns the window ID.
+    pub async fn open_overlay_preview(
+        &self,
+        stream_id: &str,
+        options: OverlayOptions,
+    ) -> Result<String> {
+        let size = options.size.unwrap_or(WindowSize {
+            width: 480.0,
+            height: 270.0,
+        });
+        let (label, builder) = preview_window_builder(&self.app, "Camera overlay");
+        let mut builder = builder
+            .inner_size(size.width, size.height)
+            .always_on_top(true)
+            .skip_taskbar(true);
+        builder = match options.position {
+            Some(position) => builder.position(position.x, position.y),
+            None => builder.center(),
         };
-        set_callback(device_id.clone(), callback)
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to set callback: {}", e)))?;
+        let window = builder.build()?;
 
-        let session_id = uuid::Uuid::new_v4().to_string();
-        let active_stream = ActiveStream {
-            camera_id: device_id.clone(),
-            start_time: Instant::now(),
-            rx,
+        let update = OverlayUpdate {
+            click_through: Some(options.click_through),
+            opacity: options.opacity,
         };
+        if let Err(e) = apply_overlay_update(&window, update) {
+            let _ = window.close();
+            return Err(e);
+        }
 
-        self.active_streams
-            .lock()
-            .await
-            .insert(session_id.clone(), active_stream);
+        self.show_in_window(stream_id, window).await?;
+        Ok(label)
+    }
 
-        Ok(session_id)
+    /// Change the click-through and opacity of an overlay preview
+    pub fn set_overlay_options(&self, window_id: &str, update: OverlayUpdate) -> Result<()> {
+        apply_overlay_update(&self.preview_window(window_id)?, update)
     }
 
-    pub async fn stop_streaming(&self, stream_id: String) -> Result<()> {
-        log::info!(" Stopping stream with stream_id: {}", stream_id);
+    /// Displays available for kiosk previews, in logical coordinates
+    pub fn get_monitors(&self) -> Result<Vec<MonitorInfo>> {
+        let primary = self
+            .app
+            .primary_monitor()?
+            .and_then(|monitor| monitor.name().cloned());
+        Ok(self
+            .app
+            .available_monitors()?
+            .into_iter()
+            .map(|monitor| {
+                let scale = monitor.scale_factor();
+                let position = monitor.position().to_logical::<f64>(scale);
+                let size = monitor.size().to_logical::<f64>(scale);
+                MonitorInfo {
+                    primary: primary.is_some() && primary.as_ref() == monitor.name(),
+                    name: monitor.name().cloned(),
+                    position: WindowPosition {
+                        x: position.x,
+                        y: position.y,
+                    },
+                    size: WindowSize {
+                        width: size.width,
+                        height: size.height,
+                    },
+                    scale_factor: scale,
+                }
+            })
+            .collect())
+    }
 
-        // First, signal the callback to stop processing frames
-        let stream = self
-            .active_streams
-            .lock()
-            .await
-            .remove(&stream_id)
-            .ok_or_else(|| Error::StreamNotFound(stream_id.clone()))?;
+    /// Show a stream fullscreen on a monitor (the primary one by default) without any
+    /// webview content, for signage and photo booths. Returns the window ID.
+    pub async fn start_kiosk_preview(
+        &self,
+        stream_id: &str,
+        monitor: Option<String>,
+    ) -> Result<String> {
+        let monitor = match monitor {
+            Some(name) => self
+                .app
+                .available_monitors()?
+                .into_iter()
+                .find(|m| m.name() == Some(&name))
+                .ok_or_else(|| Error::CameraError(format!("Monitor not found: {}", name)))?,
+            None => self
+                .app
+                .primary_monitor()?
+                .ok_or_else(|| Error::CameraError("No monitor available".to_string()))?,
+        };
+        let scale = monitor.scale_factor();
+        let position = monitor.position().to_logical::<f64>(scale);
+        let size = monitor.size().to_logical::<f64>(scale);
 
-        log::info!(
-            " Stream stopped for camera: {} (ran for {:?})",
-            stream.camera_id.clone(),
-            stream.start_time.elapsed()
-        );
+        let (label, builder) = preview_window_builder(&self.app, "Camera kiosk");
+        let window = builder
+            .position(position.x, position.y)
+            .inner_size(size.width, size.height)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .focused(true)
+            .build()?;
+        // Go fullscreen once the window sits on the chosen monitor
+        let setup = window
+            .set_fullscreen(true)
+            .and_then(|_| window.set_cursor_visible(false));
+        if let Err(e) = setup {
+            let _ = window.close();
+            return Err(e.into());
+        }
 
-        // First, clear the callback to stop receiving frames
-        log::info!(" Clearing callback for camera: {}", stream.camera_id);
-        set_callback(stream.camera_id.clone(), |_| {})
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to clear callback: {}", e)))?;
+        self.show_in_window(stream_id, window).await?;
+        Ok(label)
+    }
 
-        // Then stop the camera preview
-        log::info!(" Stopping camera preview for device: {}", stream.camera_id);
-        crabcamera::commands::capture::stop_camera_preview(stream.camera_id.clone())
-            .await
-            .map_err(|e| Error::CameraError(format!("Failed to stop camera: {}", e)))?;
+    /// Close a preview window opened by the plugin; its preview stops with it
+    pub fn close_preview_window(&self, window_id: &str) -> Result<()> {
+        self.preview_window(window_id)?.close()?;
+        Ok(())
+    }
 
-        // WORKAROUND: Give more time for camera to fully release
-        // TODO: This should be fixed in crabcamera by properly closing/dropping the camera
-        log::warn!(" Waiting 500ms for camera to fully release (crabcamera limitation)");
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        crabcamera::commands::capture::release_camera(stream.camera_id.clone())
+    /// Look up a window created by the plugin, refusing the app's own windows
+    fn preview_window(&self, window_id: &str) -> Result<WebviewWindow<R>> {
+        if !window_id.starts_with(PREVIEW_WINDOW_PREFIX) {
+            return Err(Error::WindowNotFound(window_id.to_string()));
+        }
+        self.app
+            .get_webview_window(window_id)
+            .ok_or_else(|| Error::WindowNotFound(window_id.to_string()))
+    }
+
+    /// Render a stream into the whole surface of a plugin-managed window, following its
+    /// size and stopping when it is closed. The window is closed if the preview can't start.
+    async fn show_in_window(&self, stream_id: &str, window: WebviewWindow<R>) -> Result<()> {
+        let size = window.inner_size()?;
+        let rect = PreviewRect {
+            x: 0,
+            y: 0,
+            width: size.width,
+            height: size.height,
+        };
+        let preview_id = match self
+            .start_native_preview(stream_id, window.clone(), rect)
             .await
-            .map_err(|e| Error::CameraError(format!("Failed to release camera: {}", e)))?;
-        // When stream is dropped here, the threadpool will be dropped too
-        log::info!(
-            " Stream resources cleaned up for camera: {}",
-            stream.camera_id
-        );
+        {
+            Ok(preview_id) => preview_id,
+            Err(e) => {
+                let _ = window.close();
+                return Err(e);
+            }
+        };
 
+        let app = self.app.clone();
+        window.on_window_event(move |event| match event {
+            WindowEvent::Resized(size) => {
+                let rect = PreviewRect {
+                    x: 0,
+                    y: 0,
+                    width: size.width,
+                    height: size.height,
+                };
+                if let Err(e) = app.camera().set_native_preview_rect(&preview_id, rect) {
+                    log::warn!("Failed to resize preview: {}", e);
+                }
+            }
+            WindowEvent::Destroyed => {
+                if let Err(e) = app.camera().stop_native_preview(&preview_id) {
+                    log::warn!("Failed to stop preview: {}", e);
+                }
+            }
+            _ => {}
+        });
         Ok(())
     }
 
@@ -193,11 +2759,114 @@ impl<R: Runtime> Camera<R> {
             })
     }
 
+    /// Subscribe to the encoded frames of a device's stream
+    /// The first encoding sink starts the stream's encoding pass, with the encoder settings
+    /// of the profile applied to the device; later sinks share it. `zero_latency` sinks
+    /// (WebRTC) and `Realtime` streams leave B-frames and lookahead off unless the profile
+    /// sets them. `encoder` replaces the encoder of the settings, for sinks that need
+    /// another codec; each encoder runs its own pass.
+    async fn subscribe_encoded(
+        &self,
+        device_id: &str,
+        zero_latency: bool,
+        encoder: Option<&str>,
+    ) -> Result<(EncodedFeed, broadcast::Receiver<SharedEncodedFrame>)> {
+        let mut streams = self.active_streams.lock().await;
+        let stream = streams
+            .values_mut()
+            .find(|stream| stream.camera_id == device_id)
+            .ok_or_else(|| {
+                Error::StreamNotFound(format!("No active stream for device: {}", device_id))
+            })?;
+
+        let (name, settings) = self.encoder_settings(stream, zero_latency, encoder);
+        if let Some(feed) = stream.encoded.get(&name).filter(|feed| feed.is_running()) {
+            return Ok((feed.clone(), feed.subscribe()));
+        }
+
+        let encoder = self.encoders.create(settings.encoder.as_deref())?;
+        let feed = EncodedFeed::spawn(
+            stream.rx.clone(),
+            encoder,
+            &settings,
+            stream.options.latency_mode,
+            self.memory.clone(),
+            stream.memory.clone(),
+            stream.faults.clone(),
+            self.telemetry.clone(),
+            stream.stages.clone(),
+        );
+        let receiver = feed.subscribe();
+        stream.encoded.insert(name, feed.clone());
+        Ok((feed, receiver))
+    }
+
+    /// MIME type of the encoding pass a sink of a device's stream would subscribe to, and
+    /// whether it has B-frames, without starting it; see `subscribe_encoded`
+    async fn peek_encoded(
+        &self,
+        device_id: &str,
+        zero_latency: bool,
+        encoder: Option<&str>,
+    ) -> Result<(String, bool)> {
+        let streams = self.active_streams.lock().await;
+        let stream = streams
+            .values()
+            .find(|stream| stream.camera_id == device_id)
+            .ok_or_else(|| {
+                Error::StreamNotFound(format!("No active stream for device: {}", device_id))
+            })?;
+
+        let (name, settings) = self.encoder_settings(stream, zero_latency, encoder);
+        if let Some(feed) = stream.encoded.get(&name).filter(|feed| feed.is_running()) {
+            return Ok((feed.mime_type().to_string(), feed.has_b_frames()));
+        }
+        let mime_type = self
+            .encoders
+            .create(settings.encoder.as_deref())?
+            .mime_type()
+            .to_string();
+        Ok((mime_type, settings.b_frames.unwrap_or(0) > 0))
+    }
+
+    /// Encoder settings of a stream's encoding sinks, from its options or else the profile
+    /// of its device, and the name of their encoder
+    fn encoder_settings(
+        &self,
+        stream: &ActiveStream,
+        zero_latency: bool,
+        encoder: Option<&str>,
+    ) -> (String, EncoderSettings) {
+        let mut settings = stream
+            .options
+            .encoder
+            .clone()
+            .or_else(|| {
+                self.profiles
+                    .active(&stream.camera_id)
+                    .map(|profile| profile.encoder)
+            })
+            .unwrap_or_default();
+        if let Some(encoder) = encoder {
+            settings.encoder = Some(encoder.to_string());
+        }
+        if settings.max_fps.is_none() {
+            settings.max_fps = stream.options.max_fps;
+        }
+        if zero_latency || stream.options.latency_mode == LatencyMode::Realtime {
+            settings = settings.zero_latency();
+        }
+        let name = settings
+            .encoder
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ENCODER.to_string());
+        (name, settings)
+    }
+
     /// Connect a camera stream to a WebRTC connection
-    /// This spawns a background task that:
-    /// 1. Gets the receiver from the camera stream
-    /// 2. Encodes frames to H.264
-    /// 3. Pushes encoded frames to the WebRTC track
+    /// This spawns a background task that pushes the stream's frames to the WebRTC track,
+    /// encoded to the track's codec; the encoding pass is shared with the other encoding
+    /// sinks of that codec. The connection ID is the sink ID for `detach_sink`.
     pub async fn connect_camera_to_webrtc(
         &self,
         device_id: String,
@@ -208,60 +2877,509 @@ impl<R: Runtime> Camera<R> {
             .attach_receiver_to_connection(&connection_id)
             .await?;
 
-        // Get a receiver for this device
-        let mut receiver = self.get_receiver_by_device_id(&device_id).await?;
+        let codec = self
+            .webrtc_manager
+            .video_codec(&connection_id)
+            .await?
+            .unwrap_or_default();
+        let (feed, mut receiver) = self
+            .subscribe_encoded(&device_id, true, track_encoder(codec)?)
+            .await?;
+        if !feed.mime_type().eq_ignore_ascii_case(codec.mime_type()) {
+            return Err(Error::CameraError(format!(
+                "The WebRTC track carries {}, the selected encoder produces {}",
+                codec.mime_type(),
+                feed.mime_type()
+            )));
+        }
+        if feed.has_b_frames() {
+            return Err(Error::CameraError(
+                "WebRTC tracks can't carry B-frames, set b_frames to 0 in the encoder settings"
+                    .to_string(),
+            ));
+        }
+
+        let (stop_tx, mut stop_rx) = oneshot::channel();
+        if let Some(stream) = self
+            .active_streams
+            .lock()
+            .await
+            .values_mut()
+            .find(|stream| stream.camera_id == device_id)
+        {
+            stream.sinks.insert(connection_id.clone(), stop_tx);
+        }
 
+        let conn = self.webrtc_manager.get_connection(&connection_id).await?;
+        let keyframe_requests = conn.keyframe_requests.clone();
+        let activity = conn.activity.clone();
+        let mut adaptation = self.bitrate_controller(&feed);
+        if let Some(controller) = &adaptation {
+            feed.limit_bitrate(&connection_id, controller.kbps());
+        }
+        let app = self.app.clone();
         // Clone manager for the background task
         let webrtc_manager = self.webrtc_manager.clone();
-        let connection_id_clone = connection_id.clone();
+        let telemetry = self.telemetry.clone();
+        let stages = self.event_log.for_device(&device_id);
 
-        // Spawn background task to consume frames and push to WebRTC
+        // Spawn background task to push encoded frames to WebRTC
         tokio::spawn(async move {
             log::info!(
-                "WebRTC encoding task started for connection: {} from device: {}",
+                "WebRTC sink started for connection: {} from device: {}",
                 connection_id,
                 device_id
             );
 
-            while receiver.changed().await.is_ok() {
-                // Clone the current frame out of the watch ref so no borrow lives across await
-                let maybe_frame = { receiver.borrow_and_update().clone() };
-
-                match maybe_frame {
-                    Some(frame) => {
-                        // Encode NV12 frame to H.264
-
-                        match yuv_nv12_to_h264(&frame.data, frame.width, frame.height) {
-                            Ok(h264) => {
-                                // Assume ~30fps -> 33ms duration per frame
-                                if let Err(e) = webrtc_manager
-                                    .push_h264_sample(&connection_id_clone, h264, 33)
-                                    .await
-                                {
-                                    log::error!("Failed to push H.264 sample: {}", e);
-                                    break;
+            let realtime = feed.latency_mode() == LatencyMode::Realtime;
+            // Realtime sink skipping to the newest frame, until it restarts from a keyframe
+            let mut resync = false;
+            let mut last_pts_us = None;
+            let mut adapt = tokio::time::interval(BITRATE_ADAPTATION_INTERVAL);
+            loop {
+                let received = tokio::select! {
+                    _ = &mut stop_rx => break,
+                    _ = keyframe_requests.notified() => {
+                        log::debug!("Keyframe requested for connection {}", connection_id);
+                        feed.request_keyframe();
+                        continue;
+                    }
+                    _ = adapt.tick(), if adaptation.is_some() => {
+                        if let Some(controller) = adaptation.as_mut() {
+                            let previous_kbps = controller.kbps();
+                            if let Some(kbps) = controller.update(&activity) {
+                                feed.limit_bitrate(&connection_id, kbps);
+                                let event = BitrateChangedEvent {
+                                    connection_id: connection_id.clone(),
+                                    bitrate_kbps: kbps,
+                                    previous_kbps,
+                                    loss: activity.fraction_lost(),
+                                    estimate_kbps: activity.remb_kbps(),
+                                };
+                                if let Err(e) = app.emit("camera://bitrate-changed", &event) {
+                                    log::warn!("Failed to emit bitrate-changed: {}", e);
                                 }
                             }
-                            Err(e) => {
-                                log::error!("Failed to encode frame to H.264: {}", e);
-                                break;
-                            }
                         }
-                    }
-                    None => {
                         continue;
                     }
+                    received = receiver.recv() => received,
+                };
+                match received {
+                    Ok(encoded) => {
+                        let late = realtime && !receiver.is_empty();
+                        if late || (resync && !encoded.keyframe) {
+                            // A newer frame is already waiting: showing this one would
+                            // only add delay
+                            resync = true;
+                            feed.request_keyframe();
+                            continue;
+                        }
+                        resync = false;
+
+                        let started = std::time::Instant::now();
+                        // The RTP clock advances by the gap between capture timestamps,
+                        // skipped frames included, so playback follows the capture pace
+                        let duration = match last_pts_us.replace(encoded.pts_us) {
+                            Some(last) => Duration::from_micros(encoded.pts_us - last),
+                            None => DEFAULT_FRAME_DURATION,
+                        };
+                        let captured = UNIX_EPOCH + Duration::from_millis(encoded.capture_time_ms);
+                        let pushed = webrtc_manager
+                            .push_video_sample(
+                                &connection_id,
+                                encoded.data.clone(),
+                                duration,
+                                captured,
+                            )
+                            .await;
+                        stages.record(
+                            Stage::Send,
+                            StageRecord {
+                                sink_id: Some(&connection_id),
+                                duration_us: Some(elapsed_us(started)),
+                                bytes: Some(encoded.data.len()),
+                                error: pushed.as_ref().err().map(|e| e.to_string()),
+                                ..Default::default()
+                            },
+                        );
+                        if let Err(e) = pushed {
+                            log::error!("Failed to push H.264 sample: {}", e);
+                            telemetry.error(Some(&device_id), "webrtc_sink", e.to_string());
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        // Resynchronize the remote decoder after the gap
+                        log::warn!(
+                            "WebRTC sink for {} fell behind, skipped {} frames",
+                            connection_id,
+                            skipped
+                        );
+                        feed.request_keyframe();
+                    }
+                    Err(RecvError::Closed) => break,
                 }
             }
+            feed.release_bitrate(&connection_id);
+
+            log::info!("WebRTC sink stopped for connection: {}", connection_id);
+        });
+
+        Ok(())
+    }
+
+    /// Follow the video codec negotiated by the SDP answer of a connection
+    /// The track keeps its codec when the peer accepts it. Otherwise it switches to the
+    /// peer's preferred codec the plugin can encode, and its sink restarts on that
+    /// encoder. Fails when the peer accepts none of them, or decodes a lower H.264 profile
+    /// than the stream encodes.
+    pub async fn negotiate_video(&self, connection_id: &str, answer_sdp: &str) -> Result<()> {
+        let accepted = crate::webrtc::answer_video_codecs(answer_sdp)?;
+        let current = self
+            .webrtc_manager
+            .video_codec(connection_id)
+            .await?
+            .unwrap_or_default();
+        let negotiated = match accepted.iter().find(|codec| codec.codec == current) {
+            Some(negotiated) => negotiated.clone(),
+            None => accepted
+                .iter()
+                .find(|codec| track_encoder(codec.codec).is_ok())
+                .cloned()
+                .ok_or_else(|| {
+                    let sendable: Vec<&str> = [
+                        VideoCodec::H264,
+                        VideoCodec::Vp8,
+                        VideoCodec::Vp9,
+                        VideoCodec::Av1,
+                    ]
+                    .into_iter()
+                    .filter(|codec| track_encoder(*codec).is_ok())
+                    .map(|codec| codec.mime_type())
+                    .collect();
+                    let accepted: Vec<&str> =
+                        accepted.iter().map(|codec| codec.codec.mime_type()).collect();
+                    Error::InvalidConfig(format!(
+                        "No video codec in common with the peer of {}: the plugin sends {}, the peer accepts {}",
+                        connection_id,
+                        sendable.join(", "),
+                        if accepted.is_empty() {
+                            "none of them".to_string()
+                        } else {
+                            accepted.join(", ")
+                        }
+                    ))
+                })?,
+        };
+
+        // Sink feeding the connection, if it is connected to a stream already
+        let sink = {
+            let mut streams = self.active_streams.lock().await;
+            streams
+                .values_mut()
+                .find(|stream| stream.sinks.contains_key(connection_id))
+                .map(|stream| {
+                    let stop = (negotiated.codec != current)
+                        .then(|| stream.sinks.remove(connection_id))
+                        .flatten();
+                    // The profile the sink's encoder is set to, read without starting it
+                    let (_, settings) = self.encoder_settings(stream, true, None);
+                    (stream.camera_id.clone(), stop, settings.profile)
+                })
+        };
 
+        if negotiated.codec != current {
             log::info!(
-                "WebRTC encoding task stopped for connection: {}",
-                connection_id
+                "Connection {} negotiated {} instead of {}",
+                connection_id,
+                negotiated.codec.mime_type(),
+                current.mime_type()
             );
-        });
+            self.webrtc_manager
+                .replace_video_track(connection_id, &negotiated)
+                .await?;
+            if let Some((device_id, stop, _)) = sink {
+                if let Some(stop) = stop {
+                    // The sink may already have ended on its own
+                    let _ = stop.send(());
+                }
+                self.connect_camera_to_webrtc(device_id, connection_id.to_string())
+                    .await?;
+            }
+            return Ok(());
+        }
 
+        if let (Some(decoded), Some((_, _, profile))) = (negotiated.h264_profile(), sink) {
+            if let Some(encoded) = profile.filter(|encoded| *encoded > decoded) {
+                return Err(Error::InvalidConfig(format!(
+                    "The peer of {} decodes H.264 up to the {:?} profile, the stream encodes {:?}; set the encoder profile to {:?}",
+                    connection_id, decoded, encoded, decoded
+                )));
+            }
+        }
         Ok(())
     }
 
+    /// Bitrate of a WebRTC sink following its network, unless adaptation is disabled;
+    /// it starts at the bitrate of the encoder settings, which is also its maximum
+    fn bitrate_controller(&self, feed: &EncodedFeed) -> Option<BitrateController> {
+        let config = &self.bitrate_adaptation;
+        if !config.enabled.unwrap_or(true) {
+            return None;
+        }
+        let min_kbps = config.min_kbps.unwrap_or(DEFAULT_MIN_BITRATE_KBPS);
+        let max_kbps = config
+            .max_kbps
+            .or(feed.bitrate_kbps())
+            .unwrap_or(DEFAULT_MAX_BITRATE_KBPS);
+        let start_kbps = feed.bitrate_kbps().unwrap_or(DEFAULT_START_BITRATE_KBPS);
+        Some(BitrateController::new(start_kbps, min_kbps, max_kbps))
+    }
+
+    /// Close a peer connection, and stop the stream linked to it unless other connections
+    /// or channels still use it
+    pub async fn close_connection(&self, connection_id: &str) -> Result<()> {
+        let manager = &self.webrtc_manager;
+
+        if let Some(stream_id) = manager.get_stream_for_connection(connection_id).await {
+            log::info!(
+                "Closing connection {} with linked stream {}",
+                connection_id,
+                stream_id
+            );
+            let shared = manager
+                .connections_for_stream(&stream_id)
+                .await
+                .iter()
+                .any(|id| id != connection_id);
+            let remaining_sinks =
+                self.active_streams
+                    .lock()
+                    .await
+                    .get_mut(&stream_id)
+                    .map(|stream| {
+                        if let Some(stop) = stream.sinks.remove(connection_id) {
+                            let _ = stop.send(());
+                        }
+                        stream.sinks.len()
+                    });
+            if remaining_sinks == Some(0) && !shared {
+                self.stop_streaming(stream_id).await?;
+            }
+        }
+
+        // Close the peer connection
+        manager.remove_connection(connection_id).await
+    }
+
     // Streaming methods removed to support WebRTC-based frontend streaming
 }
+
+/// Threads of the process, read from procfs
+#[cfg(target_os = "linux")]
+fn process_thread_count() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Threads:"))
+        .and_then(|count| count.trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_thread_count() -> Option<usize> {
+    None
+}
+
+/// Formats reported by a backend, largest and fastest first, without duplicates
+fn device_formats(formats: &[crabcamera::CameraFormat]) -> Vec<CameraFormat> {
+    let mut formats: Vec<CameraFormat> = formats
+        .iter()
+        .map(|format| CameraFormat {
+            width: format.width,
+            height: format.height,
+            fps: format.fps as f64,
+            format: Some(format.format_type.clone()),
+        })
+        .collect();
+    formats.sort_by(|a, b| {
+        (b.width * b.height)
+            .cmp(&(a.width * a.height))
+            .then(b.fps.total_cmp(&a.fps))
+            .then_with(|| a.format.cmp(&b.format))
+    });
+    formats.dedup_by(|a, b| {
+        (a.width, a.height, a.format.as_deref()) == (b.width, b.height, b.format.as_deref())
+            && a.fps == b.fps
+    });
+    formats
+}
+
+/// First of `formats` (sorted by `device_formats`) matching the fields set in `request`
+fn select_format(formats: &[CameraFormat], request: &StartStreamRequest) -> Option<CameraFormat> {
+    formats
+        .iter()
+        .find(|format| {
+            request.width.map_or(true, |width| width == format.width)
+                && request
+                    .height
+                    .map_or(true, |height| height == format.height)
+                && request
+                    .fps
+                    .map_or(true, |fps| (fps - format.fps).abs() < FPS_TOLERANCE)
+        })
+        .cloned()
+}
+
+/// Encoder feeding a WebRTC track of `codec`; H.264 tracks keep the encoder of the
+/// encoder settings
+fn track_encoder(codec: VideoCodec) -> Result<Option<&'static str>> {
+    match codec {
+        VideoCodec::H264 => Ok(None),
+        #[cfg(feature = "vpx")]
+        VideoCodec::Vp8 => Ok(Some(crate::vpx::VP8_ENCODER)),
+        #[cfg(feature = "vpx")]
+        VideoCodec::Vp9 => Ok(Some(crate::vpx::VP9_ENCODER)),
+        #[cfg(not(feature = "vpx"))]
+        VideoCodec::Vp8 | VideoCodec::Vp9 => Err(Error::InvalidConfig(format!(
+            "{} tracks need the plugin built with the vpx feature",
+            codec.mime_type()
+        ))),
+        #[cfg(feature = "av1")]
+        VideoCodec::Av1 => Ok(Some(crate::av1::AV1_ENCODER)),
+        #[cfg(not(feature = "av1"))]
+        VideoCodec::Av1 => Err(Error::InvalidConfig(
+            "video/av1 tracks need the plugin built with the av1 feature".to_string(),
+        )),
+    }
+}
+
+/// Error of a pre-roll recording of a device whose stream keeps none
+fn no_preroll(device_id: &str) -> Error {
+    Error::InvalidConfig(format!(
+        "No pre-roll is kept for {}, call enable_preroll first",
+        device_id
+    ))
+}
+
+fn format_label(format: &CameraFormat) -> String {
+    format!("{}x{}@{}", format.width, format.height, format.fps)
+}
+
+/// Marks a device busy while a stream starts or stops on it, so that a concurrent
+/// start or stop (double click, stop during start) fails fast instead of racing
+struct DeviceOperation<'a> {
+    busy: &'a Mutex<HashSet<String>>,
+    device_id: String,
+}
+
+impl<'a> DeviceOperation<'a> {
+    fn begin(busy: &'a Mutex<HashSet<String>>, device_id: &str) -> Result<Self> {
+        if !busy.lock().unwrap().insert(device_id.to_string()) {
+            return Err(Error::OperationInProgress(device_id.to_string()));
+        }
+        Ok(Self {
+            busy,
+            device_id: device_id.to_string(),
+        })
+    }
+}
+
+impl Drop for DeviceOperation<'_> {
+    fn drop(&mut self) {
+        self.busy.lock().unwrap().remove(&self.device_id);
+    }
+}
+
+fn apply_overlay_update<R: Runtime>(
+    window: &WebviewWindow<R>,
+    update: OverlayUpdate,
+) -> Result<()> {
+    if let Some(click_through) = update.click_through {
+        window.set_ignore_cursor_events(click_through)?;
+    }
+    if let Some(opacity) = update.opacity {
+        set_window_opacity(window, opacity)?;
+    }
+    Ok(())
+}
+
+fn to_crabcamera_white_balance(white_balance: WhiteBalance) -> CrabWhiteBalance {
+    match white_balance {
+        WhiteBalance::Auto => CrabWhiteBalance::Auto,
+        WhiteBalance::Daylight => CrabWhiteBalance::Daylight,
+        WhiteBalance::Tungsten => CrabWhiteBalance::Incandescent,
+        WhiteBalance::Fluorescent => CrabWhiteBalance::Fluorescent,
+        WhiteBalance::Manual { kelvin } => CrabWhiteBalance::Custom(kelvin),
+    }
+}
+
+fn from_crabcamera_white_balance(white_balance: CrabWhiteBalance) -> WhiteBalance {
+    // Presets without a plugin-level equivalent are reported by their nominal temperature
+    match white_balance {
+        CrabWhiteBalance::Auto => WhiteBalance::Auto,
+        CrabWhiteBalance::Daylight => WhiteBalance::Daylight,
+        CrabWhiteBalance::Incandescent => WhiteBalance::Tungsten,
+        CrabWhiteBalance::Fluorescent => WhiteBalance::Fluorescent,
+        CrabWhiteBalance::Flash => WhiteBalance::Manual { kelvin: 5500 },
+        CrabWhiteBalance::Cloudy => WhiteBalance::Manual { kelvin: 6500 },
+        CrabWhiteBalance::Shade => WhiteBalance::Manual { kelvin: 7500 },
+        CrabWhiteBalance::Custom(kelvin) => WhiteBalance::Manual { kelvin },
+    }
+}
+
+/// Image controls listed by `get_camera_controls`
+const IMAGE_CONTROLS: [CameraControl; 6] = [
+    CameraControl::Brightness,
+    CameraControl::Contrast,
+    CameraControl::Saturation,
+    CameraControl::Sharpness,
+    CameraControl::Gain,
+    CameraControl::Gamma,
+];
+
+/// Values crabcamera accepts for a control, `None` when it doesn't expose it
+/// Brightness is an offset around 0; contrast, saturation and sharpness are factors where
+/// 1 leaves the image unchanged; gain is an ISO sensitivity.
+fn control_range(control: CameraControl) -> Option<CameraControlInfo> {
+    let (min, max, step, default) = match control {
+        CameraControl::Brightness => (-1.0, 1.0, 0.01, 0.0),
+        CameraControl::Contrast | CameraControl::Saturation | CameraControl::Sharpness => {
+            (0.0, 2.0, 0.01, 1.0)
+        }
+        CameraControl::Gain => (100.0, 6400.0, 100.0, 100.0),
+        CameraControl::Gamma => return None,
+    };
+    Some(CameraControlInfo {
+        control,
+        value: default,
+        min,
+        max,
+        step,
+        default,
+    })
+}
+
+fn read_control(controls: &CameraControls, control: CameraControl) -> Option<f32> {
+    match control {
+        CameraControl::Brightness => controls.brightness,
+        CameraControl::Contrast => controls.contrast,
+        CameraControl::Saturation => controls.saturation,
+        CameraControl::Sharpness => controls.sharpness,
+        CameraControl::Gain => controls.iso_sensitivity.map(|iso| iso as f32),
+        CameraControl::Gamma => None,
+    }
+}
+
+fn control_update(control: CameraControl, value: f32) -> CameraControls {
+    let mut controls = CameraControls::default();
+    match control {
+        CameraControl::Brightness => controls.brightness = Some(value),
+        CameraControl::Contrast => controls.contrast = Some(value),
+        CameraControl::Saturation => controls.saturation = Some(value),
+        CameraControl::Sharpness => controls.sharpness = Some(value),
+        CameraControl::Gain => controls.iso_sensitivity = Some(value.round() as u32),
+        CameraControl::Gamma => {}
+    }
+    controls
+}
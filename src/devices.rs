@@ -0,0 +1,52 @@
+use crate::models::CameraInfo;
+use crate::CameraExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// How often the connected cameras are listed
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Emit `camera://device-added` and `camera://device-removed` with the camera's info
+/// whenever a camera is plugged in or unplugged
+pub fn spawn_monitor<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut known: Option<HashMap<String, CameraInfo>> = None;
+        let mut check = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            check.tick().await;
+            let cameras = match app.camera().list_cameras().await {
+                Ok(cameras) => cameras,
+                Err(e) => {
+                    log::debug!("Failed to list cameras for hot-plug detection: {}", e);
+                    continue;
+                }
+            };
+            let current: HashMap<_, _> = cameras
+                .into_iter()
+                .map(|camera| (camera.device.id.clone(), camera))
+                .collect();
+            // The first listing is the baseline, not a change
+            let Some(previous) = known.replace(current.clone()) else {
+                continue;
+            };
+
+            for (id, camera) in &previous {
+                if !current.contains_key(id) {
+                    log::info!("Camera removed: {} ({})", camera.device.name, id);
+                    if let Err(e) = app.emit("camera://device-removed", camera) {
+                        log::warn!("Failed to emit device removal: {}", e);
+                    }
+                }
+            }
+            for (id, camera) in &current {
+                if !previous.contains_key(id) {
+                    log::info!("Camera added: {} ({})", camera.device.name, id);
+                    if let Err(e) = app.emit("camera://device-added", camera) {
+                        log::warn!("Failed to emit device addition: {}", e);
+                    }
+                }
+            }
+        }
+    });
+}
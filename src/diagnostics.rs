@@ -0,0 +1,254 @@
+use crate::error::{Error, Result};
+use crate::models::{ColorSpace, DiagnosticStep, DiagnosticsReport, HdrTransfer};
+use crate::utils::{
+    convert_rgba_color_space, is_mjpeg, mjpeg_to_nv12, nv12_to_rgba, p010_to_nv12_tone_mapped,
+    source_color_space, uyvy_to_nv12, yuv_nv12_to_h264, yuy2_to_nv12, Packed422,
+};
+use crate::Camera;
+use crabcamera::commands::capture::{release_camera, stop_camera_preview};
+use crabcamera::{get_recommended_format, set_callback, start_camera_preview};
+use std::sync::Mutex;
+use tauri::Runtime;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration, Instant};
+
+/// Time allowed for the default camera to deliver its first frame
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A captured frame, from a fresh capture or from a stream already running on the device
+struct Sample {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: String,
+}
+
+/// Collects the outcome and timing of each diagnostic step
+struct Report {
+    started: Instant,
+    steps: Vec<DiagnosticStep>,
+}
+
+impl Report {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Record a step; returns its value when it succeeded
+    fn record<T>(
+        &mut self,
+        name: &str,
+        started: Instant,
+        result: Result<(T, String)>,
+    ) -> Option<T> {
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let (value, detail, error) = match result {
+            Ok((value, detail)) => (Some(value), Some(detail), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+        self.steps.push(DiagnosticStep {
+            name: name.to_string(),
+            ok: value.is_some(),
+            duration_ms,
+            detail,
+            error,
+        });
+        value
+    }
+
+    /// Record a step that could not run because an earlier one failed
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.steps.push(DiagnosticStep {
+            name: name.to_string(),
+            ok: false,
+            duration_ms: 0.0,
+            detail: None,
+            error: Some(format!("Skipped: {}", reason)),
+        });
+    }
+
+    fn finish(self) -> DiagnosticsReport {
+        DiagnosticsReport {
+            platform: std::env::consts::OS.to_string(),
+            passed: self.steps.iter().all(|step| step.ok),
+            total_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            steps: self.steps,
+        }
+    }
+}
+
+/// Check permission, enumerate devices, grab one frame from the default camera,
+/// then convert and encode it. Failures are reported per step, never returned as errors.
+pub async fn run<R: Runtime>(camera: &Camera<R>) -> DiagnosticsReport {
+    let mut report = Report::new();
+
+    let started = Instant::now();
+    let permission = camera.request_permission().await.and_then(|info| {
+        let detail = serde_json::to_string(&info)?;
+        Ok(((), detail))
+    });
+    report.record("permission", started, permission);
+
+    let started = Instant::now();
+    let devices = async {
+        let count = camera.get_available_cameras().await?.len();
+        let default = camera
+            .get_default_camera()
+            .await?
+            .map(|camera| camera.device.id)
+            .ok_or_else(|| Error::DeviceNotFound("no camera connected".to_string()))?;
+        let detail = format!("{} device(s), default: {}", count, default);
+        Ok::<_, Error>((default, detail))
+    }
+    .await;
+    let Some(device_id) = report.record("enumerate_devices", started, devices) else {
+        report.skip("capture_frame", "no camera available");
+        report.skip("convert", "no frame captured");
+        report.skip("encode", "no frame captured");
+        return report.finish();
+    };
+
+    let started = Instant::now();
+    let sample = capture_sample(camera, &device_id).await.map(|sample| {
+        let detail = format!(
+            "{}x{} {} from {}",
+            sample.width, sample.height, sample.format, device_id
+        );
+        (sample, detail)
+    });
+    let Some(sample) = report.record("capture_frame", started, sample) else {
+        report.skip("convert", "no frame captured");
+        report.skip("encode", "no frame captured");
+        return report.finish();
+    };
+
+    let started = Instant::now();
+    let nv12 = convert(&sample).map(|nv12| {
+        let detail = format!("NV12 to sRGB RGBA ({} kernels)", crate::simd::kernel_name());
+        (nv12, detail)
+    });
+    let Some(nv12) = report.record("convert", started, nv12) else {
+        report.skip("encode", "conversion failed");
+        return report.finish();
+    };
+
+    let started = Instant::now();
+    let encoded = yuv_nv12_to_h264(&nv12, sample.width, sample.height)
+        .map(|h264| ((), format!("{} bytes of H.264", h264.len())));
+    report.record("encode", started, encoded);
+
+    report.finish()
+}
+
+/// Grab one frame, reading it from the running stream when the device is already in use
+async fn capture_sample<R: Runtime>(camera: &Camera<R>, device_id: &str) -> Result<Sample> {
+    if let Ok(mut receiver) = camera.get_receiver_by_device_id(device_id).await {
+        let frame = timeout(FRAME_TIMEOUT, async {
+            loop {
+                receiver
+                    .changed()
+                    .await
+                    .map_err(|_| Error::NoActiveStream(device_id.to_string()))?;
+                let frame = receiver.borrow_and_update().clone();
+                if let Some(frame) = frame.filter(|frame| !frame.repeated) {
+                    return Ok(frame);
+                }
+            }
+        })
+        .await
+        .map_err(|_| {
+            Error::CameraError(format!("No frame received within {:?}", FRAME_TIMEOUT))
+        })??;
+
+        return Ok(Sample {
+            data: frame.data,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        });
+    }
+
+    let format = get_recommended_format()
+        .await
+        .map_err(|e| Error::CameraError(format!("Failed to get recommended format : {}", e)))?;
+    start_camera_preview(device_id.to_string(), Some(format))
+        .await
+        .map_err(crate::privacy::open_error)?;
+
+    let (tx, rx) = oneshot::channel();
+    let tx = Mutex::new(Some(tx));
+    let callback = move |frame: crabcamera::CameraFrame| {
+        if let Some(tx) = tx.lock().unwrap().take() {
+            let _ = tx.send(frame);
+        }
+    };
+    let frame = match set_callback(device_id.to_string(), callback).await {
+        Ok(_) => timeout(FRAME_TIMEOUT, rx).await,
+        Err(e) => {
+            release(device_id).await;
+            return Err(Error::CameraError(format!("Failed to set callback: {}", e)));
+        }
+    };
+    release(device_id).await;
+
+    match frame {
+        Ok(Ok(frame)) => Ok(Sample {
+            data: frame.data,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format,
+        }),
+        Ok(Err(_)) => Err(Error::CameraError(
+            "Camera callback dropped before delivering a frame".to_string(),
+        )),
+        Err(_) => Err(Error::CameraError(format!(
+            "No frame received within {:?}",
+            FRAME_TIMEOUT
+        ))),
+    }
+}
+
+/// Close the camera opened for the capture step, logging failures
+async fn release(device_id: &str) {
+    if let Err(e) = set_callback(device_id.to_string(), |_| {}).await {
+        log::warn!("Failed to clear diagnostics callback: {}", e);
+    }
+    if let Err(e) = stop_camera_preview(device_id.to_string()).await {
+        log::warn!("Failed to stop diagnostics preview: {}", e);
+    }
+    if let Err(e) = release_camera(device_id.to_string()).await {
+        log::warn!("Failed to release camera after diagnostics: {}", e);
+    }
+}
+
+/// Run the RGBA conversion on the sample and return its NV12 data for the encode step
+fn convert(sample: &Sample) -> Result<Vec<u8>> {
+    let nv12 = if sample.format.eq_ignore_ascii_case("P010") {
+        p010_to_nv12_tone_mapped(&sample.data, sample.width, sample.height, HdrTransfer::Hlg)?
+    } else if sample.format.eq_ignore_ascii_case("NV12") {
+        sample.data.clone()
+    } else if is_mjpeg(&sample.format) {
+        mjpeg_to_nv12(&sample.data)?.0
+    } else if let Some(order) = Packed422::of(&sample.format) {
+        match order {
+            Packed422::Yuyv => yuy2_to_nv12(&sample.data, sample.width, sample.height)?,
+            Packed422::Uyvy => uyvy_to_nv12(&sample.data, sample.width, sample.height)?,
+        }
+    } else {
+        return Err(Error::CameraError(format!(
+            "Unsupported frame format: {}",
+            sample.format
+        )));
+    };
+
+    let mut rgba = nv12_to_rgba(&nv12, sample.width, sample.height)?;
+    convert_rgba_color_space(
+        &mut rgba,
+        source_color_space(sample.width, sample.height),
+        ColorSpace::Srgb,
+    );
+    Ok(nv12)
+}
@@ -0,0 +1,266 @@
+use crate::consumers::FrameConsumer;
+use crate::error::{Error, Result};
+use crate::models::{CameraFormat, FrameEvent};
+use crate::sources::{FrameCallback, FrameSource};
+use crabcamera::{CameraDeviceInfo, CameraFrame};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// First bytes of a frame dump, followed by one record per frame
+const DUMP_MAGIC: &[u8; 8] = b"TPCDUMP1";
+
+/// One captured frame as stored in a dump
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpedFrame {
+    /// Capture time relative to the first frame of the dump
+    pub timestamp_us: u64,
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+impl DumpedFrame {
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(&self.timestamp_us.to_le_bytes())?;
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&[self.format.len() as u8])?;
+        writer.write_all(self.format.as_bytes())?;
+        writer.write_all(&(self.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.data)
+    }
+
+    /// Next frame of a dump, `None` at the end of the file
+    fn read_from(reader: &mut impl Read) -> Result<Option<Self>> {
+        let mut timestamp = [0u8; 8];
+        match reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut word = [0u8; 4];
+        reader.read_exact(&mut word)?;
+        let width = u32::from_le_bytes(word);
+        reader.read_exact(&mut word)?;
+        let height = u32::from_le_bytes(word);
+        let mut format_len = [0u8; 1];
+        reader.read_exact(&mut format_len)?;
+        let mut format = vec![0u8; format_len[0] as usize];
+        reader.read_exact(&mut format)?;
+        reader.read_exact(&mut word)?;
+        let mut data = vec![0u8; u32::from_le_bytes(word) as usize];
+        reader.read_exact(&mut data)?;
+
+        Ok(Some(Self {
+            timestamp_us: u64::from_le_bytes(timestamp),
+            width,
+            height,
+            format: String::from_utf8(format)
+                .map_err(|_| Error::CameraError("Corrupted frame dump".to_string()))?,
+            data,
+        }))
+    }
+}
+
+/// Open a dump and check its header, leaving the reader on the first frame
+fn open_dump(path: &Path) -> Result<BufReader<File>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != DUMP_MAGIC {
+        return Err(Error::CameraError(format!(
+            "Not a frame dump: {}",
+            path.display()
+        )));
+    }
+    Ok(reader)
+}
+
+/// Raw frame consumer writing the original capture buffers of a stream to a dump file.
+/// Like any consumer, it only gets the latest frame when writing falls behind the camera.
+pub struct DumpWriter {
+    writer: Option<BufWriter<File>>,
+    path: PathBuf,
+    first_timestamp_us: Option<i64>,
+}
+
+impl DumpWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(DUMP_MAGIC)?;
+        Ok(Self {
+            writer: Some(writer),
+            path: path.to_path_buf(),
+            first_timestamp_us: None,
+        })
+    }
+}
+
+impl FrameConsumer for DumpWriter {
+    fn on_frame(&mut self, _frame: &FrameEvent) {}
+
+    fn raw(&self) -> bool {
+        true
+    }
+
+    fn on_raw_frame(&mut self, frame: &CameraFrame) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        let timestamp_us = frame.timestamp.timestamp_micros();
+        let first = *self.first_timestamp_us.get_or_insert(timestamp_us);
+        let dumped = DumpedFrame {
+            timestamp_us: timestamp_us.saturating_sub(first).max(0) as u64,
+            width: frame.width,
+            height: frame.height,
+            format: frame.format.clone(),
+            data: frame.data.clone(),
+        };
+        if let Err(e) = dumped.write_to(writer) {
+            // Keep the frames written so far readable
+            log::error!("Failed to write frame dump {}: {}", self.path.display(), e);
+            self.writer = None;
+        }
+    }
+
+    fn on_stop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            if let Err(e) = writer.flush() {
+                log::error!("Failed to write frame dump {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// Frame source playing a dump back with its recorded timing, in a loop,
+/// so frames reported by users go through conversion and encoding exactly as captured
+pub struct ReplaySource {
+    id: String,
+    path: PathBuf,
+    first: DumpedFrame,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ReplaySource {
+    pub fn open(id: impl Into<String>, path: &Path) -> Result<Self> {
+        let first = DumpedFrame::read_from(&mut open_dump(path)?)?
+            .ok_or_else(|| Error::CameraError(format!("Empty frame dump: {}", path.display())))?;
+        Ok(Self {
+            id: id.into(),
+            path: path.to_path_buf(),
+            first,
+            running: Arc::new(AtomicBool::new(false)),
+            thread: Mutex::new(None),
+        })
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn info(&self) -> CameraDeviceInfo {
+        CameraDeviceInfo::new(
+            self.id.clone(),
+            format!("Replay of {}", self.path.display()),
+        )
+        .with_formats(vec![crabcamera::CameraFormat::new(
+            self.first.width,
+            self.first.height,
+            30.0,
+        )])
+    }
+
+    /// The recorded format is replayed whatever the profile asks for
+    fn start(&self, _format: Option<&CameraFormat>, mut on_frame: FrameCallback) -> Result<()> {
+        let mut reader = open_dump(&self.path)?;
+        self.running.store(true, Ordering::Relaxed);
+        let running = self.running.clone();
+        let id = self.id.clone();
+        let path = self.path.clone();
+        let thread = std::thread::spawn(move || {
+            let mut started = Instant::now();
+            while running.load(Ordering::Relaxed) {
+                let dumped = match DumpedFrame::read_from(&mut reader) {
+                    Ok(Some(dumped)) => dumped,
+                    // Loop from the first frame
+                    Ok(None) => match reader.seek(SeekFrom::Start(DUMP_MAGIC.len() as u64)) {
+                        Ok(_) => {
+                            started = Instant::now();
+                            continue;
+                        }
+                        Err(e) => {
+                            log::error!("Failed to rewind frame dump {}: {}", path.display(), e);
+                            break;
+                        }
+                    },
+                    Err(e) => {
+                        log::error!("Failed to read frame dump {}: {}", path.display(), e);
+                        break;
+                    }
+                };
+
+                let due = started + Duration::from_micros(dumped.timestamp_us);
+                std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                let mut frame =
+                    CameraFrame::new(dumped.data, dumped.width, dumped.height, id.clone());
+                frame.format = dumped.format;
+                on_frame(frame);
+            }
+        });
+        *self.thread.lock().unwrap() = Some(thread);
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<()> {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_round_trip() {
+        let path = std::env::temp_dir().join(format!("dump-{}.bin", uuid::Uuid::new_v4()));
+        let frames = vec![
+            DumpedFrame {
+                timestamp_us: 0,
+                width: 4,
+                height: 2,
+                format: "NV12".to_string(),
+                data: (0..12).collect(),
+            },
+            DumpedFrame {
+                timestamp_us: 33_333,
+                width: 4,
+                height: 2,
+                format: "P010".to_string(),
+                data: vec![7; 24],
+            },
+        ];
+        let mut writer = BufWriter::new(File::create(&path).unwrap());
+        writer.write_all(DUMP_MAGIC).unwrap();
+        for frame in &frames {
+            frame.write_to(&mut writer).unwrap();
+        }
+        drop(writer);
+
+        let mut reader = open_dump(&path).unwrap();
+        let mut read = Vec::new();
+        while let Some(frame) = DumpedFrame::read_from(&mut reader).unwrap() {
+            read.push(frame);
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read, frames);
+    }
+}
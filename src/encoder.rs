@@ -0,0 +1,447 @@
+use crate::budget::{Budgeted, MemoryBudget, StreamMemory};
+use crate::error::{Error, Result};
+use crate::eventlog::{elapsed_us, Stage, StageLog, StageRecord};
+use crate::faults::FaultState;
+use crate::models::{
+    EncoderSettings, FrameEvent, H264Level, H264Profile, LatencyMode, RateControl,
+};
+use crate::telemetry::Telemetry;
+use crate::utils::H264EncoderSession;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, watch};
+
+/// Name of the built-in OpenH264 encoder
+pub const DEFAULT_ENCODER: &str = "openh264";
+/// Frame rate encoders are configured for when neither the settings nor the stream set one
+const DEFAULT_FPS: f64 = 30.0;
+/// Encoded frames buffered per sink before a slow sink starts losing frames
+fn encoded_buffer(latency_mode: LatencyMode) -> usize {
+    match latency_mode {
+        LatencyMode::Realtime => 2,
+        LatencyMode::Balanced => 8,
+        LatencyMode::Quality => 60,
+    }
+}
+
+/// Parameters an encoder is configured with before its first frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncoderParams {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f64,
+    pub bitrate_kbps: Option<u32>,
+    /// Latency mode of the stream: `Realtime` asks for zero-latency settings (no lookahead,
+    /// no frame reordering), `Quality` lets the encoder spend time on the picture
+    pub latency_mode: LatencyMode,
+    /// B-frames between reference frames, the encoder default when `None`
+    pub b_frames: Option<u32>,
+    /// Frames of rate control lookahead, the encoder default when `None`
+    pub lookahead: Option<u32>,
+    /// Slices per frame, the encoder default when `None`
+    pub slices: Option<u32>,
+    /// H.264 profile and level, the encoder defaults when `None`
+    pub profile: Option<H264Profile>,
+    pub level: Option<H264Level>,
+    /// Frames between keyframes, the encoder default when `None`
+    pub keyframe_interval: Option<u32>,
+    /// Rate control mode, the encoder default when `None`
+    pub rate_control: Option<RateControl>,
+    /// Speed preset of encoders trading speed for compression (AV1), 0 to 10
+    pub speed_preset: Option<u8>,
+}
+
+/// One encoded access unit
+#[derive(Debug, Clone)]
+pub struct EncodedFrame {
+    pub data: Vec<u8>,
+    pub keyframe: bool,
+    /// `pts_us` of the frame this access unit presents; encoders reordering frames
+    /// report the one of the frame they output, not of the frame they were given
+    pub pts_us: u64,
+    /// `capture_time_ms` of that frame
+    pub capture_time_ms: u64,
+    /// Size of the encoded picture
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Video encoder used by every encoding sink of the plugin (WebRTC tracks, recordings).
+/// Host apps can plug their own implementation, e.g. a hardware SDK, with
+/// `Camera::register_encoder` and select it through `EncoderSettings::encoder`.
+pub trait Encoder: Send + 'static {
+    /// MIME type of the produced bitstream, e.g. `video/h264`
+    fn mime_type(&self) -> &str;
+
+    /// Called before the first frame and again whenever the frame size changes
+    fn configure(&mut self, params: &EncoderParams) -> Result<()>;
+
+    /// An empty `data` skips the frame, e.g. when rate control drops it
+    fn encode(&mut self, frame: &FrameEvent) -> Result<EncodedFrame>;
+
+    /// Make the next encoded frame a keyframe
+    fn force_keyframe(&mut self);
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()>;
+}
+
+/// Creates a fresh encoder for each encoding sink
+pub type EncoderFactory = Arc<dyn Fn() -> Box<dyn Encoder> + Send + Sync>;
+
+/// Encoders available to the plugin, by name
+pub struct EncoderRegistry {
+    factories: Mutex<HashMap<String, EncoderFactory>>,
+}
+
+impl Default for EncoderRegistry {
+    fn default() -> Self {
+        let registry = Self {
+            factories: Mutex::new(HashMap::new()),
+        };
+        registry.register(
+            DEFAULT_ENCODER,
+            Arc::new(|| Box::new(OpenH264Encoder::default())),
+        );
+        #[cfg(feature = "vpx")]
+        {
+            use crate::vpx::{VpxEncoder, VP8_ENCODER, VP9_ENCODER};
+            registry.register(VP8_ENCODER, Arc::new(|| Box::new(VpxEncoder::vp8())));
+            registry.register(VP9_ENCODER, Arc::new(|| Box::new(VpxEncoder::vp9())));
+        }
+        #[cfg(feature = "av1")]
+        registry.register(
+            crate::av1::AV1_ENCODER,
+            Arc::new(|| Box::new(crate::av1::Av1Encoder::default())),
+        );
+        registry
+    }
+}
+
+impl EncoderRegistry {
+    /// Add or replace an encoder
+    pub fn register(&self, name: impl Into<String>, factory: EncoderFactory) {
+        self.factories.lock().unwrap().insert(name.into(), factory);
+    }
+
+    /// Create an encoder by name, the built-in one when `None`
+    pub fn create(&self, name: Option<&str>) -> Result<Box<dyn Encoder>> {
+        let name = name.unwrap_or(DEFAULT_ENCODER);
+        let factories = self.factories.lock().unwrap();
+        let factory = factories
+            .get(name)
+            .ok_or_else(|| Error::CameraError(format!("Encoder not registered: {}", name)))?;
+        Ok(factory())
+    }
+}
+
+/// Built-in H.264 encoder backed by OpenH264
+/// Frames are encoded as groups of pictures starting at IDR frames that carry SPS/PPS:
+/// there is no frame reordering nor lookahead delay, and frames are encoded as a single
+/// slice.
+#[derive(Default)]
+pub struct OpenH264Encoder {
+    params: Option<EncoderParams>,
+    /// Started on the first frame after `configure`
+    session: Option<H264EncoderSession>,
+}
+
+impl Encoder for OpenH264Encoder {
+    fn mime_type(&self) -> &str {
+        "video/h264"
+    }
+
+    fn configure(&mut self, params: &EncoderParams) -> Result<()> {
+        if params.b_frames.unwrap_or(0) > 0 || params.lookahead.unwrap_or(0) > 0 {
+            return Err(Error::CameraError(
+                "OpenH264 encodes without B-frames nor lookahead, register another encoder for them"
+                    .to_string(),
+            ));
+        }
+        if params.slices.unwrap_or(1) > 1 {
+            return Err(Error::CameraError(
+                "OpenH264 encodes frames as a single slice".to_string(),
+            ));
+        }
+        self.params = Some(params.clone());
+        self.session = None;
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: &FrameEvent) -> Result<EncodedFrame> {
+        let params = self
+            .params
+            .as_ref()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        if !frame.format.eq_ignore_ascii_case("NV12") {
+            return Err(Error::CameraError(format!(
+                "Cannot encode {} frames to H.264, start the stream without color conversion",
+                frame.format
+            )));
+        }
+
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                let settings = EncoderSettings {
+                    bitrate_kbps: params.bitrate_kbps,
+                    max_fps: Some(params.fps),
+                    profile: params.profile,
+                    level: params.level,
+                    keyframe_interval: params.keyframe_interval,
+                    rate_control: params.rate_control,
+                    ..Default::default()
+                };
+                H264EncoderSession::new(&settings, params.latency_mode)?
+            }
+        };
+        let session = self.session.insert(session);
+        let picture = session.encode(&frame.data, frame.width, frame.height, frame.pts_us)?;
+        Ok(EncodedFrame {
+            data: picture.data,
+            keyframe: picture.keyframe,
+            pts_us: frame.pts_us,
+            capture_time_ms: frame.capture_time_ms,
+            width: frame.width,
+            height: frame.height,
+        })
+    }
+
+    fn force_keyframe(&mut self) {
+        // A new session starts with one anyway
+        if let Some(session) = &mut self.session {
+            session.force_keyframe();
+        }
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        let params = self
+            .params
+            .as_mut()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        params.bitrate_kbps = Some(bitrate_kbps);
+        match &mut self.session {
+            Some(session) => session.set_bitrate(bitrate_kbps),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Encoded frame shared by the sinks of a stream, accounted to the memory budget while buffered
+pub type SharedEncodedFrame = Arc<Budgeted<EncodedFrame>>;
+
+/// Output of a stream's encoding pass, shared by all its encoding sinks
+/// (WebRTC tracks, recordings) so each frame is encoded once
+#[derive(Clone)]
+pub struct EncodedFeed {
+    sender: broadcast::Sender<SharedEncodedFrame>,
+    mime_type: String,
+    latency_mode: LatencyMode,
+    /// The encoder was configured with B-frames, which WebRTC tracks can't carry
+    b_frames: bool,
+    /// Bitrate of the encoder settings
+    bitrate_kbps: Option<u32>,
+    /// Bitrate caps of the sinks adapting to their network, by sink ID
+    bitrate_limits: Arc<Mutex<HashMap<String, u32>>>,
+    keyframe_requested: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+}
+
+impl EncodedFeed {
+    /// Encode the frames of `receiver` until the stream stops or the last subscriber is gone
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn(
+        mut receiver: watch::Receiver<Option<FrameEvent>>,
+        mut encoder: Box<dyn Encoder>,
+        settings: &EncoderSettings,
+        latency_mode: LatencyMode,
+        budget: Arc<MemoryBudget>,
+        memory: Arc<StreamMemory>,
+        faults: Arc<FaultState>,
+        telemetry: Telemetry,
+        stages: StageLog,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(encoded_buffer(latency_mode));
+        let feed = Self {
+            sender: sender.clone(),
+            mime_type: encoder.mime_type().to_string(),
+            latency_mode,
+            b_frames: settings.b_frames.unwrap_or(0) > 0,
+            bitrate_kbps: settings.bitrate_kbps,
+            bitrate_limits: Arc::new(Mutex::new(HashMap::new())),
+            keyframe_requested: Arc::new(AtomicBool::new(false)),
+            running: Arc::new(AtomicBool::new(true)),
+        };
+
+        let keyframe_requested = feed.keyframe_requested.clone();
+        let running = feed.running.clone();
+        let bitrate_limits = feed.bitrate_limits.clone();
+        let settings = settings.clone();
+        // Profiles aren't validated like stream options, ignore a frame rate of zero
+        let max_fps = settings.max_fps.filter(|fps| fps.is_finite() && *fps > 0.0);
+        let fps = max_fps.unwrap_or(DEFAULT_FPS);
+        let frame_interval_us = max_fps.map(|fps| (1_000_000.0 / fps) as u64);
+        tokio::spawn(async move {
+            let mut configured = None;
+            let mut next_due_us: Option<u64> = None;
+            let mut bitrate_kbps = settings.bitrate_kbps;
+            while receiver.changed().await.is_ok() {
+                if sender.receiver_count() == 0 {
+                    break;
+                }
+                // Clone the current frame out of the watch ref so no borrow lives across await
+                let frame = receiver.borrow_and_update().clone();
+                // Unchanged content: decoders keep showing the last picture
+                let Some(frame) = frame.filter(|frame| !frame.repeated) else {
+                    continue;
+                };
+
+                // Skip frames coming faster than the encoder's frame rate, picking them on a
+                // fixed schedule so capture jitter doesn't lower the rate
+                if let Some(interval_us) = frame_interval_us {
+                    if next_due_us.is_some_and(|due| frame.pts_us + interval_us / 2 < due) {
+                        continue;
+                    }
+                    let base = next_due_us
+                        .filter(|due| frame.pts_us < due + interval_us)
+                        .unwrap_or(frame.pts_us);
+                    next_due_us = Some(base + interval_us);
+                }
+
+                // (Re)configure the encoder for the frame size
+                if configured != Some((frame.width, frame.height)) {
+                    let params = EncoderParams {
+                        width: frame.width,
+                        height: frame.height,
+                        fps,
+                        bitrate_kbps,
+                        latency_mode,
+                        b_frames: settings.b_frames,
+                        lookahead: settings.lookahead,
+                        slices: settings.slices,
+                        profile: settings.profile,
+                        level: settings.level,
+                        keyframe_interval: settings.keyframe_interval,
+                        rate_control: settings.rate_control,
+                        speed_preset: settings.speed_preset,
+                    };
+                    if let Err(e) = encoder.configure(&params) {
+                        log::error!("Failed to configure encoder: {}", e);
+                        telemetry.error(Some(stages.device_id()), "encode", e.to_string());
+                        break;
+                    }
+                    configured = Some((frame.width, frame.height));
+                }
+                if keyframe_requested.swap(false, Ordering::Relaxed) {
+                    encoder.force_keyframe();
+                }
+                // The lowest cap of the adapting sinks, else the bitrate of the settings
+                let wanted_kbps = bitrate_limits
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .min()
+                    .copied()
+                    .or(settings.bitrate_kbps);
+                if wanted_kbps != bitrate_kbps {
+                    if let Some(kbps) = wanted_kbps {
+                        if let Err(e) = encoder.set_bitrate(kbps) {
+                            log::warn!("Failed to set the encoder bitrate: {}", e);
+                        }
+                    }
+                    bitrate_kbps = wanted_kbps;
+                }
+
+                // A panicking encoder (e.g. a faulty hardware SDK) ends the pass like an error
+                let started = std::time::Instant::now();
+                let encoded = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    if faults.encoder_panic() {
+                        panic!("Injected encoder panic");
+                    }
+                    encoder.encode(&frame)
+                }))
+                .unwrap_or_else(|_| Err(Error::CameraError("Encoder panicked".to_string())));
+                stages.record(
+                    Stage::Encode,
+                    StageRecord {
+                        duration_us: Some(elapsed_us(started)),
+                        bytes: encoded.as_ref().ok().map(|encoded| encoded.data.len()),
+                        width: Some(frame.width),
+                        height: Some(frame.height),
+                        keyframe: encoded.as_ref().ok().map(|encoded| encoded.keyframe),
+                        error: encoded.as_ref().err().map(|e| e.to_string()),
+                        ..Default::default()
+                    },
+                );
+                match encoded {
+                    // Skipped by the encoder's rate control
+                    Ok(encoded) if encoded.data.is_empty() => {}
+                    Ok(encoded) => match budget.reserve(&memory, encoded.data.len()) {
+                        Some(reservation) => {
+                            let _ = sender.send(Arc::new(Budgeted::new(encoded, reservation)));
+                        }
+                        // Over budget: drop it, and restart from a keyframe once memory frees up
+                        None => keyframe_requested.store(true, Ordering::Relaxed),
+                    },
+                    Err(e) => {
+                        log::error!("Failed to encode frame: {}", e);
+                        telemetry.error(Some(stages.device_id()), "encode", e.to_string());
+                        break;
+                    }
+                }
+            }
+            running.store(false, Ordering::Relaxed);
+            log::info!("Stream encoder stopped");
+        });
+
+        feed
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn latency_mode(&self) -> LatencyMode {
+        self.latency_mode
+    }
+
+    pub fn has_b_frames(&self) -> bool {
+        self.b_frames
+    }
+
+    /// Bitrate of the encoder settings, before any sink limits it
+    pub fn bitrate_kbps(&self) -> Option<u32> {
+        self.bitrate_kbps
+    }
+
+    /// Cap the encoder bitrate for a sink adapting to its network; the encoder runs at the
+    /// lowest cap of all sinks
+    pub fn limit_bitrate(&self, sink_id: &str, kbps: u32) {
+        self.bitrate_limits
+            .lock()
+            .unwrap()
+            .insert(sink_id.to_string(), kbps);
+    }
+
+    /// Remove the cap of a sink, e.g. once it is detached
+    pub fn release_bitrate(&self, sink_id: &str) {
+        self.bitrate_limits.lock().unwrap().remove(sink_id);
+    }
+
+    /// Whether the encoding pass still runs; a stopped feed is replaced by a new one
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Receive the encoded frames; the next frame is a keyframe so the sink can start decoding
+    pub fn subscribe(&self) -> broadcast::Receiver<SharedEncodedFrame> {
+        let receiver = self.sender.subscribe();
+        self.request_keyframe();
+        receiver
+    }
+
+    pub fn request_keyframe(&self) {
+        self.keyframe_requested.store(true, Ordering::Relaxed);
+    }
+}
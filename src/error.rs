@@ -23,6 +23,28 @@ pub enum Error {
   InitializationFailed(String),
   #[error("Channel send error")]
   ChannelSendError,
+  #[error("Unsupported on this platform: {0}")]
+  Unsupported(String),
+  #[error("Profile not found: {0}")]
+  ProfileNotFound(String),
+  #[error("Invalid configuration: {0}")]
+  InvalidConfig(String),
+  #[error("Window not found: {0}")]
+  WindowNotFound(String),
+  #[error("Preview not found: {0}")]
+  PreviewNotFound(String),
+  #[error("Camera permission denied: {0}")]
+  PermissionDenied(String),
+  #[error("Access denied: {0}")]
+  AccessDenied(String),
+  #[error("Operation already in progress for device: {0}")]
+  OperationInProgress(String),
+  #[error("Not enough disk space: {0}")]
+  DiskFull(String),
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+  #[error(transparent)]
+  Tauri(#[from] tauri::Error),
 }
 
 impl Serialize for Error {
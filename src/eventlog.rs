@@ -0,0 +1,287 @@
+use crate::error::Result;
+use crate::models::EventLogConfig;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Name of the current log file; rotated files get a `.1`, `.2`, ... suffix
+pub const EVENT_LOG_FILE: &str = "camera-events.ndjson";
+const DEFAULT_MAX_FILE_MB: u64 = 16;
+const DEFAULT_MAX_FILES: u32 = 4;
+/// Buffered records are written out at least this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stage of the frame pipeline a record describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    /// A frame arrived from the driver or source
+    Capture,
+    /// Color conversion, HDR tone mapping, dedup and filters
+    Convert,
+    Encode,
+    /// Delivery to a frontend channel or WebRTC track
+    Send,
+}
+
+/// Measurements of one stage for one frame; unset fields are left out of the record
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageRecord<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sink_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+    /// Time since the previous captured frame
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_us: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyframe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Line<'a> {
+    ts_us: u64,
+    stage: Stage,
+    device_id: &'a str,
+    #[serde(flatten)]
+    record: StageRecord<'a>,
+}
+
+struct LogFile {
+    writer: BufWriter<File>,
+    size: u64,
+    last_flush: Instant,
+}
+
+/// Opt-in newline-delimited JSON log of the frame pipeline, one record per stage and
+/// frame, rotated by size so support can collect a performance trace from a user
+pub struct EventLog {
+    enabled: AtomicBool,
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl EventLog {
+    /// Log to `dir`, enabled right away when the config says so
+    pub fn new(dir: &Path, config: &EventLogConfig) -> Self {
+        let log = Self {
+            enabled: AtomicBool::new(false),
+            path: dir.join(EVENT_LOG_FILE),
+            max_bytes: config.max_file_mb.unwrap_or(DEFAULT_MAX_FILE_MB) * 1024 * 1024,
+            max_files: config.max_files.unwrap_or(DEFAULT_MAX_FILES),
+            file: Mutex::new(None),
+        };
+        if config.enabled {
+            if let Err(e) = log.set_enabled(true) {
+                log::warn!("Failed to open the camera event log: {}", e);
+            }
+        }
+        log
+    }
+
+    /// Current log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Start appending to the log file, or flush and close it
+    pub fn set_enabled(&self, enabled: bool) -> Result<()> {
+        let mut file = self.file.lock().unwrap();
+        if enabled && file.is_none() {
+            *file = Some(self.open()?);
+        } else if !enabled {
+            if let Some(mut file) = file.take() {
+                file.writer.flush()?;
+            }
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Records of the stages of a device's stream
+    pub fn for_device(self: &Arc<Self>, device_id: &str) -> StageLog {
+        StageLog {
+            log: self.clone(),
+            device_id: device_id.into(),
+        }
+    }
+
+    fn write(&self, stage: Stage, device_id: &str, record: StageRecord) {
+        let ts_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let line = Line {
+            ts_us,
+            stage,
+            device_id,
+            record,
+        };
+        let mut bytes = match serde_json::to_vec(&line) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::warn!("Failed to serialize event log record: {}", e);
+                return;
+            }
+        };
+        bytes.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        // Disabled while the record was being built
+        let Some(current) = file.take() else {
+            return;
+        };
+        match self.append(current, &bytes) {
+            Ok(current) => *file = Some(current),
+            Err(e) => {
+                // Out of disk or directory removed: stop logging rather than fail every frame
+                log::error!("Camera event log disabled after a write failure: {}", e);
+                self.enabled.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    fn append(&self, mut current: LogFile, bytes: &[u8]) -> std::io::Result<LogFile> {
+        if current.size > 0 && current.size + bytes.len() as u64 > self.max_bytes {
+            // Close the file before renaming it, Windows can't rename open files
+            current.writer.flush()?;
+            drop(current);
+            self.rotate()?;
+            current = self.open()?;
+        }
+        current.writer.write_all(bytes)?;
+        current.size += bytes.len() as u64;
+        if current.last_flush.elapsed() >= FLUSH_INTERVAL {
+            current.writer.flush()?;
+            current.last_flush = Instant::now();
+        }
+        Ok(current)
+    }
+
+    fn open(&self) -> std::io::Result<LogFile> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        Ok(LogFile {
+            size: file.metadata()?.len(),
+            writer: BufWriter::new(file),
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Shift `file.1` to `file.2` and so on, dropping the oldest, then move the current
+    /// file to `file.1`
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        let rotated = |index: u32| PathBuf::from(format!("{}.{}", self.path.display(), index));
+        let oldest = rotated(self.max_files);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = rotated(index);
+            if from.exists() {
+                std::fs::rename(from, rotated(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(1))
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.get_mut().unwrap().as_mut() {
+            let _ = file.writer.flush();
+        }
+    }
+}
+
+/// Event log handle bound to one device
+#[derive(Clone)]
+pub struct StageLog {
+    log: Arc<EventLog>,
+    device_id: Arc<str>,
+}
+
+impl StageLog {
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    pub fn record(&self, stage: Stage, record: StageRecord) {
+        if self.log.enabled() {
+            self.log.write(stage, &self.device_id, record);
+        }
+    }
+}
+
+/// Microseconds elapsed since `started`
+pub fn elapsed_us(started: Instant) -> u64 {
+    started.elapsed().as_micros() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_log_rotates() {
+        let dir = std::env::temp_dir().join(format!("event-log-{}", uuid::Uuid::new_v4()));
+        let config = EventLogConfig {
+            enabled: true,
+            dir: None,
+            max_file_mb: Some(0),
+            max_files: Some(2),
+        };
+        let log = Arc::new(EventLog::new(&dir, &config));
+        let stages = log.for_device("0");
+        for bytes in 0..4 {
+            stages.record(
+                Stage::Capture,
+                StageRecord {
+                    bytes: Some(bytes),
+                    ..Default::default()
+                },
+            );
+        }
+        log.set_enabled(false).unwrap();
+
+        // Every record overflows the zero-sized limit: the oldest one was dropped
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        let current = read(EVENT_LOG_FILE);
+        assert!(current.starts_with("{\"tsUs\":"));
+        assert!(current.ends_with(",\"stage\":\"capture\",\"deviceId\":\"0\",\"bytes\":3}\n"));
+        assert!(read(&format!("{}.1", EVENT_LOG_FILE)).contains("\"bytes\":2"));
+        assert!(read(&format!("{}.2", EVENT_LOG_FILE)).contains("\"bytes\":1"));
+        assert!(!dir.join(format!("{}.3", EVENT_LOG_FILE)).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
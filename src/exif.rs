@@ -0,0 +1,288 @@
+use crate::models::GpsLocation;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Field types of the TIFF structure EXIF data is stored in
+const BYTE: u16 = 1;
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+/// Metadata written to a JPEG photo
+pub struct Exif {
+    pub captured: SystemTime,
+    /// Name of the camera, as the EXIF model
+    pub camera: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// EXIF orientation, 1 to 8
+    pub orientation: u16,
+    pub description: Option<String>,
+    pub gps: Option<GpsLocation>,
+}
+
+enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(u16),
+    Long(u32),
+    Rational(Vec<(u32, u32)>),
+}
+
+impl Value {
+    fn field_type(&self) -> u16 {
+        match self {
+            Value::Byte(_) => BYTE,
+            Value::Ascii(_) => ASCII,
+            Value::Short(_) => SHORT,
+            Value::Long(_) => LONG,
+            Value::Rational(_) => RATIONAL,
+        }
+    }
+
+    /// Number of values, ASCII strings counting their NUL terminator
+    fn count(&self) -> u32 {
+        match self {
+            Value::Byte(bytes) => bytes.len() as u32,
+            Value::Ascii(text) => text.len() as u32 + 1,
+            Value::Short(_) | Value::Long(_) => 1,
+            Value::Rational(values) => values.len() as u32,
+        }
+    }
+
+    /// Big-endian bytes of the values
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Byte(bytes) => bytes.clone(),
+            Value::Ascii(text) => text.bytes().chain([0]).collect(),
+            Value::Short(value) => value.to_be_bytes().to_vec(),
+            Value::Long(value) => value.to_be_bytes().to_vec(),
+            Value::Rational(values) => values
+                .iter()
+                .flat_map(|(numerator, denominator)| {
+                    numerator
+                        .to_be_bytes()
+                        .into_iter()
+                        .chain(denominator.to_be_bytes())
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Bytes an IFD takes: entry count, entries, next IFD offset, then the values too long
+/// to fit in their entry, each padded to an even size
+fn ifd_size(entries: &[(u16, Value)]) -> usize {
+    let values: usize = entries
+        .iter()
+        .map(|(_, value)| value.bytes().len())
+        .filter(|len| *len > 4)
+        .map(|len| len + len % 2)
+        .sum();
+    2 + entries.len() * 12 + 4 + values
+}
+
+/// Append an IFD to `tiff`; its offsets are relative to the start of `tiff`
+fn write_ifd(tiff: &mut Vec<u8>, entries: &[(u16, Value)]) {
+    let mut values = Vec::new();
+    let values_offset = tiff.len() + 2 + entries.len() * 12 + 4;
+    tiff.extend((entries.len() as u16).to_be_bytes());
+    for (tag, value) in entries {
+        tiff.extend(tag.to_be_bytes());
+        tiff.extend(value.field_type().to_be_bytes());
+        tiff.extend(value.count().to_be_bytes());
+        let mut bytes = value.bytes();
+        if bytes.len() > 4 {
+            tiff.extend(((values_offset + values.len()) as u32).to_be_bytes());
+            if bytes.len() % 2 == 1 {
+                bytes.push(0);
+            }
+            values.extend(bytes);
+        } else {
+            bytes.resize(4, 0);
+            tiff.extend(bytes);
+        }
+    }
+    // No next IFD
+    tiff.extend(0u32.to_be_bytes());
+    tiff.extend(values);
+}
+
+/// `YYYY:MM:DD HH:MM:SS` in UTC, the EXIF date format
+fn datetime(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, time_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}:{:02}:{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3_600,
+        time_of_day % 3_600 / 60,
+        time_of_day % 60
+    )
+}
+
+/// Degrees, minutes and seconds of an angle, as EXIF GPS rationals
+fn degrees(angle: f64) -> Value {
+    let angle = angle.abs();
+    let minutes = angle.fract() * 60.0;
+    let seconds = minutes.fract() * 60.0;
+    Value::Rational(vec![
+        (angle.trunc() as u32, 1),
+        (minutes.trunc() as u32, 1),
+        ((seconds * 10_000.0).round() as u32, 10_000),
+    ])
+}
+
+/// TIFF structure of the metadata: IFD0, then the EXIF IFD, then the GPS IFD
+fn tiff(exif: &Exif) -> Vec<u8> {
+    let captured = datetime(exif.captured);
+    let exif_ifd = vec![
+        (0x9003, Value::Ascii(captured.clone())), // DateTimeOriginal
+        (0x9011, Value::Ascii("+00:00".to_string())), // OffsetTimeOriginal
+        (0xa002, Value::Long(exif.width)),        // PixelXDimension
+        (0xa003, Value::Long(exif.height)),       // PixelYDimension
+    ];
+    let gps_ifd = exif.gps.as_ref().map(|gps| {
+        let mut entries = vec![
+            (0x0000, Value::Byte(vec![2, 3, 0, 0])), // GPSVersionID
+            (
+                0x0001,
+                Value::Ascii(if gps.latitude < 0.0 { "S" } else { "N" }.to_string()),
+            ),
+            (0x0002, degrees(gps.latitude)),
+            (
+                0x0003,
+                Value::Ascii(if gps.longitude < 0.0 { "W" } else { "E" }.to_string()),
+            ),
+            (0x0004, degrees(gps.longitude)),
+        ];
+        if let Some(altitude) = gps.altitude {
+            entries.push((0x0005, Value::Byte(vec![u8::from(altitude < 0.0)])));
+            entries.push((
+                0x0006,
+                Value::Rational(vec![((altitude.abs() * 100.0).round() as u32, 100)]),
+            ));
+        }
+        entries
+    });
+
+    let mut ifd0 = Vec::new();
+    if let Some(description) = &exif.description {
+        ifd0.push((0x010e, Value::Ascii(description.clone()))); // ImageDescription
+    }
+    if let Some(camera) = &exif.camera {
+        ifd0.push((0x0110, Value::Ascii(camera.clone()))); // Model
+    }
+    ifd0.push((0x0112, Value::Short(exif.orientation))); // Orientation
+    ifd0.push((0x0132, Value::Ascii(captured))); // DateTime
+                                                 // Sub-IFD pointers: their size doesn't depend on their value, fill them in once known
+    ifd0.push((0x8769, Value::Long(0))); // ExifIFDPointer
+    if gps_ifd.is_some() {
+        ifd0.push((0x8825, Value::Long(0))); // GPSInfoIFDPointer
+    }
+    let exif_offset = 8 + ifd_size(&ifd0);
+    let gps_offset = exif_offset + ifd_size(&exif_ifd);
+    for (tag, value) in &mut ifd0 {
+        match tag {
+            0x8769 => *value = Value::Long(exif_offset as u32),
+            0x8825 => *value = Value::Long(gps_offset as u32),
+            _ => {}
+        }
+    }
+
+    // Big-endian header, IFD0 right after it
+    let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+    write_ifd(&mut tiff, &ifd0);
+    write_ifd(&mut tiff, &exif_ifd);
+    if let Some(gps_ifd) = &gps_ifd {
+        write_ifd(&mut tiff, gps_ifd);
+    }
+    tiff
+}
+
+/// Insert the metadata as an APP1 segment of `jpeg`, after its JFIF header if any
+pub fn embed(jpeg: &mut Vec<u8>, exif: &Exif) {
+    let tiff = tiff(exif);
+    let mut segment = vec![0xff, 0xe1];
+    segment.extend(((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    segment.extend(b"Exif\0\0");
+    segment.extend(tiff);
+
+    // Start of image, then the optional APP0 (JFIF) segment
+    let mut at = 2;
+    if jpeg.get(2..4) == Some(&[0xff, 0xe0]) {
+        if let Some(length) = jpeg.get(4..6) {
+            at += 2 + u16::from_be_bytes([length[0], length[1]]) as usize;
+        }
+    }
+    jpeg.splice(at..at, segment);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_datetime() {
+        assert_eq!(datetime(UNIX_EPOCH), "1970:01:01 00:00:00");
+        assert_eq!(
+            datetime(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+            "2023:11:14 22:13:20"
+        );
+        assert_eq!(
+            datetime(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "2000:02:29 00:00:00"
+        );
+    }
+
+    #[test]
+    fn test_embed_after_jfif() {
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0xaa, 0xbb, 0xff, 0xd9];
+        embed(
+            &mut jpeg,
+            &Exif {
+                captured: UNIX_EPOCH,
+                camera: Some("Mock Camera".to_string()),
+                width: 16,
+                height: 8,
+                orientation: 1,
+                description: None,
+                gps: Some(GpsLocation {
+                    latitude: 48.8584,
+                    longitude: -2.2945,
+                    altitude: None,
+                }),
+            },
+        );
+        assert_eq!(
+            &jpeg[..8],
+            &[0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0xaa, 0xbb]
+        );
+        assert_eq!(&jpeg[8..10], &[0xff, 0xe1]);
+        let length = u16::from_be_bytes([jpeg[10], jpeg[11]]) as usize;
+        assert_eq!(&jpeg[12..18], b"Exif\0\0");
+        assert_eq!(&jpeg[18..22], b"MM\0\x2a");
+        assert_eq!(&jpeg[10 + length..], &[0xff, 0xd9]);
+    }
+}
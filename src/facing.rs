@@ -0,0 +1,52 @@
+use crate::models::CameraFacing;
+use crabcamera::CameraDeviceInfo;
+
+/// Name fragments of cameras built into the machine
+const BUILT_IN: &[&str] = &["integrated", "built-in", "internal", "facetime"];
+const FRONT: &[&str] = &["front", "user-facing"];
+const BACK: &[&str] = &["back", "rear", "environment", "world-facing"];
+
+/// Lowercase name and description of a device, what its kind is guessed from
+pub fn label(device: &CameraDeviceInfo) -> String {
+    format!(
+        "{} {}",
+        device.name,
+        device.description.as_deref().unwrap_or_default()
+    )
+    .to_lowercase()
+}
+
+/// Where a device faces
+/// Desktop systems don't report how a camera is attached nor where it faces, so both are
+/// guessed from its name: cameras built into a laptop lid face the user, and any other
+/// named camera is taken as external.
+pub fn facing(device: &CameraDeviceInfo) -> CameraFacing {
+    let label = label(device);
+    let matches = |words: &[&str]| words.iter().any(|word| label.contains(word));
+    if matches(FRONT) {
+        CameraFacing::Front
+    } else if matches(BACK) {
+        CameraFacing::Back
+    } else if label.contains("virtual") {
+        CameraFacing::Unknown
+    } else if matches(BUILT_IN) {
+        CameraFacing::Front
+    } else {
+        CameraFacing::External
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facing_from_name() {
+        let facing_of = |name: &str| facing(&CameraDeviceInfo::new("0".into(), name.into()));
+        assert_eq!(facing_of("Integrated Webcam"), CameraFacing::Front);
+        assert_eq!(facing_of("FaceTime HD Camera"), CameraFacing::Front);
+        assert_eq!(facing_of("Microsoft Camera Rear"), CameraFacing::Back);
+        assert_eq!(facing_of("Logitech BRIO"), CameraFacing::External);
+        assert_eq!(facing_of("OBS Virtual Camera"), CameraFacing::Unknown);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(feature = "fault-injection")]
+use crate::models::FaultInjection;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Failures currently injected on a device, checked along the frame path.
+/// They can only be set with the `fault-injection` feature; otherwise every check is false.
+#[derive(Debug)]
+pub struct FaultState {
+    /// Frames left before the device is lost, negative when disabled
+    frames_until_lost: AtomicI64,
+    conversion_errors: AtomicBool,
+    channel_send_errors: AtomicBool,
+    encoder_panic: AtomicBool,
+}
+
+impl Default for FaultState {
+    fn default() -> Self {
+        Self {
+            frames_until_lost: AtomicI64::new(-1),
+            conversion_errors: AtomicBool::new(false),
+            channel_send_errors: AtomicBool::new(false),
+            encoder_panic: AtomicBool::new(false),
+        }
+    }
+}
+
+impl FaultState {
+    /// Replace the injected failures
+    #[cfg(feature = "fault-injection")]
+    pub fn set(&self, faults: &FaultInjection) {
+        let frames_until_lost = faults
+            .device_lost_after_frames
+            .map_or(-1, |frames| frames.min(i64::MAX as u64) as i64);
+        self.frames_until_lost
+            .store(frames_until_lost, Ordering::Relaxed);
+        self.conversion_errors
+            .store(faults.conversion_errors, Ordering::Relaxed);
+        self.channel_send_errors
+            .store(faults.channel_send_errors, Ordering::Relaxed);
+        self.encoder_panic
+            .store(faults.encoder_panic, Ordering::Relaxed);
+    }
+
+    /// Called for each captured frame; true once the device is lost
+    pub fn device_lost(&self) -> bool {
+        let left = self.frames_until_lost.load(Ordering::Relaxed);
+        if left < 0 {
+            return false;
+        }
+        if left > 0 {
+            self.frames_until_lost.fetch_sub(1, Ordering::Relaxed);
+        }
+        left == 0
+    }
+
+    pub fn conversion_error(&self) -> bool {
+        self.conversion_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn channel_send_error(&self) -> bool {
+        self.channel_send_errors.load(Ordering::Relaxed)
+    }
+
+    /// Fires once per injection
+    pub fn encoder_panic(&self) -> bool {
+        self.encoder_panic.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Fault states by device ID, shared with the streams of each device
+#[derive(Default)]
+pub struct Faults {
+    devices: Mutex<HashMap<String, Arc<FaultState>>>,
+}
+
+impl Faults {
+    pub fn state(&self, device_id: &str) -> Arc<FaultState> {
+        self.devices
+            .lock()
+            .unwrap()
+            .entry(device_id.to_string())
+            .or_default()
+            .clone()
+    }
+}
@@ -1,6 +1,7 @@
+use std::sync::Arc;
 use tauri::{
-    plugin::{Builder, TauriPlugin},
-    Manager, Runtime,
+    plugin::{Builder as PluginBuilder, TauriPlugin},
+    Manager, RunEvent, Runtime,
 };
 
 pub use crabcamera::permissions::PermissionStatus;
@@ -11,13 +12,96 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+#[cfg(all(desktop, feature = "audio"))]
+mod audio;
+#[cfg(all(desktop, feature = "av1"))]
+mod av1;
+mod bandwidth;
+#[cfg(desktop)]
+mod budget;
 mod commands;
+mod consumers;
+#[cfg(desktop)]
+mod default_camera;
+#[cfg(desktop)]
+mod devices;
+#[cfg(desktop)]
+mod diagnostics;
+#[cfg(desktop)]
+mod dump;
+#[cfg(desktop)]
+mod encoder;
 mod error;
+#[cfg(desktop)]
+mod eventlog;
+#[cfg(desktop)]
+mod exif;
+#[cfg(desktop)]
+mod facing;
+#[cfg(desktop)]
+mod faults;
+#[cfg(desktop)]
+mod matroska;
 mod models;
+#[cfg(all(desktop, feature = "mock-camera"))]
+mod mock;
+#[cfg(desktop)]
+mod network;
+#[cfg(desktop)]
+mod photo;
+#[cfg(desktop)]
+mod pipeline;
+#[cfg(desktop)]
+mod portal;
+#[cfg(desktop)]
+mod preroll;
+#[cfg(desktop)]
+mod preview;
+#[cfg(desktop)]
+mod privacy;
+mod profiles;
+#[cfg(desktop)]
+mod recording;
+#[cfg(desktop)]
+mod scale;
+mod scope;
+#[cfg(desktop)]
+mod session_controls;
+mod simd;
+#[cfg(desktop)]
+mod sinks;
+#[cfg(desktop)]
+mod sources;
+mod telemetry;
+#[cfg(desktop)]
+mod thumbnail;
+#[cfg(desktop)]
+mod timelapse;
 mod utils;
+#[cfg(all(desktop, feature = "vpx"))]
+mod vpx;
+#[cfg(desktop)]
+mod watchdog;
 mod webrtc;
+mod workdir;
+#[cfg(desktop)]
+mod zoom;
 use commands::*;
+pub use consumers::FrameConsumer;
+#[cfg(desktop)]
+pub use encoder::{EncodedFrame, Encoder, EncoderFactory, EncoderParams, OpenH264Encoder};
+#[cfg(desktop)]
+pub use pipeline::{FilterFactory, FrameFilter};
+#[cfg(desktop)]
+pub use sources::{FrameCallback, FrameSource};
+#[cfg(all(desktop, feature = "mock-camera"))]
+pub use mock::{mock_frame, MockCamera, MOCK_DEVICE_ID};
 pub use error::{Error, Result};
+pub use telemetry::{TelemetryEvent, TelemetryHook};
+#[cfg(all(desktop, feature = "av1"))]
+pub use av1::Av1Encoder;
+#[cfg(all(desktop, feature = "vpx"))]
+pub use vpx::VpxEncoder;
 pub use utils::*;
 
 #[cfg(desktop)]
@@ -38,28 +122,143 @@ impl<R: Runtime, T: Manager<R>> crate::CameraExt<R> for T {
 
 /// Initializes the plugin.
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("camera")
-        .invoke_handler(tauri::generate_handler![
-            request_camera_permission,
-            get_available_cameras,
-            initialize,
-            create_offer,
-            create_answer,
-            set_remote_description,
-            add_ice_candidate,
-            close_connection,
-            get_connection_state,
-            start_camera_webrtc_session,
-            start_streaming,
-            stop_streaming
-        ])
-        .setup(|app, api| {
-            #[cfg(mobile)]
-            let camera = mobile::init(app, api)?;
-            #[cfg(desktop)]
-            let camera = desktop::init(app, api)?;
-            app.manage(camera);
-            Ok(())
-        })
-        .build()
+    Builder::new().build()
+}
+
+/// Builds the plugin with hooks of the host app.
+///
+/// ```ignore
+/// tauri::Builder::default()
+///     .plugin(
+///         tauri_plugin_camera::Builder::new()
+///             .on_telemetry(|event| analytics::track(format!("{:?}", event)))
+///             .build(),
+///     )
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    telemetry: Vec<TelemetryHook>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive stream, error and connection state events, e.g. to forward them to the
+    /// app's analytics or crash reporting. Hooks are only called on desktop.
+    pub fn on_telemetry<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&TelemetryEvent) + Send + Sync + 'static,
+    {
+        self.telemetry.push(Arc::new(hook));
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R> {
+        #[cfg_attr(mobile, allow(unused_variables))]
+        let telemetry = telemetry::Telemetry::new(self.telemetry);
+        PluginBuilder::<R, Option<Config>>::new("camera")
+            .invoke_handler(tauri::generate_handler![
+                request_camera_permission,
+                get_available_cameras,
+                initialize,
+                create_offer,
+                create_answer,
+                set_remote_description,
+                add_ice_candidate,
+                close_connection,
+                get_connection_state,
+                start_camera_webrtc_session,
+                start_streaming,
+                stop_streaming,
+                set_focus_point,
+                set_white_balance,
+                get_white_balance,
+                get_stream_stats,
+                save_profile,
+                list_profiles,
+                delete_profile,
+                apply_profile,
+                export_config,
+                import_config,
+                run_diagnostics,
+                start_native_preview,
+                set_native_preview_rect,
+                stop_native_preview,
+                open_pip_preview,
+                close_preview_window,
+                open_overlay_preview,
+                set_overlay_options,
+                get_monitors,
+                start_kiosk_preview,
+                exit_kiosk_preview,
+                start_pipeline,
+                attach_sink,
+                detach_sink,
+                reattach_stream,
+                get_or_start_stream,
+                get_resource_usage,
+                start_frame_dump,
+                stop_frame_dump,
+                replay_frame_dump,
+                inject_faults,
+                get_camera_access,
+                reset_camera_permission,
+                add_screen_sources,
+                set_event_log,
+                probe_bandwidth,
+                capture_photo,
+                get_device_capabilities,
+                pause_streaming,
+                resume_streaming,
+                capture_burst,
+                start_timelapse,
+                stop_timelapse,
+                capture_frame_from_stream,
+                get_default_camera,
+                set_default_camera,
+                get_cameras_by_facing,
+                switch_stream_device,
+                capture_photo_with_timer,
+                set_exposure,
+                get_exposure,
+                set_focus_mode,
+                set_focus_distance,
+                trigger_autofocus,
+                set_zoom,
+                get_zoom_range,
+                set_torch,
+                get_camera_controls,
+                set_camera_control,
+                lock_auto_controls,
+                unlock_auto_controls,
+                save_control_profile,
+                apply_control_profile,
+                start_recording,
+                stop_recording,
+                attach_recorder,
+                pause_recording,
+                resume_recording,
+                list_microphones,
+                enable_preroll,
+                disable_preroll,
+                start_recording_with_preroll,
+                request_keyframe
+            ])
+            .setup(move |app, api| {
+                #[cfg(mobile)]
+                let camera = mobile::init(app, api)?;
+                #[cfg(desktop)]
+                let camera = desktop::init(app, api, telemetry)?;
+                app.manage(camera);
+                Ok(())
+            })
+            .on_event(|app, event| {
+                if let RunEvent::Exit = event {
+                    app.camera().work_dir.cleanup();
+                }
+            })
+            .build()
+    }
 }
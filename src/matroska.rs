@@ -0,0 +1,283 @@
+use crate::error::{Error, Result};
+use crate::recording::{AudioTrack, Muxer, Sample, Track, VideoTrack};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// EBML and Matroska element IDs, with their length marker bits
+const EBML: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+/// Block timestamps are in milliseconds
+const TIMESTAMP_SCALE_NS: u64 = 1_000_000;
+/// Track numbers of the video track and of the audio track, if any
+const VIDEO_TRACK: u64 = 1;
+const AUDIO_TRACK: u64 = 2;
+/// Clusters start at keyframes, or when they get this large
+const MAX_CLUSTER_BYTES: usize = 5 * 1024 * 1024;
+/// Size of the segment before it is known, as an 8-byte "unknown size"
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+/// Element ID bytes, the marker bits included
+fn id_bytes(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    bytes[skip..].to_vec()
+}
+
+/// Shortest variable-size integer for an element size
+fn size_bytes(size: u64) -> Vec<u8> {
+    // All ones is reserved for unknown sizes
+    let length = (1..=8)
+        .find(|length| size < (1 << (7 * length)) - 1)
+        .expect("element size below 2^56");
+    let marked = size | 1 << (7 * length);
+    marked.to_be_bytes()[8 - length..].to_vec()
+}
+
+fn element(id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = id_bytes(id);
+    bytes.extend(size_bytes(payload.len() as u64));
+    bytes.extend(payload);
+    bytes
+}
+
+fn uint(id: u32, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    element(id, &bytes[skip..])
+}
+
+fn float(id: u32, value: f64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn string(id: u32, value: &str) -> Vec<u8> {
+    element(id, value.as_bytes())
+}
+
+/// `AVCDecoderConfigurationRecord` of an H.264 track, its codec private data
+fn avc_configuration(sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut record = vec![1, sps[1], sps[2], sps[3], 0xFF, 0xE1];
+    record.extend((sps.len() as u16).to_be_bytes());
+    record.extend(sps);
+    record.push(1);
+    record.extend((pps.len() as u16).to_be_bytes());
+    record.extend(pps);
+    record
+}
+
+fn codec_id(mime_type: &str) -> Result<&'static str> {
+    match mime_type {
+        "video/h264" => Ok("V_MPEG4/ISO/AVC"),
+        "video/vp8" => Ok("V_VP8"),
+        "video/vp9" => Ok("V_VP9"),
+        "video/av1" => Ok("V_AV1"),
+        _ => Err(Error::CameraError(format!(
+            "No Matroska codec for {}",
+            mime_type
+        ))),
+    }
+}
+
+/// Matroska or WebM file, written as frames come
+/// Blocks are grouped in clusters starting at keyframes; the segment size and the
+/// duration are filled in by `finish`.
+pub struct MatroskaMuxer {
+    writer: BufWriter<File>,
+    /// Offset of the segment size, and of the segment data right after it
+    segment_size_at: u64,
+    /// Offset of the duration value in the segment info
+    duration_at: u64,
+    /// Blocks of the cluster being built, and its timestamp
+    cluster: Vec<u8>,
+    cluster_ms: u64,
+    end_ms: u64,
+}
+
+impl MatroskaMuxer {
+    pub fn create(path: &Path, webm: bool) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = [
+            uint(EBML_VERSION, 1),
+            uint(EBML_READ_VERSION, 1),
+            uint(EBML_MAX_ID_LENGTH, 4),
+            uint(EBML_MAX_SIZE_LENGTH, 8),
+            string(DOC_TYPE, if webm { "webm" } else { "matroska" }),
+            uint(DOC_TYPE_VERSION, 4),
+            uint(DOC_TYPE_READ_VERSION, 2),
+        ]
+        .concat();
+        writer.write_all(&element(EBML, &header))?;
+
+        writer.write_all(&id_bytes(SEGMENT))?;
+        let segment_size_at = writer.stream_position()?;
+        writer.write_all(&UNKNOWN_SIZE)?;
+
+        // Duration as an 8-byte float, rewritten once known
+        let info = [
+            uint(TIMESTAMP_SCALE, TIMESTAMP_SCALE_NS),
+            string(MUXING_APP, "tauri-plugin-camera"),
+            string(WRITING_APP, "tauri-plugin-camera"),
+            float(DURATION, 0.0),
+        ]
+        .concat();
+        let info = element(INFO, &info);
+        let duration_at = writer.stream_position()? + (info.len() - 8) as u64;
+        writer.write_all(&info)?;
+
+        Ok(Self {
+            writer,
+            segment_size_at,
+            duration_at,
+            cluster: Vec::new(),
+            cluster_ms: 0,
+            end_ms: 0,
+        })
+    }
+
+    fn flush_cluster(&mut self) -> Result<()> {
+        if self.cluster.is_empty() {
+            return Ok(());
+        }
+        let mut payload = uint(TIMESTAMP, self.cluster_ms);
+        payload.append(&mut self.cluster);
+        self.writer.write_all(&element(CLUSTER, &payload))?;
+        Ok(())
+    }
+}
+
+impl Muxer for MatroskaMuxer {
+    fn add_tracks(&mut self, video: &VideoTrack, audio: Option<&AudioTrack>) -> Result<()> {
+        let mut entry = [
+            uint(TRACK_NUMBER, VIDEO_TRACK),
+            uint(TRACK_UID, VIDEO_TRACK),
+            uint(TRACK_TYPE, 1),
+            uint(FLAG_LACING, 0),
+            string(CODEC_ID, codec_id(&video.mime_type)?),
+        ]
+        .concat();
+        if !video.sps.is_empty() {
+            entry.extend(element(
+                CODEC_PRIVATE,
+                &avc_configuration(&video.sps, &video.pps),
+            ));
+        }
+        let picture = [
+            uint(PIXEL_WIDTH, video.width as u64),
+            uint(PIXEL_HEIGHT, video.height as u64),
+        ]
+        .concat();
+        entry.extend(element(VIDEO, &picture));
+        let mut tracks = element(TRACK_ENTRY, &entry);
+
+        if let Some(audio) = audio {
+            let sound = [
+                float(SAMPLING_FREQUENCY, audio.sample_rate as f64),
+                uint(CHANNELS, audio.channels as u64),
+            ]
+            .concat();
+            let entry = [
+                uint(TRACK_NUMBER, AUDIO_TRACK),
+                uint(TRACK_UID, AUDIO_TRACK),
+                uint(TRACK_TYPE, 2),
+                uint(FLAG_LACING, 0),
+                string(CODEC_ID, "A_AAC"),
+                element(CODEC_PRIVATE, &audio.config),
+                element(AUDIO, &sound),
+            ]
+            .concat();
+            tracks.extend(element(TRACK_ENTRY, &entry));
+        }
+        self.writer.write_all(&element(TRACKS, &tracks))?;
+        Ok(())
+    }
+
+    fn write_sample(&mut self, track: Track, sample: Sample) -> Result<()> {
+        let ms = sample.start_us / 1000;
+        // Block timestamps are 16-bit offsets from their cluster's, audio blocks may come
+        // slightly before it
+        let offset = ms as i64 - self.cluster_ms as i64;
+        if (track == Track::Video && sample.keyframe)
+            || i16::try_from(offset).is_err()
+            || self.cluster.len() > MAX_CLUSTER_BYTES
+        {
+            self.flush_cluster()?;
+        }
+        if self.cluster.is_empty() {
+            self.cluster_ms = ms;
+        }
+
+        let number = match track {
+            Track::Video => VIDEO_TRACK,
+            Track::Audio => AUDIO_TRACK,
+        };
+        let mut block = vec![0x80 | number as u8];
+        block.extend(((ms as i64 - self.cluster_ms as i64) as i16).to_be_bytes());
+        block.push(if sample.keyframe { 0x80 } else { 0 });
+        block.extend(sample.data);
+        self.cluster.extend(element(SIMPLE_BLOCK, &block));
+        self.end_ms = self
+            .end_ms
+            .max((sample.start_us + sample.duration_us) / 1000);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush_cluster()?;
+        let end = self.writer.stream_position()?;
+        let segment_size = end - self.segment_size_at - UNKNOWN_SIZE.len() as u64;
+        self.writer.seek(SeekFrom::Start(self.segment_size_at))?;
+        self.writer
+            .write_all(&(segment_size | 1 << 56).to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(self.duration_at))?;
+        self.writer.write_all(&(self.end_ms as f64).to_be_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ebml_encoding() {
+        assert_eq!(size_bytes(0), [0x80]);
+        assert_eq!(size_bytes(126), [0xFE]);
+        // 127 as a 1-byte size would read as "unknown"
+        assert_eq!(size_bytes(127), [0x40, 0x7F]);
+        assert_eq!(uint(TRACK_NUMBER, 1), [0xD7, 0x81, 0x01]);
+        assert_eq!(uint(TIMESTAMP, 0), [0xE7, 0x81, 0x00]);
+        assert_eq!(&element(SEGMENT, &[])[..], &[0x18, 0x53, 0x80, 0x67, 0x80]);
+    }
+}
@@ -1,34 +1,579 @@
-use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
 use tauri::{
+  ipc::Channel,
   plugin::{PluginApi, PluginHandle},
-  AppHandle, Runtime,
+  AppHandle, Runtime, WebviewWindow,
 };
 
+use crate::consumers::FrameConsumer;
 use crate::models::*;
+use crate::profiles::ProfileStore;
+use crate::scope::OutputScope;
+use crate::workdir::WorkDir;
+use crate::Error;
 
 #[cfg(target_os = "ios")]
 tauri::ios_plugin_binding!(init_plugin_camera);
 
 // initializes the Kotlin or Swift plugin classes
-pub fn init<R: Runtime, C: DeserializeOwned>(
-  _app: &AppHandle<R>,
-  api: PluginApi<R, C>,
+pub fn init<R: Runtime>(
+  app: &AppHandle<R>,
+  api: PluginApi<R, Option<Config>>,
 ) -> crate::Result<Camera<R>> {
+  let config = api.config().clone().unwrap_or_default();
   #[cfg(target_os = "android")]
   let handle = api.register_android_plugin("", "ExamplePlugin")?;
   #[cfg(target_os = "ios")]
   let handle = api.register_ios_plugin(init_plugin_camera)?;
-  Ok(Camera(handle))
+
+  Ok(Camera {
+    handle,
+    profiles: ProfileStore::load(app)?,
+    output_scope: OutputScope::new(app, &config),
+    work_dir: WorkDir::new(app, &config)?,
+  })
+}
+
+/// Error of a camera feature the native Android and iOS plugins don't implement yet
+fn not_native(feature: &str) -> Error {
+  Error::Unsupported(format!("{} is not implemented on mobile yet", feature))
 }
 
 /// Access to the camera APIs.
-pub struct Camera<R: Runtime>(PluginHandle<R>);
+pub struct Camera<R: Runtime> {
+  handle: PluginHandle<R>,
+  pub profiles: ProfileStore,
+  output_scope: OutputScope,
+  /// Temporary encoding artifacts, removed on exit
+  pub(crate) work_dir: WorkDir,
+}
 
 impl<R: Runtime> Camera<R> {
   pub fn ping(&self, payload: PingRequest) -> crate::Result<PingResponse> {
     self
-      .0
+      .handle
       .run_mobile_plugin("ping", payload)
       .map_err(Into::into)
   }
+
+  pub async fn set_focus_point(&self, _device_id: String, _x: f64, _y: f64) -> crate::Result<()> {
+    Err(not_native("Tap-to-focus"))
+  }
+
+  pub async fn set_focus_mode(&self, _device_id: String, _mode: FocusMode) -> crate::Result<()> {
+    Err(not_native("Focus modes"))
+  }
+
+  pub async fn set_focus_distance(&self, _device_id: String, _distance: f32) -> crate::Result<()> {
+    Err(not_native("Manual focus"))
+  }
+
+  pub async fn trigger_autofocus(
+    &self,
+    _device_id: String,
+    _roi: Option<CropRect>,
+  ) -> crate::Result<()> {
+    Err(not_native("Autofocus"))
+  }
+
+  pub async fn lock_auto_controls(
+    &self,
+    _device_id: String,
+    _controls: AutoControls,
+  ) -> crate::Result<()> {
+    Err(not_native("Locking auto controls"))
+  }
+
+  pub async fn unlock_auto_controls(
+    &self,
+    _device_id: String,
+    _controls: AutoControls,
+  ) -> crate::Result<()> {
+    Err(not_native("Locking auto controls"))
+  }
+
+  pub async fn get_camera_controls(
+    &self,
+    _device_id: String,
+  ) -> crate::Result<Vec<CameraControlInfo>> {
+    Err(not_native("Image controls"))
+  }
+
+  pub async fn set_camera_control(
+    &self,
+    _device_id: String,
+    _control: CameraControl,
+    _value: f32,
+  ) -> crate::Result<()> {
+    Err(not_native("Image controls"))
+  }
+
+  pub async fn set_torch(&self, _device_id: String, _on: bool) -> crate::Result<()> {
+    Err(not_native("The torch"))
+  }
+
+  pub async fn set_zoom(&self, _device_id: String, _factor: f32) -> crate::Result<()> {
+    Err(not_native("Zoom"))
+  }
+
+  pub async fn get_zoom_range(&self, _device_id: String) -> crate::Result<ZoomRange> {
+    Err(not_native("Zoom"))
+  }
+
+  pub async fn set_white_balance(
+    &self,
+    _device_id: String,
+    _white_balance: WhiteBalance,
+  ) -> crate::Result<()> {
+    Err(not_native("White balance"))
+  }
+
+  pub async fn set_exposure(
+    &self,
+    _device_id: String,
+    _mode: ExposureMode,
+    _value: Option<f32>,
+  ) -> crate::Result<()> {
+    Err(not_native("Exposure control"))
+  }
+
+  pub async fn get_exposure(&self, _device_id: String) -> crate::Result<Exposure> {
+    Err(not_native("Exposure control"))
+  }
+
+  pub async fn get_device_capabilities(
+    &self,
+    _device_id: &str,
+  ) -> crate::Result<Vec<CameraFormat>> {
+    Err(not_native("Listing capture formats"))
+  }
+
+  pub async fn get_default_camera(&self) -> crate::Result<Option<crabcamera::CameraDeviceInfo>> {
+    Err(not_native("The default camera"))
+  }
+
+  pub async fn set_default_camera(&self, _device_id: Option<String>) -> crate::Result<()> {
+    Err(not_native("The default camera"))
+  }
+
+  pub async fn get_white_balance(&self, _device_id: String) -> crate::Result<WhiteBalance> {
+    Err(not_native("White balance"))
+  }
+
+  pub async fn run_diagnostics(&self) -> crate::Result<DiagnosticsReport> {
+    Err(not_native("Diagnostics"))
+  }
+
+  /// Native previews render into a desktop window surface
+  pub async fn start_native_preview(
+    &self,
+    _stream_id: &str,
+    _window: WebviewWindow<R>,
+    _rect: PreviewRect,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Native preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn set_native_preview_rect(
+    &self,
+    _preview_id: &str,
+    _rect: PreviewRect,
+  ) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Native preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn stop_native_preview(&self, _preview_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Native preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  /// Picture-in-picture is handled by the OS on mobile (e.g. `AVPictureInPictureController`)
+  pub async fn open_pip_preview(
+    &self,
+    _stream_id: &str,
+    _options: PipOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn open_overlay_preview(
+    &self,
+    _stream_id: &str,
+    _options: OverlayOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn set_overlay_options(&self, _window_id: &str, _update: OverlayUpdate) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn get_monitors(&self) -> crate::Result<Vec<MonitorInfo>> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_kiosk_preview(
+    &self,
+    _stream_id: &str,
+    _monitor: Option<String>,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn close_preview_window(&self, _window_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Preview windows are only available on desktop".to_string(),
+    ))
+  }
+
+  /// Frames stay in the native capture session on mobile
+  pub async fn register_consumer(
+    &self,
+    _id: impl Into<String>,
+    _device_id: &str,
+    _consumer: Box<dyn FrameConsumer>,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Frame consumers are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn unregister_consumer(&self, _id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Frame consumers are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_pipeline(
+    &self,
+    _desc: PipelineDesc,
+    _on_frame: Option<Channel<FrameEvent>>,
+  ) -> crate::Result<PipelineInfo> {
+    Err(Error::Unsupported(
+      "Pipelines are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn attach_sink(
+    &self,
+    _stream_id: &str,
+    _sink: SinkDesc,
+    _on_frame: Option<Channel<FrameEvent>>,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Pipelines are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn reattach_stream(
+    &self,
+    _stream_id: &str,
+    _on_frame: Channel<FrameEvent>,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Frame channels are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn pause_streaming(&self, _stream_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Pausing streams is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn resume_streaming(&self, _stream_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Pausing streams is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn switch_stream_device(
+    &self,
+    _stream_id: &str,
+    _device_id: String,
+  ) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Switching stream devices is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn get_or_start_stream(
+    &self,
+    _device_id: String,
+    _options: StreamOptions,
+    _on_frame: Channel<FrameEvent>,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Frame channels are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn get_camera_access(&self) -> crate::Result<CameraAccess> {
+    Err(Error::Unsupported(
+      "Camera authorization details are only available on macOS".to_string(),
+    ))
+  }
+
+  pub fn reset_camera_permission(&self) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Resetting the camera permission is only available on macOS".to_string(),
+    ))
+  }
+
+  pub async fn add_screen_sources(&self) -> crate::Result<Vec<String>> {
+    Err(Error::Unsupported(
+      "Screen capture is only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn inject_faults(&self, _device_id: &str, _faults: FaultInjection) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Fault injection is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_frame_dump(&self, _device_id: &str, _path: &Path) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Frame dumps are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn stop_frame_dump(&self, _dump_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Frame dumps are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn replay_frame_dump(&self, _path: &Path) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Frame dumps are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn get_resource_usage(&self) -> crate::Result<ResourceUsage> {
+    Err(Error::Unsupported(
+      "Resource usage is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn capture_photo(
+    &self,
+    _device_id: &str,
+    _options: PhotoOptions,
+  ) -> crate::Result<Photo> {
+    Err(Error::Unsupported(
+      "Photo capture is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn capture_raw_frame(&self, _device_id: &str) -> crate::Result<RawFrame> {
+    Err(Error::Unsupported(
+      "Raw frame capture is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn capture_photo_with_timer(
+    &self,
+    _device_id: &str,
+    _delay_seconds: u32,
+    _options: PhotoOptions,
+    _on_countdown: Channel<CountdownEvent>,
+  ) -> crate::Result<Photo> {
+    Err(Error::Unsupported(
+      "Timed photo capture is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn capture_burst(
+    &self,
+    _device_id: &str,
+    _count: u32,
+    _interval: std::time::Duration,
+    _options: PhotoOptions,
+  ) -> crate::Result<Vec<Photo>> {
+    Err(Error::Unsupported(
+      "Burst capture is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn capture_frame_from_stream(
+    &self,
+    _stream_id: &str,
+    _options: PhotoOptions,
+  ) -> crate::Result<Photo> {
+    Err(Error::Unsupported(
+      "Stream snapshots are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_timelapse(
+    &self,
+    _device_id: &str,
+    _interval: std::time::Duration,
+    _output_dir: &Path,
+    _options: TimelapseOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Time-lapses are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn stop_timelapse(&self, _timelapse_id: &str) -> crate::Result<TimelapseSummary> {
+    Err(Error::Unsupported(
+      "Time-lapses are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_recording(
+    &self,
+    _device_id: &str,
+    _path: &Path,
+    _options: RecordingOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn attach_recorder(
+    &self,
+    _stream_id: &str,
+    _path: &Path,
+    _options: RecordingOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn enable_preroll(&self, _stream_id: &str, _seconds: u32) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn disable_preroll(&self, _stream_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn start_recording_with_preroll(
+    &self,
+    _stream_id: &str,
+    _preroll_secs: u32,
+    _path: &Path,
+    _options: RecordingOptions,
+  ) -> crate::Result<String> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn pause_recording(&self, _recording_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn list_microphones(&self) -> crate::Result<Vec<String>> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn resume_recording(&self, _recording_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn stop_recording(&self, _recording_id: &str) -> crate::Result<RecordingSummary> {
+    Err(Error::Unsupported(
+      "Recordings are only available on desktop".to_string(),
+    ))
+  }
+
+  pub fn set_event_log(&self, _enabled: bool) -> crate::Result<PathBuf> {
+    Err(Error::Unsupported(
+      "The pipeline event log is only available on desktop".to_string(),
+    ))
+  }
+
+  pub async fn detach_sink(&self, _stream_id: &str, _sink_id: &str) -> crate::Result<()> {
+    Err(Error::Unsupported(
+      "Pipelines are only available on desktop".to_string(),
+    ))
+  }
+
+  /// Check a path recordings, snapshots or frame dumps are about to be written to
+  /// against the allowed output directories (`Config::output_dirs`)
+  pub fn check_output_path(&self, path: &Path) -> crate::Result<PathBuf> {
+    self.output_scope.check(path)
+  }
+
+  /// Path for a temporary encoding artifact in the plugin's work directory (`Config::temp_dir`)
+  pub fn temp_file(&self, name: &str) -> crate::Result<PathBuf> {
+    self.work_dir.file(name)
+  }
+
+  /// Apply a saved profile to a device
+  /// Controls are set right away and the profile is remembered for the device
+  pub async fn apply_profile(&self, device_id: String, name: String) -> crate::Result<()> {
+    let profile = self.profiles.get(&device_id, &name)?;
+    if let Some(white_balance) = profile.white_balance {
+      self
+        .set_white_balance(device_id.clone(), white_balance)
+        .await?;
+    }
+    if let Some(controls) = &profile.controls {
+      self
+        .apply_control_settings(device_id.clone(), controls.clone())
+        .await?;
+    }
+    self.profiles.set_active(device_id, profile);
+    Ok(())
+  }
+
+  pub async fn save_control_profile(&self, _device_id: String, _name: String) -> crate::Result<()> {
+    Err(not_native("Control profiles"))
+  }
+
+  /// Restore the controls saved in the profile `name`
+  pub async fn apply_control_profile(&self, device_id: String, name: String) -> crate::Result<()> {
+    let controls = self
+      .profiles
+      .get(&device_id, &name)?
+      .controls
+      .ok_or_else(|| {
+        Error::ProfileNotFound(format!(
+          "{} has no saved controls (device {})",
+          name, device_id
+        ))
+      })?;
+    self.apply_control_settings(device_id, controls).await
+  }
+
+  async fn apply_control_settings(
+    &self,
+    _device_id: String,
+    _settings: ControlSettings,
+  ) -> crate::Result<()> {
+    Err(not_native("Control profiles"))
+  }
 }
@@ -1,4 +1,182 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// Plugin configuration, from `plugins.camera` in tauri.conf.json
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// Absolute directories recordings, snapshots and frame dumps may be written to.
+    /// Defaults to the app data and cache directories and the user's video and picture directories.
+    #[serde(default)]
+    pub output_dirs: Vec<PathBuf>,
+    /// Where temporary encoding artifacts (segment files, thumbnails, replay buffers) are
+    /// written, in a `tauri-plugin-camera` subdirectory removed on exit. Defaults to the app cache directory.
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+    /// Cap on the memory of frame buffers and queues, across all streams. When reached,
+    /// frames are dropped instead of queued. Unlimited by default.
+    #[serde(default)]
+    pub memory_budget_mb: Option<u64>,
+    /// How cameras are opened on Linux
+    #[serde(default)]
+    pub capture_backend: CaptureBackend,
+    /// Seconds without frames after which a stream is considered stalled (e.g. a device wedged
+    /// after sleep/resume) and its capture is reopened. 5 by default, 0 disables the watchdog.
+    #[serde(default)]
+    pub stall_timeout_secs: Option<u64>,
+    /// Restart ICE on active WebRTC connections when the network changes (Wi-Fi switch,
+    /// VPN, cable unplugged), emitting the new offer on `camera://ice-restart`.
+    /// Enabled unless set to `false`.
+    #[serde(default)]
+    pub auto_ice_restart: Option<bool>,
+    /// Desktop only: emit `camera://device-added` and `camera://device-removed` when cameras
+    /// are plugged in or unplugged. Enabled unless set to `false`.
+    #[serde(default)]
+    pub watch_devices: Option<bool>,
+    /// Detection of WebRTC connections that stay connected while media stopped flowing
+    #[serde(default)]
+    pub media_liveness: MediaLivenessConfig,
+    /// Diagnostic JSON log of the frame pipeline, off unless enabled here or with `set_event_log`
+    #[serde(default)]
+    pub event_log: EventLogConfig,
+    /// How the camera used when none is specified is picked
+    #[serde(default)]
+    pub default_camera: DefaultCameraConfig,
+    /// Desktop only: free disk space recordings leave. They don't start below it, and stop
+    /// with `camera://recording-stopped` (reason `disk_full`) when it is reached. 500 MB by
+    /// default, 0 disables the guard.
+    #[serde(default)]
+    pub recording_min_free_mb: Option<u64>,
+    /// Desktop only: encoder bitrate of WebRTC connections following their network
+    #[serde(default)]
+    pub bitrate_adaptation: BitrateAdaptationConfig,
+}
+
+// Selection of the default camera
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultCameraConfig {
+    /// Kind of camera preferred when neither `set_default_camera` nor the last used device
+    /// picks one. External cameras on desktop and the back camera on mobile by default.
+    #[serde(default)]
+    pub prefer: Option<CameraPreference>,
+    /// Use the device of the last started stream, persisted in the app data directory.
+    /// Enabled unless set to `false`.
+    #[serde(default)]
+    pub remember_last_used: Option<bool>,
+}
+
+// Where a camera faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CameraFacing {
+    /// Towards the user, like laptop and phone selfie cameras
+    Front,
+    /// Away from the user
+    Back,
+    /// Plugged in, e.g. a USB webcam
+    External,
+    Unknown,
+}
+
+// A camera with where it faces, as listed to the frontend
+#[cfg(desktop)]
+#[derive(Debug, Clone, Serialize)]
+pub struct CameraInfo {
+    #[serde(flatten)]
+    pub device: crabcamera::CameraDeviceInfo,
+    pub facing: CameraFacing,
+    /// Physical location reported by the system (e.g. `lid`, `bezel`), when it reports one;
+    /// desktop systems don't
+    pub position: Option<String>,
+}
+
+#[cfg(desktop)]
+impl From<crabcamera::CameraDeviceInfo> for CameraInfo {
+    fn from(device: crabcamera::CameraDeviceInfo) -> Self {
+        Self {
+            facing: crate::facing::facing(&device),
+            device,
+            position: None,
+        }
+    }
+}
+
+// Kind of camera the default camera policy prefers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CameraPreference {
+    /// USB and other non built-in cameras
+    External,
+    /// Cameras facing the user
+    Front,
+    /// Cameras facing away from the user
+    Back,
+    /// The first camera listed by the system
+    First,
+}
+
+// Encoder bitrate following the loss and bandwidth estimates reported by WebRTC peers
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BitrateAdaptationConfig {
+    /// Enabled unless set to `false`
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Lowest bitrate the encoder is lowered to, 150 kbps by default
+    #[serde(default)]
+    pub min_kbps: Option<u32>,
+    /// Highest bitrate it is raised to. Defaults to the bitrate of the encoder settings,
+    /// else 4000 kbps.
+    #[serde(default)]
+    pub max_kbps: Option<u32>,
+}
+
+// Zombie WebRTC connection detection
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaLivenessConfig {
+    /// Seconds without a sent sample or a received RTCP report after which a connection's
+    /// media is reported stalled. 10 by default, 0 disables the check.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Close connections whose media stalled, with their stream
+    #[serde(default)]
+    pub close_stalled: bool,
+}
+
+// Newline-delimited JSON records per pipeline stage, for offline performance analysis
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogConfig {
+    /// Log from startup
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory of the log files. Defaults to the app log directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Size after which the file is rotated, 16 MB by default
+    #[serde(default)]
+    pub max_file_mb: Option<u64>,
+    /// Rotated files kept next to the current one, 4 by default
+    #[serde(default)]
+    pub max_files: Option<u32>,
+}
+
+// Camera access path on Linux
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureBackend {
+    /// The PipeWire camera portal inside Flatpak/Snap sandboxes or when no V4L2 device
+    /// can be opened, direct V4L2 access otherwise
+    #[default]
+    Auto,
+    /// Direct V4L2 access through crabcamera
+    Native,
+    /// The PipeWire camera portal (xdg-desktop-portal); needs the `pipewire-portal` feature
+    Portal,
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -22,6 +200,321 @@ pub struct CameraFormat {
     pub format: Option<String>,
 }
 
+// Encoding of a still photo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PhotoFormat {
+    #[default]
+    Jpeg,
+    Png,
+}
+
+// Options of `capture_photo`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhotoOptions {
+    #[serde(default)]
+    pub format: PhotoFormat,
+    /// JPEG quality, 1-100, 90 by default. PNG is lossless and ignores it.
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Write the photo to this file, within `Config::output_dirs`, instead of returning its bytes
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// EXIF metadata embedded in JPEG photos
+    #[serde(default)]
+    pub metadata: CaptureOptions,
+    #[serde(default)]
+    pub flash: FlashMode,
+}
+
+// Flash of a still photo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FlashMode {
+    #[default]
+    Off,
+    /// Fire when the scene is dark
+    Auto,
+    On,
+}
+
+// EXIF metadata of a JPEG photo; the capture time and camera name are always filled in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureOptions {
+    /// Embed EXIF metadata. Enabled unless set to `false`.
+    #[serde(default)]
+    pub exif: Option<bool>,
+    /// EXIF orientation of the photo, 1-8, 1 (upright) by default
+    #[serde(default)]
+    pub orientation: Option<u16>,
+    /// Stored as the image description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Where the photo was taken, e.g. from the frontend's geolocation API
+    #[serde(default)]
+    pub gps: Option<GpsLocation>,
+}
+
+// Position written to the GPS tags of a photo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsLocation {
+    /// Degrees, north positive
+    pub latitude: f64,
+    /// Degrees, east positive
+    pub longitude: f64,
+    /// Meters above sea level
+    #[serde(default)]
+    pub altitude: Option<f64>,
+}
+
+// A single frame returned to Rust callers by `Camera::capture_raw_frame`
+#[derive(Debug, Clone)]
+pub struct RawFrame {
+    pub device_id: String,
+    /// RGBA pixels in sRGB, 4 bytes per pixel, rows packed
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// Wall-clock capture time, in milliseconds since the Unix epoch
+    pub capture_time_ms: u64,
+}
+
+// Sent on the channel of `capture_photo_with_timer` every second of the countdown
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CountdownEvent {
+    /// Seconds left before the shot, 0 as the frame is taken
+    pub remaining_seconds: u32,
+}
+
+// A captured still photo: its bytes, or the file it was written to
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Photo {
+    pub width: u32,
+    pub height: u32,
+    pub format: PhotoFormat,
+    #[serde(with = "serde_bytes", skip_serializing_if = "Option::is_none")]
+    pub data: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+// Options of `start_timelapse`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelapseOptions {
+    /// JPEG quality of the shots, 1-100, 90 by default
+    #[serde(default)]
+    pub quality: Option<u8>,
+    /// Assemble the shots into an H.264 video when the time-lapse stops
+    #[serde(default)]
+    pub assemble_video: bool,
+}
+
+// Emitted on `camera://timelapse-progress` after each time-lapse shot
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelapseProgressEvent {
+    pub timelapse_id: String,
+    /// Shots written so far
+    pub frames: u32,
+    /// File of this shot, `None` when it failed
+    pub path: Option<PathBuf>,
+    pub error: Option<String>,
+}
+
+// Result of `stop_timelapse`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelapseSummary {
+    pub timelapse_id: String,
+    pub frames: u32,
+    pub output_dir: PathBuf,
+    /// Raw H.264 (Annex B) video of the shots, when assembled
+    pub video: Option<PathBuf>,
+}
+
+// Options of `start_recording`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingOptions {
+    /// Options of the stream started when none runs on the device, the applied profile's
+    /// by default. Recordings encode raw frames: leave `color_space` unset.
+    #[serde(default)]
+    pub stream: Option<StreamOptions>,
+    /// Go on in a new file after this many seconds, e.g. for dashcam-style continuous capture
+    #[serde(default)]
+    pub segment_duration_secs: Option<u32>,
+    /// Go on in a new file once this many bytes are written
+    #[serde(default)]
+    pub segment_max_bytes: Option<u64>,
+    #[serde(default)]
+    pub container: RecordingContainer,
+    /// Record sound from a microphone along with the video (AAC, mp4 or mkv only)
+    #[serde(default)]
+    pub audio: Option<AudioOptions>,
+    /// Write a JPEG thumbnail next to the recording when it stops (H.264 only)
+    #[serde(default)]
+    pub thumbnail: Option<ThumbnailOptions>,
+}
+
+// Thumbnail of a recording, `clip.jpg` next to `clip.mp4`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailOptions {
+    /// Time in the recording to show, pauses left out; the latest keyframe up to it is
+    /// used. The first keyframe by default.
+    #[serde(default)]
+    pub at_ms: Option<u64>,
+    /// Longest side of the thumbnail in pixels, 320 by default
+    #[serde(default)]
+    pub max_size: Option<u32>,
+}
+
+// Microphone recorded by `start_recording`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioOptions {
+    /// Name of the microphone, as listed by `list_microphones`; the default input device
+    /// otherwise
+    #[serde(default)]
+    pub device: Option<String>,
+    /// AAC bitrate in bits per second, 128 kbps by default
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+}
+
+// File format of a recording
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingContainer {
+    /// H.264
+    #[default]
+    Mp4,
+    /// Matroska: H.264, VP8, VP9 or AV1
+    Mkv,
+    /// VP8, VP9 or AV1, played back directly by webviews
+    Webm,
+}
+
+// Result of `stop_recording`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSummary {
+    pub recording_id: String,
+    /// The recorded file, the last segment of a segmented recording
+    pub path: PathBuf,
+    pub frames: u32,
+    pub duration_ms: u64,
+    /// Files of a segmented recording, in order
+    pub segments: Vec<PathBuf>,
+    /// JPEG thumbnail written next to the recording, when asked for
+    pub thumbnail: Option<PathBuf>,
+}
+
+// Emitted on `camera://segment-finalized` once a segment of a recording is complete
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentFinalizedEvent {
+    pub recording_id: String,
+    /// Number of the segment, from 0, as in its file name
+    pub index: u32,
+    pub path: PathBuf,
+    pub frames: u32,
+    pub duration_ms: u64,
+}
+
+// Emitted on `camera://recording-progress` every second while a recording runs
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingProgressEvent {
+    pub recording_id: String,
+    /// Recorded time, pauses left out, across all segments
+    pub elapsed_ms: u64,
+    /// Bytes of the recorded frames and sound so far, across all segments
+    pub bytes: u64,
+    pub frames: u32,
+    /// Frames lost because the recorder fell behind the stream, and the frames left out
+    /// until the next keyframe after such a gap or a resume
+    pub dropped_frames: u64,
+    /// Bits per second written since the previous event
+    pub bitrate: u64,
+    pub paused: bool,
+}
+
+// Emitted on `camera://recording-stopped` when a recording ends without `stop_recording`,
+// once its file is complete; `stop_recording` still returns its summary
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingStoppedEvent {
+    pub recording_id: String,
+    pub reason: RecordingStopReason,
+    /// What failed, for `failed` and for a write that hit a full disk
+    pub error: Option<String>,
+}
+
+// Why a recording ended on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingStopReason {
+    /// The free space of the disk fell under `Config::recording_min_free_mb`
+    DiskFull,
+    /// The stream stopped or lost its device
+    StreamEnded,
+    /// The frame size changed (e.g. `rotation`), which a recording can't follow
+    FrameSizeChanged,
+    Failed,
+}
+
+// Color space of a frame: YUV matrices for raw frames, RGB spaces for converted ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColorSpace {
+    Bt601,
+    Bt709,
+    Bt2020,
+    Srgb,
+    DisplayP3,
+}
+
+// Transfer function of an HDR source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HdrTransfer {
+    Hlg,
+    Pq,
+}
+
+// Power profile of a stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PowerMode {
+    #[default]
+    Normal,
+    /// Capture at 15fps or less, skip gamut mapping and deduplicate static frames,
+    /// for small always-on previews that must stay quiet on fans and battery
+    Low,
+}
+
+// Trade-off of a stream between delay and picture quality, applied to the whole chain:
+// encoder tuning, queue depths and which frames a lagging sink skips
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LatencyMode {
+    /// Remote control and calls: fastest encoder settings, no queued frames, a lagging
+    /// WebRTC sink jumps to the newest frame
+    Realtime,
+    #[default]
+    Balanced,
+    /// Recording-focused: slowest encoder settings and deep queues so no frame is skipped
+    /// while a sink catches up
+    Quality,
+}
+
 // Frame event sent to frontend
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -31,6 +524,160 @@ pub struct FrameEvent {
     pub width: u32,
     pub height: u32,
     pub format: String,
+    pub color_space: ColorSpace,
+    /// Set when the frame still carries an HDR signal (passthrough mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hdr: Option<HdrTransfer>,
+    /// The frame is (nearly) identical to the previous one; `data` is empty
+    pub repeated: bool,
+    /// Presentation timestamp, in microseconds on a monotonic clock started with the stream,
+    /// taken when the frame arrived from the device. Strictly increasing.
+    pub pts_us: u64,
+    /// Wall-clock arrival time, in milliseconds since the Unix epoch
+    pub capture_time_ms: u64,
+}
+
+// Per-stream processing options
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamOptions {
+    /// Convert frames to RGBA in this color space (`srgb` for canvas, `displayP3` for
+    /// wide-gamut displays). Raw frames are passed through, tagged with their source space.
+    #[serde(default)]
+    pub color_space: Option<ColorSpace>,
+    /// Transfer function of 10-bit (P010) frames. Defaults to HLG, the common camera HDR format.
+    #[serde(default)]
+    pub hdr_transfer: Option<HdrTransfer>,
+    /// Deliver HDR frames untouched for HDR-capable consumers instead of tone mapping them to SDR
+    #[serde(default)]
+    pub hdr_passthrough: bool,
+    /// Skip frames whose content did not change: mean absolute difference (0-255) of a sparse
+    /// sample below which a frame is reported as repeated. `None` disables deduplication.
+    #[serde(default)]
+    pub dedup_tolerance: Option<f64>,
+    /// Lower the frame rate delivered to a frontend channel (30→15→5fps) while the webview
+    /// can't keep up, and restore it once it recovers. Enabled unless set to `false`.
+    #[serde(default)]
+    pub adaptive_fps: Option<bool>,
+    #[serde(default)]
+    pub power_mode: PowerMode,
+    #[serde(default)]
+    pub latency_mode: LatencyMode,
+    /// Only convert and deliver this region of the captured frames
+    #[serde(default)]
+    pub crop: Option<CropRect>,
+    /// Scale frames down to fit this width, keeping their aspect ratio
+    #[serde(default)]
+    pub target_width: Option<u32>,
+    /// Scale frames down to fit this height, keeping their aspect ratio
+    #[serde(default)]
+    pub target_height: Option<u32>,
+    /// Reopen the capture when frames stop, and as soon as an unplugged device is back,
+    /// keeping the stream ID. Enabled unless set to `false`, in which case a stalled
+    /// stream is stopped after `camera://stream-error`.
+    #[serde(default)]
+    pub auto_reconnect: Option<bool>,
+    /// Mirror frames left to right, e.g. for selfie previews; recordings and WebRTC
+    /// tracks get the mirrored frames too
+    #[serde(default)]
+    pub mirror: bool,
+    /// Flip frames upside down
+    #[serde(default)]
+    pub flip_vertical: bool,
+    /// Rotate frames clockwise by 0, 90, 180 or 270 degrees, e.g. for sideways mounted
+    /// sensors; applied before mirroring and flipping
+    #[serde(default)]
+    pub rotation: u32,
+    /// Deliver at most this many frames per second, evenly spaced, while the camera keeps
+    /// capturing at its own rate; raw consumers (photos, frame dumps) still get every frame
+    #[serde(default)]
+    pub max_fps: Option<f64>,
+    /// Encoder settings of the stream's WebRTC tracks and recordings, instead of the ones
+    /// of the device's profile
+    #[serde(default)]
+    pub encoder: Option<EncoderSettings>,
+}
+
+// Region of a frame, normalized to its size (0.0..=1.0, origin top-left)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// A stream and the access token required to control it from a webview
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSession {
+    pub stream_id: String,
+    pub token: String,
+}
+
+// Resources held by one stream
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUsage {
+    pub stream_id: String,
+    pub device_id: String,
+    pub uptime_ms: u64,
+    /// Memory held by the stream's frame buffers and queues
+    pub buffer_bytes: u64,
+    /// Channel and WebRTC sinks attached to the stream
+    pub sinks: usize,
+    /// Whether the shared encoding pass is running
+    pub encoding: bool,
+}
+
+// Resources held by the plugin, to spot leaks such as a forgotten session
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    pub sessions: Vec<SessionUsage>,
+    /// Camera devices held open by the plugin
+    pub open_devices: Vec<String>,
+    pub webrtc_connections: usize,
+    pub native_previews: usize,
+    pub frame_consumers: usize,
+    pub memory_used_bytes: u64,
+    pub memory_budget_bytes: Option<u64>,
+    /// Worker threads of the async runtime
+    pub runtime_workers: usize,
+    /// Threads of the whole process, where the platform reports it (Linux)
+    pub process_threads: Option<usize>,
+}
+
+// Scope entry of the stream commands in a capability, e.g. `{ "anyStream": true }`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamScope {
+    /// Control any stream without presenting its token (trusted windows)
+    #[serde(default)]
+    pub any_stream: bool,
+}
+
+// Runtime statistics of a stream
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStats {
+    pub stream_id: String,
+    pub device_id: String,
+    pub uptime_ms: u64,
+    pub frames_delivered: u64,
+    pub frames_skipped: u64,
+    pub send_errors: u64,
+    /// Frame rate cap currently applied by adaptive delivery (`None` = capture rate)
+    pub target_fps: Option<f64>,
+    /// Frames per second actually delivered to the frontend over the last second
+    pub effective_fps: f64,
+    /// Memory held by the stream's frame buffers and queues
+    pub buffer_bytes: u64,
+    /// Frames dropped because the plugin's memory budget was exhausted
+    pub frames_over_budget: u64,
+    /// Paused with `pause_streaming`
+    pub paused: bool,
 }
 
 // Request to start streaming
@@ -50,3 +697,621 @@ pub struct StartStreamResponse {
     pub session_id: String,
     pub format: CameraFormat,
 }
+
+// Emitted on `camera://focus-locked` after a focus point request completes
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusLockedEvent {
+    pub device_id: String,
+    pub x: f64,
+    pub y: f64,
+    pub locked: bool,
+}
+
+// How the lens focuses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FocusMode {
+    /// Focus when triggered with `trigger_autofocus`, then hold
+    Auto,
+    /// Fixed focus distance, set with `set_focus_distance`
+    Manual,
+    /// Refocus continuously as the scene changes
+    Continuous,
+}
+
+// Zoom factors a device supports, and its current zoom
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoomRange {
+    pub min: f32,
+    pub max: f32,
+    pub zoom: f32,
+    /// Zoom crops and scales frames instead of using the camera's own zoom
+    pub digital: bool,
+}
+
+// Automatic adjustments to freeze or resume: exposure, focus and white balance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoControls {
+    #[serde(default)]
+    pub ae: bool,
+    #[serde(default)]
+    pub af: bool,
+    #[serde(default)]
+    pub awb: bool,
+}
+
+// Standard UVC image control
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CameraControl {
+    Brightness,
+    Contrast,
+    Saturation,
+    Sharpness,
+    Gain,
+    Gamma,
+}
+
+// A control supported by a device: its current value and the values it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraControlInfo {
+    pub control: CameraControl,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub default: f32,
+}
+
+// White balance mode applied through the camera controls API
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum WhiteBalance {
+    Auto,
+    Daylight,
+    Tungsten,
+    Fluorescent,
+    Manual { kelvin: u32 },
+}
+
+impl WhiteBalance {
+    /// Supported range for manual color temperature, in Kelvin
+    pub const KELVIN_RANGE: std::ops::RangeInclusive<u32> = 2000..=10000;
+}
+
+// Exposure mode applied through the camera controls API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExposureMode {
+    /// Metered continuously by the camera, with an optional EV compensation
+    Auto,
+    /// Fixed exposure time
+    Manual,
+    /// Hold the exposure the camera metered last, e.g. to avoid flicker between frames
+    Locked,
+}
+
+// Exposure of a device: the mode and its value, the EV compensation in auto mode or the
+// exposure time in milliseconds otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Exposure {
+    pub mode: ExposureMode,
+    #[serde(default)]
+    pub value: Option<f32>,
+}
+
+impl Exposure {
+    /// Supported range for EV compensation, in stops
+    pub const COMPENSATION_RANGE: std::ops::RangeInclusive<f32> = -3.0..=3.0;
+    /// Supported range for manual exposure time, in milliseconds
+    pub const TIME_RANGE_MS: std::ops::RangeInclusive<f32> = 0.05..=1000.0;
+}
+
+// Emitted on `camera://stream-stalled` when a stream stops receiving frames from its device
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStalledEvent {
+    pub stream_id: String,
+    pub device_id: String,
+    /// Time since the last frame
+    pub idle_ms: u64,
+}
+
+// Emitted on `camera://stream-recovered` once frames flow again after a stall
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRecoveredEvent {
+    pub stream_id: String,
+    pub device_id: String,
+    /// Time without frames
+    pub stalled_ms: u64,
+    /// Times the capture was reopened
+    pub restarts: u32,
+}
+
+// Emitted on `camera://stream-error` when a stalled stream's device is gone or can't be reopened
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamErrorEvent {
+    pub stream_id: String,
+    pub device_id: String,
+    pub error: String,
+    /// The device is still listed by the system
+    pub device_connected: bool,
+    /// The stream waits for the device and keeps trying to reopen it; otherwise it was stopped
+    pub reconnecting: bool,
+}
+
+// Way media travels on a WebRTC connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MediaDirection {
+    /// Video samples sent to the peer
+    Outbound,
+    /// RTCP reports received from the peer
+    Inbound,
+}
+
+// Emitted on `camera://media-stalled` when a connected peer's media goes quiet in one
+// direction, and on `camera://media-resumed` when it flows again
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaLivenessEvent {
+    pub connection_id: String,
+    pub direction: MediaDirection,
+    pub stalled: bool,
+    /// Time without media in that direction
+    pub idle_ms: u64,
+}
+
+// Emitted on `camera://bitrate-changed` when the encoder bitrate of a WebRTC connection
+// adapts to its network
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BitrateChangedEvent {
+    pub connection_id: String,
+    pub bitrate_kbps: u32,
+    pub previous_kbps: u32,
+    /// Share of packets lost in the peer's latest receiver report
+    pub loss: f32,
+    /// Bandwidth estimate of the peer (REMB), if it sends one
+    pub estimate_kbps: Option<u32>,
+}
+
+// Codec of a WebRTC video track
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    /// Needs the `vpx` feature
+    Vp8,
+    /// Needs the `vpx` feature
+    Vp9,
+    /// Needs the `av1` feature
+    Av1,
+}
+
+impl VideoCodec {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "video/h264",
+            VideoCodec::Vp8 => "video/vp8",
+            VideoCodec::Vp9 => "video/vp9",
+            VideoCodec::Av1 => "video/av1",
+        }
+    }
+
+    pub fn from_mime_type(mime_type: &str) -> Option<Self> {
+        [
+            VideoCodec::H264,
+            VideoCodec::Vp8,
+            VideoCodec::Vp9,
+            VideoCodec::Av1,
+        ]
+        .into_iter()
+        .find(|codec| codec.mime_type().eq_ignore_ascii_case(mime_type))
+    }
+}
+
+// H.264 profile, the set of coding tools a decoder must support; each profile is decoded
+// by the decoders of the following ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum H264Profile {
+    /// Decoded by every WebRTC peer
+    Baseline,
+    Main,
+    High,
+}
+
+// H.264 level, the resolution, frame rate and bitrate a decoder must handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum H264Level {
+    #[serde(rename = "1")]
+    L1,
+    #[serde(rename = "1b")]
+    L1b,
+    #[serde(rename = "1.1")]
+    L1_1,
+    #[serde(rename = "1.2")]
+    L1_2,
+    #[serde(rename = "1.3")]
+    L1_3,
+    #[serde(rename = "2")]
+    L2,
+    #[serde(rename = "2.1")]
+    L2_1,
+    #[serde(rename = "2.2")]
+    L2_2,
+    #[serde(rename = "3")]
+    L3,
+    #[serde(rename = "3.1")]
+    L3_1,
+    #[serde(rename = "3.2")]
+    L3_2,
+    #[serde(rename = "4")]
+    L4,
+    #[serde(rename = "4.1")]
+    L4_1,
+    #[serde(rename = "4.2")]
+    L4_2,
+    #[serde(rename = "5")]
+    L5,
+    #[serde(rename = "5.1")]
+    L5_1,
+    #[serde(rename = "5.2")]
+    L5_2,
+}
+
+// How the encoder spends bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RateControl {
+    /// Constant picture quality, the bitrate follows the content
+    Quality,
+    /// Hold the target bitrate
+    Bitrate,
+    /// Hold the target bitrate over a buffer window, smoothing peaks
+    Buffer,
+    /// Hold the target bitrate using frame timestamps, for variable frame rates
+    Timestamp,
+    /// Fixed quantization, no rate control
+    Off,
+}
+
+// H.264 encoder settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderSettings {
+    /// Target bitrate; the encoder default is used when unset
+    #[serde(default)]
+    pub bitrate_kbps: Option<u32>,
+    /// Name of a registered encoder; the built-in OpenH264 encoder when unset
+    #[serde(default)]
+    pub encoder: Option<String>,
+    /// B-frames between reference frames. Each one delays the stream by a frame and WebRTC
+    /// peers can't decode them: 0 when unset for WebRTC sinks and `Realtime` streams,
+    /// the encoder default otherwise.
+    #[serde(default)]
+    pub b_frames: Option<u32>,
+    /// Frames the rate control analyzes ahead of the one it encodes; unset defaults as `b_frames`
+    #[serde(default)]
+    pub lookahead: Option<u32>,
+    /// Slices per frame, so a decoder can start on a frame before all of it arrived
+    #[serde(default)]
+    pub slices: Option<u32>,
+    /// Highest frame rate encoded, faster frames are skipped; the stream's `max_fps`
+    /// when unset
+    #[serde(default)]
+    pub max_fps: Option<f64>,
+    /// The encoder default when unset
+    #[serde(default)]
+    pub profile: Option<H264Profile>,
+    /// Derived from the frame size and rate when unset
+    #[serde(default)]
+    pub level: Option<H264Level>,
+    /// Frames between keyframes; two seconds of frames when unset
+    #[serde(default)]
+    pub keyframe_interval: Option<u32>,
+    /// The encoder default when unset
+    #[serde(default)]
+    pub rate_control: Option<RateControl>,
+    /// AV1 speed preset, from 0 (slowest, smallest files) to 10 (fastest); 10 when unset
+    #[serde(default)]
+    pub speed_preset: Option<u8>,
+}
+
+impl EncoderSettings {
+    /// Fill unset frame reordering and lookahead with zero-latency values
+    pub fn zero_latency(mut self) -> Self {
+        self.b_frames.get_or_insert(0);
+        self.lookahead.get_or_insert(0);
+        self
+    }
+}
+
+// Named per-device configuration, applied with `apply_profile`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraProfile {
+    /// Capture format used instead of the recommended one
+    #[serde(default)]
+    pub format: Option<CameraFormat>,
+    #[serde(default)]
+    pub white_balance: Option<WhiteBalance>,
+    /// Processing options used when a stream is started without explicit options
+    #[serde(default)]
+    pub stream: StreamOptions,
+    #[serde(default)]
+    pub encoder: EncoderSettings,
+    /// Calibrated controls, saved from the device with `save_control_profile`
+    #[serde(default)]
+    pub controls: Option<ControlSettings>,
+}
+
+// Controls of a device saved in a profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlSettings {
+    #[serde(default)]
+    pub exposure: Option<Exposure>,
+    #[serde(default)]
+    pub white_balance: Option<WhiteBalance>,
+    #[serde(default)]
+    pub focus_mode: Option<FocusMode>,
+    /// Manual focus distance, 0.0 (infinity) to 1.0 (closest)
+    #[serde(default)]
+    pub focus_distance: Option<f32>,
+    #[serde(default)]
+    pub zoom: Option<f32>,
+    /// Brightness, contrast, ...
+    #[serde(default)]
+    pub image: HashMap<CameraControl, f32>,
+}
+
+// Portable plugin configuration, produced by `export_config` and consumed by `import_config`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginConfig {
+    pub version: u32,
+    /// Saved profiles by device ID, then by name
+    #[serde(default)]
+    pub profiles: HashMap<String, HashMap<String, CameraProfile>>,
+}
+
+impl PluginConfig {
+    pub const VERSION: u32 = 1;
+}
+
+// Outcome of one `run_diagnostics` step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticStep {
+    pub name: String,
+    pub ok: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Self-test report returned by `run_diagnostics`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub platform: String,
+    /// Every step succeeded
+    pub passed: bool,
+    pub total_ms: f64,
+    pub steps: Vec<DiagnosticStep>,
+}
+
+// Region of a window, in physical pixels from its top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Logical size of a window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+// Logical position of a window on the desktop
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+// Options of `open_pip_preview`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipOptions {
+    /// Defaults to 320x180
+    #[serde(default)]
+    pub size: Option<WindowSize>,
+    /// Defaults to the bottom-right corner of the primary monitor
+    #[serde(default)]
+    pub position: Option<WindowPosition>,
+    /// Keep the window above other apps. Enabled unless set to `false`.
+    #[serde(default)]
+    pub always_on_top: Option<bool>,
+}
+
+// Options of `open_overlay_preview`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayOptions {
+    /// Defaults to 480x270
+    #[serde(default)]
+    pub size: Option<WindowSize>,
+    /// Centered on the primary monitor by default
+    #[serde(default)]
+    pub position: Option<WindowPosition>,
+    /// Let mouse events pass through to the windows below
+    #[serde(default)]
+    pub click_through: bool,
+    /// Window opacity, from 0 (invisible) to 1 (opaque, the default)
+    #[serde(default)]
+    pub opacity: Option<f64>,
+}
+
+// Changes applied to an overlay preview by `set_overlay_options`; unset fields are kept
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayUpdate {
+    #[serde(default)]
+    pub click_through: Option<bool>,
+    #[serde(default)]
+    pub opacity: Option<f64>,
+}
+
+// A display connected to the machine
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    pub position: WindowPosition,
+    pub size: WindowSize,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+// Description of a frame pipeline: source → [filters] → {sinks}
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineDesc {
+    /// Device ID of a camera or of a frame source registered by the app
+    pub source: String,
+    /// Built-in processing (conversion, HDR, dedup); the applied profile's options by default
+    #[serde(default)]
+    pub options: Option<StreamOptions>,
+    /// Filters registered with `register_filter`, applied in order to converted frames
+    #[serde(default)]
+    pub filters: Vec<String>,
+    #[serde(default)]
+    pub sinks: Vec<SinkDesc>,
+}
+
+impl PipelineDesc {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn options(mut self, options: StreamOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    pub fn filter(mut self, name: impl Into<String>) -> Self {
+        self.filters.push(name.into());
+        self
+    }
+
+    pub fn sink(mut self, sink: SinkDesc) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+}
+
+// Destination of a pipeline's frames
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SinkDesc {
+    /// The frontend channel passed along with the pipeline
+    Channel,
+    #[serde(rename_all = "camelCase")]
+    WebRtc {
+        connection_id: String,
+    },
+    /// Native preview behind a webview (the `main` window by default)
+    #[serde(rename_all = "camelCase")]
+    NativePreview {
+        #[serde(default)]
+        window_label: Option<String>,
+        rect: PreviewRect,
+    },
+    Pip(PipOptions),
+    Overlay(OverlayOptions),
+    Kiosk {
+        #[serde(default)]
+        monitor: Option<String>,
+    },
+    /// MP4 file inside the output directories
+    Recording {
+        path: PathBuf,
+        #[serde(default, flatten)]
+        options: RecordingOptions,
+    },
+}
+
+// A started pipeline
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineInfo {
+    pub stream_id: String,
+    /// Access token of the stream, for the commands controlling it
+    pub token: String,
+    /// ID of each sink, in order, for `detach_sink`: the connection ID for WebRTC,
+    /// the preview or window ID for previews
+    pub sinks: Vec<String>,
+}
+
+// Failures to simulate on a device, for testing error handling (`fault-injection` feature)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultInjection {
+    /// Stop delivering frames, as if the device was unplugged, after this many more frames
+    #[serde(default)]
+    pub device_lost_after_frames: Option<u64>,
+    /// Fail the processing (color conversion, filters) of every frame
+    #[serde(default)]
+    pub conversion_errors: bool,
+    /// Fail every frame sent to frontend channels
+    #[serde(default)]
+    pub channel_send_errors: bool,
+    /// Panic in the encoder on the next encoded frame
+    #[serde(default)]
+    pub encoder_panic: bool,
+}
+
+// Camera authorization of the app on macOS (TCC) and what keeps the permission prompt from appearing
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraAccess {
+    /// `NotDetermined` (not asked yet), `Denied`, `Restricted` (MDM or Screen Time) or `Granted`
+    pub status: crate::PermissionStatus,
+    /// `NSCameraUsageDescription` is in Info.plist; without it the prompt never appears
+    pub usage_description: bool,
+    /// The executable is code signed, possibly ad-hoc
+    pub signed: bool,
+    /// Ad-hoc signature: macOS forgets the permission whenever the binary changes
+    pub ad_hoc: bool,
+    pub hardened_runtime: bool,
+    pub sandboxed: bool,
+    /// `com.apple.security.device.camera`, required with the hardened runtime or the sandbox
+    pub camera_entitlement: bool,
+    /// Whether macOS can show the permission prompt to this app
+    pub can_prompt: bool,
+    /// Steps to get camera access, most important first
+    pub guidance: Vec<String>,
+}
@@ -0,0 +1,72 @@
+use crate::webrtc::IceRestartEvent;
+use crate::CameraExt;
+use std::net::{IpAddr, UdpSocket};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// How often the routes are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Local addresses the OS sends internet traffic from, over IPv4 and IPv6. They change
+/// when the active interface does (Wi-Fi roam to another network, VPN, cable unplugged).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Routes {
+    v4: Option<IpAddr>,
+    v6: Option<IpAddr>,
+}
+
+impl Routes {
+    fn current() -> Self {
+        // Connecting a UDP socket only looks up the route, nothing is sent
+        // (documentation addresses, RFC 5737 and RFC 3849)
+        Self {
+            v4: route_source("0.0.0.0:0", "192.0.2.1:9"),
+            v6: route_source("[::]:0", "[2001:db8::1]:9"),
+        }
+    }
+}
+
+fn route_source(bind: &str, destination: &str) -> Option<IpAddr> {
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect(destination).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+/// Restart ICE on the active WebRTC connections whenever the network routes change,
+/// emitting each new offer on `camera://ice-restart` for the app's signaling to relay
+pub fn spawn_monitor<R: Runtime>(app: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        let mut routes = Routes::current();
+        let mut check = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            check.tick().await;
+            let current = Routes::current();
+            if current == routes {
+                continue;
+            }
+            log::info!("Network changed from {:?} to {:?}", routes, current);
+            routes = current;
+            if current.v4.is_none() && current.v6.is_none() {
+                // Offline: restart once a network is back
+                continue;
+            }
+
+            let manager = &app.camera().webrtc_manager;
+            for connection_id in manager.restartable_connections().await {
+                match manager.restart_ice(&connection_id).await {
+                    Ok(offer) => {
+                        log::info!("Restarting ICE on connection {}", connection_id);
+                        let event = IceRestartEvent {
+                            connection_id,
+                            offer,
+                        };
+                        if let Err(e) = app.emit("camera://ice-restart", event) {
+                            log::warn!("Failed to emit ICE restart offer: {}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to restart ICE on {}: {}", connection_id, e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,355 @@
+use crate::error::{Error, Result};
+use crate::exif::Exif;
+use crate::models::{
+    CaptureOptions, ColorSpace, FrameEvent, Photo, PhotoFormat, PhotoOptions, StreamOptions,
+};
+use crate::pipeline::FrameProcessor;
+use crate::utils::{convert_rgba_color_space, nv12_to_rgba_in};
+use crate::zoom::DigitalZoom;
+use crabcamera::CameraFrame;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::watch;
+
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+/// Frames dropped after opening a camera for a photo, while exposure and white balance settle
+pub const WARMUP_FRAMES: usize = 5;
+/// Time allowed for the photo frame to arrive, warm-up included
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most shots of a burst, all held in memory until encoded
+pub const MAX_BURST_SHOTS: u32 = 60;
+/// Longest countdown of `capture_photo_with_timer`
+pub const MAX_TIMER_SECONDS: u32 = 60;
+
+/// Read `count` frames of a stream's raw feed, `interval` apart, after dropping the first `skip` ones
+pub async fn next_frames(
+    mut receiver: watch::Receiver<Option<Arc<CameraFrame>>>,
+    skip: usize,
+    count: u32,
+    interval: Duration,
+) -> Result<Vec<Arc<CameraFrame>>> {
+    let mut frames = vec![next_frame(&mut receiver, skip).await?];
+    let first = tokio::time::Instant::now();
+    for index in 1..count {
+        tokio::time::sleep_until(first + interval * index).await;
+        // The newest frame, or the next one if none arrived since the previous shot
+        frames.push(next_frame(&mut receiver, 0).await?);
+    }
+    Ok(frames)
+}
+
+/// Wait for a frame of a stream's raw feed, after dropping the first `skip` ones
+async fn next_frame(
+    receiver: &mut watch::Receiver<Option<Arc<CameraFrame>>>,
+    skip: usize,
+) -> Result<Arc<CameraFrame>> {
+    let mut skipped = 0;
+    let frame = async {
+        loop {
+            receiver.changed().await.map_err(|_| {
+                Error::CameraError("Stream stopped before delivering a frame".to_string())
+            })?;
+            let frame = receiver.borrow_and_update().clone();
+            if let Some(frame) = frame {
+                if skipped >= skip {
+                    return Ok(frame);
+                }
+                skipped += 1;
+            }
+        }
+    };
+    tokio::time::timeout(FRAME_TIMEOUT, frame)
+        .await
+        .map_err(|_| Error::CameraError(format!("No frame received within {:?}", FRAME_TIMEOUT)))?
+}
+
+/// Wait for the newest full frame a stream delivered, without consuming its feed
+/// The current frame is returned right away unless it is a repeat, which carries no data.
+pub async fn latest_event(mut receiver: watch::Receiver<Option<FrameEvent>>) -> Result<FrameEvent> {
+    let event = async {
+        loop {
+            if let Some(event) = receiver.borrow_and_update().clone() {
+                if !event.repeated {
+                    return Ok(event);
+                }
+            }
+            receiver.changed().await.map_err(|_| {
+                Error::CameraError("Stream stopped before delivering a frame".to_string())
+            })?;
+        }
+    };
+    tokio::time::timeout(FRAME_TIMEOUT, event)
+        .await
+        .map_err(|_| Error::CameraError(format!("No frame received within {:?}", FRAME_TIMEOUT)))?
+}
+
+/// Frame a photo is encoded from
+pub enum Shot {
+    /// Raw frame of the camera, converted to sRGB with the device's digital zoom
+    Raw { frame: Arc<CameraFrame>, zoom: f32 },
+    /// Frame as a stream delivered it, after its crop, scaling and filters
+    Delivered(FrameEvent),
+}
+
+/// Convert a captured frame to sRGB and encode it; returns its size and encoded bytes
+pub fn encode(frame: Arc<CameraFrame>, options: &PhotoOptions) -> Result<(u32, u32, Vec<u8>)> {
+    encode_event(&to_srgb(frame, 1.0)?, options)
+}
+
+/// Convert a captured frame to RGBA in sRGB, zoomed digitally by `zoom`
+pub fn to_srgb(frame: Arc<CameraFrame>, zoom: f32) -> Result<FrameEvent> {
+    FrameProcessor::new(
+        StreamOptions {
+            color_space: Some(ColorSpace::Srgb),
+            ..Default::default()
+        },
+        Vec::new(),
+    )
+    .with_zoom(DigitalZoom::new(zoom))
+    .process(frame, Instant::now())
+}
+
+/// Encode a frame delivered by a stream, RGBA or NV12 in the stream's color space
+///
+/// The photo carries no color profile, so wide-gamut frames are mapped to sRGB first.
+pub fn encode_event(event: &FrameEvent, options: &PhotoOptions) -> Result<(u32, u32, Vec<u8>)> {
+    let (width, height) = (event.width, event.height);
+    let mut rgba = if event.format.eq_ignore_ascii_case("RGBA") {
+        Cow::Borrowed(&event.data)
+    } else if event.format.eq_ignore_ascii_case("NV12") {
+        Cow::Owned(nv12_to_rgba_in(
+            &event.data,
+            width,
+            height,
+            event.color_space,
+        )?)
+    } else {
+        return Err(Error::CameraError(format!(
+            "Cannot encode {} frames as a photo",
+            event.format
+        )));
+    };
+    if matches!(
+        event.color_space,
+        ColorSpace::DisplayP3 | ColorSpace::Bt2020
+    ) {
+        convert_rgba_color_space(rgba.to_mut(), event.color_space, ColorSpace::Srgb);
+    }
+
+    let mut data = Vec::new();
+    let encoded = match options.format {
+        PhotoFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb: Vec<u8> = rgba
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                .collect();
+            let quality = options
+                .quality
+                .unwrap_or(DEFAULT_JPEG_QUALITY)
+                .clamp(1, 100);
+            JpegEncoder::new_with_quality(&mut data, quality).write_image(
+                &rgb,
+                width,
+                height,
+                ExtendedColorType::Rgb8,
+            )
+        }
+        PhotoFormat::Png => {
+            PngEncoder::new(&mut data).write_image(&rgba, width, height, ExtendedColorType::Rgba8)
+        }
+    };
+    encoded.map_err(|e| Error::CameraError(format!("Failed to encode photo: {}", e)))?;
+    Ok((width, height, data))
+}
+
+/// Reject EXIF metadata that can't be written
+pub fn check_metadata(metadata: &CaptureOptions) -> Result<()> {
+    if let Some(orientation) = metadata.orientation {
+        if !(1..=8).contains(&orientation) {
+            return Err(Error::InvalidConfig(format!(
+                "EXIF orientation is 1 to 8, not {}",
+                orientation
+            )));
+        }
+    }
+    if let Some(gps) = &metadata.gps {
+        if !(-90.0..=90.0).contains(&gps.latitude) || !(-180.0..=180.0).contains(&gps.longitude) {
+            return Err(Error::InvalidConfig(format!(
+                "Invalid GPS location: {}, {}",
+                gps.latitude, gps.longitude
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Encode a shot off the async runtime, then write it to `path` or return its bytes;
+/// `options` are checked beforehand with `check_metadata`
+/// JPEG photos carry EXIF metadata unless `options.metadata` turns it off, with `camera`
+/// as the model name.
+pub async fn save(
+    shot: Shot,
+    options: &PhotoOptions,
+    path: Option<PathBuf>,
+    camera: Option<String>,
+) -> Result<Photo> {
+    let encode_options = options.clone();
+    let (width, height, data) = tokio::task::spawn_blocking(move || {
+        let (captured, encoded) = match shot {
+            Shot::Raw { frame, zoom } => (
+                SystemTime::now(),
+                to_srgb(frame, zoom).and_then(|event| encode_event(&event, &encode_options)),
+            ),
+            Shot::Delivered(event) => (
+                UNIX_EPOCH + Duration::from_millis(event.capture_time_ms),
+                encode_event(&event, &encode_options),
+            ),
+        };
+        let (width, height, mut data) = encoded?;
+        let metadata = encode_options.metadata;
+        if encode_options.format == PhotoFormat::Jpeg && metadata.exif != Some(false) {
+            crate::exif::embed(
+                &mut data,
+                &Exif {
+                    captured,
+                    camera,
+                    width,
+                    height,
+                    orientation: metadata.orientation.unwrap_or(1),
+                    description: metadata.description,
+                    gps: metadata.gps,
+                },
+            );
+        }
+        Ok::<_, Error>((width, height, data))
+    })
+    .await
+    .map_err(|e| Error::CameraError(format!("Photo encoding failed: {}", e)))??;
+    let data = match &path {
+        Some(path) => {
+            tokio::fs::write(path, &data).await?;
+            None
+        }
+        None => Some(data),
+    };
+    Ok(Photo {
+        width,
+        height,
+        format: options.format,
+        data,
+        path,
+    })
+}
+
+/// File of the `index`th shot of a burst written to `path`: `shot.jpg` gives `shot-0.jpg`, ...
+pub fn burst_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_photo() {
+        let mut frame = CameraFrame::new(vec![128; 16 * 8 * 3 / 2], 16, 8, "0".to_string());
+        frame.format = "NV12".to_string();
+        let frame = Arc::new(frame);
+
+        let png = PhotoOptions {
+            format: PhotoFormat::Png,
+            ..Default::default()
+        };
+        let (width, height, data) = encode(frame.clone(), &png).unwrap();
+        assert_eq!((width, height), (16, 8));
+        assert!(data.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let (_, _, data) = encode(frame, &PhotoOptions::default()).unwrap();
+        assert!(data.starts_with(&[0xff, 0xd8]));
+    }
+
+    #[tokio::test]
+    async fn test_latest_event_skips_repeated_frames() {
+        let event = FrameEvent {
+            data: vec![0; 4 * 4 * 4],
+            width: 4,
+            height: 4,
+            format: "RGBA".to_string(),
+            color_space: ColorSpace::Srgb,
+            hdr: None,
+            repeated: true,
+            pts_us: 0,
+            capture_time_ms: 0,
+        };
+        let (sender, receiver) = watch::channel(Some(FrameEvent {
+            data: Vec::new(),
+            ..event.clone()
+        }));
+        let latest = tokio::spawn(latest_event(receiver));
+        sender
+            .send(Some(FrameEvent {
+                repeated: false,
+                pts_us: 1,
+                ..event
+            }))
+            .unwrap();
+
+        let latest = latest.await.unwrap().unwrap();
+        assert_eq!(latest.pts_us, 1);
+        let (width, height, data) = encode_event(&latest, &PhotoOptions::default()).unwrap();
+        assert_eq!((width, height), (4, 4));
+        assert!(data.starts_with(&[0xff, 0xd8]));
+    }
+
+    #[test]
+    fn test_encode_event_maps_display_p3_to_srgb() {
+        // Fully saturated P3 red lies outside sRGB and clips to sRGB red
+        let event = FrameEvent {
+            data: vec![255, 0, 0, 255],
+            width: 1,
+            height: 1,
+            format: "RGBA".to_string(),
+            color_space: ColorSpace::DisplayP3,
+            hdr: None,
+            repeated: false,
+            pts_us: 0,
+            capture_time_ms: 0,
+        };
+        let png = PhotoOptions {
+            format: PhotoFormat::Png,
+            ..Default::default()
+        };
+        let (_, _, data) = encode_event(&event, &png).unwrap();
+        let decoded = image::load_from_memory(&data).unwrap().to_rgba8();
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+        // A muted P3 color is desaturated into the smaller gamut, not copied as is
+        let event = FrameEvent {
+            data: vec![200, 100, 50, 255],
+            ..event
+        };
+        let (_, _, data) = encode_event(&event, &png).unwrap();
+        let decoded = image::load_from_memory(&data).unwrap().to_rgba8();
+        assert_ne!(decoded.get_pixel(0, 0).0, [200, 100, 50, 255]);
+    }
+
+    #[test]
+    fn test_burst_path() {
+        assert_eq!(
+            burst_path(Path::new("/pictures/shot.jpg"), 2),
+            Path::new("/pictures/shot-2.jpg")
+        );
+        assert_eq!(burst_path(Path::new("shot"), 0), Path::new("shot-0"));
+    }
+}
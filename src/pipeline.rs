@@ -0,0 +1,509 @@
+use crate::error::{Error, Result};
+use crate::models::{ColorSpace, CropRect, FrameEvent, HdrTransfer, PowerMode, StreamOptions};
+use crate::scale::{target_size, Scaler};
+use crate::utils::{
+    convert_rgba_color_space, crop_yuv420sp, flip_plane, frame_signature, is_mjpeg, mjpeg_to_nv12,
+    nv12_to_rgba_in, p010_to_nv12_tone_mapped, packed_422_to_rgba_in, rotate_plane,
+    signature_distance, source_color_space, uyvy_to_nv12, yuy2_to_nv12, Packed422,
+};
+use crate::zoom::DigitalZoom;
+use crabcamera::CameraFrame;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Consecutive repeated frames after which a full frame is sent anyway,
+/// so late consumers and encoders still get a fresh picture (~1s at 30fps)
+const MAX_REPEATED_FRAMES: u32 = 30;
+/// Dedup tolerance applied in low power mode when none is configured
+const LOW_POWER_DEDUP_TOLERANCE: f64 = 1.0;
+
+/// Check a crop rectangle lies within the frame and isn't empty
+pub fn check_crop(crop: &CropRect) -> Result<()> {
+    let within =
+        |start: f64, size: f64| start >= 0.0 && size > 0.0 && start + size <= 1.0 + f64::EPSILON;
+    if within(crop.x, crop.width) && within(crop.y, crop.height) {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "Crop rectangle {:?} must lie within 0.0..=1.0 and not be empty",
+            crop
+        )))
+    }
+}
+
+/// Pixel region of a crop rectangle, rounded to even coordinates as 4:2:0 chroma requires
+fn crop_region(crop: &CropRect, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let even = |value: f64, size: u32| ((value * size as f64) as u32).min(size) & !1;
+    // At least 2x2 pixels, within the frame
+    let x = even(crop.x, width).min(width.saturating_sub(2) & !1);
+    let y = even(crop.y, height).min(height.saturating_sub(2) & !1);
+    let crop_width = even(crop.width, width).max(2).min((width - x) & !1);
+    let crop_height = even(crop.height, height).max(2).min((height - y) & !1);
+    (x, y, crop_width, crop_height)
+}
+
+/// Custom processing step of a pipeline (blur, text overlay, ...), run on converted frames
+/// after the built-in stages. Repeated frames carry no data and are not filtered.
+///
+/// Closures implement this trait.
+pub trait FrameFilter: Send + 'static {
+    fn apply(&mut self, frame: FrameEvent) -> Result<FrameEvent>;
+}
+
+impl<F> FrameFilter for F
+where
+    F: FnMut(FrameEvent) -> Result<FrameEvent> + Send + 'static,
+{
+    fn apply(&mut self, frame: FrameEvent) -> Result<FrameEvent> {
+        self(frame)
+    }
+}
+
+/// Creates a fresh filter for each pipeline using it
+pub type FilterFactory = Arc<dyn Fn() -> Box<dyn FrameFilter> + Send + Sync>;
+
+/// Filters available to pipelines, by name
+#[derive(Default)]
+pub struct FilterRegistry {
+    factories: Mutex<HashMap<String, FilterFactory>>,
+}
+
+impl FilterRegistry {
+    /// Add or replace a filter
+    pub fn register(&self, name: impl Into<String>, factory: FilterFactory) {
+        self.factories.lock().unwrap().insert(name.into(), factory);
+    }
+
+    pub fn create(&self, name: &str) -> Result<Box<dyn FrameFilter>> {
+        let factories = self.factories.lock().unwrap();
+        let factory = factories
+            .get(name)
+            .ok_or_else(|| Error::CameraError(format!("Filter not registered: {}", name)))?;
+        Ok(factory())
+    }
+}
+
+/// Per-stream frame processing, run inside the capture callback
+/// Turns a raw `crabcamera::CameraFrame` into the `FrameEvent` handed to consumers
+pub struct FrameProcessor {
+    options: StreamOptions,
+    filters: Vec<Box<dyn FrameFilter>>,
+    last_signature: Option<Vec<u8>>,
+    repeated_frames: u32,
+    /// Origin of the stream's presentation timestamps
+    started: Instant,
+    last_pts_us: Option<u64>,
+    /// Arrival of the previous captured frame, and when the next delivered frame is due,
+    /// for `max_fps`
+    last_arrival: Option<Instant>,
+    next_due: Option<Instant>,
+    scaler: Scaler,
+    zoom: Option<DigitalZoom>,
+}
+
+impl FrameProcessor {
+    pub fn new(mut options: StreamOptions, filters: Vec<Box<dyn FrameFilter>>) -> Self {
+        if options.power_mode == PowerMode::Low {
+            options
+                .dedup_tolerance
+                .get_or_insert(LOW_POWER_DEDUP_TOLERANCE);
+        }
+        Self {
+            options,
+            filters,
+            last_signature: None,
+            repeated_frames: 0,
+            started: Instant::now(),
+            last_pts_us: None,
+            last_arrival: None,
+            next_due: None,
+            scaler: Scaler::default(),
+            zoom: None,
+        }
+    }
+
+    /// Follow the digital zoom of the device
+    pub fn with_zoom(mut self, zoom: DigitalZoom) -> Self {
+        self.zoom = Some(zoom);
+        self
+    }
+
+    /// Run the built-in stages, then the pipeline's filters, on a frame that arrived from
+    /// the device at `arrived`
+    pub fn process(&mut self, frame: Arc<CameraFrame>, arrived: Instant) -> Result<FrameEvent> {
+        let pts_us = self.pts_us(arrived);
+        let capture_time_ms = (SystemTime::now() - arrived.elapsed())
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut event = self.convert(frame, pts_us, capture_time_ms)?;
+        if !event.repeated {
+            event = self.scale(event)?;
+            event = self.orient(event)?;
+            for filter in &mut self.filters {
+                event = filter.apply(event)?;
+            }
+        }
+        Ok(event)
+    }
+
+    /// Scale stage: shrink converted RGBA or raw NV12 frames to the target size, and apply
+    /// the digital zoom
+    /// HDR frames passed through untouched keep their size
+    fn scale(&mut self, mut event: FrameEvent) -> Result<FrameEvent> {
+        let size = (event.width, event.height);
+        // Targets are given for the rotated frame
+        let (target_width, target_height) = if self.options.rotation / 90 % 2 == 1 {
+            (self.options.target_height, self.options.target_width)
+        } else {
+            (self.options.target_width, self.options.target_height)
+        };
+        if let Some(zoom) = &self.zoom {
+            self.scaler.set_zoom(zoom.get() as f64);
+        }
+        // A zoomed frame is scaled back up to its size
+        let Some(target) = target_size(event.width, event.height, target_width, target_height)
+            .or_else(|| self.scaler.zoomed().then_some(size))
+        else {
+            return Ok(event);
+        };
+        event.data = if event.format.eq_ignore_ascii_case("RGBA") {
+            self.scaler.rgba(&event.data, size, target)?
+        } else if event.format.eq_ignore_ascii_case("NV12") {
+            self.scaler.nv12(&event.data, size, target)?
+        } else {
+            return Ok(event);
+        };
+        (event.width, event.height) = target;
+        Ok(event)
+    }
+
+    /// Orientation stage: rotate, then mirror and/or flip RGBA or NV12 frames, before
+    /// encoding so every sink sees the same picture
+    /// HDR frames passed through untouched are left as they are
+    fn orient(&self, mut event: FrameEvent) -> Result<FrameEvent> {
+        let turns = self.options.rotation / 90 % 4;
+        // A half turn is a mirror and a flip
+        let horizontal = self.options.mirror ^ (turns == 2);
+        let vertical = self.options.flip_vertical ^ (turns == 2);
+        if turns % 2 == 0 && !horizontal && !vertical {
+            return Ok(event);
+        }
+        let (mut width, mut height) = (event.width as usize, event.height as usize);
+        if event.format.eq_ignore_ascii_case("RGBA") {
+            check_size(&event, width * height * 4)?;
+            if turns % 2 == 1 {
+                event.data = rotate_plane(&event.data, width, height, 4, turns == 1);
+                (width, height) = (height, width);
+            }
+            flip_plane(&mut event.data, width * 4, 4, horizontal, vertical);
+        } else if event.format.eq_ignore_ascii_case("NV12") {
+            check_size(&event, width * height * 3 / 2)?;
+            if turns % 2 == 1 {
+                let (luma, chroma) = event.data.split_at(width * height);
+                let mut rotated = rotate_plane(luma, width, height, 1, turns == 1);
+                rotated.extend(rotate_plane(
+                    &chroma[..width * height / 2],
+                    width / 2,
+                    height / 2,
+                    2,
+                    turns == 1,
+                ));
+                event.data = rotated;
+                (width, height) = (height, width);
+            }
+            let (luma, chroma) = event.data.split_at_mut(width * height);
+            flip_plane(luma, width, 1, horizontal, vertical);
+            flip_plane(
+                &mut chroma[..width * height / 2],
+                width,
+                2,
+                horizontal,
+                vertical,
+            );
+        } else {
+            return Ok(event);
+        }
+        (event.width, event.height) = (width as u32, height as u32);
+        Ok(event)
+    }
+
+    /// Frame rate stage: whether a frame that arrived at `arrived` is delivered under
+    /// `max_fps`; skipped frames aren't processed at all
+    /// Frames are picked on a fixed schedule rather than as soon as the interval has passed,
+    /// the one closest to each due time, so capture jitter neither lowers the rate nor
+    /// bunches frames up.
+    pub fn admit(&mut self, arrived: Instant) -> bool {
+        let capture_interval = self
+            .last_arrival
+            .replace(arrived)
+            .map(|last| arrived.saturating_duration_since(last));
+        let Some(max_fps) = self.options.max_fps else {
+            return true;
+        };
+        let interval = Duration::from_secs_f64(1.0 / max_fps);
+        // Closer to the due time than the next capture will be
+        let tolerance = capture_interval.unwrap_or_default() / 2;
+        match self.next_due {
+            Some(due) if arrived + tolerance < due => false,
+            due => {
+                // Catch up after a gap instead of delivering a burst
+                let base = due
+                    .filter(|due| arrived < *due + interval)
+                    .unwrap_or(arrived);
+                self.next_due = Some(base + interval);
+                true
+            }
+        }
+    }
+
+    /// Time since the stream started, kept strictly increasing for muxers and decoders
+    fn pts_us(&mut self, arrived: Instant) -> u64 {
+        let elapsed = arrived.saturating_duration_since(self.started).as_micros() as u64;
+        let pts_us = match self.last_pts_us {
+            Some(last) => elapsed.max(last + 1),
+            None => elapsed,
+        };
+        self.last_pts_us = Some(pts_us);
+        pts_us
+    }
+
+    /// Frames shared with raw consumers are only copied when passed through unconverted
+    fn convert(
+        &mut self,
+        frame: Arc<CameraFrame>,
+        pts_us: u64,
+        capture_time_ms: u64,
+    ) -> Result<FrameEvent> {
+        // Decode stage: MJPEG cameras deliver JPEG pictures, the later stages see NV12.
+        // Packed 4:2:2 frames too, unless they are only converted to RGBA, which keeps
+        // their full chroma resolution.
+        let mjpeg = is_mjpeg(&frame.format);
+        let packed = Packed422::of(&frame.format)
+            .filter(|_| self.options.crop.is_some() || self.options.color_space.is_none());
+        let decoded = if mjpeg {
+            Some(mjpeg_to_nv12(&frame.data)?)
+        } else {
+            let (width, height) = (frame.width, frame.height);
+            match packed {
+                Some(Packed422::Yuyv) => {
+                    Some((yuy2_to_nv12(&frame.data, width, height)?, width, height))
+                }
+                Some(Packed422::Uyvy) => {
+                    Some((uyvy_to_nv12(&frame.data, width, height)?, width, height))
+                }
+                None => None,
+            }
+        };
+        let frame = match decoded {
+            Some((nv12, width, height)) => {
+                let mut decoded = CameraFrame::new(nv12, width, height, frame.device_id.clone());
+                decoded.format = "NV12".to_string();
+                decoded.timestamp = frame.timestamp;
+                Arc::new(decoded)
+            }
+            None => frame,
+        };
+
+        // Crop stage: the later stages only see the region
+        let frame = match &self.options.crop {
+            Some(crop) => Arc::new(crop_frame(&frame, crop)?),
+            None => frame,
+        };
+        let (width, height) = (frame.width, frame.height);
+
+        // Dedup stage: runs on the raw buffer so repeated frames skip every conversion
+        if self.is_repeated(&frame.data) {
+            return Ok(FrameEvent {
+                width,
+                height,
+                data: Vec::new(),
+                format: frame.format.clone(),
+                color_space: source_color_space(width, height),
+                hdr: None,
+                repeated: true,
+                pts_us,
+                capture_time_ms,
+            });
+        }
+
+        // JPEG pictures are BT.601 whatever their size
+        let mut source = if mjpeg {
+            ColorSpace::Bt601
+        } else {
+            source_color_space(width, height)
+        };
+        let mut tone_mapped = None;
+
+        // HDR stage: tone map 10-bit frames to SDR NV12 unless passthrough is requested
+        if frame.format.eq_ignore_ascii_case("P010") {
+            let transfer = self.options.hdr_transfer.unwrap_or(HdrTransfer::Hlg);
+            if self.options.hdr_passthrough {
+                let (data, format) = into_parts(frame);
+                return Ok(FrameEvent {
+                    width,
+                    height,
+                    data,
+                    format,
+                    color_space: ColorSpace::Bt2020,
+                    hdr: Some(transfer),
+                    repeated: false,
+                    pts_us,
+                    capture_time_ms,
+                });
+            }
+            tone_mapped = Some(p010_to_nv12_tone_mapped(
+                &frame.data,
+                width,
+                height,
+                transfer,
+            )?);
+            source = ColorSpace::Bt2020;
+        }
+
+        // No conversion requested: pass the buffer through, tagged with its source space
+        let Some(target) = self.options.color_space else {
+            let (data, format) = match tone_mapped {
+                Some(nv12) => (nv12, "NV12".to_string()),
+                None => into_parts(frame),
+            };
+            return Ok(FrameEvent {
+                width,
+                height,
+                data,
+                format,
+                color_space: source,
+                hdr: None,
+                repeated: false,
+                pts_us,
+                capture_time_ms,
+            });
+        };
+
+        let (data, format) = match &tone_mapped {
+            Some(nv12) => (nv12, "NV12"),
+            None => (&frame.data, frame.format.as_str()),
+        };
+        let mut rgba = if format.eq_ignore_ascii_case("NV12") {
+            nv12_to_rgba_in(data, width, height, source)?
+        } else if let Some(order) = Packed422::of(format) {
+            packed_422_to_rgba_in(data, width, height, order, source)?
+        } else {
+            return Err(Error::CameraError(format!(
+                "Unsupported frame format: {}",
+                format
+            )));
+        };
+
+        // Gamut mapping is optional work: low power mode delivers RGBA in the source primaries
+        let color_space = if self.options.power_mode == PowerMode::Low {
+            source
+        } else {
+            convert_rgba_color_space(&mut rgba, source, target);
+            target
+        };
+
+        Ok(FrameEvent {
+            width,
+            height,
+            data: rgba,
+            format: "RGBA".to_string(),
+            color_space,
+            hdr: None,
+            repeated: false,
+            pts_us,
+            capture_time_ms,
+        })
+    }
+
+    /// Compare the frame against the last one sent in full
+    fn is_repeated(&mut self, data: &[u8]) -> bool {
+        let Some(tolerance) = self.options.dedup_tolerance else {
+            return false;
+        };
+
+        let signature = frame_signature(data);
+        let repeated = self.repeated_frames < MAX_REPEATED_FRAMES
+            && self
+                .last_signature
+                .as_deref()
+                .is_some_and(|last| signature_distance(last, &signature) <= tolerance);
+
+        if repeated {
+            self.repeated_frames += 1;
+        } else {
+            self.last_signature = Some(signature);
+            self.repeated_frames = 0;
+        }
+        repeated
+    }
+}
+
+/// Copy the region of a raw frame, before any conversion
+fn crop_frame(frame: &CameraFrame, crop: &CropRect) -> Result<CameraFrame> {
+    let sample_bytes = if frame.format.eq_ignore_ascii_case("NV12") {
+        1
+    } else if frame.format.eq_ignore_ascii_case("P010") {
+        2
+    } else {
+        return Err(Error::CameraError(format!(
+            "Cannot crop {} frames",
+            frame.format
+        )));
+    };
+    let region = crop_region(crop, frame.width, frame.height);
+    let data = crop_yuv420sp(&frame.data, frame.width, frame.height, sample_bytes, region)?;
+    let mut cropped = CameraFrame::new(data, region.2, region.3, frame.device_id.clone());
+    cropped.format = frame.format.clone();
+    cropped.timestamp = frame.timestamp;
+    Ok(cropped)
+}
+
+/// Reject frames whose buffer is shorter than their size requires
+fn check_size(event: &FrameEvent, expected: usize) -> Result<()> {
+    if event.data.len() < expected {
+        return Err(Error::CameraError(format!(
+            "Invalid {} buffer: {} bytes for a {}x{} frame",
+            event.format,
+            event.data.len(),
+            event.width,
+            event.height
+        )));
+    }
+    Ok(())
+}
+
+/// Take the buffer out of a frame, copying it only if raw consumers still hold the frame
+fn into_parts(frame: Arc<CameraFrame>) -> (Vec<u8>, String) {
+    match Arc::try_unwrap(frame) {
+        Ok(frame) => (frame.data, frame.format),
+        Err(shared) => (shared.data.clone(), shared.format.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_fps_picks_evenly_spaced_frames() {
+        let mut processor = FrameProcessor::new(
+            StreamOptions {
+                max_fps: Some(10.0),
+                ..Default::default()
+            },
+            Vec::new(),
+        );
+        let start = Instant::now();
+        // 30fps capture with a little jitter: every third frame goes through
+        let admitted: Vec<usize> = (0..30)
+            .filter(|&frame| {
+                let jitter = if frame % 2 == 0 { 0 } else { 2 };
+                processor.admit(start + Duration::from_millis(frame as u64 * 33 + jitter))
+            })
+            .collect();
+        assert_eq!(admitted.len(), 10);
+        assert!(admitted.windows(2).all(|pair| pair[1] - pair[0] == 3));
+    }
+}
@@ -0,0 +1,508 @@
+use crate::error::{Error, Result};
+use crate::models::CaptureBackend;
+use crate::sources::Sources;
+
+/// Cameras reached through the PipeWire camera portal (xdg-desktop-portal) instead of V4L2,
+/// for Flatpak/Snap sandboxes and systems where the video devices can't be opened directly,
+/// and screens shared through the ScreenCast portal.
+/// Portal nodes are registered as frame sources, so they stream like any device.
+pub struct Portal {
+    enabled: bool,
+    #[cfg(all(target_os = "linux", feature = "pipewire-portal"))]
+    remote: pipewire_portal::Remote,
+}
+
+impl Portal {
+    pub fn new(backend: CaptureBackend) -> Result<Self> {
+        let enabled = match backend {
+            CaptureBackend::Native => false,
+            CaptureBackend::Portal => {
+                if !cfg!(all(target_os = "linux", feature = "pipewire-portal")) {
+                    return Err(Error::InvalidConfig(
+                        "The portal capture backend requires Linux and the pipewire-portal feature"
+                            .to_string(),
+                    ));
+                }
+                true
+            }
+            CaptureBackend::Auto => {
+                cfg!(all(target_os = "linux", feature = "pipewire-portal"))
+                    && (sandboxed() || !v4l2_accessible())
+            }
+        };
+        if enabled {
+            log::info!("Using the PipeWire camera portal");
+        }
+
+        Ok(Self {
+            enabled,
+            #[cfg(all(target_os = "linux", feature = "pipewire-portal"))]
+            remote: pipewire_portal::Remote::default(),
+        })
+    }
+
+    /// Whether cameras are opened through the portal rather than directly
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Let the user pick screens or windows to share through the ScreenCast portal, which works
+    /// on Wayland as well as X11, and register each one as a source. Returns their device IDs.
+    pub async fn add_screen_sources(&self, sources: &Sources) -> Result<Vec<String>> {
+        #[cfg(all(target_os = "linux", feature = "pipewire-portal"))]
+        {
+            pipewire_portal::add_screen_sources(sources).await
+        }
+        #[cfg(not(all(target_os = "linux", feature = "pipewire-portal")))]
+        {
+            let _ = sources;
+            Err(Error::Unsupported(
+                "Screen capture requires Linux and the pipewire-portal feature".to_string(),
+            ))
+        }
+    }
+
+    /// Register the cameras currently shared by the portal as sources, replacing the previous ones
+    /// The first call asks the user for camera access.
+    pub async fn refresh(&self, sources: &Sources) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        #[cfg(all(target_os = "linux", feature = "pipewire-portal"))]
+        self.remote.refresh(sources).await?;
+        #[cfg(not(all(target_os = "linux", feature = "pipewire-portal")))]
+        let _ = sources;
+        Ok(())
+    }
+}
+
+/// Running inside a Flatpak or Snap sandbox
+fn sandboxed() -> bool {
+    std::path::Path::new("/.flatpak-info").exists() || std::env::var_os("SNAP").is_some()
+}
+
+/// Whether at least one V4L2 device can be opened
+fn v4l2_accessible() -> bool {
+    let Ok(entries) = std::fs::read_dir("/dev") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry.file_name().to_string_lossy().starts_with("video")
+            && std::fs::File::open(entry.path()).is_ok()
+    })
+}
+
+#[cfg(all(target_os = "linux", feature = "pipewire-portal"))]
+mod pipewire_portal {
+    use crate::error::{Error, Result};
+    use crate::models::CameraFormat;
+    use crate::sources::{FrameCallback, FrameSource, Sources};
+    use crate::utils::{rgbx_to_nv12, yuy2_to_nv12};
+    use ashpd::desktop::camera::{pipewire_streams, Camera};
+    use ashpd::desktop::screencast::{CursorMode, Screencast, SourceType};
+    use ashpd::desktop::{PersistMode, Session};
+    use crabcamera::{CameraDeviceInfo, CameraFrame};
+    use pipewire as pw;
+    use pw::spa::param::format::{FormatProperties, MediaSubtype, MediaType};
+    use pw::spa::param::video::{VideoFormat, VideoInfoRaw};
+    use pw::spa::pod::{serialize::PodSerializer, Pod, Value};
+    use pw::spa::utils::{Direction, Fraction, Rectangle, SpaTypes};
+    use std::io::Cursor;
+    use std::os::fd::{AsFd, OwnedFd};
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+    use tokio::sync::Mutex as AsyncMutex;
+
+    /// Prefix of the device IDs of portal cameras, followed by the PipeWire node ID
+    const DEVICE_PREFIX: &str = "pipewire-";
+    /// Prefix of the device IDs of shared screens and windows, followed by the PipeWire node ID
+    const SCREEN_PREFIX: &str = "screen-";
+
+    /// Connection to the PipeWire remote opened by the portal
+    #[derive(Default)]
+    pub struct Remote {
+        fd: AsyncMutex<Option<Arc<OwnedFd>>>,
+        /// Device IDs registered by the last refresh
+        registered: Mutex<Vec<String>>,
+    }
+
+    impl Remote {
+        async fn fd(&self) -> Result<Arc<OwnedFd>> {
+            let mut fd = self.fd.lock().await;
+            if let Some(fd) = fd.as_ref() {
+                return Ok(fd.clone());
+            }
+            let camera = Camera::new().await.map_err(portal_error)?;
+            if !camera.is_present().await.map_err(portal_error)? {
+                return Err(Error::DeviceNotFound(
+                    "no camera shared by the portal".to_string(),
+                ));
+            }
+            camera
+                .request_access()
+                .await
+                .map_err(portal_error)?
+                .response()
+                .map_err(|e| Error::PermissionDenied(format!("Camera portal: {}", e)))?;
+            let remote = Arc::new(camera.open_pipe_wire_remote().await.map_err(portal_error)?);
+            *fd = Some(remote.clone());
+            Ok(remote)
+        }
+
+        pub async fn refresh(&self, sources: &Sources) -> Result<()> {
+            let fd = self.fd().await?;
+            let streams = pipewire_streams(fd.as_fd())
+                .await
+                .map_err(|e| Error::CameraError(format!("Failed to list portal cameras: {}", e)))?;
+
+            let mut registered = self.registered.lock().unwrap();
+            for id in registered.drain(..) {
+                sources.unregister(&id);
+            }
+            for stream in streams {
+                let properties = stream.properties();
+                let name = ["node.description", "node.nick", "node.name"]
+                    .iter()
+                    .find_map(|key| properties.get(*key))
+                    .cloned()
+                    .unwrap_or_else(|| format!("Camera {}", stream.node_id()));
+                let camera = PipeWireSource {
+                    id: format!("{}{}", DEVICE_PREFIX, stream.node_id()),
+                    name,
+                    description: "PipeWire camera portal",
+                    node_id: stream.node_id(),
+                    fd: fd.clone(),
+                    screencast: None,
+                    capture: Mutex::new(None),
+                };
+                registered.push(camera.id.clone());
+                sources.register(Arc::new(camera));
+            }
+            Ok(())
+        }
+    }
+
+    fn portal_error(e: ashpd::Error) -> Error {
+        Error::CameraError(format!("Camera portal: {}", e))
+    }
+
+    /// ScreenCast session shared by the screens and windows picked together,
+    /// closed (ending the system's sharing indicator) once none of them is registered
+    struct ScreenCast {
+        session: Option<Session<'static, Screencast<'static>>>,
+    }
+
+    impl Drop for ScreenCast {
+        fn drop(&mut self) {
+            if let Some(session) = self.session.take() {
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = session.close().await {
+                        log::warn!("Failed to close screen cast session: {}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    pub async fn add_screen_sources(sources: &Sources) -> Result<Vec<String>> {
+        let screencast_error =
+            |e: ashpd::Error| Error::CameraError(format!("Screen cast portal: {}", e));
+        let proxy = Screencast::new().await.map_err(screencast_error)?;
+        let session = proxy.create_session().await.map_err(screencast_error)?;
+        proxy
+            .select_sources(
+                &session,
+                CursorMode::Embedded,
+                SourceType::Monitor | SourceType::Window,
+                true,
+                None,
+                PersistMode::DoNot,
+            )
+            .await
+            .map_err(screencast_error)?;
+        let response = proxy
+            .start(&session, None)
+            .await
+            .map_err(screencast_error)?
+            .response()
+            .map_err(|e| Error::PermissionDenied(format!("Screen cast portal: {}", e)))?;
+        let fd = Arc::new(
+            proxy
+                .open_pipe_wire_remote(&session)
+                .await
+                .map_err(screencast_error)?,
+        );
+
+        let screencast = Arc::new(ScreenCast {
+            session: Some(session),
+        });
+        let mut ids = Vec::new();
+        for stream in response.streams() {
+            let node_id = stream.pipe_wire_node_id();
+            let source = PipeWireSource {
+                id: format!("{}{}", SCREEN_PREFIX, node_id),
+                name: format!("Screen {}", node_id),
+                description: "PipeWire screen cast portal",
+                node_id,
+                fd: fd.clone(),
+                screencast: Some(screencast.clone()),
+                capture: Mutex::new(None),
+            };
+            ids.push(source.id.clone());
+            sources.register(Arc::new(source));
+        }
+        Ok(ids)
+    }
+
+    /// A running capture: its PipeWire loop thread and the channel to quit it
+    struct Capture {
+        quit: pw::channel::Sender<()>,
+        thread: JoinHandle<()>,
+    }
+
+    /// A camera, screen or window node of a portal's PipeWire remote
+    struct PipeWireSource {
+        id: String,
+        name: String,
+        description: &'static str,
+        node_id: u32,
+        fd: Arc<OwnedFd>,
+        /// Kept alive while the source is registered
+        screencast: Option<Arc<ScreenCast>>,
+        capture: Mutex<Option<Capture>>,
+    }
+
+    impl FrameSource for PipeWireSource {
+        fn info(&self) -> CameraDeviceInfo {
+            CameraDeviceInfo::new(self.id.clone(), self.name.clone())
+                .with_description(self.description.to_string())
+        }
+
+        fn continuous(&self) -> bool {
+            // Screen casts only deliver frames on damage
+            self.screencast.is_none()
+        }
+
+        fn start(&self, format: Option<&CameraFormat>, on_frame: FrameCallback) -> Result<()> {
+            let fd = self.fd.try_clone()?;
+            let (quit, quit_rx) = pw::channel::channel();
+            let (started_tx, started_rx) = std::sync::mpsc::channel();
+            let node_id = self.node_id;
+            let id = self.id.clone();
+            let format = format.cloned();
+            let thread = std::thread::spawn(move || {
+                let result = run_capture(fd, node_id, id, format, on_frame, quit_rx, &started_tx);
+                if let Err(e) = result {
+                    let _ = started_tx.send(Err(e));
+                }
+            });
+            // Wait for the stream to connect so failures are reported to the caller
+            started_rx
+                .recv()
+                .map_err(|_| Error::CameraError("PipeWire capture thread exited".to_string()))??;
+            *self.capture.lock().unwrap() = Some(Capture { quit, thread });
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<()> {
+            if let Some(capture) = self.capture.lock().unwrap().take() {
+                let _ = capture.quit.send(());
+                let _ = capture.thread.join();
+            }
+            Ok(())
+        }
+    }
+
+    /// State of the stream listener
+    struct StreamState {
+        id: String,
+        info: VideoInfoRaw,
+        on_frame: FrameCallback,
+    }
+
+    /// PipeWire main loop of one capture, until `quit` receives
+    fn run_capture(
+        fd: OwnedFd,
+        node_id: u32,
+        id: String,
+        format: Option<CameraFormat>,
+        on_frame: FrameCallback,
+        quit: pw::channel::Receiver<()>,
+        started: &std::sync::mpsc::Sender<Result<()>>,
+    ) -> Result<()> {
+        let pw_error = |e: pw::Error| Error::CameraError(format!("PipeWire: {}", e));
+        pw::init();
+        let mainloop = pw::main_loop::MainLoop::new(None).map_err(pw_error)?;
+        let context = pw::context::Context::new(&mainloop).map_err(pw_error)?;
+        let core = context.connect_fd(fd, None).map_err(pw_error)?;
+
+        let stream = pw::stream::Stream::new(
+            &core,
+            "tauri-plugin-camera",
+            pw::properties::properties! {
+                *pw::keys::MEDIA_TYPE => "Video",
+                *pw::keys::MEDIA_CATEGORY => "Capture",
+                *pw::keys::MEDIA_ROLE => "Camera",
+            },
+        )
+        .map_err(pw_error)?;
+        let state = StreamState {
+            id,
+            info: VideoInfoRaw::default(),
+            on_frame,
+        };
+        let _listener = stream
+            .add_local_listener_with_user_data(state)
+            .param_changed(|_, state, id, param| {
+                let Some(param) = param else {
+                    return;
+                };
+                if id != pw::spa::param::ParamType::Format.as_raw() {
+                    return;
+                }
+                if let Ok((MediaType::Video, MediaSubtype::Raw)) =
+                    pw::spa::param::format_utils::parse_format(param)
+                {
+                    if let Err(e) = state.info.parse(param) {
+                        log::error!("Failed to parse PipeWire video format: {}", e);
+                    }
+                }
+            })
+            .process(|stream, state| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                let datas = buffer.datas_mut();
+                let Some(data) = datas.first_mut() else {
+                    return;
+                };
+                let size = data.chunk().size() as usize;
+                let stride = data.chunk().stride().max(0) as usize;
+                let Some(bytes) = data.data() else {
+                    return;
+                };
+                let bytes = &bytes[..size.min(bytes.len())];
+                let (width, height) = (state.info.size().width, state.info.size().height);
+                let nv12 = match state.info.format() {
+                    VideoFormat::NV12 => bytes.to_vec(),
+                    VideoFormat::YUY2 => match yuy2_to_nv12(bytes, width, height) {
+                        Ok(nv12) => nv12,
+                        Err(e) => {
+                            log::error!("Failed to convert portal frame: {}", e);
+                            return;
+                        }
+                    },
+                    format @ (VideoFormat::BGRx
+                    | VideoFormat::BGRA
+                    | VideoFormat::RGBx
+                    | VideoFormat::RGBA) => {
+                        let bgr = matches!(format, VideoFormat::BGRx | VideoFormat::BGRA);
+                        let stride = if stride == 0 {
+                            width as usize * 4
+                        } else {
+                            stride
+                        };
+                        match rgbx_to_nv12(bytes, width, height, stride, bgr) {
+                            Ok(nv12) => nv12,
+                            Err(e) => {
+                                log::error!("Failed to convert screen frame: {}", e);
+                                return;
+                            }
+                        }
+                    }
+                    other => {
+                        log::error!("Unexpected PipeWire video format: {:?}", other);
+                        return;
+                    }
+                };
+                let mut frame = CameraFrame::new(nv12, width, height, state.id.clone());
+                frame.format = "NV12".to_string();
+                (state.on_frame)(frame);
+            })
+            .register()
+            .map_err(pw_error)?;
+
+        let format_pod = format_params(format.as_ref())?;
+        let mut params = [Pod::from_bytes(&format_pod)
+            .ok_or_else(|| Error::CameraError("Invalid PipeWire format".to_string()))?];
+        stream
+            .connect(
+                Direction::Input,
+                Some(node_id),
+                pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+                &mut params,
+            )
+            .map_err(pw_error)?;
+
+        let loop_ = mainloop.clone();
+        let _quit = quit.attach(mainloop.loop_(), move |_| loop_.quit());
+        let _ = started.send(Ok(()));
+        mainloop.run();
+        Ok(())
+    }
+
+    /// Formats offered to the node: NV12 or YUY2 for cameras, packed RGB for screens,
+    /// at the requested size and rate when given
+    fn format_params(format: Option<&CameraFormat>) -> Result<Vec<u8>> {
+        let size = format.map_or(
+            Rectangle {
+                width: 1280,
+                height: 720,
+            },
+            |format| Rectangle {
+                width: format.width,
+                height: format.height,
+            },
+        );
+        let fps = format.map_or(30, |format| format.fps.round() as u32);
+        let object = pw::spa::pod::object!(
+            SpaTypes::ObjectParamFormat,
+            pw::spa::param::ParamType::EnumFormat,
+            pw::spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+            pw::spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+            pw::spa::pod::property!(
+                FormatProperties::VideoFormat,
+                Choice,
+                Enum,
+                Id,
+                VideoFormat::NV12,
+                VideoFormat::NV12,
+                VideoFormat::YUY2,
+                VideoFormat::BGRx,
+                VideoFormat::BGRA,
+                VideoFormat::RGBx,
+                VideoFormat::RGBA
+            ),
+            pw::spa::pod::property!(
+                FormatProperties::VideoSize,
+                Choice,
+                Range,
+                Rectangle,
+                size,
+                Rectangle {
+                    width: 1,
+                    height: 1
+                },
+                Rectangle {
+                    width: 4096,
+                    height: 4096
+                }
+            ),
+            pw::spa::pod::property!(
+                FormatProperties::VideoFramerate,
+                Choice,
+                Range,
+                Fraction,
+                Fraction { num: fps, denom: 1 },
+                Fraction { num: 0, denom: 1 },
+                Fraction {
+                    num: 1000,
+                    denom: 1
+                }
+            ),
+        );
+        PodSerializer::serialize(Cursor::new(Vec::new()), &Value::Object(object))
+            .map(|(cursor, _)| cursor.into_inner())
+            .map_err(|e| Error::CameraError(format!("Failed to build PipeWire format: {:?}", e)))
+    }
+}
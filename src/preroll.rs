@@ -0,0 +1,91 @@
+use crate::encoder::{EncodedFeed, SharedEncodedFrame};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::oneshot;
+
+/// Longest pre-roll a stream can keep
+pub const MAX_PREROLL_SECS: u32 = 60;
+
+/// Encoded frames of the last seconds of a stream, so recordings can start before they
+/// were asked for
+/// The buffer starts on a keyframe and covers at least its duration; its frames count
+/// against the memory budget like any encoded frame in flight. Dropping it stops it.
+pub struct PrerollBuffer {
+    frames: Arc<Mutex<VecDeque<SharedEncodedFrame>>>,
+    _stop: oneshot::Sender<()>,
+}
+
+impl PrerollBuffer {
+    /// Keep the last `seconds` of the frames of `feed`
+    pub fn spawn(
+        feed: EncodedFeed,
+        mut receiver: broadcast::Receiver<SharedEncodedFrame>,
+        seconds: u32,
+    ) -> Self {
+        let frames = Arc::new(Mutex::new(VecDeque::new()));
+        let (stop_tx, mut stop) = oneshot::channel::<()>();
+        let buffer = frames.clone();
+        let duration_us = seconds as u64 * 1_000_000;
+        tokio::spawn(async move {
+            loop {
+                let received = tokio::select! {
+                    _ = &mut stop => break,
+                    received = receiver.recv() => received,
+                };
+                match received {
+                    Ok(frame) => push(&mut buffer.lock().unwrap(), frame, duration_us),
+                    Err(RecvError::Lagged(_)) => {
+                        // Frames after a gap can't be decoded, start over at a keyframe
+                        buffer.lock().unwrap().clear();
+                        feed.request_keyframe();
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Self {
+            frames,
+            _stop: stop_tx,
+        }
+    }
+
+    /// Buffered frames from the latest keyframe at least `seconds` before the newest
+    /// frame, or from the oldest one when the buffer holds less
+    pub fn snapshot(&self, seconds: u32) -> Vec<SharedEncodedFrame> {
+        let frames = self.frames.lock().unwrap();
+        let Some(newest) = frames.back() else {
+            return Vec::new();
+        };
+        let target_us = newest.pts_us.saturating_sub(seconds as u64 * 1_000_000);
+        let start = frames
+            .iter()
+            .rposition(|frame| frame.keyframe && frame.pts_us <= target_us)
+            .unwrap_or(0);
+        frames.range(start..).cloned().collect()
+    }
+}
+
+fn push(frames: &mut VecDeque<SharedEncodedFrame>, frame: SharedEncodedFrame, duration_us: u64) {
+    if frame.keyframe
+        && frames
+            .front()
+            .is_some_and(|first| (first.width, first.height) != (frame.width, frame.height))
+    {
+        // A recording can't change its frame size, the older frames are no use anymore
+        frames.clear();
+    }
+    if frames.is_empty() && !frame.keyframe {
+        return;
+    }
+    let oldest_needed_us = frame.pts_us.saturating_sub(duration_us);
+    frames.push_back(frame);
+
+    // Drop whole groups of pictures as long as the rest covers the duration
+    while let Some(next) = frames.iter().skip(1).position(|frame| frame.keyframe) {
+        if frames[next + 1].pts_us > oldest_needed_us {
+            break;
+        }
+        frames.drain(..next + 1);
+    }
+}
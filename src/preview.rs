@@ -0,0 +1,355 @@
+use crate::error::{Error, Result};
+use crate::models::{FrameEvent, PreviewRect};
+use crate::utils::nv12_to_rgba;
+use rayon::prelude::*;
+use softbuffer::{Context, Surface};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, Runtime, Url, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+
+/// Label prefix of the windows created by the plugin to show previews
+pub const PREVIEW_WINDOW_PREFIX: &str = "camera-preview-";
+/// Blank page with a transparent background, draggable, laid over the preview surface
+const PREVIEW_PAGE: &str = "data:text/html,%3Cbody%20data-tauri-drag-region%20style%3D%22margin:0;height:100vh;background:transparent%22%3E%3C/body%3E";
+
+type Presenter = Box<dyn FnMut(&PreviewImage)>;
+
+thread_local! {
+    /// Native surfaces must be used from the main thread, so presenters live there
+    static PRESENTERS: RefCell<HashMap<String, Presenter>> = RefCell::new(HashMap::new());
+}
+
+/// A frame scaled to its place in the window, packed as 0RGB pixels
+struct PreviewImage {
+    pixels: Vec<u32>,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+struct NativePreview {
+    rect: Arc<Mutex<PreviewRect>>,
+    task: JoinHandle<()>,
+}
+
+/// Streams rendered straight into a native window surface, bypassing the webview IPC
+#[derive(Default)]
+pub struct NativePreviews {
+    previews: Mutex<HashMap<String, NativePreview>>,
+}
+
+impl NativePreviews {
+    /// Render the frames of `receiver` into `window`, inside `rect` (physical pixels)
+    /// The webview must be transparent over that region for the preview to show through
+    pub async fn start<R: Runtime>(
+        &self,
+        window: WebviewWindow<R>,
+        receiver: watch::Receiver<Option<FrameEvent>>,
+        rect: PreviewRect,
+    ) -> Result<String> {
+        let preview_id = uuid::Uuid::new_v4().to_string();
+
+        let (created_tx, created_rx) = oneshot::channel();
+        let presenter_window = window.clone();
+        let presenter_id = preview_id.clone();
+        window.run_on_main_thread(move || {
+            let created = create_presenter(presenter_window).map(|presenter| {
+                PRESENTERS.with(|presenters| {
+                    presenters.borrow_mut().insert(presenter_id, presenter);
+                })
+            });
+            let _ = created_tx.send(created);
+        })?;
+        created_rx
+            .await
+            .map_err(|_| Error::CameraError("Preview surface was not created".to_string()))??;
+
+        let rect = Arc::new(Mutex::new(rect));
+        let task = tokio::spawn(render_loop(
+            window.clone(),
+            receiver,
+            preview_id.clone(),
+            rect.clone(),
+        ));
+        self.previews
+            .lock()
+            .unwrap()
+            .insert(preview_id.clone(), NativePreview { rect, task });
+
+        Ok(preview_id)
+    }
+
+    /// Move or resize a preview, e.g. to follow the layout of the page
+    pub fn set_rect(&self, preview_id: &str, rect: PreviewRect) -> Result<()> {
+        let previews = self.previews.lock().unwrap();
+        let preview = previews
+            .get(preview_id)
+            .ok_or_else(|| Error::PreviewNotFound(preview_id.to_string()))?;
+        *preview.rect.lock().unwrap() = rect;
+        Ok(())
+    }
+
+    /// Number of running previews
+    pub fn count(&self) -> usize {
+        self.previews.lock().unwrap().len()
+    }
+
+    pub fn stop<R: Runtime>(&self, app: &AppHandle<R>, preview_id: &str) -> Result<()> {
+        let preview = self
+            .previews
+            .lock()
+            .unwrap()
+            .remove(preview_id)
+            .ok_or_else(|| Error::PreviewNotFound(preview_id.to_string()))?;
+        preview.task.abort();
+
+        let preview_id = preview_id.to_string();
+        app.run_on_main_thread(move || {
+            PRESENTERS.with(|presenters| presenters.borrow_mut().remove(&preview_id));
+        })?;
+        Ok(())
+    }
+}
+
+/// Frameless window with a transparent page, for previews managed by the plugin
+/// Returns the window label with the builder
+pub fn preview_window_builder<'a, R: Runtime, M: Manager<R>>(
+    manager: &'a M,
+    title: &str,
+) -> (String, WebviewWindowBuilder<'a, R, M>) {
+    let label = format!("{}{}", PREVIEW_WINDOW_PREFIX, uuid::Uuid::new_v4());
+    let url = Url::parse(PREVIEW_PAGE).expect("valid preview page URL");
+    let builder = WebviewWindowBuilder::new(manager, &label, WebviewUrl::External(url))
+        .title(title)
+        .decorations(false)
+        .shadow(false);
+    // Transparent windows need the private API on macOS
+    #[cfg(any(not(target_os = "macos"), feature = "macos-private-api"))]
+    let builder = builder.transparent(true);
+    (label, builder)
+}
+
+/// Build the main-thread presenter drawing into the window surface
+fn create_presenter<R: Runtime>(window: WebviewWindow<R>) -> Result<Presenter> {
+    let context = Context::new(window.clone())
+        .map_err(|e| Error::CameraError(format!("Failed to create preview context: {}", e)))?;
+    let mut surface = Surface::new(&context, window.clone())
+        .map_err(|e| Error::CameraError(format!("Failed to create preview surface: {}", e)))?;
+
+    Ok(Box::new(move |image: &PreviewImage| {
+        let size = match window.inner_size() {
+            Ok(size) => size,
+            Err(e) => {
+                log::error!("Failed to get preview window size: {}", e);
+                return;
+            }
+        };
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            // Minimized
+            return;
+        };
+        if let Err(e) = surface.resize(width, height) {
+            log::error!("Failed to resize preview surface: {}", e);
+            return;
+        }
+        let mut buffer = match surface.buffer_mut() {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                log::error!("Failed to get preview buffer: {}", e);
+                return;
+            }
+        };
+        buffer.fill(0);
+        blit(&mut buffer, size.width, size.height, image);
+        if let Err(e) = buffer.present() {
+            log::error!("Failed to present preview: {}", e);
+        }
+    }))
+}
+
+/// Scale frames off the main thread and hand them to the presenter, dropping frames
+/// while the previous one is still waiting to be presented
+async fn render_loop<R: Runtime>(
+    window: WebviewWindow<R>,
+    mut receiver: watch::Receiver<Option<FrameEvent>>,
+    preview_id: String,
+    rect: Arc<Mutex<PreviewRect>>,
+) {
+    let pending = Arc::new(AtomicBool::new(false));
+
+    while receiver.changed().await.is_ok() {
+        let frame = receiver.borrow_and_update().clone();
+        // Repeated frames keep the last presented picture on screen
+        let Some(frame) = frame.filter(|frame| !frame.repeated) else {
+            continue;
+        };
+        if pending.load(Ordering::Acquire) {
+            continue;
+        }
+
+        let rect = *rect.lock().unwrap();
+        let image = match fit_frame(&frame, rect) {
+            Ok(Some(image)) => image,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Native preview stopped: {}", e);
+                break;
+            }
+        };
+
+        pending.store(true, Ordering::Release);
+        let pending = pending.clone();
+        let preview_id = preview_id.clone();
+        let presented = window.run_on_main_thread(move || {
+            PRESENTERS.with(|presenters| {
+                if let Some(present) = presenters.borrow_mut().get_mut(&preview_id) {
+                    present(&image);
+                }
+            });
+            pending.store(false, Ordering::Release);
+        });
+        if presented.is_err() {
+            // Window closed
+            break;
+        }
+    }
+}
+
+/// Convert a frame to 0RGB and scale it to fit `rect`, keeping its aspect ratio
+fn fit_frame(frame: &FrameEvent, rect: PreviewRect) -> Result<Option<PreviewImage>> {
+    if frame.width == 0 || frame.height == 0 || rect.width == 0 || rect.height == 0 {
+        return Ok(None);
+    }
+
+    let converted;
+    let rgba = if frame.format.eq_ignore_ascii_case("RGBA") {
+        &frame.data
+    } else if frame.format.eq_ignore_ascii_case("NV12") {
+        converted = nv12_to_rgba(&frame.data, frame.width, frame.height)?;
+        &converted
+    } else {
+        return Err(Error::CameraError(format!(
+            "Unsupported frame format for native preview: {}",
+            frame.format
+        )));
+    };
+
+    let scale =
+        (rect.width as f64 / frame.width as f64).min(rect.height as f64 / frame.height as f64);
+    let width = ((frame.width as f64 * scale) as u32).max(1);
+    let height = ((frame.height as f64 * scale) as u32).max(1);
+    let src_width = frame.width as usize;
+
+    // Nearest-neighbour sampling is enough for a preview and keeps 4K60 affordable
+    let mut pixels = vec![0u32; width as usize * height as usize];
+    pixels
+        .par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let src_y = (y as u64 * frame.height as u64 / height as u64) as usize;
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let src_x = (x as u64 * frame.width as u64 / width as u64) as usize;
+                let i = (src_y * src_width + src_x) * 4;
+                *pixel = (rgba[i] as u32) << 16 | (rgba[i + 1] as u32) << 8 | rgba[i + 2] as u32;
+            }
+        });
+
+    Ok(Some(PreviewImage {
+        pixels,
+        x: rect.x + ((rect.width - width) / 2) as i32,
+        y: rect.y + ((rect.height - height) / 2) as i32,
+        width,
+        height,
+    }))
+}
+
+/// Copy an image into the window buffer, clipped to its bounds
+fn blit(buffer: &mut [u32], buffer_width: u32, buffer_height: u32, image: &PreviewImage) {
+    let x0 = image.x.max(0);
+    let y0 = image.y.max(0);
+    let x1 = (image.x + image.width as i32).min(buffer_width as i32);
+    let y1 = (image.y + image.height as i32).min(buffer_height as i32);
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+
+    let len = (x1 - x0) as usize;
+    for y in y0..y1 {
+        let src = (y - image.y) as usize * image.width as usize + (x0 - image.x) as usize;
+        let dst = y as usize * buffer_width as usize + x0 as usize;
+        buffer[dst..dst + len].copy_from_slice(&image.pixels[src..src + len]);
+    }
+}
+
+/// Set the opacity of a whole window, from 0 (invisible) to 1 (opaque)
+pub fn set_window_opacity<R: Runtime>(window: &WebviewWindow<R>, opacity: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err(Error::CameraError(format!(
+            "Opacity {} is outside the range 0..=1",
+            opacity
+        )));
+    }
+
+    // Native window objects must be touched from the main thread
+    let target = window.clone();
+    window.run_on_main_thread(move || {
+        if let Err(e) = apply_window_opacity(&target, opacity) {
+            log::error!("Failed to set window opacity: {}", e);
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn apply_window_opacity<R: Runtime>(window: &WebviewWindow<R>, opacity: f64) -> Result<()> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA,
+        WS_EX_LAYERED,
+    };
+
+    let hwnd = window.hwnd()?.0 as _;
+    // SAFETY: the handle belongs to a live window owned by this process
+    unsafe {
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED as isize);
+        if SetLayeredWindowAttributes(hwnd, 0, (opacity * 255.0).round() as u8, LWA_ALPHA) == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn apply_window_opacity<R: Runtime>(window: &WebviewWindow<R>, opacity: f64) -> Result<()> {
+    use objc2::{msg_send, runtime::AnyObject};
+
+    let ns_window = window.ns_window()? as *mut AnyObject;
+    // SAFETY: the pointer is the NSWindow of a live window, used on the main thread
+    unsafe {
+        let _: () = msg_send![&*ns_window, setAlphaValue: opacity];
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn apply_window_opacity<R: Runtime>(window: &WebviewWindow<R>, opacity: f64) -> Result<()> {
+    use gtk::prelude::WidgetExt;
+
+    window.gtk_window()?.set_opacity(opacity);
+    Ok(())
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn apply_window_opacity<R: Runtime>(_window: &WebviewWindow<R>, _opacity: f64) -> Result<()> {
+    Err(Error::Unsupported(
+        "Window opacity is not supported on this platform".to_string(),
+    ))
+}
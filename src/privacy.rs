@@ -0,0 +1,278 @@
+#[cfg(target_os = "macos")]
+pub use self::macos::{camera_access, reset_camera_access};
+use crate::error::Error;
+#[cfg(not(target_os = "macos"))]
+use crate::error::Result;
+#[cfg(not(target_os = "macos"))]
+use crate::models::CameraAccess;
+use std::fmt::Display;
+
+/// OS setting keeping the app from the cameras, if any, with what to change
+/// Windows privacy switches, or the macOS authorization and what keeps its prompt from appearing.
+pub fn blocked_reason() -> Option<String> {
+    #[cfg(windows)]
+    {
+        windows::blocked_reason()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        macos::blocked_reason()
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        None
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn camera_access() -> Result<CameraAccess> {
+    Err(Error::Unsupported(
+        "Camera authorization details are only available on macOS".to_string(),
+    ))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn reset_camera_access(_identifier: &str) -> Result<()> {
+    Err(Error::Unsupported(
+        "Resetting the camera permission is only available on macOS".to_string(),
+    ))
+}
+
+/// Error for a camera that failed to open: a privacy setting when one blocks access,
+/// a driver error otherwise
+pub fn open_error(e: impl Display) -> Error {
+    match blocked_reason() {
+        Some(reason) => Error::PermissionDenied(reason),
+        None => Error::CameraError(format!("Failed to start camera preview: {}", e)),
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::System::Registry::{
+        RegGetValueW, HKEY, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ,
+    };
+
+    /// Consent store of the camera privacy settings (Settings > Privacy & security > Camera)
+    const WEBCAM_CONSENT: &str =
+        r"Software\Microsoft\Windows\CurrentVersion\CapabilityAccessManager\ConsentStore\webcam";
+
+    pub fn blocked_reason() -> Option<String> {
+        // Device-wide switch, usually set by an administrator or a policy
+        if consent(HKEY_LOCAL_MACHINE, WEBCAM_CONSENT) == Some(false) {
+            return Some(
+                "Camera access is turned off for this device (Settings > Privacy & security > Camera > Camera access)"
+                    .to_string(),
+            );
+        }
+        if consent(HKEY_CURRENT_USER, WEBCAM_CONSENT) == Some(false) {
+            return Some(
+                "Camera access is turned off for apps (Settings > Privacy & security > Camera > Let apps access your camera)"
+                    .to_string(),
+            );
+        }
+        // Tauri apps are desktop apps unless packaged as MSIX
+        let desktop_apps = format!(r"{}\NonPackaged", WEBCAM_CONSENT);
+        if consent(HKEY_CURRENT_USER, &desktop_apps) == Some(false) {
+            return Some(
+                "Camera access is turned off for desktop apps (Settings > Privacy & security > Camera > Let desktop apps access your camera)"
+                    .to_string(),
+            );
+        }
+        None
+    }
+
+    /// `Value` of a consent key: `Allow` or `Deny`, `None` when missing
+    fn consent(root: HKEY, key: &str) -> Option<bool> {
+        let key: Vec<u16> = key.encode_utf16().chain(Some(0)).collect();
+        let name: Vec<u16> = "Value".encode_utf16().chain(Some(0)).collect();
+        let mut buffer = [0u16; 16];
+        let mut size = std::mem::size_of_val(&buffer) as u32;
+        // SAFETY: the strings are null-terminated and `size` is the byte size of `buffer`
+        let status = unsafe {
+            RegGetValueW(
+                root,
+                key.as_ptr(),
+                name.as_ptr(),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return None;
+        }
+
+        let len = (size as usize / 2).min(buffer.len());
+        let value = String::from_utf16_lossy(&buffer[..len]);
+        match value.trim_end_matches('\0') {
+            "Allow" => Some(true),
+            "Deny" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use crate::error::{Error, Result};
+    use crate::models::CameraAccess;
+    use crate::PermissionStatus;
+    use objc2::runtime::{AnyClass, AnyObject};
+    use objc2::{class, msg_send};
+    use std::ffi::CStr;
+    use std::process::Command;
+
+    const CAMERA_ENTITLEMENT: &str = "com.apple.security.device.camera";
+    const SANDBOX_ENTITLEMENT: &str = "com.apple.security.app-sandbox";
+
+    pub fn blocked_reason() -> Option<String> {
+        let access = camera_access().ok()?;
+        if matches!(access.status, PermissionStatus::Granted) {
+            return None;
+        }
+        if matches!(access.status, PermissionStatus::NotDetermined) && access.can_prompt {
+            return None;
+        }
+        access.guidance.into_iter().next()
+    }
+
+    /// Authorization status plus the bundle and signature checks the prompt depends on
+    pub fn camera_access() -> Result<CameraAccess> {
+        let status = authorization_status();
+        let usage_description = has_usage_description();
+        let exe = std::env::current_exe()?;
+
+        // codesign reports the signature on stderr and exits with 1 for unsigned code
+        let display = Command::new("codesign")
+            .arg("--display")
+            .arg("--verbose=2")
+            .arg(&exe)
+            .output()?;
+        let details = String::from_utf8_lossy(&display.stderr);
+        let signed = display.status.success();
+        let ad_hoc = signed && details.contains("Signature=adhoc");
+        let hardened_runtime = signed && details.contains("(runtime)");
+
+        let entitlements = if signed {
+            let output = Command::new("codesign")
+                .args(["--display", "--entitlements", "-", "--xml"])
+                .arg(&exe)
+                .output()?;
+            String::from_utf8_lossy(&output.stdout).into_owned()
+        } else {
+            String::new()
+        };
+        let sandboxed = entitlement(&entitlements, SANDBOX_ENTITLEMENT);
+        let camera_entitlement = entitlement(&entitlements, CAMERA_ENTITLEMENT);
+        let entitled = camera_entitlement || !(hardened_runtime || sandboxed);
+
+        let mut guidance = Vec::new();
+        match status {
+            PermissionStatus::Denied => guidance.push(
+                "Camera access was denied: turn the app on in System Settings > Privacy & Security > Camera, or reset the permission to be asked again"
+                    .to_string(),
+            ),
+            PermissionStatus::Restricted => guidance.push(
+                "Camera access is restricted by a configuration profile (MDM) or Screen Time; only an administrator can allow it"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+        if !usage_description {
+            guidance.push(
+                "NSCameraUsageDescription is missing from Info.plist, so macOS never shows the permission prompt"
+                    .to_string(),
+            );
+        }
+        if !signed {
+            guidance.push(
+                "The app is not code signed, so macOS can't record a camera permission for it"
+                    .to_string(),
+            );
+        }
+        if !entitled {
+            guidance.push(format!(
+                "The app uses the hardened runtime or the sandbox without the {} entitlement, so camera access is refused without a prompt",
+                CAMERA_ENTITLEMENT
+            ));
+        }
+        if ad_hoc {
+            guidance.push(
+                "The app is ad-hoc signed, so macOS forgets the permission on every rebuild; sign it with a stable identity"
+                    .to_string(),
+            );
+        }
+
+        Ok(CameraAccess {
+            status,
+            usage_description,
+            signed,
+            ad_hoc,
+            hardened_runtime,
+            sandboxed,
+            camera_entitlement,
+            can_prompt: usage_description && signed && entitled,
+            guidance,
+        })
+    }
+
+    /// Forget the app's camera decision so the prompt appears again on the next request
+    pub fn reset_camera_access(identifier: &str) -> Result<()> {
+        let output = Command::new("tccutil")
+            .args(["reset", "Camera", identifier])
+            .output()?;
+        if !output.status.success() {
+            return Err(Error::CameraError(format!(
+                "Failed to reset the camera permission: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether an entitlements plist sets `key` to true
+    fn entitlement(plist: &str, key: &str) -> bool {
+        let Some(start) = plist.find(&format!("<key>{}</key>", key)) else {
+            return false;
+        };
+        plist[start..]
+            .split_once("</key>")
+            .is_some_and(|(_, rest)| rest.trim_start().starts_with("<true/>"))
+    }
+
+    fn ns_string(value: &CStr) -> *mut AnyObject {
+        // SAFETY: NSString copies the null-terminated UTF-8 string
+        unsafe { msg_send![class!(NSString), stringWithUTF8String: value.as_ptr()] }
+    }
+
+    fn authorization_status() -> PermissionStatus {
+        // AVFoundation is loaded by the capture backend; without it nothing was asked yet
+        let Some(device) = AnyClass::get(c"AVCaptureDevice") else {
+            return PermissionStatus::NotDetermined;
+        };
+        // AVMediaTypeVideo
+        let media_type = ns_string(c"vide");
+        // SAFETY: class method of AVCaptureDevice taking an AVMediaType
+        let status: isize =
+            unsafe { msg_send![device, authorizationStatusForMediaType: media_type] };
+        match status {
+            1 => PermissionStatus::Restricted,
+            2 => PermissionStatus::Denied,
+            3 => PermissionStatus::Granted,
+            _ => PermissionStatus::NotDetermined,
+        }
+    }
+
+    fn has_usage_description() -> bool {
+        let key = ns_string(c"NSCameraUsageDescription");
+        // SAFETY: the main bundle lives for the whole process
+        unsafe {
+            let bundle: *mut AnyObject = msg_send![class!(NSBundle), mainBundle];
+            let value: *mut AnyObject = msg_send![&*bundle, objectForInfoDictionaryKey: key];
+            !value.is_null()
+        }
+    }
+}
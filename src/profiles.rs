@@ -0,0 +1,131 @@
+use crate::error::{Error, Result};
+use crate::models::{CameraProfile, PluginConfig};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, Runtime};
+
+const PROFILES_FILE: &str = "camera-profiles.json";
+
+/// Named camera profiles per device ID, persisted in the app data directory
+pub struct ProfileStore {
+    path: PathBuf,
+    profiles: Mutex<HashMap<String, HashMap<String, CameraProfile>>>,
+    /// Profile last applied to each device, used by streams started afterwards
+    active: Mutex<HashMap<String, CameraProfile>>,
+}
+
+impl ProfileStore {
+    pub fn load<R: Runtime>(app: &AppHandle<R>) -> Result<Self> {
+        let path = app.path().app_data_dir()?.join(PROFILES_FILE);
+        let profiles = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable camera profiles {:?}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            profiles: Mutex::new(profiles),
+            active: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Profiles saved for a device, by name
+    pub fn list(&self, device_id: &str) -> HashMap<String, CameraProfile> {
+        self.profiles
+            .lock()
+            .unwrap()
+            .get(device_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, device_id: &str, name: &str) -> Result<CameraProfile> {
+        self.profiles
+            .lock()
+            .unwrap()
+            .get(device_id)
+            .and_then(|profiles| profiles.get(name))
+            .cloned()
+            .ok_or_else(|| Error::ProfileNotFound(format!("{} (device {})", name, device_id)))
+    }
+
+    /// Create or replace a profile
+    pub fn save(&self, device_id: String, name: String, profile: CameraProfile) -> Result<()> {
+        let mut profiles = self.profiles.lock().unwrap();
+        profiles.entry(device_id).or_default().insert(name, profile);
+        self.persist(&profiles)
+    }
+
+    pub fn delete(&self, device_id: &str, name: &str) -> Result<()> {
+        let mut profiles = self.profiles.lock().unwrap();
+        let device_profiles = profiles
+            .get_mut(device_id)
+            .ok_or_else(|| Error::ProfileNotFound(format!("{} (device {})", name, device_id)))?;
+        if device_profiles.remove(name).is_none() {
+            return Err(Error::ProfileNotFound(format!(
+                "{} (device {})",
+                name, device_id
+            )));
+        }
+        if device_profiles.is_empty() {
+            profiles.remove(device_id);
+        }
+        self.persist(&profiles)
+    }
+
+    /// Snapshot of every saved profile, for export
+    pub fn export(&self) -> PluginConfig {
+        PluginConfig {
+            version: PluginConfig::VERSION,
+            profiles: self.profiles.lock().unwrap().clone(),
+        }
+    }
+
+    /// Import profiles exported on another machine
+    /// With `replace`, existing profiles are dropped; otherwise imported ones override
+    /// profiles with the same device ID and name
+    pub fn import(&self, config: PluginConfig, replace: bool) -> Result<()> {
+        if config.version > PluginConfig::VERSION {
+            return Err(Error::InvalidConfig(format!(
+                "version {} is newer than the supported version {}",
+                config.version,
+                PluginConfig::VERSION
+            )));
+        }
+
+        let mut profiles = self.profiles.lock().unwrap();
+        if replace {
+            profiles.clear();
+        }
+        for (device_id, device_profiles) in config.profiles {
+            profiles
+                .entry(device_id)
+                .or_default()
+                .extend(device_profiles);
+        }
+        self.persist(&profiles)
+    }
+
+    /// Remember the profile applied to a device
+    pub fn set_active(&self, device_id: String, profile: CameraProfile) {
+        self.active.lock().unwrap().insert(device_id, profile);
+    }
+
+    /// Profile last applied to a device, if any
+    pub fn active(&self, device_id: &str) -> Option<CameraProfile> {
+        self.active.lock().unwrap().get(device_id).cloned()
+    }
+
+    fn persist(&self, profiles: &HashMap<String, HashMap<String, CameraProfile>>) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(profiles)?)?;
+        Ok(())
+    }
+}
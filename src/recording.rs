@@ -0,0 +1,1097 @@
+#[cfg(feature = "audio")]
+pub use crate::audio::AudioCapture;
+use crate::encoder::{EncodedFeed, EncodedFrame, SharedEncodedFrame};
+use crate::error::{Error, Result};
+use crate::matroska::MatroskaMuxer;
+use crate::models::{
+    RecordingContainer, RecordingOptions, RecordingProgressEvent, RecordingStopReason,
+    RecordingStoppedEvent, RecordingSummary, SegmentFinalizedEvent,
+};
+use crate::thumbnail::Thumbnail;
+use bytes::Bytes;
+use mp4::{
+    AacConfig, AudioObjectType, AvcConfig, ChannelConfig, MediaConfig, Mp4Config, Mp4Sample,
+    Mp4Writer, SampleFreqIndex, TrackConfig, TrackType,
+};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// Clock of MP4 video tracks, the usual one for video
+const TIMESCALE: u32 = 90_000;
+/// Duration given to the last frame when there is no previous one to copy (30fps)
+const DEFAULT_FRAME_US: u64 = 1_000_000 / 30;
+/// Time between two `camera://recording-progress` events
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// Samples per channel in an AAC-LC frame
+pub const AAC_FRAME: usize = 1024;
+/// Track IDs of MP4 files: the video track, then the audio track if any
+const VIDEO_TRACK: u32 = 1;
+const AUDIO_TRACK: u32 = 2;
+
+/// H.264 NAL unit types carried out of band, in the track's codec configuration
+const NAL_SPS: u8 = 7;
+const NAL_PPS: u8 = 8;
+const NAL_AUD: u8 = 9;
+
+/// NAL units of an Annex B access unit, without their start codes
+fn nal_units(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let ends: Vec<usize> = starts
+        .iter()
+        .skip(1)
+        .map(|(start_code, _)| *start_code)
+        .chain([data.len()])
+        .collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        // The zero before a 3-byte start code belongs to a 4-byte one
+        .map(move |((_, start), end)| {
+            let unit = &data[start..end];
+            unit.strip_suffix(&[0]).unwrap_or(unit)
+        })
+        .filter(|unit| !unit.is_empty())
+}
+
+/// Path of segment `index` of a recording to `path`: `clip.mp4` gives `clip-00000.mp4`, ...
+fn segment_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{:05}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}-{:05}", stem, index),
+    };
+    path.with_file_name(name)
+}
+
+/// Video track of a recording file, known from its first keyframe
+pub struct VideoTrack {
+    pub width: u32,
+    pub height: u32,
+    /// MIME type of the encoded frames, e.g. `video/h264`
+    pub mime_type: String,
+    /// H.264 parameter sets, empty for other codecs
+    pub sps: Vec<u8>,
+    pub pps: Vec<u8>,
+}
+
+/// Audio track of a recording file
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bitrate: u32,
+    /// Index of `sample_rate` in the AAC sampling frequency table
+    pub freq_index: u8,
+    /// `AudioSpecificConfig` of the AAC stream
+    pub config: Vec<u8>,
+}
+
+impl AudioTrack {
+    /// Duration of one AAC frame
+    pub fn frame_us(&self) -> u64 {
+        AAC_FRAME as u64 * 1_000_000 / self.sample_rate as u64
+    }
+}
+
+/// One encoded AAC frame
+pub struct AudioPacket {
+    /// Wall-clock time of its first sample, in microseconds since the Unix epoch
+    pub capture_time_us: u64,
+    pub data: Vec<u8>,
+}
+
+/// Microphone of a recording; builds without the `audio` feature can't capture one
+#[cfg(not(feature = "audio"))]
+pub enum AudioCapture {}
+
+#[cfg(not(feature = "audio"))]
+impl AudioCapture {
+    pub fn start(_options: &crate::models::AudioOptions) -> Result<Self> {
+        Err(Error::Unsupported(
+            "Recording sound needs the plugin built with the audio feature".to_string(),
+        ))
+    }
+
+    pub fn track(&self) -> &AudioTrack {
+        match *self {}
+    }
+}
+
+/// Track of a file a sample goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Track {
+    Video,
+    Audio,
+}
+
+/// One frame as written to a file
+pub struct Sample {
+    /// Time from the start of the file
+    pub start_us: u64,
+    pub duration_us: u64,
+    pub keyframe: bool,
+    /// Length-prefixed NAL units for H.264, the frame as encoded for other codecs and
+    /// audio
+    pub data: Vec<u8>,
+}
+
+/// Writes the container format of a recording file
+pub trait Muxer: Send {
+    fn add_tracks(&mut self, video: &VideoTrack, audio: Option<&AudioTrack>) -> Result<()>;
+
+    /// Samples of each track come in order, each with its final duration
+    fn write_sample(&mut self, track: Track, sample: Sample) -> Result<()>;
+
+    /// Write the index and flush the file
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// Codecs a container can carry
+fn container_codecs(container: RecordingContainer) -> &'static [&'static str] {
+    match container {
+        RecordingContainer::Mp4 => &["video/h264"],
+        RecordingContainer::Mkv => &["video/h264", "video/vp8", "video/vp9", "video/av1"],
+        RecordingContainer::Webm => &["video/vp8", "video/vp9", "video/av1"],
+    }
+}
+
+/// Check that recordings in `container` can carry the frames of an encoder, and AAC
+/// audio if recorded
+pub fn check_container(container: RecordingContainer, mime_type: &str, audio: bool) -> Result<()> {
+    let codecs = container_codecs(container);
+    if !codecs.contains(&mime_type) {
+        return Err(Error::CameraError(format!(
+            "{:?} recordings carry {}, the selected encoder produces {}",
+            container,
+            codecs.join(", "),
+            mime_type
+        )));
+    }
+    if audio && container == RecordingContainer::Webm {
+        return Err(Error::InvalidConfig(
+            "WebM recordings can't carry AAC audio, record to mp4 or mkv".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn create_muxer(container: RecordingContainer, path: &Path) -> Result<Box<dyn Muxer>> {
+    Ok(match container {
+        RecordingContainer::Mp4 => Box::new(Mp4Muxer::create(path)?),
+        RecordingContainer::Mkv => Box::new(MatroskaMuxer::create(path, false)?),
+        RecordingContainer::Webm => Box::new(MatroskaMuxer::create(path, true)?),
+    })
+}
+
+/// MP4 file written with the `mp4` crate; the `moov` box is written by `finish`
+struct Mp4Muxer {
+    writer: Mp4Writer<BufWriter<File>>,
+    path: PathBuf,
+    /// Clock of the audio track, its sample rate
+    audio_timescale: u32,
+}
+
+impl Mp4Muxer {
+    fn create(path: &Path) -> Result<Self> {
+        let config = Mp4Config {
+            major_brand: brand("isom"),
+            minor_version: 512,
+            compatible_brands: ["isom", "iso2", "avc1", "mp41"].map(brand).to_vec(),
+            timescale: 1000,
+        };
+        let writer = Mp4Writer::write_start(BufWriter::new(File::create(path)?), &config)
+            .map_err(|e| mp4_error(path, e))?;
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            audio_timescale: 0,
+        })
+    }
+}
+
+fn ticks(us: u64, timescale: u32) -> u64 {
+    us * timescale as u64 / 1_000_000
+}
+
+impl Muxer for Mp4Muxer {
+    fn add_tracks(&mut self, video: &VideoTrack, audio: Option<&AudioTrack>) -> Result<()> {
+        let config = TrackConfig {
+            track_type: TrackType::Video,
+            timescale: TIMESCALE,
+            language: "und".to_string(),
+            media_conf: MediaConfig::AvcConfig(AvcConfig {
+                width: video.width as u16,
+                height: video.height as u16,
+                seq_param_set: video.sps.clone(),
+                pic_param_set: video.pps.clone(),
+            }),
+        };
+        self.writer
+            .add_track(&config)
+            .map_err(|e| mp4_error(&self.path, e))?;
+        let Some(audio) = audio else {
+            return Ok(());
+        };
+
+        let freq_index =
+            SampleFreqIndex::try_from(audio.freq_index).map_err(|e| mp4_error(&self.path, e))?;
+        let chan_conf =
+            ChannelConfig::try_from(audio.channels as u8).map_err(|e| mp4_error(&self.path, e))?;
+        let config = TrackConfig {
+            track_type: TrackType::Audio,
+            timescale: audio.sample_rate,
+            language: "und".to_string(),
+            media_conf: MediaConfig::AacConfig(AacConfig {
+                bitrate: audio.bitrate,
+                profile: AudioObjectType::AacLowComplexity,
+                freq_index,
+                chan_conf,
+            }),
+        };
+        self.writer
+            .add_track(&config)
+            .map_err(|e| mp4_error(&self.path, e))?;
+        self.audio_timescale = audio.sample_rate;
+        Ok(())
+    }
+
+    fn write_sample(&mut self, track: Track, sample: Sample) -> Result<()> {
+        let (track_id, timescale) = match track {
+            Track::Video => (VIDEO_TRACK, TIMESCALE),
+            Track::Audio => (AUDIO_TRACK, self.audio_timescale),
+        };
+        // Durations from rounded start and end times, so rounding errors don't add up
+        let start_time = ticks(sample.start_us, timescale);
+        let duration = ticks(sample.start_us + sample.duration_us, timescale) - start_time;
+        self.writer
+            .write_sample(
+                track_id,
+                &Mp4Sample {
+                    start_time,
+                    duration: duration.max(1) as u32,
+                    rendering_offset: 0,
+                    is_sync: sample.keyframe,
+                    bytes: Bytes::from(sample.data),
+                },
+            )
+            .map_err(|e| mp4_error(&self.path, e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let Self {
+            mut writer, path, ..
+        } = *self;
+        writer.write_end().map_err(|e| mp4_error(&path, e))?;
+        writer.into_writer().flush()?;
+        Ok(())
+    }
+}
+
+fn brand(name: &str) -> mp4::FourCC {
+    name.parse().expect("four character brand")
+}
+
+fn mp4_error(path: &Path, e: mp4::Error) -> Error {
+    Error::CameraError(format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Recording file written from the encoded frames of a stream
+/// The video track is added on the first keyframe, with the H.264 parameter sets it
+/// carries; frames before it are dropped. Sample times follow the capture timestamps,
+/// audio included: both tracks start at the capture time of the first frame.
+pub struct Recorder {
+    muxer: Box<dyn Muxer>,
+    path: PathBuf,
+    mime_type: String,
+    audio: Option<AudioTrack>,
+    /// Picture size of the track, once added
+    size: Option<(u32, u32)>,
+    first_pts_us: u64,
+    /// Wall-clock capture time of the first frame, the origin of the audio timeline
+    first_capture_us: u64,
+    /// Timestamp of the latest frame taken in
+    last_pts_us: Option<u64>,
+    /// Time spent paused, taken out of the sample times
+    paused_us: u64,
+    /// The next frame follows a pause and goes right after the previous one
+    close_gap: bool,
+    /// Previous frame, written once the next one gives its duration
+    pending: Option<Sample>,
+    /// Same for audio packets
+    pending_audio: Option<Sample>,
+    last_duration_us: u64,
+    frames: u32,
+    /// Bytes of the frames so far
+    bytes: u64,
+}
+
+/// A finalized recording file
+#[derive(Debug, Clone)]
+pub struct RecordedFile {
+    pub path: PathBuf,
+    pub frames: u32,
+    pub duration_ms: u64,
+    /// Bytes of the frames and sound of the file, without the index
+    pub bytes: u64,
+}
+
+impl Recorder {
+    /// Create a file for frames of `mime_type` and packets of the `audio` track, checked
+    /// with `check_container`
+    pub fn create(
+        container: RecordingContainer,
+        path: &Path,
+        mime_type: &str,
+        audio: Option<AudioTrack>,
+    ) -> Result<Self> {
+        Ok(Self {
+            muxer: create_muxer(container, path)?,
+            path: path.to_path_buf(),
+            mime_type: mime_type.to_string(),
+            audio,
+            size: None,
+            first_pts_us: 0,
+            first_capture_us: 0,
+            last_pts_us: None,
+            paused_us: 0,
+            close_gap: false,
+            pending: None,
+            pending_audio: None,
+            last_duration_us: DEFAULT_FRAME_US,
+            frames: 0,
+            bytes: 0,
+        })
+    }
+
+    /// Add a frame; returns `false` when the frame size changed, which a track can't
+    /// follow: the recording has to end there
+    pub fn write(&mut self, frame: &EncodedFrame) -> Result<bool> {
+        // Frames already written, e.g. from the pre-roll, as they come in live
+        if self.last_pts_us.is_some_and(|last| frame.pts_us <= last) {
+            return Ok(true);
+        }
+        let mut sps = None;
+        let mut pps = None;
+        let data = if self.mime_type == "video/h264" {
+            let mut data = Vec::with_capacity(frame.data.len());
+            for unit in nal_units(&frame.data) {
+                match unit[0] & 0x1f {
+                    NAL_SPS => sps = Some(unit),
+                    NAL_PPS => pps = Some(unit),
+                    NAL_AUD => {}
+                    _ => {
+                        data.extend((unit.len() as u32).to_be_bytes());
+                        data.extend(unit);
+                    }
+                }
+            }
+            data
+        } else {
+            frame.data.clone()
+        };
+
+        match self.size {
+            Some(size) if size != (frame.width, frame.height) => return Ok(false),
+            Some(_) => {}
+            None => {
+                if !frame.keyframe {
+                    return Ok(true);
+                }
+                let (sps, pps) = match (sps, pps) {
+                    (Some(sps), Some(pps)) => (sps.to_vec(), pps.to_vec()),
+                    _ if self.mime_type == "video/h264" => {
+                        return Err(Error::CameraError(
+                            "The first keyframe of the recording has no SPS/PPS".to_string(),
+                        ))
+                    }
+                    _ => (Vec::new(), Vec::new()),
+                };
+                let video = VideoTrack {
+                    width: frame.width,
+                    height: frame.height,
+                    mime_type: self.mime_type.clone(),
+                    sps,
+                    pps,
+                };
+                self.muxer.add_tracks(&video, self.audio.as_ref())?;
+                self.size = Some((frame.width, frame.height));
+                self.first_pts_us = frame.pts_us;
+                self.first_capture_us = frame.capture_time_ms * 1000;
+            }
+        }
+
+        let since_first_us = frame.pts_us.saturating_sub(self.first_pts_us);
+        if std::mem::take(&mut self.close_gap) {
+            if let Some(pending) = &self.pending {
+                let resume_at = pending.start_us + self.last_duration_us;
+                self.paused_us = since_first_us.saturating_sub(resume_at);
+            }
+        }
+        let start_us = since_first_us.saturating_sub(self.paused_us);
+        if let Some(mut pending) = self.pending.take() {
+            // Frames dropped in between extend the previous one, so playback keeps the
+            // capture pace
+            pending.duration_us = start_us.saturating_sub(pending.start_us).max(1);
+            self.write_sample(pending)?;
+        }
+        self.last_pts_us = Some(frame.pts_us);
+        self.bytes += data.len() as u64;
+        self.pending = Some(Sample {
+            start_us,
+            duration_us: 0,
+            keyframe: frame.keyframe,
+            data,
+        });
+        Ok(true)
+    }
+
+    /// Add an AAC packet of the audio track; packets captured before the first frame, or
+    /// before the first frame after a pause, are dropped
+    pub fn write_audio(&mut self, packet: &AudioPacket) -> Result<()> {
+        if self.audio.is_none() || self.size.is_none() || self.close_gap {
+            return Ok(());
+        }
+        let Some(start_us) = packet
+            .capture_time_us
+            .checked_sub(self.first_capture_us)
+            .and_then(|since_first_us| since_first_us.checked_sub(self.paused_us))
+        else {
+            return Ok(());
+        };
+        if let Some(mut pending) = self.pending_audio.take() {
+            if start_us <= pending.start_us {
+                self.pending_audio = Some(pending);
+                return Ok(());
+            }
+            // Like frames, packets stretch over the ones the device dropped
+            pending.duration_us = start_us - pending.start_us;
+            self.muxer.write_sample(Track::Audio, pending)?;
+        }
+        self.bytes += packet.data.len() as u64;
+        self.pending_audio = Some(Sample {
+            start_us,
+            duration_us: 0,
+            keyframe: true,
+            data: packet.data.clone(),
+        });
+        Ok(())
+    }
+
+    /// Resume after a pause: the next frame follows the previous one without a gap
+    pub fn close_gap(&mut self) {
+        self.close_gap = true;
+    }
+
+    /// Bytes of the frames written so far, without the index
+    pub fn size(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Frames written so far; the latest one is only written with the next one
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Time from the first frame to the latest one
+    pub fn elapsed_us(&self) -> u64 {
+        self.pending.as_ref().map_or(0, |pending| pending.start_us)
+    }
+
+    fn write_sample(&mut self, sample: Sample) -> Result<()> {
+        self.last_duration_us = sample.duration_us;
+        self.muxer.write_sample(Track::Video, sample)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Write the last frame and the index; a file without any frame is removed
+    pub fn finish(mut self) -> Result<RecordedFile> {
+        if let Some(mut pending) = self.pending.take() {
+            pending.duration_us = self.last_duration_us;
+            let end_us = pending.start_us + pending.duration_us;
+            self.write_sample(pending)?;
+            if let (Some(mut pending), Some(audio)) = (self.pending_audio.take(), &self.audio) {
+                pending.duration_us = audio.frame_us();
+                self.muxer.write_sample(Track::Audio, pending)?;
+            }
+            self.muxer.finish()?;
+            return Ok(RecordedFile {
+                path: self.path,
+                frames: self.frames,
+                duration_ms: end_us / 1000,
+                bytes: self.bytes,
+            });
+        }
+
+        drop(self.muxer);
+        std::fs::remove_file(&self.path)?;
+        Err(Error::CameraError(format!(
+            "No frame was recorded to {}",
+            self.path.display()
+        )))
+    }
+}
+
+/// Size or duration after which a recording goes on in a new file
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentLimits {
+    duration_us: Option<u64>,
+    max_bytes: Option<u64>,
+}
+
+impl SegmentLimits {
+    /// Limits set by the options, `None` to record a single file
+    pub fn new(options: &RecordingOptions) -> Result<Option<Self>> {
+        if options.segment_duration_secs == Some(0) || options.segment_max_bytes == Some(0) {
+            return Err(Error::InvalidConfig(
+                "Segment limits must be positive".to_string(),
+            ));
+        }
+        if options.segment_duration_secs.is_none() && options.segment_max_bytes.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            duration_us: options
+                .segment_duration_secs
+                .map(|secs| secs as u64 * 1_000_000),
+            max_bytes: options.segment_max_bytes,
+        }))
+    }
+}
+
+/// Space left on the disk of `path`, for the recording about to be written there
+pub fn free_space(path: &Path) -> Result<u64> {
+    Ok(fs2::available_space(path.parent().unwrap_or(path))?)
+}
+
+/// Check there is room for a recording to `path` before starting it
+pub fn check_free_space(path: &Path, min_free_bytes: u64) -> Result<()> {
+    if min_free_bytes == 0 {
+        return Ok(());
+    }
+    let free = free_space(path)?;
+    if free < min_free_bytes {
+        return Err(Error::DiskFull(format!(
+            "{} MB free for {}, recordings keep {} MB",
+            free / (1024 * 1024),
+            path.display(),
+            min_free_bytes / (1024 * 1024)
+        )));
+    }
+    Ok(())
+}
+
+/// Files of a recording: the requested path, or numbered segments rotated at keyframes
+pub struct RecordingOutput {
+    path: PathBuf,
+    container: RecordingContainer,
+    mime_type: String,
+    audio: Option<AudioTrack>,
+    limits: Option<SegmentLimits>,
+    recorder: Recorder,
+    /// Segments finalized so far
+    files: Vec<RecordedFile>,
+    thumbnail: Option<Thumbnail>,
+    /// Free space the recording leaves on its disk, 0 to fill it up
+    min_free_bytes: u64,
+}
+
+impl RecordingOutput {
+    pub fn create(
+        path: &Path,
+        container: RecordingContainer,
+        mime_type: &str,
+        audio: Option<AudioTrack>,
+        limits: Option<SegmentLimits>,
+    ) -> Result<Self> {
+        let first = match limits {
+            Some(_) => segment_path(path, 0),
+            None => path.to_path_buf(),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            container,
+            mime_type: mime_type.to_string(),
+            audio: audio.clone(),
+            limits,
+            recorder: Recorder::create(container, &first, mime_type, audio)?,
+            files: Vec::new(),
+            thumbnail: None,
+            min_free_bytes: 0,
+        })
+    }
+
+    /// Stop the recording before the free space of its disk falls under `min_free_bytes`
+    pub fn with_disk_guard(mut self, min_free_bytes: u64) -> Self {
+        self.min_free_bytes = min_free_bytes;
+        self
+    }
+
+    /// Make a thumbnail of the recording once it stops
+    pub fn with_thumbnail(mut self, thumbnail: Thumbnail) -> Self {
+        self.thumbnail = Some(thumbnail);
+        self
+    }
+
+    /// Add a frame to the current file, see `Recorder::write`
+    fn write(&mut self, frame: &EncodedFrame) -> Result<bool> {
+        if !self.recorder.write(frame)? {
+            return Ok(false);
+        }
+        // Frames already written or before the first keyframe aren't taken in
+        let taken = self.recorder.last_pts_us == Some(frame.pts_us);
+        if frame.keyframe && taken {
+            let at_ms = self.elapsed_us() / 1000;
+            if let Some(thumbnail) = &mut self.thumbnail {
+                thumbnail.offer(at_ms, &frame.data);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Recorded time across segments
+    fn elapsed_us(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|file| file.duration_ms * 1000)
+            .sum::<u64>()
+            + self.recorder.elapsed_us()
+    }
+
+    /// Write frames kept from before the recording started, ahead of the live ones
+    pub fn write_preroll(&mut self, frames: &[SharedEncodedFrame]) -> Result<()> {
+        for frame in frames {
+            if !self.write(frame)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the current segment reached a limit; it is rotated at the next keyframe
+    fn segment_due(&self) -> bool {
+        let Some(limits) = self.limits else {
+            return false;
+        };
+        limits
+            .duration_us
+            .is_some_and(|max| self.recorder.elapsed_us() >= max)
+            || limits
+                .max_bytes
+                .is_some_and(|max| self.recorder.size() >= max)
+    }
+
+    /// Finalize the current segment and go on in the next one; returns the segment index
+    fn rotate(&mut self) -> Result<usize> {
+        let next = Recorder::create(
+            self.container,
+            &segment_path(&self.path, self.files.len() + 1),
+            &self.mime_type,
+            self.audio.clone(),
+        )?;
+        let file = std::mem::replace(&mut self.recorder, next).finish()?;
+        self.files.push(file);
+        Ok(self.files.len() - 1)
+    }
+
+    /// Progress of the recording across its segments
+    fn progress(
+        &self,
+        recording_id: &str,
+        dropped_frames: u64,
+        bitrate: u64,
+        paused: bool,
+    ) -> RecordingProgressEvent {
+        RecordingProgressEvent {
+            recording_id: recording_id.to_string(),
+            elapsed_ms: self.elapsed_us() / 1000,
+            bytes: self.bytes(),
+            frames: self.files.iter().map(|file| file.frames).sum::<u32>() + self.recorder.frames(),
+            dropped_frames,
+            bitrate,
+            paused,
+        }
+    }
+
+    /// Whether the disk of the recording has less free space left than the guard keeps
+    fn disk_full(&self) -> bool {
+        if self.min_free_bytes == 0 {
+            return false;
+        }
+        match free_space(&self.path) {
+            Ok(free) => free < self.min_free_bytes,
+            Err(e) => {
+                log::warn!(
+                    "Failed to check the free space for {}: {}",
+                    self.path.display(),
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Stop reason of a recording that failed with `error`; a failed write on a disk that
+    /// filled up anyway is reported as such
+    fn failure(&self, error: Error) -> (RecordingStopReason, Option<String>) {
+        let reason = if self.disk_full() {
+            RecordingStopReason::DiskFull
+        } else {
+            RecordingStopReason::Failed
+        };
+        (reason, Some(error.to_string()))
+    }
+
+    fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum::<u64>() + self.recorder.size()
+    }
+
+    /// Finalize the current file, then write the thumbnail; returns every file of the
+    /// recording, and the thumbnail if one could be made
+    fn finish(self) -> Result<(Vec<RecordedFile>, Option<PathBuf>)> {
+        let mut files = self.files;
+        files.push(self.recorder.finish()?);
+        let thumbnail = self
+            .thumbnail
+            .and_then(|thumbnail| thumbnail.write(&self.path));
+        Ok((files, thumbnail))
+    }
+
+    /// Close and remove the files of a recording that failed before it started
+    pub fn discard(self) {
+        let mut paths: Vec<PathBuf> = self.files.into_iter().map(|file| file.path).collect();
+        paths.push(self.recorder.path.clone());
+        drop(self.recorder);
+        for path in paths {
+            if let Err(e) = std::fs::remove_file(&path) {
+                log::warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Report a finalized segment on `camera://segment-finalized`
+fn emit_segment<R: Runtime>(
+    app: &AppHandle<R>,
+    recording_id: &str,
+    index: usize,
+    file: &RecordedFile,
+) {
+    let event = SegmentFinalizedEvent {
+        recording_id: recording_id.to_string(),
+        index: index as u32,
+        path: file.path.clone(),
+        frames: file.frames,
+        duration_ms: file.duration_ms,
+    };
+    if let Err(e) = app.emit("camera://segment-finalized", event) {
+        log::warn!("Failed to emit segment-finalized: {}", e);
+    }
+}
+
+pub struct Recording {
+    pub device_id: String,
+    /// Started for the recording, stopped with it unless other sinks use it
+    pub owned_stream: Option<String>,
+    /// Frames are dropped while set, the file stays open
+    paused: Arc<AtomicBool>,
+    /// Resolves once the file is finalized
+    pub task: JoinHandle<Result<RecordingSummary>>,
+}
+
+/// Running recordings, by ID
+#[derive(Default)]
+pub struct Recordings {
+    running: Mutex<HashMap<String, Recording>>,
+}
+
+impl Recordings {
+    /// Write the encoded frames of `feed`, and the packets of `audio`, to `output` until
+    /// `stop` fires or the stream ends
+    #[allow(clippy::too_many_arguments)]
+    pub fn start<R: Runtime>(
+        &self,
+        app: AppHandle<R>,
+        recording_id: String,
+        device_id: String,
+        owned_stream: Option<String>,
+        output: RecordingOutput,
+        feed: EncodedFeed,
+        receiver: broadcast::Receiver<SharedEncodedFrame>,
+        audio: Option<AudioCapture>,
+        stop: oneshot::Receiver<()>,
+    ) {
+        let paused = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn(run(
+            app,
+            recording_id.clone(),
+            output,
+            feed,
+            receiver,
+            audio,
+            paused.clone(),
+            stop,
+        ));
+        self.running.lock().unwrap().insert(
+            recording_id,
+            Recording {
+                device_id,
+                owned_stream,
+                paused,
+                task,
+            },
+        );
+    }
+
+    /// Pause or resume a recording; a resumed recording goes on in the same file, with
+    /// no gap in its timeline
+    pub fn set_paused(&self, recording_id: &str, paused: bool) -> Result<()> {
+        self.running
+            .lock()
+            .unwrap()
+            .get(recording_id)
+            .ok_or_else(|| Error::CameraError(format!("Recording not found: {}", recording_id)))?
+            .paused
+            .store(paused, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Forget a recording, to stop it and wait for its file
+    pub fn remove(&self, recording_id: &str) -> Result<Recording> {
+        self.running
+            .lock()
+            .unwrap()
+            .remove(recording_id)
+            .ok_or_else(|| Error::CameraError(format!("Recording not found: {}", recording_id)))
+    }
+}
+
+/// Next packet of the microphone, never for recordings without one
+async fn next_audio_packet(audio: &mut Option<AudioCapture>) -> Option<AudioPacket> {
+    match audio {
+        #[cfg(feature = "audio")]
+        Some(audio) => audio.packets.recv().await,
+        #[cfg(not(feature = "audio"))]
+        Some(audio) => match *audio {},
+        None => std::future::pending().await,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run<R: Runtime>(
+    app: AppHandle<R>,
+    recording_id: String,
+    mut output: RecordingOutput,
+    feed: EncodedFeed,
+    mut receiver: broadcast::Receiver<SharedEncodedFrame>,
+    mut audio: Option<AudioCapture>,
+    paused: Arc<AtomicBool>,
+    mut stop: oneshot::Receiver<()>,
+) -> Result<RecordingSummary> {
+    // After a gap, frames are dropped until the next keyframe so the file stays decodable
+    let mut resync = false;
+    let mut was_paused = false;
+    let mut dropped_frames = 0;
+    // Why the recording ended without `stop_recording`, and the failure if any
+    let mut ended = None;
+    let mut progress =
+        tokio::time::interval_at(Instant::now() + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+    let mut last_progress = (Instant::now(), 0);
+    loop {
+        let received = tokio::select! {
+            stopped = &mut stop => {
+                // The stop handle is dropped with the stream
+                if stopped.is_err() {
+                    ended = Some((RecordingStopReason::StreamEnded, None));
+                }
+                break;
+            }
+            received = receiver.recv() => received,
+            _ = progress.tick() => {
+                let (last_at, last_bytes) = last_progress;
+                let bytes = output.bytes();
+                let elapsed_ms = last_at.elapsed().as_millis().max(1) as u64;
+                let bitrate = (bytes - last_bytes) * 8 * 1000 / elapsed_ms;
+                last_progress = (Instant::now(), bytes);
+                let event = output.progress(
+                    &recording_id,
+                    dropped_frames,
+                    bitrate,
+                    paused.load(Ordering::Relaxed),
+                );
+                if let Err(e) = app.emit("camera://recording-progress", event) {
+                    log::warn!("Failed to emit recording-progress: {}", e);
+                }
+                if output.disk_full() {
+                    log::warn!("Disk almost full, recording {} stops", recording_id);
+                    ended = Some((RecordingStopReason::DiskFull, None));
+                    break;
+                }
+                continue;
+            }
+            packet = next_audio_packet(&mut audio) => {
+                match packet {
+                    // Sound is left out wherever frames are, until the timeline resumes
+                    Some(_) if paused.load(Ordering::Relaxed) || was_paused || resync => {}
+                    Some(packet) => {
+                        if let Err(e) = output.recorder.write_audio(&packet) {
+                            log::error!("Recording {} failed: {}", recording_id, e);
+                            ended = Some(output.failure(e));
+                            break;
+                        }
+                    }
+                    None => {
+                        log::warn!(
+                            "The microphone of recording {} stopped, the recording goes on without sound",
+                            recording_id
+                        );
+                        audio = None;
+                    }
+                }
+                continue;
+            }
+        };
+        match received {
+            Ok(encoded) => {
+                if paused.load(Ordering::Relaxed) {
+                    was_paused = true;
+                    continue;
+                }
+                if std::mem::take(&mut was_paused) {
+                    output.recorder.close_gap();
+                    resync = true;
+                    feed.request_keyframe();
+                }
+                if resync && !encoded.keyframe {
+                    dropped_frames += 1;
+                    continue;
+                }
+                resync = false;
+                if output.segment_due() {
+                    if !encoded.keyframe {
+                        // Segments start on a keyframe so each file plays on its own
+                        feed.request_keyframe();
+                    } else {
+                        match output.rotate() {
+                            Ok(index) => {
+                                emit_segment(&app, &recording_id, index, &output.files[index])
+                            }
+                            Err(e) => {
+                                log::error!("Recording {} failed: {}", recording_id, e);
+                                ended = Some(output.failure(e));
+                                break;
+                            }
+                        }
+                    }
+                }
+                match output.write(&encoded) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log::warn!("Frame size changed, recording {} ends there", recording_id);
+                        ended = Some((RecordingStopReason::FrameSizeChanged, None));
+                        break;
+                    }
+                    Err(e) => {
+                        log::error!("Recording {} failed: {}", recording_id, e);
+                        ended = Some(output.failure(e));
+                        break;
+                    }
+                }
+            }
+            Err(RecvError::Lagged(skipped)) => {
+                log::warn!(
+                    "Recording {} fell behind, skipped {} frames",
+                    recording_id,
+                    skipped
+                );
+                dropped_frames += skipped;
+                resync = true;
+                feed.request_keyframe();
+            }
+            Err(RecvError::Closed) => {
+                ended = Some((RecordingStopReason::StreamEnded, None));
+                break;
+            }
+        }
+    }
+
+    let segmented = output.limits.is_some();
+    let finished = tokio::task::spawn_blocking(move || output.finish())
+        .await
+        .map_err(|e| Error::CameraError(format!("Recording task failed: {}", e)))?;
+    // Reported once the file is complete, so the app can get it with `stop_recording`
+    if let Some((reason, error)) = ended {
+        let event = RecordingStoppedEvent {
+            recording_id: recording_id.clone(),
+            reason,
+            error: error.or_else(|| finished.as_ref().err().map(|e| e.to_string())),
+        };
+        if let Err(e) = app.emit("camera://recording-stopped", event) {
+            log::warn!("Failed to emit recording-stopped: {}", e);
+        }
+    }
+    let (files, thumbnail) = finished?;
+    let last = &files[files.len() - 1];
+    if segmented {
+        emit_segment(&app, &recording_id, files.len() - 1, last);
+    }
+    Ok(RecordingSummary {
+        recording_id,
+        path: last.path.clone(),
+        frames: files.iter().map(|file| file.frames).sum(),
+        duration_ms: files.iter().map(|file| file.duration_ms).sum(),
+        segments: if segmented {
+            files.iter().map(|file| file.path.clone()).collect()
+        } else {
+            Vec::new()
+        },
+        thumbnail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nal_units() {
+        let data = [
+            0, 0, 0, 1, 0x67, 1, 2, 0, 0, 1, 0x68, 3, 0, 0, 0, 1, 0x65, 4, 5, 0,
+        ];
+        let units: Vec<&[u8]> = nal_units(&data).collect();
+        assert_eq!(
+            units,
+            [&[0x67, 1, 2][..], &[0x68, 3][..], &[0x65, 4, 5][..]]
+        );
+    }
+
+    #[test]
+    fn test_segment_path() {
+        assert_eq!(
+            segment_path(Path::new("/videos/dashcam.mp4"), 3),
+            Path::new("/videos/dashcam-00003.mp4")
+        );
+        assert_eq!(
+            segment_path(Path::new("/videos/dashcam"), 0),
+            Path::new("/videos/dashcam-00000")
+        );
+    }
+}
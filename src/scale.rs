@@ -0,0 +1,143 @@
+use crate::error::{Error, Result};
+use fast_image_resize::images::{Image, ImageRef};
+use fast_image_resize::{FilterType, PixelType, ResizeAlg, ResizeOptions, Resizer};
+
+/// Size of a `width`x`height` frame scaled down to fit the targets, keeping its aspect ratio
+/// and even dimensions for 4:2:0 chroma; `None` when it already fits
+pub fn target_size(
+    width: u32,
+    height: u32,
+    target_width: Option<u32>,
+    target_height: Option<u32>,
+) -> Option<(u32, u32)> {
+    let factor = |target: Option<u32>, size: u32| target.map_or(1.0, |t| t as f64 / size as f64);
+    let scale = factor(target_width, width).min(factor(target_height, height));
+    if scale >= 1.0 {
+        return None;
+    }
+    let scaled = |size: u32| (((size as f64 * scale).round() as u32) & !1).max(2);
+    Some((scaled(width), scaled(height)))
+}
+
+/// Downscaling of a stream's frames, keeping the resizer's buffers between frames
+pub struct Scaler {
+    resizer: Resizer,
+    /// Digital zoom: the center `1 / zoom` of each frame is scaled instead of the whole frame
+    zoom: f64,
+}
+
+impl Default for Scaler {
+    fn default() -> Self {
+        Self {
+            resizer: Resizer::new(),
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Scaler {
+    pub fn set_zoom(&mut self, zoom: f64) {
+        self.zoom = zoom.max(1.0);
+    }
+
+    pub fn zoomed(&self) -> bool {
+        self.zoom > 1.0
+    }
+
+    pub fn rgba(&mut self, data: &[u8], size: (u32, u32), target: (u32, u32)) -> Result<Vec<u8>> {
+        self.plane(data, size, target, PixelType::U8x4)
+    }
+
+    /// Scale the luma plane and the interleaved chroma plane of an NV12 frame separately
+    pub fn nv12(&mut self, data: &[u8], size: (u32, u32), target: (u32, u32)) -> Result<Vec<u8>> {
+        let luma_len = (size.0 * size.1) as usize;
+        if data.len() < luma_len * 3 / 2 {
+            return Err(Error::CameraError(format!(
+                "Invalid NV12 buffer: {}x{}, got {} bytes",
+                size.0,
+                size.1,
+                data.len()
+            )));
+        }
+        let (luma, chroma) = data.split_at(luma_len);
+        let mut scaled = self.plane(luma, size, target, PixelType::U8)?;
+        scaled.extend(self.plane(
+            &chroma[..luma_len / 2],
+            (size.0 / 2, size.1 / 2),
+            (target.0 / 2, target.1 / 2),
+            PixelType::U8x2,
+        )?);
+        Ok(scaled)
+    }
+
+    fn plane(
+        &mut self,
+        data: &[u8],
+        (width, height): (u32, u32),
+        (target_width, target_height): (u32, u32),
+        pixel_type: PixelType,
+    ) -> Result<Vec<u8>> {
+        let source = ImageRef::new(width, height, data, pixel_type)
+            .map_err(|e| Error::CameraError(format!("Invalid frame to scale: {}", e)))?;
+        let mut scaled = Image::new(target_width, target_height, pixel_type);
+        // Bilinear convolution: smooth at any ratio, and fast enough for every frame
+        let mut options =
+            ResizeOptions::new().resize_alg(ResizeAlg::Convolution(FilterType::Bilinear));
+        if self.zoomed() {
+            let (crop_width, crop_height) = (width as f64 / self.zoom, height as f64 / self.zoom);
+            options = options.crop(
+                (width as f64 - crop_width) / 2.0,
+                (height as f64 - crop_height) / 2.0,
+                crop_width,
+                crop_height,
+            );
+        }
+        self.resizer
+            .resize(&source, &mut scaled, &options)
+            .map_err(|e| Error::CameraError(format!("Failed to scale frame: {}", e)))?;
+        Ok(scaled.into_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_size() {
+        assert_eq!(target_size(1920, 1080, Some(320), None), Some((320, 180)));
+        assert_eq!(
+            target_size(1920, 1080, Some(320), Some(120)),
+            Some((212, 120))
+        );
+        assert_eq!(target_size(640, 480, Some(1280), None), None);
+        assert_eq!(target_size(640, 480, None, None), None);
+    }
+
+    #[test]
+    fn test_scale_keeps_uniform_color() {
+        let mut scaler = Scaler::default();
+        let rgba = [10, 20, 30, 255].repeat(8 * 8);
+        let scaled = scaler.rgba(&rgba, (8, 8), (4, 4)).unwrap();
+        assert_eq!(scaled, [10, 20, 30, 255].repeat(4 * 4));
+
+        let nv12 = [[50; 64].as_slice(), [128, 64].repeat(16).as_slice()].concat();
+        let scaled = scaler.nv12(&nv12, (8, 8), (4, 4)).unwrap();
+        assert_eq!(scaled.len(), 4 * 4 * 3 / 2);
+        assert!(scaled[..16].iter().all(|&y| y == 50));
+        assert!(scaled[16..].chunks(2).all(|uv| uv == [128, 64]));
+    }
+
+    #[test]
+    fn test_zoom_scales_the_center() {
+        let mut scaler = Scaler::default();
+        scaler.set_zoom(2.0);
+        // Bright center on a black border, wider than the 4x4 crop for the filter's support
+        let mut luma = vec![0u8; 8 * 8];
+        for y in 1..7 {
+            luma[y * 8 + 1..y * 8 + 7].fill(200);
+        }
+        let zoomed = scaler.plane(&luma, (8, 8), (8, 8), PixelType::U8).unwrap();
+        assert!(zoomed.iter().all(|&y| y == 200));
+    }
+}
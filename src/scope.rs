@@ -0,0 +1,107 @@
+use crate::error::{Error, Result};
+use crate::models::Config;
+use std::path::{Component, Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Directories the plugin may write recordings, snapshots and frame dumps to, so that
+/// granting the camera capability doesn't grant arbitrary disk writes
+pub struct OutputScope {
+    allowed: Vec<PathBuf>,
+}
+
+impl OutputScope {
+    /// `Config::output_dirs` when set, otherwise the app's data and cache directories
+    /// and the user's video and picture directories
+    pub fn new<R: Runtime>(app: &AppHandle<R>, config: &Config) -> Self {
+        if !config.output_dirs.is_empty() {
+            return Self::from_dirs(config.output_dirs.clone());
+        }
+        let paths = app.path();
+        let defaults = [
+            paths.app_data_dir(),
+            paths.app_cache_dir(),
+            paths.video_dir(),
+            paths.picture_dir(),
+        ];
+        Self::from_dirs(defaults.into_iter().filter_map(|dir| dir.ok()).collect())
+    }
+
+    fn from_dirs(dirs: Vec<PathBuf>) -> Self {
+        let allowed = dirs
+            .iter()
+            .filter_map(|dir| {
+                let normalized = normalize(dir);
+                if normalized.is_none() {
+                    log::warn!("Ignoring relative camera output directory {:?}", dir);
+                }
+                normalized.map(|dir| resolve_links(&dir))
+            })
+            .collect();
+        Self { allowed }
+    }
+
+    /// Check a path the plugin is asked to write to; returns it normalized
+    pub fn check(&self, path: &Path) -> Result<PathBuf> {
+        let normalized = normalize(path).ok_or_else(|| {
+            Error::AccessDenied(format!("{} is not an absolute path", path.display()))
+        })?;
+        let resolved = resolve_links(&normalized);
+        if self.allowed.iter().any(|dir| resolved.starts_with(dir)) {
+            Ok(normalized)
+        } else {
+            Err(Error::AccessDenied(format!(
+                "{} is outside the allowed output directories",
+                path.display()
+            )))
+        }
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem; `None` for relative paths
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    Some(normalized)
+}
+
+/// Follow symlinks in the longest existing part of the path, so a link inside an
+/// allowed directory can't redirect a write outside of it
+fn resolve_links(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(real) = ancestor.canonicalize() {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return real.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_scope_rejects_escapes() {
+        let root = std::env::temp_dir().join("camera-scope-test");
+        let allowed = root.join("videos");
+        std::fs::create_dir_all(&allowed).unwrap();
+        let scope = OutputScope::from_dirs(vec![allowed.clone()]);
+
+        assert!(scope.check(&allowed.join("clip.mp4")).is_ok());
+        assert!(scope.check(&allowed.join("new/dir/clip.mp4")).is_ok());
+        assert!(scope.check(&allowed.join("../clip.mp4")).is_err());
+        assert!(scope.check(&root.join("videos-other/clip.mp4")).is_err());
+        assert!(scope.check(Path::new("clip.mp4")).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use crabcamera::commands::advanced::set_camera_controls;
+use crabcamera::types::CameraControls;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Camera controls applied to each device during this session
+/// Drivers reset controls when a camera is closed; they are applied again each time the
+/// device is opened, for photos, reconnections or a new stream, until the app exits.
+#[derive(Default)]
+pub struct SessionControls {
+    applied: Mutex<HashMap<String, CameraControls>>,
+}
+
+impl SessionControls {
+    /// Record controls successfully applied to a device, over the ones set before
+    pub fn remember(&self, device_id: &str, controls: CameraControls) {
+        let mut applied = self.applied.lock().unwrap();
+        let current = applied.entry(device_id.to_string()).or_default();
+        merge(current, controls);
+    }
+
+    /// Controls applied to a device this session, if any
+    pub fn get(&self, device_id: &str) -> Option<CameraControls> {
+        self.applied.lock().unwrap().get(device_id).cloned()
+    }
+
+    /// Apply the remembered controls to a device that was just opened
+    pub async fn restore(&self, device_id: &str) {
+        let Some(controls) = self.get(device_id) else {
+            return;
+        };
+        if let Err(e) = set_camera_controls(device_id.to_string(), controls).await {
+            log::warn!("Failed to restore the controls of {}: {}", device_id, e);
+        }
+    }
+}
+
+/// Overwrite the controls of `current` that `update` sets
+fn merge(current: &mut CameraControls, update: CameraControls) {
+    macro_rules! take {
+        ($($field:ident),*) => {
+            $(
+                if update.$field.is_some() {
+                    current.$field = update.$field;
+                }
+            )*
+        };
+    }
+    take!(
+        white_balance,
+        auto_exposure,
+        exposure_time,
+        auto_focus,
+        focus_distance,
+        zoom,
+        brightness,
+        contrast,
+        saturation,
+        sharpness,
+        iso_sensitivity
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crabcamera::types::WhiteBalance;
+
+    #[test]
+    fn test_later_controls_win() {
+        let controls = SessionControls::default();
+        controls.remember(
+            "0",
+            CameraControls {
+                white_balance: Some(WhiteBalance::Daylight),
+                auto_exposure: Some(true),
+                ..Default::default()
+            },
+        );
+        controls.remember(
+            "0",
+            CameraControls {
+                white_balance: Some(WhiteBalance::Custom(4000)),
+                ..Default::default()
+            },
+        );
+        let applied = controls.get("0").unwrap();
+        assert!(matches!(
+            applied.white_balance,
+            Some(WhiteBalance::Custom(4000))
+        ));
+        assert_eq!(applied.auto_exposure, Some(true));
+        assert!(controls.get("1").is_none());
+    }
+}
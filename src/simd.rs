@@ -0,0 +1,316 @@
+//! Hand-written SIMD kernels for the conversion paths the `yuv` crate doesn't cover.
+//! The kernels are picked once from the CPU features detected at runtime, so portable
+//! release builds (without `-C target-cpu=native`) still get AVX2/AVX-512/NEON speed.
+
+use std::sync::OnceLock;
+
+type DeinterleaveKernel = fn(&[u8], &mut [u8], &mut [u8]);
+type RowPairKernel = fn(&[u8], &[u8], &mut [u8], &mut [u8], &mut [u8]);
+
+/// Kernels selected for this CPU
+struct Kernels {
+    name: &'static str,
+    deinterleave_uv: DeinterleaveKernel,
+    yuy2_row_pair: RowPairKernel,
+}
+
+fn kernels() -> &'static Kernels {
+    static KERNELS: OnceLock<Kernels> = OnceLock::new();
+    KERNELS.get_or_init(detect)
+}
+
+fn detect() -> Kernels {
+    #[cfg(target_arch = "x86_64")]
+    {
+        #[cfg(feature = "avx512")]
+        if is_x86_feature_detected!("avx512f") && is_x86_feature_detected!("avx512bw") {
+            return Kernels {
+                name: "avx512",
+                deinterleave_uv: x86::deinterleave_uv_avx512,
+                yuy2_row_pair: yuy2_row_pair_to_nv12_scalar,
+            };
+        }
+        if is_x86_feature_detected!("avx2") {
+            return Kernels {
+                name: "avx2",
+                deinterleave_uv: x86::deinterleave_uv_avx2,
+                yuy2_row_pair: yuy2_row_pair_to_nv12_scalar,
+            };
+        }
+    }
+    // NEON is part of the aarch64 baseline (Apple Silicon, Android)
+    #[cfg(target_arch = "aarch64")]
+    {
+        return Kernels {
+            name: "neon",
+            deinterleave_uv: arm::deinterleave_uv_neon,
+            yuy2_row_pair: arm::yuy2_row_pair_to_nv12_neon,
+        };
+    }
+    #[allow(unreachable_code)]
+    Kernels {
+        name: "scalar",
+        deinterleave_uv: deinterleave_uv_scalar,
+        yuy2_row_pair: yuy2_row_pair_to_nv12_scalar,
+    }
+}
+
+/// Instruction set of the kernels in use: `avx512`, `avx2`, `neon` or `scalar`
+pub fn kernel_name() -> &'static str {
+    kernels().name
+}
+
+/// SIMD features of this CPU relevant to the conversions, detected at runtime
+pub fn cpu_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        for (feature, detected) in [
+            ("sse4.1", is_x86_feature_detected!("sse4.1")),
+            ("avx2", is_x86_feature_detected!("avx2")),
+            ("avx512bw", is_x86_feature_detected!("avx512bw")),
+        ] {
+            if detected {
+                features.push(feature);
+            }
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        features.push("neon");
+        if std::arch::is_aarch64_feature_detected!("rdm") {
+            features.push("rdm");
+        }
+    }
+    features
+}
+
+/// Split an interleaved UV plane (NV12 chroma) into separate U and V planes
+/// Converts `min(u.len(), v.len(), uv.len() / 2)` samples.
+pub fn deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+    (kernels().deinterleave_uv)(uv, u, v)
+}
+
+/// Convert a pair of YUY2 rows to their two NV12 luma rows and one chroma row,
+/// averaging the chroma of both rows (rounded up)
+pub fn yuy2_row_pair_to_nv12(
+    top: &[u8],
+    bottom: &[u8],
+    y_top: &mut [u8],
+    y_bottom: &mut [u8],
+    uv: &mut [u8],
+) {
+    (kernels().yuy2_row_pair)(top, bottom, y_top, y_bottom, uv)
+}
+
+fn deinterleave_uv_scalar(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+    for ((pair, u), v) in uv.chunks_exact(2).zip(u.iter_mut()).zip(v.iter_mut()) {
+        *u = pair[0];
+        *v = pair[1];
+    }
+}
+
+fn yuy2_row_pair_to_nv12_scalar(
+    top: &[u8],
+    bottom: &[u8],
+    y_top: &mut [u8],
+    y_bottom: &mut [u8],
+    uv: &mut [u8],
+) {
+    let pixels = top
+        .chunks_exact(4)
+        .zip(bottom.chunks_exact(4))
+        .zip(y_top.chunks_exact_mut(2))
+        .zip(y_bottom.chunks_exact_mut(2))
+        .zip(uv.chunks_exact_mut(2));
+    for ((((top, bottom), y_top), y_bottom), uv) in pixels {
+        y_top.copy_from_slice(&[top[0], top[2]]);
+        y_bottom.copy_from_slice(&[bottom[0], bottom[2]]);
+        uv[0] = (top[1] as u16 + bottom[1] as u16).div_ceil(2) as u8;
+        uv[1] = (top[3] as u16 + bottom[3] as u16).div_ceil(2) as u8;
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use super::{deinterleave_uv_scalar, yuy2_row_pair_to_nv12_scalar};
+    use std::arch::aarch64::*;
+
+    /// 16 UV pairs per iteration
+    pub fn deinterleave_uv_neon(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+        let n = u.len().min(v.len()).min(uv.len() / 2);
+        let mut i = 0;
+        while i + 16 <= n {
+            // SAFETY: i + 16 <= n keeps the 32 bytes read and 2x16 bytes written in bounds
+            unsafe {
+                let pairs = vld2q_u8(uv.as_ptr().add(i * 2));
+                vst1q_u8(u.as_mut_ptr().add(i), pairs.0);
+                vst1q_u8(v.as_mut_ptr().add(i), pairs.1);
+            }
+            i += 16;
+        }
+        deinterleave_uv_scalar(&uv[i * 2..n * 2], &mut u[i..n], &mut v[i..n]);
+    }
+
+    /// 16 YUY2 macropixels (32 pixels) of each row per iteration
+    pub fn yuy2_row_pair_to_nv12_neon(
+        top: &[u8],
+        bottom: &[u8],
+        y_top: &mut [u8],
+        y_bottom: &mut [u8],
+        uv: &mut [u8],
+    ) {
+        let n = (top.len().min(bottom.len()) / 4)
+            .min(y_top.len().min(y_bottom.len()) / 2)
+            .min(uv.len() / 2);
+        let mut i = 0;
+        while i + 16 <= n {
+            // SAFETY: i + 16 <= n keeps the 64 bytes read per row and 32 bytes written
+            // per output row in bounds
+            unsafe {
+                // Lanes: Y0, U, Y1, V
+                let t = vld4q_u8(top.as_ptr().add(i * 4));
+                let b = vld4q_u8(bottom.as_ptr().add(i * 4));
+                vst2q_u8(y_top.as_mut_ptr().add(i * 2), uint8x16x2_t(t.0, t.2));
+                vst2q_u8(y_bottom.as_mut_ptr().add(i * 2), uint8x16x2_t(b.0, b.2));
+                // Rounding halving add: (top + bottom + 1) / 2, like the scalar path
+                let chroma = uint8x16x2_t(vrhaddq_u8(t.1, b.1), vrhaddq_u8(t.3, b.3));
+                vst2q_u8(uv.as_mut_ptr().add(i * 2), chroma);
+            }
+            i += 16;
+        }
+        yuy2_row_pair_to_nv12_scalar(
+            &top[i * 4..n * 4],
+            &bottom[i * 4..n * 4],
+            &mut y_top[i * 2..n * 2],
+            &mut y_bottom[i * 2..n * 2],
+            &mut uv[i * 2..n * 2],
+        );
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::deinterleave_uv_scalar;
+    use std::arch::x86_64::*;
+
+    /// Only selected by `detect` when AVX2 is available
+    pub fn deinterleave_uv_avx2(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+        // SAFETY: the CPU supports AVX2
+        unsafe { avx2_deinterleave_uv(uv, u, v) }
+    }
+
+    /// 32 UV pairs per iteration
+    #[target_feature(enable = "avx2")]
+    unsafe fn avx2_deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+        let n = u.len().min(v.len()).min(uv.len() / 2);
+        // Per 128-bit lane: even bytes (U) then odd bytes (V)
+        let shuffle = _mm256_setr_epi8(
+            0, 2, 4, 6, 8, 10, 12, 14, 1, 3, 5, 7, 9, 11, 13, 15, 0, 2, 4, 6, 8, 10, 12, 14, 1, 3,
+            5, 7, 9, 11, 13, 15,
+        );
+        let mut i = 0;
+        while i + 32 <= n {
+            let a = _mm256_loadu_si256(uv.as_ptr().add(i * 2) as *const __m256i);
+            let b = _mm256_loadu_si256(uv.as_ptr().add(i * 2 + 32) as *const __m256i);
+            // [U V | U V] quads reordered to [U U | V V]
+            let a = _mm256_permute4x64_epi64(_mm256_shuffle_epi8(a, shuffle), 0xD8);
+            let b = _mm256_permute4x64_epi64(_mm256_shuffle_epi8(b, shuffle), 0xD8);
+            _mm256_storeu_si256(
+                u.as_mut_ptr().add(i) as *mut __m256i,
+                _mm256_permute2x128_si256(a, b, 0x20),
+            );
+            _mm256_storeu_si256(
+                v.as_mut_ptr().add(i) as *mut __m256i,
+                _mm256_permute2x128_si256(a, b, 0x31),
+            );
+            i += 32;
+        }
+        deinterleave_uv_scalar(&uv[i * 2..n * 2], &mut u[i..n], &mut v[i..n]);
+    }
+
+    /// Only selected by `detect` when AVX-512 F and BW are available
+    #[cfg(feature = "avx512")]
+    pub fn deinterleave_uv_avx512(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+        // SAFETY: the CPU supports AVX-512 F and BW
+        unsafe { avx512_deinterleave_uv(uv, u, v) }
+    }
+
+    /// 64 UV pairs per iteration
+    #[cfg(feature = "avx512")]
+    #[target_feature(enable = "avx512f,avx512bw")]
+    unsafe fn avx512_deinterleave_uv(uv: &[u8], u: &mut [u8], v: &mut [u8]) {
+        let n = u.len().min(v.len()).min(uv.len() / 2);
+        let shuffle = _mm512_broadcast_i32x4(_mm_setr_epi8(
+            0, 2, 4, 6, 8, 10, 12, 14, 1, 3, 5, 7, 9, 11, 13, 15,
+        ));
+        // Quads of each lane reordered so U fills the low half and V the high half
+        let order = _mm512_setr_epi64(0, 2, 4, 6, 1, 3, 5, 7);
+        let mut i = 0;
+        while i + 64 <= n {
+            let a = _mm512_loadu_si512(uv.as_ptr().add(i * 2) as *const _);
+            let b = _mm512_loadu_si512(uv.as_ptr().add(i * 2 + 64) as *const _);
+            let a = _mm512_permutexvar_epi64(order, _mm512_shuffle_epi8(a, shuffle));
+            let b = _mm512_permutexvar_epi64(order, _mm512_shuffle_epi8(b, shuffle));
+            _mm512_storeu_si512(
+                u.as_mut_ptr().add(i) as *mut _,
+                _mm512_shuffle_i64x2(a, b, 0x44),
+            );
+            _mm512_storeu_si512(
+                v.as_mut_ptr().add(i) as *mut _,
+                _mm512_shuffle_i64x2(a, b, 0xEE),
+            );
+            i += 64;
+        }
+        deinterleave_uv_scalar(&uv[i * 2..n * 2], &mut u[i..n], &mut v[i..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_follow_cpu_features() {
+        let features = cpu_features();
+        match kernel_name() {
+            "avx2" => assert!(features.contains(&"avx2")),
+            "avx512" => assert!(features.contains(&"avx512bw")),
+            "neon" => assert!(features.contains(&"neon")),
+            name => assert_eq!(name, "scalar"),
+        }
+    }
+
+    #[test]
+    fn test_deinterleave_uv_matches_scalar() {
+        // Lengths around the vector widths exercise the scalar tails
+        for n in [0, 1, 31, 32, 33, 64, 127, 200, 1000] {
+            let uv: Vec<u8> = (0..n * 2).map(|i| (i * 7 % 251) as u8).collect();
+            let (mut u, mut v) = (vec![0; n], vec![0; n]);
+            deinterleave_uv(&uv, &mut u, &mut v);
+            let (mut expected_u, mut expected_v) = (vec![0; n], vec![0; n]);
+            deinterleave_uv_scalar(&uv, &mut expected_u, &mut expected_v);
+            assert_eq!((u, v), (expected_u, expected_v), "{} samples", n);
+        }
+    }
+
+    #[test]
+    fn test_yuy2_row_pair_matches_scalar() {
+        for width in [2, 30, 32, 34, 64, 66, 1280] {
+            let top: Vec<u8> = (0..width * 2).map(|i| (i * 3 % 256) as u8).collect();
+            let bottom: Vec<u8> = (0..width * 2).map(|i| (i * 5 % 256) as u8).collect();
+            let run = |convert: RowPairKernel| {
+                let (mut y_top, mut y_bottom, mut uv) =
+                    (vec![0; width], vec![0; width], vec![0; width]);
+                convert(&top, &bottom, &mut y_top, &mut y_bottom, &mut uv);
+                (y_top, y_bottom, uv)
+            };
+            assert_eq!(
+                run(yuy2_row_pair_to_nv12),
+                run(yuy2_row_pair_to_nv12_scalar),
+                "width {}",
+                width
+            );
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::eventlog::{elapsed_us, Stage, StageLog, StageRecord};
+use crate::faults::FaultState;
+use crate::models::FrameEvent;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tokio::sync::{oneshot, watch};
+
+/// Delivered frame rate steps, from unthrottled (capture rate) down to 5fps
+const RATE_LEVELS: [Option<f64>; 3] = [None, Some(15.0), Some(5.0)];
+/// A channel send slower than this means the webview is falling behind
+const SLOW_SEND: Duration = Duration::from_millis(30);
+/// Minimum time between two rate changes
+const RATE_CHANGE_COOLDOWN: Duration = Duration::from_secs(1);
+/// How long sends must stay fast before stepping the rate back up
+const RECOVERY_PERIOD: Duration = Duration::from_secs(3);
+/// Consecutive send failures after which the consumer is considered gone
+const MAX_CONSECUTIVE_ERRORS: u32 = 30;
+
+/// Counters of a frontend channel sink, read back by `get_stream_stats`
+#[derive(Debug, Default, Clone)]
+pub struct SinkStats {
+    pub frames_delivered: u64,
+    pub frames_skipped: u64,
+    pub send_errors: u64,
+    pub target_fps: Option<f64>,
+    pub effective_fps: f64,
+}
+
+/// Chooses which frames to deliver based on how fast the consumer accepts them
+pub struct RateController {
+    level: usize,
+    avg_send: Duration,
+    last_change: Instant,
+    last_delivered: Option<Instant>,
+}
+
+impl RateController {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            level: 0,
+            avg_send: Duration::ZERO,
+            last_change: now,
+            last_delivered: None,
+        }
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        RATE_LEVELS[self.level]
+    }
+
+    /// Whether a frame arriving at `now` fits the current target rate
+    pub fn should_deliver(&mut self, now: Instant) -> bool {
+        let (Some(fps), Some(last)) = (self.target_fps(), self.last_delivered) else {
+            return true;
+        };
+        now.duration_since(last) >= Duration::from_secs_f64(1.0 / fps)
+    }
+
+    pub fn on_sent(&mut self, now: Instant, send_time: Duration) {
+        self.last_delivered = Some(now);
+        // EWMA so a single slow frame (e.g. a GC pause in the webview) doesn't trigger a step
+        self.avg_send = (self.avg_send * 4 + send_time) / 5;
+
+        let since_change = now.duration_since(self.last_change);
+        if self.avg_send > SLOW_SEND && since_change >= RATE_CHANGE_COOLDOWN {
+            self.step_down(now);
+        } else if self.avg_send < SLOW_SEND / 3 && since_change >= RECOVERY_PERIOD {
+            self.step_up(now);
+        }
+    }
+
+    pub fn on_error(&mut self, now: Instant) {
+        if now.duration_since(self.last_change) >= RATE_CHANGE_COOLDOWN {
+            self.step_down(now);
+        }
+    }
+
+    fn step_down(&mut self, now: Instant) {
+        if self.level + 1 < RATE_LEVELS.len() {
+            self.level += 1;
+            log::warn!(
+                "Consumer lagging, lowering frame rate to {:?}fps",
+                self.target_fps()
+            );
+        }
+        self.last_change = now;
+    }
+
+    fn step_up(&mut self, now: Instant) {
+        if self.level > 0 {
+            self.level -= 1;
+            log::info!(
+                "Consumer recovered, raising frame rate to {:?}fps",
+                self.target_fps()
+            );
+        }
+        self.last_change = now;
+    }
+}
+
+/// Forward a stream's frames to a frontend channel until the stream stops, the sink
+/// is detached through `stop`, or the channel stops accepting frames (e.g. the webview went away)
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_channel_sink(
+    mut receiver: watch::Receiver<Option<FrameEvent>>,
+    channel: Channel<FrameEvent>,
+    adaptive: bool,
+    stats: Arc<Mutex<SinkStats>>,
+    faults: Arc<FaultState>,
+    stages: StageLog,
+    sink_id: String,
+    mut stop: oneshot::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let mut rate = RateController::new(Instant::now());
+        let mut consecutive_errors = 0;
+        let mut window_start = Instant::now();
+        let mut window_frames = 0u32;
+
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                changed = receiver.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+
+            let now = Instant::now();
+            if adaptive && !rate.should_deliver(now) {
+                stats.lock().unwrap().frames_skipped += 1;
+                continue;
+            }
+
+            // Clone the current frame out of the watch ref so no borrow lives across the send
+            let Some(frame) = receiver.borrow_and_update().clone() else {
+                continue;
+            };
+
+            let started = Instant::now();
+            let bytes = frame.data.len();
+            let sent = if faults.channel_send_error() {
+                Err(std::io::Error::other("Injected channel send failure").into())
+            } else {
+                channel.send(frame)
+            };
+            stages.record(
+                Stage::Send,
+                StageRecord {
+                    sink_id: Some(&sink_id),
+                    duration_us: Some(elapsed_us(started)),
+                    bytes: Some(bytes),
+                    error: sent.as_ref().err().map(|e| e.to_string()),
+                    ..Default::default()
+                },
+            );
+            let mut counters = stats.lock().unwrap();
+            match sent {
+                Ok(()) => {
+                    consecutive_errors = 0;
+                    rate.on_sent(now, started.elapsed());
+                    counters.frames_delivered += 1;
+                    window_frames += 1;
+                }
+                Err(e) => {
+                    consecutive_errors += 1;
+                    rate.on_error(now);
+                    counters.send_errors += 1;
+                    if consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+                        log::warn!("Frame channel closed, detaching sink: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            let window = now.duration_since(window_start);
+            if window >= Duration::from_secs(1) {
+                counters.effective_fps = window_frames as f64 / window.as_secs_f64();
+                window_start = now;
+                window_frames = 0;
+            }
+            counters.target_fps = if adaptive { rate.target_fps() } else { None };
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_controller_steps_down_and_recovers() {
+        let start = Instant::now();
+        let mut rate = RateController::new(start);
+        assert_eq!(rate.target_fps(), None);
+
+        // Slow consumer: every send takes 100ms
+        let mut now = start;
+        for _ in 0..40 {
+            now += Duration::from_millis(100);
+            rate.on_sent(now, Duration::from_millis(100));
+        }
+        assert_eq!(rate.target_fps(), Some(5.0));
+
+        // Frames closer than 200ms apart are skipped at 5fps
+        assert!(!rate.should_deliver(now + Duration::from_millis(50)));
+        assert!(rate.should_deliver(now + Duration::from_millis(200)));
+
+        // Fast consumer again: back to full rate after the recovery periods
+        for _ in 0..100 {
+            now += Duration::from_millis(200);
+            rate.on_sent(now, Duration::from_millis(1));
+        }
+        assert_eq!(rate.target_fps(), None);
+    }
+}
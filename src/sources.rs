@@ -0,0 +1,59 @@
+use crate::error::Result;
+use crate::models::CameraFormat;
+use crabcamera::{CameraDeviceInfo, CameraFrame};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Receives the frames produced by a source, from any thread
+pub type FrameCallback = Box<dyn FnMut(CameraFrame) + Send>;
+
+/// Custom frame producer (GigE vision camera, frame grabber, network feed, ...)
+/// registered by the host app with `Camera::register_source`. It is listed by
+/// `get_available_cameras` and streams like any camera, through every sink of the plugin.
+pub trait FrameSource: Send + Sync + 'static {
+    /// Device entry listed next to the cameras; its `id` is the device ID to stream from
+    fn info(&self) -> CameraDeviceInfo;
+
+    /// Start producing frames, handing each one to `on_frame`
+    /// `format` is the capture format of the profile applied to the device, if any
+    fn start(&self, format: Option<&CameraFormat>, on_frame: FrameCallback) -> Result<()>;
+
+    fn stop(&self) -> Result<()>;
+
+    /// Whether frames arrive at a steady rate. Sources producing frames only when the
+    /// picture changes (screen casts) return false so idle periods aren't taken for stalls.
+    fn continuous(&self) -> bool {
+        true
+    }
+}
+
+/// Registered sources, by device ID
+#[derive(Default)]
+pub struct Sources {
+    sources: Mutex<HashMap<String, Arc<dyn FrameSource>>>,
+}
+
+impl Sources {
+    /// Add a source, replacing any source with the same device ID
+    pub fn register(&self, source: Arc<dyn FrameSource>) {
+        let id = source.info().id;
+        self.sources.lock().unwrap().insert(id, source);
+    }
+
+    pub fn unregister(&self, device_id: &str) -> Option<Arc<dyn FrameSource>> {
+        self.sources.lock().unwrap().remove(device_id)
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<Arc<dyn FrameSource>> {
+        self.sources.lock().unwrap().get(device_id).cloned()
+    }
+
+    pub fn infos(&self) -> Vec<CameraDeviceInfo> {
+        self.sources
+            .lock()
+            .unwrap()
+            .values()
+            .map(|source| source.info())
+            .collect()
+    }
+}
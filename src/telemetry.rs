@@ -0,0 +1,111 @@
+use crate::models::MediaDirection;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Lifecycle event reported to the host app's telemetry hooks
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TelemetryEvent {
+    StreamStarted {
+        stream_id: String,
+        device_id: String,
+    },
+    StreamStopped {
+        stream_id: String,
+        device_id: String,
+        duration: Duration,
+    },
+    /// No frame arrived for the stall timeout; the capture is being reopened
+    StreamStalled {
+        stream_id: String,
+        device_id: String,
+    },
+    StreamRecovered {
+        stream_id: String,
+        device_id: String,
+        stalled_for: Duration,
+        restarts: u32,
+    },
+    /// A failure that ended an operation: a stream that couldn't start, an encoding
+    /// pass or a WebRTC sink that stopped
+    Error {
+        device_id: Option<String>,
+        operation: &'static str,
+        message: String,
+    },
+    /// Media stopped (`stalled`) or resumed flowing on a connected WebRTC peer
+    MediaLiveness {
+        connection_id: String,
+        direction: MediaDirection,
+        stalled: bool,
+    },
+    /// WebRTC peer connection state change, e.g. `connected`, `disconnected`, `failed`
+    ConnectionStateChanged {
+        connection_id: String,
+        state: String,
+    },
+}
+
+/// Called with every telemetry event, on the thread that produced it; keep it quick
+/// and hand the event off to the app's analytics or crash reporting
+pub type TelemetryHook = Arc<dyn Fn(&TelemetryEvent) + Send + Sync>;
+
+/// Hooks registered on the plugin builder
+#[derive(Clone, Default)]
+pub struct Telemetry {
+    hooks: Arc<Vec<TelemetryHook>>,
+}
+
+impl Telemetry {
+    pub fn new(hooks: Vec<TelemetryHook>) -> Self {
+        Self {
+            hooks: Arc::new(hooks),
+        }
+    }
+
+    pub fn emit(&self, event: TelemetryEvent) {
+        for hook in self.hooks.iter() {
+            // A failing hook must not take the capture or encoding thread down with it
+            if std::panic::catch_unwind(AssertUnwindSafe(|| hook(&event))).is_err() {
+                log::error!("Telemetry hook panicked on {:?}", event);
+            }
+        }
+    }
+
+    /// Report a failure ending an operation
+    pub fn error(&self, device_id: Option<&str>, operation: &'static str, message: String) {
+        self.emit(TelemetryEvent::Error {
+            device_id: device_id.map(str::to_string),
+            operation,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_panicking_hook_does_not_stop_others() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let telemetry = Telemetry::new(vec![
+            Arc::new(|_: &TelemetryEvent| panic!("hook failure")),
+            Arc::new(move |event: &TelemetryEvent| sink.lock().unwrap().push(event.clone())),
+        ]);
+
+        telemetry.error(Some("0"), "start_stream", "Camera busy".to_string());
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![TelemetryEvent::Error {
+                device_id: Some("0".to_string()),
+                operation: "start_stream",
+                message: "Camera busy".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::error::{Error, Result};
+use crate::models::ThumbnailOptions;
+use image::codecs::jpeg::JpegEncoder;
+use image::{imageops, ExtendedColorType, ImageEncoder, RgbImage};
+use openh264::decoder::{Decoder, DecoderConfig};
+use openh264::formats::YUVSource;
+use openh264::OpenH264API;
+use std::path::{Path, PathBuf};
+
+/// Longest side of thumbnails, unless set by the options
+const DEFAULT_THUMBNAIL_SIZE: u32 = 320;
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Keyframe of a recording its thumbnail is made from once the recording stops
+/// Keyframes decode on their own, so no other frame has to be kept.
+pub struct Thumbnail {
+    options: ThumbnailOptions,
+    keyframe: Option<Vec<u8>>,
+}
+
+impl Thumbnail {
+    pub fn new(options: ThumbnailOptions) -> Self {
+        Self {
+            options,
+            keyframe: None,
+        }
+    }
+
+    /// Offer an H.264 keyframe written `at_ms` into the recording; the first one is kept,
+    /// or the latest one up to `options.at_ms`
+    pub fn offer(&mut self, at_ms: u64, keyframe: &[u8]) {
+        let wanted = match self.options.at_ms {
+            Some(target_ms) => self.keyframe.is_none() || at_ms <= target_ms,
+            None => self.keyframe.is_none(),
+        };
+        if wanted {
+            self.keyframe = Some(keyframe.to_vec());
+        }
+    }
+
+    /// Write the thumbnail next to `recording`, `clip.jpg` for `clip.mp4`; a recording
+    /// without thumbnail is still a recording, failures are only logged
+    pub fn write(self, recording: &Path) -> Option<PathBuf> {
+        let keyframe = self.keyframe?;
+        let path = recording.with_extension("jpg");
+        let max_size = self
+            .options
+            .max_size
+            .unwrap_or(DEFAULT_THUMBNAIL_SIZE)
+            .max(1);
+        match encode(&keyframe, max_size).and_then(|jpeg| Ok(std::fs::write(&path, jpeg)?)) {
+            Ok(()) => Some(path),
+            Err(e) => {
+                log::warn!("Failed to write the thumbnail {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Decode an H.264 keyframe and encode it as a JPEG whose longest side is at most
+/// `max_size`
+fn encode(keyframe: &[u8], max_size: u32) -> Result<Vec<u8>> {
+    let mut decoder = Decoder::with_api_config(OpenH264API::from_source(), DecoderConfig::new())
+        .map_err(|e| Error::CameraError(format!("Failed to create the H.264 decoder: {}", e)))?;
+    let decoded = decoder
+        .decode(keyframe)
+        .map_err(|e| Error::CameraError(format!("Failed to decode the keyframe: {}", e)))?
+        .ok_or_else(|| Error::CameraError("The keyframe decoded to no picture".to_string()))?;
+    let (width, height) = decoded.dimensions();
+    let mut rgb = vec![0; width * height * 3];
+    decoded.write_rgb8(&mut rgb);
+    let image = RgbImage::from_raw(width as u32, height as u32, rgb)
+        .ok_or_else(|| Error::CameraError("Decoded picture of the wrong size".to_string()))?;
+
+    let scale = (max_size as f32 / width.max(height) as f32).min(1.0);
+    let image = if scale < 1.0 {
+        imageops::thumbnail(
+            &image,
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+        )
+    } else {
+        image
+    };
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY)
+        .write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ExtendedColorType::Rgb8,
+        )
+        .map_err(|e| Error::CameraError(format!("Failed to encode the thumbnail: {}", e)))?;
+    Ok(jpeg)
+}
@@ -0,0 +1,225 @@
+use crate::error::{Error, Result};
+use crate::models::{
+    PhotoFormat, PhotoOptions, StreamOptions, TimelapseOptions, TimelapseProgressEvent,
+    TimelapseSummary,
+};
+use crate::utils::{rgbx_to_nv12, yuv_nv12_to_h264};
+use crate::CameraExt;
+use image::ImageFormat;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// Intervals below this keep the camera open between shots, longer ones reopen it for each
+const KEEP_OPEN_INTERVAL: Duration = Duration::from_secs(10);
+/// File the shots are assembled into, in the output directory
+const VIDEO_FILE: &str = "timelapse.h264";
+
+struct Timelapse {
+    output_dir: PathBuf,
+    assemble_video: bool,
+    stop: oneshot::Sender<()>,
+    /// Resolves to the shots written
+    task: JoinHandle<Vec<PathBuf>>,
+}
+
+/// Running time-lapses, by ID
+#[derive(Default)]
+pub struct Timelapses {
+    running: Mutex<HashMap<String, Timelapse>>,
+}
+
+impl Timelapses {
+    /// Write a JPEG of the device to `output_dir` every `interval` until stopped,
+    /// emitting `camera://timelapse-progress` after each shot; returns the time-lapse ID
+    pub fn start<R: Runtime>(
+        &self,
+        app: AppHandle<R>,
+        device_id: String,
+        interval: Duration,
+        output_dir: PathBuf,
+        options: TimelapseOptions,
+    ) -> String {
+        let timelapse_id = format!("timelapse-{}", uuid::Uuid::new_v4());
+        let (stop, stop_rx) = oneshot::channel();
+        let task = tokio::spawn(run(
+            app,
+            timelapse_id.clone(),
+            device_id,
+            interval,
+            output_dir.clone(),
+            options.quality,
+            stop_rx,
+        ));
+        self.running.lock().unwrap().insert(
+            timelapse_id.clone(),
+            Timelapse {
+                output_dir,
+                assemble_video: options.assemble_video,
+                stop,
+                task,
+            },
+        );
+        timelapse_id
+    }
+
+    /// Stop a time-lapse once its current shot is written, then assemble the shots
+    /// into a video if it was started with `assemble_video`
+    pub async fn stop(&self, timelapse_id: &str) -> Result<TimelapseSummary> {
+        let timelapse = self
+            .running
+            .lock()
+            .unwrap()
+            .remove(timelapse_id)
+            .ok_or_else(|| Error::CameraError(format!("Time-lapse not found: {}", timelapse_id)))?;
+        let _ = timelapse.stop.send(());
+        let frames = timelapse
+            .task
+            .await
+            .map_err(|e| Error::CameraError(format!("Time-lapse task failed: {}", e)))?;
+
+        let video = if timelapse.assemble_video && !frames.is_empty() {
+            let output = timelapse.output_dir.join(VIDEO_FILE);
+            let (shots, path) = (frames.clone(), output.clone());
+            tokio::task::spawn_blocking(move || assemble(&shots, &path))
+                .await
+                .map_err(|e| Error::CameraError(format!("Time-lapse assembly failed: {}", e)))??;
+            Some(output)
+        } else {
+            None
+        };
+        Ok(TimelapseSummary {
+            timelapse_id: timelapse_id.to_string(),
+            frames: frames.len() as u32,
+            output_dir: timelapse.output_dir,
+            video,
+        })
+    }
+}
+
+async fn run<R: Runtime>(
+    app: AppHandle<R>,
+    timelapse_id: String,
+    device_id: String,
+    interval: Duration,
+    output_dir: PathBuf,
+    quality: Option<u8>,
+    mut stop: oneshot::Receiver<()>,
+) -> Vec<PathBuf> {
+    let camera = app.camera();
+    // Keep a stream open for short intervals, reopening the camera takes seconds
+    let mut owned_stream = None;
+    if interval < KEEP_OPEN_INTERVAL && camera.stream_id_by_device_id(&device_id).await.is_none() {
+        match camera
+            .start_streaming(device_id.clone(), StreamOptions::default())
+            .await
+        {
+            Ok(stream_id) => owned_stream = Some(stream_id),
+            Err(e) => log::warn!("Time-lapse {} shoots without a stream: {}", timelapse_id, e),
+        }
+    }
+
+    let mut frames = Vec::new();
+    let mut tick = tokio::time::interval(interval);
+    // A shot slower than the interval delays the next ones instead of bunching them up
+    tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = tick.tick() => {}
+        }
+        let path = output_dir.join(format!("frame-{:05}.jpg", frames.len()));
+        let options = PhotoOptions {
+            format: PhotoFormat::Jpeg,
+            quality,
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        let error = match camera.capture_photo(&device_id, options).await {
+            Ok(_) => {
+                frames.push(path.clone());
+                None
+            }
+            Err(e) => {
+                log::warn!("Time-lapse {} missed a shot: {}", timelapse_id, e);
+                Some(e.to_string())
+            }
+        };
+        let event = TimelapseProgressEvent {
+            timelapse_id: timelapse_id.clone(),
+            frames: frames.len() as u32,
+            path: error.is_none().then_some(path),
+            error,
+        };
+        if let Err(e) = app.emit("camera://timelapse-progress", event) {
+            log::warn!("Failed to emit time-lapse progress: {}", e);
+        }
+    }
+
+    if let Some(stream_id) = owned_stream {
+        if let Err(e) = camera.stop_streaming(stream_id).await {
+            log::warn!(
+                "Failed to stop the stream of time-lapse {}: {}",
+                timelapse_id,
+                e
+            );
+        }
+    }
+    frames
+}
+
+/// Encode JPEG shots into a raw H.264 stream, one keyframe per shot
+fn assemble(shots: &[PathBuf], output: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    for shot in shots {
+        let jpeg = std::fs::read(shot)?;
+        let rgba = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg)
+            .map_err(|e| Error::CameraError(format!("Failed to decode {}: {}", shot.display(), e)))?
+            .to_rgba8();
+        // 4:2:0 needs even dimensions: drop the last row or column if odd
+        let (width, height) = (rgba.width() & !1, rgba.height() & !1);
+        let nv12 = rgbx_to_nv12(&rgba, width, height, rgba.width() as usize * 4, false)?;
+        writer.write_all(&yuv_nv12_to_h264(&nv12, width, height)?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::photo;
+    use crabcamera::CameraFrame;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_assemble_jpeg_shots() {
+        let dir = std::env::temp_dir().join(format!("timelapse-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut frame = CameraFrame::new(vec![128; 32 * 32 * 3 / 2], 32, 32, "0".to_string());
+        frame.format = "NV12".to_string();
+        let frame = Arc::new(frame);
+
+        let shots: Vec<PathBuf> = (0..2)
+            .map(|index| {
+                let (_, _, jpeg) = photo::encode(frame.clone(), &PhotoOptions::default()).unwrap();
+                let path = dir.join(format!("frame-{:05}.jpg", index));
+                std::fs::write(&path, jpeg).unwrap();
+                path
+            })
+            .collect();
+        let output = dir.join(VIDEO_FILE);
+        assemble(&shots, &output).unwrap();
+
+        let video = std::fs::read(&output).unwrap();
+        assert!(video.starts_with(&[0, 0, 0, 1]) || video.starts_with(&[0, 0, 1]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
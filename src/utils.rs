@@ -1,8 +1,28 @@
-use openh264::{encoder::Encoder, formats::YUVSlices};
-use yuv::{YuvBiPlanarImage, YuvConversionMode, YuvPlanarImage, YuvRange, YuvStandardMatrix};
+use openh264::{
+    encoder::{
+        BitRate, Complexity, Encoder, EncoderConfig, FrameRate, FrameType, IntraFramePeriod, Level,
+        Profile, RateControlMode,
+    },
+    formats::YUVSlices,
+    OpenH264API, Timestamp,
+};
+use openh264_sys2::{SBitrateInfo, ENCODER_OPTION_BITRATE, SPATIAL_LAYER_ALL};
+use rayon::prelude::*;
+use std::os::raw::c_int;
+use std::sync::OnceLock;
+use yuv::{
+    YuvBiPlanarImage, YuvConversionMode, YuvPackedImage, YuvPlanarImage, YuvRange,
+    YuvStandardMatrix,
+};
+use zune_jpeg::zune_core::colorspace::ColorSpace as JpegColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
 
 /// Utility functions for image format conversion and processing
 use crate::error::{Error, Result};
+use crate::models::{
+    ColorSpace, EncoderSettings, H264Level, H264Profile, HdrTransfer, LatencyMode, RateControl,
+};
 
 /// Convert YUV (I420/YV12) buffer to RGBA
 ///
@@ -83,6 +103,16 @@ pub fn yuv_to_rgba(yuv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>>
 /// # Returns
 /// RGB24 buffer where each pixel is 3 bytes (R, G, B)
 pub fn nv12_to_rgba(yuv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    nv12_to_rgba_in(yuv_data, width, height, source_color_space(width, height))
+}
+
+/// Convert YUV (NV12) buffer to RGBA using the matrix of an explicit source color space
+pub fn nv12_to_rgba_in(
+    yuv_data: &[u8],
+    width: u32,
+    height: u32,
+    source: ColorSpace,
+) -> Result<Vec<u8>> {
     let width_usize = width as usize;
     let height_usize = height as usize;
 
@@ -116,26 +146,793 @@ pub fn nv12_to_rgba(yuv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>>
     }
 
     let rgb_stride = width * 4;
+    let matrix = yuv_matrix(source);
+
+    // Convert bands of rows in parallel; the yuv crate picks its SIMD kernels (SSE, AVX2,
+    // AVX-512 with the `avx512` feature, NEON) for each band. Bands start on even rows
+    // so each one owns whole chroma rows.
+    let band_rows = (height_usize.div_ceil(rayon::current_num_threads().max(1)) + 1) & !1;
+    let band_rows = band_rows.max(16);
+    rgb_data
+        .par_chunks_mut(band_rows * width_usize * 4)
+        .enumerate()
+        .try_for_each(|(band, rgba)| {
+            let first_row = band * band_rows;
+            let rows = rgba.len() / (width_usize * 4);
+            let uv_start = first_row / 2 * width_usize;
+            let uv_end =
+                ((first_row + rows).div_ceil(2) * width_usize).min(yuv_image.uv_plane.len());
+            let band_image = YuvBiPlanarImage {
+                y_plane: &yuv_image.y_plane
+                    [first_row * width_usize..(first_row + rows) * width_usize],
+                y_stride: width,
+                uv_plane: &yuv_image.uv_plane[uv_start..uv_end],
+                uv_stride: width,
+                width,
+                height: rows as u32,
+            };
+            yuv::yuv_nv12_to_rgba(
+                &band_image,
+                rgba,
+                rgb_stride,
+                YuvRange::Limited,
+                matrix,
+                YuvConversionMode::Fast,
+            )
+        })
+        .map_err(|e| Error::CameraError(format!("NV12 to RGB conversion failed: {:?}", e)))?;
 
-    // ⚡ OPTIMISATION: Détection auto de la matrice couleur selon résolution
-    let matrix = if width >= 1280 || height >= 720 {
-        YuvStandardMatrix::Bt709 // HD et plus
+    Ok(rgb_data)
+}
+
+/// Color space a raw YUV frame of this size is assumed to be encoded in
+/// (BT.709 for HD and above, BT.601 for SD)
+pub fn source_color_space(width: u32, height: u32) -> ColorSpace {
+    if width >= 1280 || height >= 720 {
+        ColorSpace::Bt709
     } else {
-        YuvStandardMatrix::Bt601 // SD
+        ColorSpace::Bt601
+    }
+}
+
+fn yuv_matrix(color_space: ColorSpace) -> YuvStandardMatrix {
+    match color_space {
+        ColorSpace::Bt601 => YuvStandardMatrix::Bt601,
+        ColorSpace::Bt2020 => YuvStandardMatrix::Bt2020,
+        ColorSpace::Bt709 | ColorSpace::Srgb | ColorSpace::DisplayP3 => YuvStandardMatrix::Bt709,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Primaries {
+    Rec709,
+    DisplayP3,
+    Rec2020,
+}
+
+fn primaries(color_space: ColorSpace) -> Primaries {
+    match color_space {
+        // BT.601 primaries are close enough to BT.709 for preview purposes
+        ColorSpace::Bt601 | ColorSpace::Bt709 | ColorSpace::Srgb => Primaries::Rec709,
+        ColorSpace::DisplayP3 => Primaries::DisplayP3,
+        ColorSpace::Bt2020 => Primaries::Rec2020,
+    }
+}
+
+/// Linear-light RGB conversion matrix between two sets of primaries (D65 white point)
+fn gamut_matrix(from: Primaries, to: Primaries) -> Option<[[f32; 3]; 3]> {
+    use Primaries::*;
+    let m = match (from, to) {
+        (Rec709, DisplayP3) => [
+            [0.8225, 0.1774, 0.0000],
+            [0.0332, 0.9669, 0.0000],
+            [0.0171, 0.0724, 0.9108],
+        ],
+        (DisplayP3, Rec709) => [
+            [1.2249, -0.2247, 0.0000],
+            [-0.0420, 1.0419, 0.0000],
+            [-0.0197, -0.0786, 1.0979],
+        ],
+        (Rec2020, Rec709) => [
+            [1.6605, -0.5876, -0.0728],
+            [-0.1246, 1.1329, -0.0083],
+            [-0.0182, -0.1006, 1.1187],
+        ],
+        (Rec709, Rec2020) => [
+            [0.6274, 0.3293, 0.0433],
+            [0.0691, 0.9195, 0.0114],
+            [0.0164, 0.0880, 0.8956],
+        ],
+        (Rec2020, DisplayP3) => [
+            [1.3435, -0.2822, -0.0613],
+            [-0.0653, 1.0758, -0.0105],
+            [0.0028, -0.0196, 1.0168],
+        ],
+        (DisplayP3, Rec2020) => [
+            [0.7539, 0.1986, 0.0476],
+            [0.0457, 0.9418, 0.0125],
+            [-0.0012, 0.0176, 0.9836],
+        ],
+        _ => return None,
     };
+    Some(m)
+}
 
-    // Convert using yuv crate
-    yuv::yuv_nv12_to_rgba(
-        &yuv_image,
-        &mut rgb_data,
-        rgb_stride,
-        YuvRange::Limited,
-        matrix,
-        YuvConversionMode::Fast,
+const ENCODE_LUT_SIZE: usize = 4096;
+
+/// sRGB transfer function lookup tables (8-bit → linear, linear → 8-bit)
+fn transfer_luts() -> &'static ([f32; 256], Vec<u8>) {
+    static LUTS: OnceLock<([f32; 256], Vec<u8>)> = OnceLock::new();
+    LUTS.get_or_init(|| {
+        let mut to_linear = [0f32; 256];
+        for (i, v) in to_linear.iter_mut().enumerate() {
+            let c = i as f32 / 255.0;
+            *v = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+        }
+
+        let to_encoded = (0..ENCODE_LUT_SIZE)
+            .map(|i| {
+                let l = i as f32 / (ENCODE_LUT_SIZE - 1) as f32;
+                let c = if l <= 0.0031308 {
+                    l * 12.92
+                } else {
+                    1.055 * l.powf(1.0 / 2.4) - 0.055
+                };
+                (c * 255.0).round().clamp(0.0, 255.0) as u8
+            })
+            .collect();
+
+        (to_linear, to_encoded)
+    })
+}
+
+/// Convert an RGBA buffer in place from one color space's gamut to another's
+///
+/// Display P3 and sRGB share the same transfer curve, so only the primaries are
+/// remapped. Out-of-gamut values are clipped. No-op when both spaces share primaries.
+pub fn convert_rgba_color_space(rgba: &mut [u8], source: ColorSpace, target: ColorSpace) {
+    let Some(m) = gamut_matrix(primaries(source), primaries(target)) else {
+        return;
+    };
+    let (to_linear, to_encoded) = transfer_luts();
+    let scale = (ENCODE_LUT_SIZE - 1) as f32;
+
+    rgba.par_chunks_mut(4).for_each(|px| {
+        let r = to_linear[px[0] as usize];
+        let g = to_linear[px[1] as usize];
+        let b = to_linear[px[2] as usize];
+        for (c, row) in m.iter().enumerate() {
+            let l = (row[0] * r + row[1] * g + row[2] * b).clamp(0.0, 1.0);
+            px[c] = to_encoded[(l * scale).round() as usize];
+        }
+    });
+}
+
+/// SDR reference white in nits (ITU-R BT.2408)
+const SDR_REFERENCE_WHITE_NITS: f32 = 203.0;
+/// Peak luminance assumed for HDR camera output
+const HDR_PEAK_NITS: f32 = 1000.0;
+
+/// Display luminance in nits for a normalized HDR signal value
+fn hdr_signal_to_nits(e: f32, transfer: HdrTransfer) -> f32 {
+    match transfer {
+        HdrTransfer::Pq => {
+            const M1: f32 = 0.159_301_76;
+            const M2: f32 = 78.843_75;
+            const C1: f32 = 0.835_937_5;
+            const C2: f32 = 18.851_563;
+            const C3: f32 = 18.687_5;
+            let p = e.powf(1.0 / M2);
+            10000.0 * ((p - C1).max(0.0) / (C2 - C3 * p)).powf(1.0 / M1)
+        }
+        HdrTransfer::Hlg => {
+            const A: f32 = 0.178_832_77;
+            const B: f32 = 0.284_668_92;
+            const C: f32 = 0.559_910_7;
+            let scene = if e <= 0.5 {
+                e * e / 3.0
+            } else {
+                (((e - C) / A).exp() + B) / 12.0
+            };
+            // Nominal HLG OOTF (system gamma 1.2) for a 1000 nit display
+            HDR_PEAK_NITS * scene.powf(1.2)
+        }
+    }
+}
+
+/// 10-bit limited-range HDR luma code → 8-bit limited-range SDR luma code
+fn tone_map_lut(transfer: HdrTransfer) -> &'static [u8; 1024] {
+    static HLG: OnceLock<[u8; 1024]> = OnceLock::new();
+    static PQ: OnceLock<[u8; 1024]> = OnceLock::new();
+    let cell = match transfer {
+        HdrTransfer::Hlg => &HLG,
+        HdrTransfer::Pq => &PQ,
+    };
+    cell.get_or_init(|| {
+        let white = HDR_PEAK_NITS / SDR_REFERENCE_WHITE_NITS;
+        let mut lut = [0u8; 1024];
+        for (code, out) in lut.iter_mut().enumerate() {
+            let e = ((code as f32 - 64.0) / 876.0).clamp(0.0, 1.0);
+            let x = hdr_signal_to_nits(e, transfer) / SDR_REFERENCE_WHITE_NITS;
+            // Extended Reinhard: maps the HDR peak to SDR white, keeps shadows linear
+            let y = (x * (1.0 + x / (white * white)) / (1.0 + x)).clamp(0.0, 1.0);
+            let v = y.powf(1.0 / 2.4);
+            *out = (16.0 + 219.0 * v).round() as u8;
+        }
+        lut
+    })
+}
+
+/// Tone map a 10-bit HDR P010 frame (HLG or PQ) to an 8-bit SDR NV12 frame
+///
+/// Luma goes through the tone curve; chroma is reduced to 8 bits. The output keeps
+/// BT.2020 primaries, so it should be decoded with the BT.2020 matrix.
+///
+/// # Arguments
+/// * `p010_data` - Input P010 buffer (16-bit little-endian samples, data in the top 10 bits)
+/// * `width` - Frame width in pixels
+/// * `height` - Frame height in pixels
+/// * `transfer` - HDR transfer function of the source
+pub fn p010_to_nv12_tone_mapped(
+    p010_data: &[u8],
+    width: u32,
+    height: u32,
+    transfer: HdrTransfer,
+) -> Result<Vec<u8>> {
+    let y_samples = width as usize * height as usize;
+    let uv_samples = y_samples / 2;
+    let expected_size = (y_samples + uv_samples) * 2;
+    if p010_data.len() < expected_size {
+        return Err(Error::CameraError(format!(
+            "Invalid P010 buffer size: expected at least {}, got {}",
+            expected_size,
+            p010_data.len()
+        )));
+    }
+
+    let lut = tone_map_lut(transfer);
+    let sample = |chunk: &[u8]| u16::from_le_bytes([chunk[0], chunk[1]]) >> 6;
+
+    let mut nv12 = vec![0u8; y_samples + uv_samples];
+    let (y_out, uv_out) = nv12.split_at_mut(y_samples);
+    let (y_in, uv_in) = p010_data[..expected_size].split_at(y_samples * 2);
+
+    y_out
+        .par_iter_mut()
+        .zip(y_in.par_chunks_exact(2))
+        .for_each(|(out, chunk)| *out = lut[sample(chunk) as usize]);
+    uv_out
+        .par_iter_mut()
+        .zip(uv_in.par_chunks_exact(2))
+        .for_each(|(out, chunk)| *out = (sample(chunk) >> 2) as u8);
+
+    Ok(nv12)
+}
+
+/// Convert packed YUY2 (YUYV 4:2:2) to NV12, averaging the chroma of each row pair
+///
+/// # Arguments
+/// * `yuy2_data` - Input YUY2 buffer (Y0 U Y1 V for each pair of pixels)
+/// * `width` - Frame width in pixels (even)
+/// * `height` - Frame height in pixels (even)
+pub fn yuy2_to_nv12(yuy2_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let (w, h) = (width as usize, height as usize);
+    let expected_size = w * h * 2;
+    if w == 0 || h == 0 || yuy2_data.len() < expected_size || w % 2 != 0 || h % 2 != 0 {
+        return Err(Error::CameraError(format!(
+            "Invalid YUY2 buffer: {}x{}, expected at least {} bytes, got {}",
+            width,
+            height,
+            expected_size,
+            yuy2_data.len()
+        )));
+    }
+
+    let mut nv12 = vec![0u8; w * h * 3 / 2];
+    let (y_out, uv_out) = nv12.split_at_mut(w * h);
+    y_out
+        .par_chunks_exact_mut(w * 2)
+        .zip(uv_out.par_chunks_exact_mut(w))
+        .zip(yuy2_data[..expected_size].par_chunks_exact(w * 4))
+        .for_each(|((y_rows, uv), rows)| {
+            let (top, bottom) = rows.split_at(w * 2);
+            let (y_top, y_bottom) = y_rows.split_at_mut(w);
+            crate::simd::yuy2_row_pair_to_nv12(top, bottom, y_top, y_bottom, uv);
+        });
+
+    Ok(nv12)
+}
+
+/// Whether frames of this format are JPEG pictures (MJPEG streams of UVC cameras)
+pub(crate) fn is_mjpeg(format: &str) -> bool {
+    ["MJPG", "MJPEG", "JPEG"]
+        .iter()
+        .any(|mjpeg| format.eq_ignore_ascii_case(mjpeg))
+}
+
+/// Decode an MJPEG frame (one JPEG picture) to NV12, BT.601 limited range
+/// JPEG pictures are full-range BT.601 Y'CbCr (JFIF): they are decoded without going
+/// through RGB, rescaled to limited range, and their chroma averaged over each 2x2
+/// block. Frames of UVC cameras that leave out the Huffman tables use the standard ones.
+///
+/// # Arguments
+/// * `jpeg_data` - One JPEG picture, of even width and height
+///
+/// # Returns
+/// NV12 buffer, and the width and height of the picture
+pub fn mjpeg_to_nv12(jpeg_data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(JpegColorSpace::YCbCr);
+    let mut decoder = JpegDecoder::new_with_options(jpeg_data, options);
+    let ycbcr = decoder
+        .decode()
+        .map_err(|e| Error::CameraError(format!("Failed to decode MJPEG frame: {:?}", e)))?;
+    let (w, h) = decoder
+        .dimensions()
+        .ok_or_else(|| Error::CameraError("MJPEG frame without dimensions".to_string()))?;
+    if w % 2 != 0 || h % 2 != 0 || ycbcr.len() < w * h * 3 {
+        return Err(Error::CameraError(format!(
+            "Invalid MJPEG frame: {}x{}, {} decoded bytes",
+            w,
+            h,
+            ycbcr.len()
+        )));
+    }
+
+    // Full range to limited range; chroma is the sum of a 2x2 block
+    let luma = |y: u8| (16 + (y as u32 * 219 + 127) / 255) as u8;
+    let chroma = |sum: u32| (16 + (sum * 224 + 510) / 1020) as u8;
+    let mut nv12 = vec![0u8; w * h * 3 / 2];
+    let (y_out, uv_out) = nv12.split_at_mut(w * h);
+    y_out
+        .par_chunks_exact_mut(w * 2)
+        .zip(uv_out.par_chunks_exact_mut(w))
+        .zip(ycbcr[..w * h * 3].par_chunks_exact(w * 6))
+        .for_each(|((y_rows, uv), rows)| {
+            let (top, bottom) = rows.split_at(w * 3);
+            for (y, pixel) in y_rows
+                .iter_mut()
+                .zip(top.chunks_exact(3).chain(bottom.chunks_exact(3)))
+            {
+                *y = luma(pixel[0]);
+            }
+            for (x, uv) in uv.chunks_exact_mut(2).enumerate() {
+                let at = x * 6;
+                let sum = |c: usize| {
+                    [
+                        top[at + c],
+                        top[at + 3 + c],
+                        bottom[at + c],
+                        bottom[at + 3 + c],
+                    ]
+                    .iter()
+                    .map(|&v| v as u32)
+                    .sum::<u32>()
+                };
+                uv[0] = chroma(sum(1));
+                uv[1] = chroma(sum(2));
+            }
+        });
+
+    Ok((nv12, w as u32, h as u32))
+}
+
+/// Byte order of packed 4:2:2 frames, two pixels per 4-byte macropixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Packed422 {
+    /// Y0 U Y1 V (YUY2)
+    Yuyv,
+    /// U Y0 V Y1
+    Uyvy,
+}
+
+impl Packed422 {
+    /// Order of a frame format, `None` for formats that aren't packed 4:2:2
+    pub(crate) fn of(format: &str) -> Option<Self> {
+        if format.eq_ignore_ascii_case("YUYV") || format.eq_ignore_ascii_case("YUY2") {
+            Some(Packed422::Yuyv)
+        } else if format.eq_ignore_ascii_case("UYVY") {
+            Some(Packed422::Uyvy)
+        } else {
+            None
+        }
+    }
+
+    /// Offsets of Y0, U, Y1 and V in a macropixel
+    fn offsets(self) -> [usize; 4] {
+        match self {
+            Packed422::Yuyv => [0, 1, 2, 3],
+            Packed422::Uyvy => [1, 0, 3, 2],
+        }
+    }
+}
+
+fn check_packed_422(data: &[u8], width: u32, height: u32, order: Packed422) -> Result<()> {
+    let expected_size = width as usize * height as usize * 2;
+    if width == 0 || height == 0 || data.len() < expected_size || width % 2 != 0 || height % 2 != 0
+    {
+        return Err(Error::CameraError(format!(
+            "Invalid {:?} buffer: {}x{}, expected at least {} bytes, got {}",
+            order,
+            width,
+            height,
+            expected_size,
+            data.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Convert packed YUYV (YUY2 4:2:2) to RGBA, keeping the full chroma resolution
+///
+/// # Arguments
+/// * `yuyv_data` - Input buffer (Y0 U Y1 V for each pair of pixels)
+/// * `width` - Frame width in pixels (even)
+/// * `height` - Frame height in pixels (even)
+pub fn yuyv_to_rgba(yuyv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    packed_422_to_rgba_in(
+        yuyv_data,
+        width,
+        height,
+        Packed422::Yuyv,
+        source_color_space(width, height),
     )
-    .map_err(|e| Error::CameraError(format!("NV12 to RGB conversion failed: {:?}", e)))?;
+}
 
-    Ok(rgb_data)
+/// Convert packed UYVY (4:2:2) to RGBA, keeping the full chroma resolution
+///
+/// # Arguments
+/// * `uyvy_data` - Input buffer (U Y0 V Y1 for each pair of pixels)
+/// * `width` - Frame width in pixels (even)
+/// * `height` - Frame height in pixels (even)
+pub fn uyvy_to_rgba(uyvy_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    packed_422_to_rgba_in(
+        uyvy_data,
+        width,
+        height,
+        Packed422::Uyvy,
+        source_color_space(width, height),
+    )
+}
+
+/// Convert packed 4:2:2 to RGBA using the matrix of an explicit source color space
+pub(crate) fn packed_422_to_rgba_in(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    order: Packed422,
+    source: ColorSpace,
+) -> Result<Vec<u8>> {
+    check_packed_422(data, width, height, order)?;
+    let (w, h) = (width as usize, height as usize);
+    let mut rgba = vec![0u8; w * h * 4];
+    let matrix = yuv_matrix(source);
+
+    // Bands of rows in parallel, as for NV12; packed rows are independent
+    let band_rows = h.div_ceil(rayon::current_num_threads().max(1)).max(16);
+    rgba.par_chunks_mut(band_rows * w * 4)
+        .zip(data[..w * h * 2].par_chunks(band_rows * w * 2))
+        .try_for_each(|(rgba, packed)| {
+            let image = YuvPackedImage {
+                yuy: packed,
+                yuy_stride: width * 2,
+                width,
+                height: (packed.len() / (w * 2)) as u32,
+            };
+            match order {
+                Packed422::Yuyv => {
+                    yuv::yuyv422_to_rgba(&image, rgba, width * 4, YuvRange::Limited, matrix)
+                }
+                Packed422::Uyvy => {
+                    yuv::uyvy422_to_rgba(&image, rgba, width * 4, YuvRange::Limited, matrix)
+                }
+            }
+        })
+        .map_err(|e| {
+            Error::CameraError(format!("{:?} to RGB conversion failed: {:?}", order, e))
+        })?;
+
+    Ok(rgba)
+}
+
+/// Convert packed UYVY (4:2:2) to NV12, averaging the chroma of each row pair
+///
+/// # Arguments
+/// * `uyvy_data` - Input buffer (U Y0 V Y1 for each pair of pixels)
+/// * `width` - Frame width in pixels (even)
+/// * `height` - Frame height in pixels (even)
+pub fn uyvy_to_nv12(uyvy_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    check_packed_422(uyvy_data, width, height, Packed422::Uyvy)?;
+    let w = width as usize;
+    let [y0, u, y1, v] = Packed422::Uyvy.offsets();
+    let mut nv12 = vec![0u8; w * height as usize * 3 / 2];
+    let (y_out, uv_out) = nv12.split_at_mut(w * height as usize);
+    y_out
+        .par_chunks_exact_mut(w * 2)
+        .zip(uv_out.par_chunks_exact_mut(w))
+        .zip(uyvy_data.par_chunks_exact(w * 4))
+        .for_each(|((y_rows, uv), rows)| {
+            let (top, bottom) = rows.split_at(w * 2);
+            for (y_row, row) in y_rows.chunks_exact_mut(w).zip([top, bottom]) {
+                for (y, pixel) in y_row.chunks_exact_mut(2).zip(row.chunks_exact(4)) {
+                    y[0] = pixel[y0];
+                    y[1] = pixel[y1];
+                }
+            }
+            for ((uv, a), b) in uv
+                .chunks_exact_mut(2)
+                .zip(top.chunks_exact(4))
+                .zip(bottom.chunks_exact(4))
+            {
+                uv[0] = ((a[u] as u16 + b[u] as u16 + 1) / 2) as u8;
+                uv[1] = ((a[v] as u16 + b[v] as u16 + 1) / 2) as u8;
+            }
+        });
+    Ok(nv12)
+}
+
+/// Convert packed YUYV (YUY2 4:2:2) to I420 for encoders taking planar frames, averaging
+/// the chroma of each row pair
+pub fn yuyv_to_i420(yuyv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    packed_422_to_i420(yuyv_data, width, height, Packed422::Yuyv)
+}
+
+/// Convert packed UYVY (4:2:2) to I420 for encoders taking planar frames, averaging the
+/// chroma of each row pair
+pub fn uyvy_to_i420(uyvy_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    packed_422_to_i420(uyvy_data, width, height, Packed422::Uyvy)
+}
+
+fn packed_422_to_i420(data: &[u8], width: u32, height: u32, order: Packed422) -> Result<Vec<u8>> {
+    check_packed_422(data, width, height, order)?;
+    let (w, h) = (width as usize, height as usize);
+    let [y0, u, y1, v] = order.offsets();
+    let mut i420 = vec![0u8; w * h * 3 / 2];
+    let (y_out, chroma_out) = i420.split_at_mut(w * h);
+    let (u_out, v_out) = chroma_out.split_at_mut(w * h / 4);
+    y_out
+        .par_chunks_exact_mut(w * 2)
+        .zip(u_out.par_chunks_exact_mut(w / 2))
+        .zip(v_out.par_chunks_exact_mut(w / 2))
+        .zip(data.par_chunks_exact(w * 4))
+        .for_each(|(((y_rows, u_row), v_row), rows)| {
+            let (top, bottom) = rows.split_at(w * 2);
+            for (y_row, row) in y_rows.chunks_exact_mut(w).zip([top, bottom]) {
+                for (y, pixel) in y_row.chunks_exact_mut(2).zip(row.chunks_exact(4)) {
+                    y[0] = pixel[y0];
+                    y[1] = pixel[y1];
+                }
+            }
+            for (((u_out, v_out), a), b) in u_row
+                .iter_mut()
+                .zip(v_row.iter_mut())
+                .zip(top.chunks_exact(4))
+                .zip(bottom.chunks_exact(4))
+            {
+                *u_out = ((a[u] as u16 + b[u] as u16 + 1) / 2) as u8;
+                *v_out = ((a[v] as u16 + b[v] as u16 + 1) / 2) as u8;
+            }
+        });
+    Ok(i420)
+}
+
+/// Convert 4-byte packed RGB (RGBx/RGBA, or BGRx/BGRA when `bgr` is set) to NV12,
+/// BT.709 limited range, e.g. for screen capture buffers
+///
+/// # Arguments
+/// * `data` - Input buffer, `stride` bytes per row
+/// * `width` - Frame width in pixels (even)
+/// * `height` - Frame height in pixels (even)
+/// * `stride` - Bytes per row, at least `width * 4`
+/// * `bgr` - Blue is the first byte of each pixel
+pub fn rgbx_to_nv12(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    bgr: bool,
+) -> Result<Vec<u8>> {
+    let (w, h) = (width as usize, height as usize);
+    if stride < w * 4
+        || data.len() < stride * h.saturating_sub(1) + w * 4
+        || w % 2 != 0
+        || h % 2 != 0
+    {
+        return Err(Error::CameraError(format!(
+            "Invalid RGB buffer: {}x{} with stride {}, got {} bytes",
+            width,
+            height,
+            stride,
+            data.len()
+        )));
+    }
+
+    let rgb = |row: &[u8], x: usize| {
+        let pixel = &row[x * 4..x * 4 + 3];
+        let (r, g, b) = if bgr {
+            (pixel[2], pixel[1], pixel[0])
+        } else {
+            (pixel[0], pixel[1], pixel[2])
+        };
+        (r as f32, g as f32, b as f32)
+    };
+    let luma = |(r, g, b): (f32, f32, f32)| 16.0 + (0.1826 * r + 0.6142 * g + 0.0620 * b);
+
+    let mut nv12 = vec![0u8; w * h * 3 / 2];
+    let (y_out, uv_out) = nv12.split_at_mut(w * h);
+    y_out
+        .par_chunks_exact_mut(w)
+        .enumerate()
+        .for_each(|(y, out)| {
+            let row = &data[y * stride..];
+            for (x, value) in out.iter_mut().enumerate() {
+                *value = luma(rgb(row, x)).round() as u8;
+            }
+        });
+    uv_out
+        .par_chunks_exact_mut(w)
+        .enumerate()
+        .for_each(|(y, out)| {
+            let (top, bottom) = (&data[y * 2 * stride..], &data[(y * 2 + 1) * stride..]);
+            for x in 0..w / 2 {
+                let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+                for (pr, pg, pb) in [
+                    rgb(top, x * 2),
+                    rgb(top, x * 2 + 1),
+                    rgb(bottom, x * 2),
+                    rgb(bottom, x * 2 + 1),
+                ] {
+                    r += pr / 4.0;
+                    g += pg / 4.0;
+                    b += pb / 4.0;
+                }
+                let u = 128.0 + (-0.1006 * r - 0.3386 * g + 0.4392 * b);
+                let v = 128.0 + (0.4392 * r - 0.3989 * g - 0.0403 * b);
+                out[x * 2] = u.round().clamp(0.0, 255.0) as u8;
+                out[x * 2 + 1] = v.round().clamp(0.0, 255.0) as u8;
+            }
+        });
+
+    Ok(nv12)
+}
+
+/// Number of bytes sampled by [`frame_signature`]
+const SIGNATURE_SAMPLES: usize = 4096;
+
+/// Cheap content signature of a frame buffer: an evenly spaced sample of its bytes
+///
+/// Works on any pixel layout; for planar formats most samples land in the luma plane.
+pub fn frame_signature(data: &[u8]) -> Vec<u8> {
+    let step = (data.len() / SIGNATURE_SAMPLES).max(1);
+    data.iter().step_by(step).copied().collect()
+}
+
+/// Mean absolute difference between two signatures (0.0 = identical, 255.0 = opposite)
+/// Signatures of different lengths (resolution or format change) never match.
+pub fn signature_distance(a: &[u8], b: &[u8]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return f64::MAX;
+    }
+    let total: u64 = a.iter().zip(b).map(|(&x, &y)| x.abs_diff(y) as u64).sum();
+    total as f64 / a.len() as f64
+}
+
+/// Copy a region out of a semi-planar 4:2:0 frame (NV12, or P010 with 2 bytes per sample)
+///
+/// # Arguments
+/// * `data` - Input buffer (Y plane + interleaved UV plane)
+/// * `width` - Frame width in pixels
+/// * `height` - Frame height in pixels
+/// * `sample_bytes` - Bytes per sample: 1 for NV12, 2 for P010
+/// * `region` - `(x, y, width, height)` in pixels, all even
+pub fn crop_yuv420sp(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    sample_bytes: usize,
+    region: (u32, u32, u32, u32),
+) -> Result<Vec<u8>> {
+    let (w, h) = (width as usize, height as usize);
+    let (x, y, crop_w, crop_h) = (
+        region.0 as usize,
+        region.1 as usize,
+        region.2 as usize,
+        region.3 as usize,
+    );
+    let expected_size = w * h * 3 / 2 * sample_bytes;
+    if data.len() < expected_size
+        || x + crop_w > w
+        || y + crop_h > h
+        || (x | y | crop_w | crop_h) % 2 != 0
+    {
+        return Err(Error::CameraError(format!(
+            "Invalid crop of {}x{} at ({}, {}) from a {}x{} frame of {} bytes",
+            crop_w,
+            crop_h,
+            x,
+            y,
+            width,
+            height,
+            data.len()
+        )));
+    }
+
+    let stride = w * sample_bytes;
+    let row = x * sample_bytes..(x + crop_w) * sample_bytes;
+    let mut cropped = Vec::with_capacity(crop_w * crop_h * 3 / 2 * sample_bytes);
+    for line in y..y + crop_h {
+        cropped.extend_from_slice(&data[line * stride..][row.clone()]);
+    }
+    // Each chroma row covers two luma rows, and each UV pair two columns: the same byte range
+    let uv = &data[h * stride..];
+    for line in y / 2..(y + crop_h) / 2 {
+        cropped.extend_from_slice(&uv[line * stride..][row.clone()]);
+    }
+    Ok(cropped)
+}
+
+/// Mirror (`horizontal`) and/or flip upside down (`vertical`) an image plane in place
+///
+/// # Arguments
+/// * `plane` - Rows of `row_bytes` bytes each
+/// * `row_bytes` - Bytes per row
+/// * `sample_bytes` - Bytes per sample, kept in order: 4 for RGBA, 2 for an NV12 UV pair
+pub fn flip_plane(
+    plane: &mut [u8],
+    row_bytes: usize,
+    sample_bytes: usize,
+    horizontal: bool,
+    vertical: bool,
+) {
+    if horizontal {
+        for row in plane.chunks_exact_mut(row_bytes) {
+            row.reverse();
+            for sample in row.chunks_exact_mut(sample_bytes) {
+                sample.reverse();
+            }
+        }
+    }
+    if vertical {
+        let rows = plane.len() / row_bytes;
+        for top in 0..rows / 2 {
+            let (upper, lower) = plane.split_at_mut((rows - 1 - top) * row_bytes);
+            upper[top * row_bytes..][..row_bytes].swap_with_slice(&mut lower[..row_bytes]);
+        }
+    }
+}
+
+/// Rotate an image plane a quarter turn, clockwise or counterclockwise
+///
+/// # Arguments
+/// * `plane` - `height` rows of `width` samples
+/// * `sample_bytes` - Bytes per sample, kept in order: 4 for RGBA, 2 for an NV12 UV pair
+///
+/// # Returns
+/// `width` rows of `height` samples
+pub fn rotate_plane(
+    plane: &[u8],
+    width: usize,
+    height: usize,
+    sample_bytes: usize,
+    clockwise: bool,
+) -> Vec<u8> {
+    let mut rotated = vec![0; width * height * sample_bytes];
+    for y in 0..height {
+        for x in 0..width {
+            let (to_x, to_y) = if clockwise {
+                (height - 1 - y, x)
+            } else {
+                (y, width - 1 - x)
+            };
+            let from = (y * width + x) * sample_bytes;
+            let to = (to_y * height + to_x) * sample_bytes;
+            rotated[to..to + sample_bytes].copy_from_slice(&plane[from..from + sample_bytes]);
+        }
+    }
+    rotated
 }
 
 /// Convert NV12 to I420 format (de-interleave UV plane)
@@ -147,7 +944,7 @@ pub fn nv12_to_rgba(yuv_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>>
 ///
 /// # Returns
 /// I420 buffer (Y plane + U plane + V plane)
-fn nv12_to_i420(nv12_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+pub(crate) fn nv12_to_i420(nv12_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
     let width_usize = width as usize;
     let height_usize = height as usize;
 
@@ -165,23 +962,21 @@ fn nv12_to_i420(nv12_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
     }
 
     // Allocate I420 buffer
-    let mut i420_data = Vec::with_capacity(expected_size);
+    let mut i420_data = vec![0u8; expected_size];
+    let (y_out, uv_out) = i420_data.split_at_mut(y_plane_size);
+    let (u_out, v_out) = uv_out.split_at_mut(uv_plane_size);
 
     // Copy Y plane (unchanged)
-    i420_data.extend_from_slice(&nv12_data[..y_plane_size]);
+    y_out.copy_from_slice(&nv12_data[..y_plane_size]);
 
-    // De-interleave UV plane: UVUVUV... → UUU... VVV...
-    let uv_plane = &nv12_data[y_plane_size..];
-    let mut u_plane = Vec::with_capacity(uv_plane_size);
-    let mut v_plane = Vec::with_capacity(uv_plane_size);
-
-    for i in 0..uv_plane_size {
-        u_plane.push(uv_plane[i * 2]); // U values at even indices
-        v_plane.push(uv_plane[i * 2 + 1]); // V values at odd indices
-    }
-
-    i420_data.extend(u_plane);
-    i420_data.extend(v_plane);
+    // De-interleave UV plane: UVUVUV... → UUU... VVV..., in parallel bands of SIMD work
+    let uv_plane = &nv12_data[y_plane_size..expected_size];
+    let band = (uv_plane_size / rayon::current_num_threads().max(1)).max(64 * 1024);
+    uv_plane
+        .par_chunks(band * 2)
+        .zip(u_out.par_chunks_mut(band))
+        .zip(v_out.par_chunks_mut(band))
+        .for_each(|((uv, u), v)| crate::simd::deinterleave_uv(uv, u, v));
 
     Ok(i420_data)
 }
@@ -196,36 +991,191 @@ fn nv12_to_i420(nv12_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
 /// # Returns
 /// A `Vec<u8>` containing the H.264 Annex B bitstream (SPS/PPS + frame NALs)
 pub fn yuv_nv12_to_h264(nv12_data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
-    let width_usize = width as usize;
-    let height_usize = height as usize;
+    yuv_nv12_to_h264_with(nv12_data, width, height, &EncoderSettings::default())
+}
 
-    // Convert NV12 to I420 first
-    let i420_data = nv12_to_i420(nv12_data, width, height)?;
+/// Encode a NV12 frame into H.264 using OpenH264 with the given encoder settings
+/// The frame is a standalone keyframe; encode a sequence of frames with a
+/// `H264EncoderSession` instead.
+pub fn yuv_nv12_to_h264_with(
+    nv12_data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &EncoderSettings,
+) -> Result<Vec<u8>> {
+    let mut session = H264EncoderSession::new(settings, LatencyMode::Balanced)?;
+    Ok(session.encode(nv12_data, width, height, 0)?.data)
+}
 
-    // Split I420 planes: Y [w*h], U [w*h/4], V [w*h/4]
-    let y_plane_size = width_usize * height_usize;
-    let uv_plane_size = y_plane_size / 4;
-    let (y_plane, rest) = i420_data.split_at(y_plane_size);
-    let (u_plane, v_plane) = rest.split_at(uv_plane_size);
+fn openh264_profile(profile: H264Profile) -> Profile {
+    match profile {
+        H264Profile::Baseline => Profile::Baseline,
+        H264Profile::Main => Profile::Main,
+        H264Profile::High => Profile::High,
+    }
+}
 
-    let chroma_width = width_usize / 2;
+fn openh264_level(level: H264Level) -> Level {
+    match level {
+        H264Level::L1 => Level::Level_1_0,
+        H264Level::L1b => Level::Level_1_B,
+        H264Level::L1_1 => Level::Level_1_1,
+        H264Level::L1_2 => Level::Level_1_2,
+        H264Level::L1_3 => Level::Level_1_3,
+        H264Level::L2 => Level::Level_2_0,
+        H264Level::L2_1 => Level::Level_2_1,
+        H264Level::L2_2 => Level::Level_2_2,
+        H264Level::L3 => Level::Level_3_0,
+        H264Level::L3_1 => Level::Level_3_1,
+        H264Level::L3_2 => Level::Level_3_2,
+        H264Level::L4 => Level::Level_4_0,
+        H264Level::L4_1 => Level::Level_4_1,
+        H264Level::L4_2 => Level::Level_4_2,
+        H264Level::L5 => Level::Level_5_0,
+        H264Level::L5_1 => Level::Level_5_1,
+        H264Level::L5_2 => Level::Level_5_2,
+    }
+}
 
-    // Wrap as YUVSlices (4:2:0), using tight strides
-    let yuv = YUVSlices::new(
-        (y_plane, u_plane, v_plane),
-        (width_usize, height_usize),
-        (width_usize, chroma_width, chroma_width),
-    );
+fn openh264_rate_control(rate_control: RateControl) -> RateControlMode {
+    match rate_control {
+        RateControl::Quality => RateControlMode::Quality,
+        RateControl::Bitrate => RateControlMode::Bitrate,
+        RateControl::Buffer => RateControlMode::Bufferbased,
+        RateControl::Timestamp => RateControlMode::Timestamp,
+        RateControl::Off => RateControlMode::Off,
+    }
+}
+
+/// OpenH264 configuration of the settings, tuned for a stream's latency mode: lowest
+/// complexity and frame skipping under rate pressure in `Realtime`, highest complexity
+/// without skipping in `Quality`, the OpenH264 defaults otherwise
+fn openh264_config(settings: &EncoderSettings, latency_mode: LatencyMode) -> EncoderConfig {
+    let mut config = EncoderConfig::new();
+    if let Some(kbps) = settings.bitrate_kbps {
+        config = config.bitrate(BitRate::from_bps(kbps * 1000));
+    }
+    if let Some(fps) = settings.max_fps {
+        config = config.max_frame_rate(FrameRate::from_hz(fps as f32));
+    }
+    if let Some(profile) = settings.profile {
+        config = config.profile(openh264_profile(profile));
+    }
+    if let Some(level) = settings.level {
+        config = config.level(openh264_level(level));
+    }
+    if let Some(frames) = settings.keyframe_interval {
+        config = config.intra_frame_period(IntraFramePeriod::from_num_frames(frames));
+    }
+    if let Some(rate_control) = settings.rate_control {
+        config = config.rate_control_mode(openh264_rate_control(rate_control));
+    }
+    match latency_mode {
+        LatencyMode::Realtime => config.complexity(Complexity::Low).skip_frames(true),
+        LatencyMode::Balanced => config,
+        LatencyMode::Quality => config.complexity(Complexity::High).skip_frames(false),
+    }
+}
+
+/// Keyframe interval of sessions whose settings don't set one
+const DEFAULT_KEYFRAME_INTERVAL_SECS: f64 = 2.0;
+
+/// Frame encoded by a `H264EncoderSession`
+pub struct H264Picture {
+    /// Annex B bitstream, empty when rate control skipped the frame
+    pub data: Vec<u8>,
+    /// IDR frame, preceded by SPS/PPS so decoders can start on it
+    pub keyframe: bool,
+}
+
+/// OpenH264 encoder kept for the frames of a stream, so they form groups of pictures
+/// instead of each being a keyframe
+/// A group starts with an IDR frame every `keyframe_interval` frames (two seconds of
+/// frames when unset), and on `force_keyframe`.
+pub struct H264EncoderSession {
+    encoder: Encoder,
+}
+
+impl H264EncoderSession {
+    pub fn new(settings: &EncoderSettings, latency_mode: LatencyMode) -> Result<Self> {
+        let mut settings = settings.clone();
+        let fps = settings.max_fps.unwrap_or(30.0);
+        settings
+            .keyframe_interval
+            .get_or_insert((fps * DEFAULT_KEYFRAME_INTERVAL_SECS).round().max(1.0) as u32);
+        let config = openh264_config(&settings, latency_mode);
+        let encoder = Encoder::with_api_config(OpenH264API::from_source(), config)
+            .map_err(|e| Error::CameraError(format!("Failed to create OpenH264 encoder: {}", e)))?;
+        Ok(Self { encoder })
+    }
+
+    /// Encode the next NV12 frame of the stream, captured `pts_us` after its start
+    pub fn encode(
+        &mut self,
+        nv12_data: &[u8],
+        width: u32,
+        height: u32,
+        pts_us: u64,
+    ) -> Result<H264Picture> {
+        let width_usize = width as usize;
+        let height_usize = height as usize;
+
+        // Convert NV12 to I420 first
+        let i420_data = nv12_to_i420(nv12_data, width, height)?;
+
+        // Split I420 planes: Y [w*h], U [w*h/4], V [w*h/4]
+        let y_plane_size = width_usize * height_usize;
+        let uv_plane_size = y_plane_size / 4;
+        let (y_plane, rest) = i420_data.split_at(y_plane_size);
+        let (u_plane, v_plane) = rest.split_at(uv_plane_size);
+
+        let chroma_width = width_usize / 2;
+
+        // Wrap as YUVSlices (4:2:0), using tight strides
+        let yuv = YUVSlices::new(
+            (y_plane, u_plane, v_plane),
+            (width_usize, height_usize),
+            (width_usize, chroma_width, chroma_width),
+        );
 
-    // Create encoder with default config and encode one frame
-    let mut encoder = Encoder::new()
-        .map_err(|e| Error::CameraError(format!("Failed to create OpenH264 encoder: {}", e)))?;
+        // Timestamps drive the rate control between frames
+        let bitstream = self
+            .encoder
+            .encode_at(&yuv, Timestamp::from_millis(pts_us / 1000))
+            .map_err(|e| Error::CameraError(format!("Failed to encode frame: {}", e)))?;
 
-    let bitstream = encoder
-        .encode(&yuv)
-        .map_err(|e| Error::CameraError(format!("Failed to encode frame: {}", e)))?;
+        Ok(H264Picture {
+            keyframe: matches!(bitstream.frame_type(), FrameType::IDR),
+            data: bitstream.to_vec(),
+        })
+    }
 
-    Ok(bitstream.to_vec())
+    /// Make the next encoded frame an IDR frame
+    pub fn force_keyframe(&mut self) {
+        self.encoder.force_intra_frame();
+    }
+
+    /// Change the target bitrate from the next frame on, within the current group of
+    /// pictures
+    pub fn set_bitrate(&mut self, kbps: u32) -> Result<()> {
+        let mut info = SBitrateInfo {
+            iLayer: SPATIAL_LAYER_ALL,
+            iBitrate: kbps.saturating_mul(1000).min(c_int::MAX as u32) as c_int,
+        };
+        // SAFETY: `info` is the value type of the bitrate option and outlives the call
+        let status = unsafe {
+            self.encoder
+                .raw_api()
+                .set_option(ENCODER_OPTION_BITRATE, std::ptr::addr_of_mut!(info).cast())
+        };
+        if status != 0 {
+            return Err(Error::CameraError(format!(
+                "Failed to set the H.264 bitrate to {} kbps (error {})",
+                kbps, status
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -246,6 +1196,36 @@ mod tests {
         assert_eq!(rgb_data.len(), (width * height * 4) as usize); // RGBA = 4 bytes per pixel
     }
 
+    #[test]
+    fn test_crop_yuv420sp() {
+        // 4x4 NV12: luma bytes are their index, chroma rows are 100.. and 110..
+        let mut nv12: Vec<u8> = (0..16).collect();
+        nv12.extend([100, 101, 102, 103, 110, 111, 112, 113]);
+
+        let cropped = crop_yuv420sp(&nv12, 4, 4, 1, (2, 2, 2, 2)).unwrap();
+        assert_eq!(cropped, vec![10, 11, 14, 15, 112, 113]);
+        assert!(crop_yuv420sp(&nv12, 4, 4, 1, (1, 0, 2, 2)).is_err());
+        assert!(crop_yuv420sp(&nv12, 4, 4, 1, (2, 2, 4, 2)).is_err());
+    }
+
+    #[test]
+    fn test_flip_plane() {
+        // 2x2 RGBA-like plane of 2-byte samples: [a b / c d]
+        let mut plane = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        flip_plane(&mut plane, 4, 2, true, false);
+        assert_eq!(plane, vec![3, 4, 1, 2, 7, 8, 5, 6]);
+        flip_plane(&mut plane, 4, 2, false, true);
+        assert_eq!(plane, vec![7, 8, 5, 6, 3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn test_rotate_plane() {
+        // [1 2 3 / 4 5 6]
+        let plane = vec![1, 2, 3, 4, 5, 6];
+        assert_eq!(rotate_plane(&plane, 3, 2, 1, true), vec![4, 1, 5, 2, 6, 3]);
+        assert_eq!(rotate_plane(&plane, 3, 2, 1, false), vec![3, 6, 2, 5, 1, 4]);
+    }
+
     #[test]
     fn test_yuv_to_rgb_invalid_size() {
         let width = 640u32;
@@ -438,6 +1418,234 @@ mod tests {
         assert_eq!(rgba_data[3], 255, "Alpha should be 255");
     }
 
+    #[test]
+    fn test_convert_rgba_color_space_same_primaries_is_noop() {
+        let mut rgba = vec![255, 0, 0, 255, 12, 200, 90, 255];
+        let original = rgba.clone();
+
+        convert_rgba_color_space(&mut rgba, ColorSpace::Bt709, ColorSpace::Srgb);
+        assert_eq!(rgba, original);
+    }
+
+    #[test]
+    fn test_convert_rgba_color_space_srgb_to_p3() {
+        // White and black are shared by both gamuts, saturated red is not
+        let mut rgba = vec![255, 255, 255, 255, 0, 0, 0, 255, 255, 0, 0, 255];
+
+        convert_rgba_color_space(&mut rgba, ColorSpace::Srgb, ColorSpace::DisplayP3);
+
+        assert!(
+            rgba[0..3].iter().all(|&c| c >= 254),
+            "white: {:?}",
+            &rgba[0..3]
+        );
+        assert_eq!(&rgba[4..7], &[0, 0, 0]);
+        assert!(rgba[8] < 255 && rgba[8] > 200, "red R: {}", rgba[8]);
+        assert!(rgba[9] > 0, "red G should pick up some green: {}", rgba[9]);
+        assert_eq!(rgba[11], 255, "alpha must be preserved");
+    }
+
+    fn p010_frame(width: u32, height: u32, luma: u16, chroma: u16) -> Vec<u8> {
+        let y_samples = (width * height) as usize;
+        let mut data = Vec::with_capacity(y_samples * 3);
+        for _ in 0..y_samples {
+            data.extend_from_slice(&(luma << 6).to_le_bytes());
+        }
+        for _ in 0..y_samples / 2 {
+            data.extend_from_slice(&(chroma << 6).to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_p010_tone_map_buffer_size() {
+        let data = p010_frame(64, 48, 512, 512);
+        let nv12 = p010_to_nv12_tone_mapped(&data, 64, 48, HdrTransfer::Hlg).unwrap();
+        assert_eq!(nv12.len(), 64 * 48 * 3 / 2);
+
+        let result = p010_to_nv12_tone_mapped(&data[..100], 64, 48, HdrTransfer::Hlg);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_p010_tone_map_levels() {
+        for transfer in [HdrTransfer::Hlg, HdrTransfer::Pq] {
+            let black =
+                p010_to_nv12_tone_mapped(&p010_frame(4, 4, 64, 512), 4, 4, transfer).unwrap();
+            assert_eq!(black[0], 16, "{:?} black", transfer);
+            assert_eq!(black[16], 128, "{:?} neutral chroma", transfer);
+
+            let peak =
+                p010_to_nv12_tone_mapped(&p010_frame(4, 4, 940, 512), 4, 4, transfer).unwrap();
+            assert!(
+                peak[0] >= 230,
+                "{:?} peak should reach SDR white, got {}",
+                transfer,
+                peak[0]
+            );
+
+            // Tone curve must be monotonic
+            let lut = tone_map_lut(transfer);
+            assert!(
+                lut.windows(2).all(|w| w[0] <= w[1]),
+                "{:?} not monotonic",
+                transfer
+            );
+        }
+    }
+
+    #[test]
+    fn test_frame_signature_distance() {
+        let frame = (0..640 * 480 * 3 / 2)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let signature = frame_signature(&frame);
+        assert!(signature.len() >= SIGNATURE_SAMPLES);
+        assert_eq!(
+            signature_distance(&signature, &frame_signature(&frame)),
+            0.0
+        );
+
+        // Sensor noise of +-1 stays close, a scene change does not
+        let noisy = frame
+            .iter()
+            .map(|&b| b.saturating_add(1))
+            .collect::<Vec<_>>();
+        assert!(signature_distance(&signature, &frame_signature(&noisy)) <= 1.0);
+        let inverted = frame.iter().map(|&b| 255 - b).collect::<Vec<_>>();
+        assert!(signature_distance(&signature, &frame_signature(&inverted)) > 50.0);
+
+        assert_eq!(signature_distance(&signature, &signature[1..]), f64::MAX);
+    }
+
+    #[test]
+    fn test_yuy2_to_nv12() {
+        // 2x2 frame: Y0 U Y1 V per row, chroma averaged over the two rows
+        let yuy2 = [10, 100, 20, 200, 30, 110, 40, 210];
+        let nv12 = yuy2_to_nv12(&yuy2, 2, 2).unwrap();
+        assert_eq!(nv12, vec![10, 20, 30, 40, 105, 205]);
+
+        assert!(yuy2_to_nv12(&yuy2, 4, 2).is_err());
+        assert!(yuy2_to_nv12(&yuy2, 1, 2).is_err());
+        assert!(yuy2_to_nv12(&yuy2, 0, 2).is_err());
+        assert!(yuy2_to_nv12(&yuy2, 2, 0).is_err());
+    }
+
+    #[test]
+    fn test_mjpeg_to_nv12() {
+        use image::codecs::jpeg::JpegEncoder;
+        use image::{ExtendedColorType, ImageEncoder};
+
+        // Mid gray: luma in limited range, neutral chroma
+        let rgb = vec![128u8; 16 * 8 * 3];
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 100)
+            .write_image(&rgb, 16, 8, ExtendedColorType::Rgb8)
+            .unwrap();
+        let (nv12, width, height) = mjpeg_to_nv12(&jpeg).unwrap();
+        assert_eq!((width, height), (16, 8));
+        assert_eq!(nv12.len(), 16 * 8 * 3 / 2);
+        assert!(nv12[..16 * 8].iter().all(|&y| y.abs_diff(126) <= 2));
+        assert!(nv12[16 * 8..].iter().all(|&c| c.abs_diff(128) <= 2));
+
+        assert!(mjpeg_to_nv12(&jpeg[..jpeg.len() / 2]).is_err());
+        assert!(is_mjpeg("mjpg") && !is_mjpeg("NV12"));
+    }
+
+    #[test]
+    fn test_packed_422_conversions() {
+        // 4x2 frame, YUYV and the same pixels as UYVY
+        let yuyv = [
+            10, 100, 20, 200, 11, 101, 21, 201, 30, 110, 40, 210, 31, 111, 41, 211,
+        ];
+        let uyvy: Vec<u8> = yuyv
+            .chunks_exact(2)
+            .flat_map(|pair| [pair[1], pair[0]])
+            .collect();
+        let luma = [10, 20, 11, 21, 30, 40, 31, 41];
+
+        let nv12 = uyvy_to_nv12(&uyvy, 4, 2).unwrap();
+        assert_eq!(nv12[..8], luma);
+        assert_eq!(nv12[8..], [105, 205, 106, 206]);
+        assert_eq!(nv12, yuy2_to_nv12(&yuyv, 4, 2).unwrap());
+
+        let i420 = yuyv_to_i420(&yuyv, 4, 2).unwrap();
+        assert_eq!(i420[..8], luma);
+        assert_eq!(i420[8..], [105, 106, 205, 206]);
+        assert_eq!(uyvy_to_i420(&uyvy, 4, 2).unwrap(), i420);
+        assert!(uyvy_to_i420(&uyvy, 3, 2).is_err());
+        assert!(uyvy_to_i420(&uyvy, 0, 2).is_err());
+        assert!(yuyv_to_rgba(&yuyv, 4, 0).is_err());
+
+        // Mid gray in limited range
+        let gray = [126u8, 128].repeat(16 * 8);
+        let rgba = yuyv_to_rgba(&gray, 16, 8).unwrap();
+        assert_eq!(rgba.len(), 16 * 8 * 4);
+        assert!(rgba
+            .chunks_exact(4)
+            .all(|pixel| pixel[..3].iter().all(|&c| c.abs_diff(128) <= 2) && pixel[3] == 255));
+        assert_eq!(
+            uyvy_to_rgba(&[128u8, 126].repeat(16 * 8), 16, 8).unwrap(),
+            rgba
+        );
+        assert_eq!(Packed422::of("YUY2"), Some(Packed422::Yuyv));
+        assert_eq!(Packed422::of("NV12"), None);
+    }
+
+    #[test]
+    fn test_rgbx_to_nv12_levels() {
+        // 2x2 white BGRx frame with 4 bytes of row padding
+        let mut bgrx = vec![255u8; 12 * 2];
+        let nv12 = rgbx_to_nv12(&bgrx, 2, 2, 12, true).unwrap();
+        assert_eq!(nv12, vec![235, 235, 235, 235, 128, 128]);
+
+        // Pure blue: low luma, U above and V below neutral; RGBx order reads it as red
+        for pixel in bgrx.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[255, 0, 0, 0]);
+        }
+        let blue = rgbx_to_nv12(&bgrx[..20], 2, 2, 12, true).unwrap();
+        assert_eq!(blue[0], 32);
+        assert!(blue[4] > 200 && blue[5] < 128);
+        let red = rgbx_to_nv12(&bgrx[..20], 2, 2, 12, false).unwrap();
+        assert_eq!(red[0], 63);
+        assert!(red[4] < 128 && red[5] > 200);
+
+        assert!(rgbx_to_nv12(&bgrx, 2, 2, 4, true).is_err());
+    }
+
+    #[test]
+    fn test_nv12_to_i420_planes() {
+        let (width, height) = (640u32, 482u32);
+        let y_size = (width * height) as usize;
+        let nv12: Vec<u8> = (0..y_size * 3 / 2).map(|i| (i % 253) as u8).collect();
+        let i420 = nv12_to_i420(&nv12, width, height).unwrap();
+
+        assert_eq!(&i420[..y_size], &nv12[..y_size]);
+        let (u, v) = i420[y_size..].split_at(y_size / 4);
+        for (i, pair) in nv12[y_size..].chunks_exact(2).enumerate() {
+            assert_eq!((u[i], v[i]), (pair[0], pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_h264_session_keyframes() {
+        let (width, height) = (64u32, 64u32);
+        let nv12: Vec<u8> = (0..width * height * 3 / 2)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut session =
+            H264EncoderSession::new(&EncoderSettings::default(), LatencyMode::Quality).unwrap();
+
+        let first = session.encode(&nv12, width, height, 0).unwrap();
+        let second = session.encode(&nv12, width, height, 33_333).unwrap();
+        assert!(first.keyframe);
+        assert!(!second.keyframe);
+
+        session.force_keyframe();
+        let forced = session.encode(&nv12, width, height, 66_666).unwrap();
+        assert!(forced.keyframe);
+    }
+
     // ========================================================================
     // BENCHMARKS DE PERFORMANCE
     // ========================================================================
@@ -505,15 +1713,14 @@ mod tests {
             println!("🚀 Excellent performance (AVX2 optimized)!");
         }
 
-        // Affiche les features CPU détectées
-        #[cfg(target_feature = "avx2")]
-        println!("   CPU Features: AVX2 ✅");
-
-        #[cfg(all(target_feature = "sse2", not(target_feature = "avx2")))]
-        println!("   CPU Features: SSE2 ✅");
-
-        #[cfg(not(any(target_feature = "sse2", target_feature = "avx2")))]
-        println!("   CPU Features: None (fallback mode)");
+        // Affiche les features CPU détectées à l'exécution
+        let features = crate::simd::cpu_features();
+        if features.is_empty() {
+            println!("   CPU Features: None (fallback mode)");
+        } else {
+            println!("   CPU Features: {} ✅", features.join(", "));
+        }
+        println!("   Kernels: {}", crate::simd::kernel_name());
     }
 
     #[test]
@@ -541,6 +1748,45 @@ mod tests {
         println!("   FPS: {:.1}", fps);
     }
 
+    #[test]
+    fn bench_nv12_4k() {
+        use std::time::Instant;
+
+        let width = 3840u32;
+        let height = 2160u32;
+        let nv12_data: Vec<u8> = (0..(width * height * 3 / 2) as usize)
+            .map(|i| (i % 256) as u8)
+            .collect();
+
+        for _ in 0..3 {
+            let _ = nv12_to_rgba(&nv12_data, width, height).unwrap();
+        }
+        let iterations = 20;
+        let start = Instant::now();
+        for _ in 0..iterations {
+            let _ = nv12_to_rgba(&nv12_data, width, height).unwrap();
+        }
+        let rgba_ms = start.elapsed().as_micros() as f64 / iterations as f64 / 1000.0;
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            let _ = nv12_to_i420(&nv12_data, width, height).unwrap();
+        }
+        let i420_ms = start.elapsed().as_micros() as f64 / iterations as f64 / 1000.0;
+
+        println!(
+            "\n📊 4K Performance ({}, {} kernels, {} threads):",
+            std::env::consts::ARCH,
+            crate::simd::kernel_name(),
+            rayon::current_num_threads()
+        );
+        println!("   NV12→RGBA: {:.2}ms", rgba_ms);
+        println!("   NV12→I420: {:.2}ms", i420_ms);
+        if rgba_ms > 8.0 {
+            println!("⚠️  Above the 8 ms target, make sure you're running in --release mode");
+        }
+    }
+
     #[test]
     fn bench_yuv_to_rgb_hd() {
         use std::time::Instant;
@@ -0,0 +1,123 @@
+use crate::encoder::{EncodedFrame, Encoder, EncoderParams};
+use crate::error::{Error, Result};
+use crate::models::FrameEvent;
+use crate::utils::nv12_to_i420;
+use vpx_encode::{Config, VideoCodecId};
+
+/// Names of the libvpx encoders in the encoder registry
+pub const VP8_ENCODER: &str = "vp8";
+pub const VP9_ENCODER: &str = "vp9";
+/// Bitrate of the encoder when the settings have none
+const DEFAULT_BITRATE_KBPS: u32 = 1000;
+
+/// VP8 or VP9 encoder backed by libvpx, for peers and SFUs that don't negotiate H.264
+/// libvpx encodes in realtime mode without frame reordering. It has no call to force a
+/// keyframe or change the bitrate, so both restart the encoder, whose first frame is a
+/// keyframe.
+pub struct VpxEncoder {
+    codec: VideoCodecId,
+    params: Option<EncoderParams>,
+    /// Started on the first frame after `configure`, `force_keyframe` or `set_bitrate`
+    encoder: Option<vpx_encode::Encoder>,
+}
+
+impl VpxEncoder {
+    pub fn vp8() -> Self {
+        Self::new(VideoCodecId::VP8)
+    }
+
+    pub fn vp9() -> Self {
+        Self::new(VideoCodecId::VP9)
+    }
+
+    fn new(codec: VideoCodecId) -> Self {
+        Self {
+            codec,
+            params: None,
+            encoder: None,
+        }
+    }
+}
+
+impl Encoder for VpxEncoder {
+    fn mime_type(&self) -> &str {
+        match self.codec {
+            VideoCodecId::VP8 => "video/vp8",
+            VideoCodecId::VP9 => "video/vp9",
+        }
+    }
+
+    fn configure(&mut self, params: &EncoderParams) -> Result<()> {
+        if params.b_frames.unwrap_or(0) > 0 || params.lookahead.unwrap_or(0) > 0 {
+            return Err(Error::CameraError(
+                "libvpx encodes in realtime mode, without B-frames nor lookahead".to_string(),
+            ));
+        }
+        self.params = Some(params.clone());
+        self.encoder = None;
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: &FrameEvent) -> Result<EncodedFrame> {
+        let params = self
+            .params
+            .as_ref()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        if !frame.format.eq_ignore_ascii_case("NV12") {
+            return Err(Error::CameraError(format!(
+                "Cannot encode {} frames to {}, start the stream without color conversion",
+                frame.format,
+                self.mime_type()
+            )));
+        }
+        let i420 = nv12_to_i420(&frame.data, frame.width, frame.height)?;
+
+        let encoder = match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => vpx_encode::Encoder::new(Config {
+                width: frame.width,
+                height: frame.height,
+                // Millisecond timestamps
+                timebase: [1, 1000],
+                bitrate: params.bitrate_kbps.unwrap_or(DEFAULT_BITRATE_KBPS),
+                codec: self.codec,
+            })
+            .map_err(|e| Error::CameraError(format!("Failed to create libvpx encoder: {:?}", e)))?,
+        };
+        let encoder = self.encoder.insert(encoder);
+
+        let mut data = Vec::new();
+        let mut keyframe = false;
+        let packets = encoder
+            .encode((frame.pts_us / 1000) as i64, &i420)
+            .map_err(|e| Error::CameraError(format!("Failed to encode frame: {:?}", e)))?;
+        for packet in packets {
+            data.extend_from_slice(packet.data);
+            keyframe |= packet.key;
+        }
+        Ok(EncodedFrame {
+            data,
+            keyframe,
+            pts_us: frame.pts_us,
+            capture_time_ms: frame.capture_time_ms,
+            width: frame.width,
+            height: frame.height,
+        })
+    }
+
+    fn force_keyframe(&mut self) {
+        self.encoder = None;
+    }
+
+    fn set_bitrate(&mut self, bitrate_kbps: u32) -> Result<()> {
+        let params = self
+            .params
+            .as_mut()
+            .ok_or_else(|| Error::CameraError("Encoder used before configure".to_string()))?;
+        if params.bitrate_kbps != Some(bitrate_kbps) {
+            params.bitrate_kbps = Some(bitrate_kbps);
+            self.encoder = None;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use crate::models::{StreamErrorEvent, StreamRecoveredEvent, StreamStalledEvent};
+use crate::telemetry::{Telemetry, TelemetryEvent};
+use crate::CameraExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio::sync::oneshot;
+
+/// How often each stream is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest wait between two attempts to reopen a stalled capture
+const MAX_RESTART_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time of the latest frame of a stream, updated by its capture callback
+#[derive(Clone)]
+pub struct Heartbeat {
+    started: Instant,
+    /// Milliseconds since `started`
+    last_ms: Arc<AtomicU64>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn beat(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        self.last_ms.store(now, Ordering::Relaxed);
+    }
+
+    /// Time since the latest frame, or since the stream started before the first one
+    pub fn idle(&self) -> Duration {
+        let last = Duration::from_millis(self.last_ms.load(Ordering::Relaxed));
+        self.started.elapsed().saturating_sub(last)
+    }
+}
+
+struct Stall {
+    since: Instant,
+    restarts: u32,
+    next_restart: Instant,
+    /// The device was missing from the device list at the last check
+    disconnected: bool,
+}
+
+/// Wait before the next attempt: the stall timeout, doubled after every failed attempt
+fn restart_interval(timeout: Duration, restarts: u32) -> Duration {
+    timeout
+        .saturating_mul(1 << restarts.min(16))
+        .min(MAX_RESTART_INTERVAL)
+}
+
+/// Report a stream failure on `camera://stream-error` and to telemetry
+fn emit_error<R: Runtime>(app: &AppHandle<R>, telemetry: &Telemetry, event: StreamErrorEvent) {
+    telemetry.error(Some(&event.device_id), "stream", event.error.clone());
+    if let Err(e) = app.emit("camera://stream-error", event) {
+        log::warn!("Failed to emit stream error: {}", e);
+    }
+}
+
+/// Whether the system still lists the device; assumed so when devices can't be listed
+async fn device_connected<R: Runtime>(app: &AppHandle<R>, device_id: &str) -> bool {
+    match app.camera().get_available_cameras().await {
+        Ok(devices) => devices.iter().any(|device| device.id == device_id),
+        Err(_) => true,
+    }
+}
+
+/// Watch a stream until `stop` fires or is dropped with the stream. When no frame arrives
+/// for `timeout` (e.g. a device wedged after sleep/resume), emit `camera://stream-stalled`
+/// and reopen the capture, with backoff, until frames flow again and
+/// `camera://stream-recovered` is emitted. An unplugged device is reported on
+/// `camera://stream-error` and reopened as soon as it is listed again. Without
+/// `auto_reconnect`, the stalled stream is stopped instead.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn<R: Runtime>(
+    app: AppHandle<R>,
+    stream_id: String,
+    device_id: String,
+    heartbeat: Heartbeat,
+    timeout: Duration,
+    auto_reconnect: bool,
+    telemetry: Telemetry,
+    mut stop: oneshot::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let mut check = tokio::time::interval(CHECK_INTERVAL);
+        let mut stalled: Option<Stall> = None;
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                _ = check.tick() => {}
+            }
+
+            let idle = heartbeat.idle();
+            if idle < timeout {
+                if let Some(stall) = stalled.take() {
+                    let stalled_for = stall.since.elapsed();
+                    log::info!(
+                        "Stream {} recovered after {:?} and {} restart(s)",
+                        stream_id,
+                        stalled_for,
+                        stall.restarts
+                    );
+                    let event = StreamRecoveredEvent {
+                        stream_id: stream_id.clone(),
+                        device_id: device_id.clone(),
+                        stalled_ms: stalled_for.as_millis() as u64,
+                        restarts: stall.restarts,
+                    };
+                    if let Err(e) = app.emit("camera://stream-recovered", event) {
+                        log::warn!("Failed to emit stream recovery: {}", e);
+                    }
+                    telemetry.emit(TelemetryEvent::StreamRecovered {
+                        stream_id: stream_id.clone(),
+                        device_id: device_id.clone(),
+                        stalled_for,
+                        restarts: stall.restarts,
+                    });
+                }
+                continue;
+            }
+
+            let stall = stalled.get_or_insert_with(|| {
+                log::warn!("Stream {} stalled: no frame for {:?}", stream_id, idle);
+                let event = StreamStalledEvent {
+                    stream_id: stream_id.clone(),
+                    device_id: device_id.clone(),
+                    idle_ms: idle.as_millis() as u64,
+                };
+                if let Err(e) = app.emit("camera://stream-stalled", event) {
+                    log::warn!("Failed to emit stream stall: {}", e);
+                }
+                telemetry.emit(TelemetryEvent::StreamStalled {
+                    stream_id: stream_id.clone(),
+                    device_id: device_id.clone(),
+                });
+                let now = Instant::now();
+                Stall {
+                    since: now.checked_sub(idle).unwrap_or(now),
+                    restarts: 0,
+                    next_restart: now,
+                    disconnected: false,
+                }
+            });
+
+            let connected = device_connected(&app, &device_id).await;
+            if !auto_reconnect {
+                log::info!("Stopping stalled stream {}", stream_id);
+                emit_error(
+                    &app,
+                    &telemetry,
+                    StreamErrorEvent {
+                        stream_id: stream_id.clone(),
+                        device_id: device_id.clone(),
+                        error: if connected {
+                            format!("No frame received for {:?}", idle)
+                        } else {
+                            "Device disconnected".to_string()
+                        },
+                        device_connected: connected,
+                        reconnecting: false,
+                    },
+                );
+                if let Err(e) = app.camera().stop_streaming(stream_id.clone()).await {
+                    log::warn!("Failed to stop stalled stream {}: {}", stream_id, e);
+                }
+                break;
+            }
+            if !connected {
+                if !stall.disconnected {
+                    log::warn!("Device {} of stream {} disconnected", device_id, stream_id);
+                    stall.disconnected = true;
+                    emit_error(
+                        &app,
+                        &telemetry,
+                        StreamErrorEvent {
+                            stream_id: stream_id.clone(),
+                            device_id: device_id.clone(),
+                            error: "Device disconnected".to_string(),
+                            device_connected: false,
+                            reconnecting: true,
+                        },
+                    );
+                }
+                continue;
+            }
+            if stall.disconnected {
+                // Back: reopen it right away
+                log::info!("Device {} of stream {} is back", device_id, stream_id);
+                stall.disconnected = false;
+                stall.next_restart = Instant::now();
+            }
+            if Instant::now() < stall.next_restart {
+                continue;
+            }
+
+            log::info!("Reopening the capture of stalled stream {}", stream_id);
+            if let Err(e) = app.camera().restart_capture(&stream_id).await {
+                log::warn!("Failed to reopen the capture of {}: {}", stream_id, e);
+                emit_error(
+                    &app,
+                    &telemetry,
+                    StreamErrorEvent {
+                        stream_id: stream_id.clone(),
+                        device_id: device_id.clone(),
+                        error: e.to_string(),
+                        device_connected: true,
+                        reconnecting: true,
+                    },
+                );
+            }
+            stall.next_restart = Instant::now() + restart_interval(timeout, stall.restarts);
+            stall.restarts += 1;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_backoff() {
+        let timeout = Duration::from_secs(5);
+        let intervals: Vec<u64> = (0..6)
+            .map(|restarts| restart_interval(timeout, restarts).as_secs())
+            .collect();
+        assert_eq!(intervals, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(restart_interval(timeout, u32::MAX), MAX_RESTART_INTERVAL);
+    }
+}
@@ -1,11 +1,14 @@
 use crate::error::{Error, Result};
+use crate::models::{EncoderSettings, H264Profile, MediaDirection, MediaLivenessEvent, VideoCodec};
+use crate::telemetry::{Telemetry, TelemetryEvent};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use tokio::sync::mpsc;
-use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::{Mutex as AsyncMutex, Notify};
 use tokio::time::Duration;
 use webrtc::api::interceptor_registry::register_default_interceptors;
 use webrtc::api::media_engine::MediaEngine;
@@ -13,8 +16,16 @@ use webrtc::api::APIBuilder;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::media::Sample;
 use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::signaling_state::RTCSignalingState;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtcp::payload_feedbacks::full_intra_request::FullIntraRequest;
+use webrtc::rtcp::payload_feedbacks::picture_loss_indication::PictureLossIndication;
+use webrtc::rtcp::payload_feedbacks::receiver_estimated_maximum_bitrate::ReceiverEstimatedMaximumBitrate;
+use webrtc::rtcp::receiver_report::ReceiverReport;
 use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::rtp_transceiver::rtp_sender::RTCRtpSender;
 use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
 use webrtc::track::track_local::TrackLocal;
 
@@ -25,14 +36,118 @@ pub struct VideoStream {
     pub tx: mpsc::UnboundedSender<Vec<u8>>, // Send encoded H.264 data
 }
 
+/// `fmtp` of H.264 tracks before an answer negotiated one: Constrained Baseline 3.1, in
+/// non-interleaved mode, which every WebRTC peer decodes
+const DEFAULT_H264_FMTP: &str =
+    "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f";
+
+/// How often connections are checked for stalled media
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 /// WebRTC peer connection wrapper
 pub struct PeerConnection {
-    #[allow(dead_code)]
     pub id: String,
     pub pc: Arc<RTCPeerConnection>,
-    pub video_track: AsyncMutex<Option<Arc<TrackLocalStaticSample>>>, // H.264 video track if attached
+    pub video_track: AsyncMutex<Option<Arc<TrackLocalStaticSample>>>, // Video track if attached
+    /// Sender of the video track, kept to replace the track when the answer picks another codec
+    video_sender: AsyncMutex<Option<Arc<RTCRtpSender>>>,
+    pub activity: Arc<MediaActivity>,
+    /// Set while a bandwidth probe pads the video track
+    pub probing: AtomicBool,
+    /// Keyframes asked by the peer (PLI/FIR) or by `request_keyframe`, for the sink
+    /// feeding the track; requests made before the next keyframe count as one
+    pub keyframe_requests: Arc<Notify>,
+}
+
+/// Last media seen on a connection in each direction
+pub struct MediaActivity {
+    created: Instant,
+    /// Milliseconds since `created` of the last sample sent, 0 before the first one
+    sent_ms: AtomicU64,
+    /// Milliseconds since `created` of the last RTCP packet received, 0 before the first one
+    received_ms: AtomicU64,
+    /// Bytes of video samples sent
+    sent_bytes: AtomicU64,
+    /// Receiver reports received, and the loss (out of 256) in the latest one
+    loss_reports: AtomicU64,
+    fraction_lost: AtomicU8,
+    /// Latest REMB bandwidth estimate of the peer in kbps, 0 before the first one
+    remb_kbps: AtomicU32,
 }
 
+impl MediaActivity {
+    fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            sent_ms: AtomicU64::new(0),
+            received_ms: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            loss_reports: AtomicU64::new(0),
+            fraction_lost: AtomicU8::new(0),
+            remb_kbps: AtomicU32::new(0),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        (self.created.elapsed().as_millis() as u64).max(1)
+    }
+
+    fn sent(&self, bytes: usize) {
+        self.sent_ms.store(self.now_ms(), Ordering::Relaxed);
+        self.sent_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn received(&self) {
+        self.received_ms.store(self.now_ms(), Ordering::Relaxed);
+    }
+
+    fn report(&self, fraction_lost: u8) {
+        self.fraction_lost.store(fraction_lost, Ordering::Relaxed);
+        self.loss_reports.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn estimate(&self, kbps: u32) {
+        self.remb_kbps.store(kbps.max(1), Ordering::Relaxed);
+    }
+
+    pub(crate) fn sent_bytes(&self) -> u64 {
+        self.sent_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Receiver reports received so far
+    pub(crate) fn loss_reports(&self) -> u64 {
+        self.loss_reports.load(Ordering::Relaxed)
+    }
+
+    /// Share of packets lost, from the latest receiver report
+    pub(crate) fn fraction_lost(&self) -> f32 {
+        self.fraction_lost.load(Ordering::Relaxed) as f32 / 256.0
+    }
+
+    /// Bandwidth the peer estimates it receives, if it sends REMB feedback
+    #[cfg_attr(mobile, allow(dead_code))]
+    pub(crate) fn remb_kbps(&self) -> Option<u32> {
+        Some(self.remb_kbps.load(Ordering::Relaxed)).filter(|kbps| *kbps > 0)
+    }
+
+    fn sending(&self) -> bool {
+        self.sent_ms.load(Ordering::Relaxed) != 0
+    }
+
+    /// Time without media in `direction`, counted from `since_ms` at the earliest
+    fn idle(&self, direction: MediaDirection, since_ms: u64) -> Duration {
+        let last = match direction {
+            MediaDirection::Outbound => &self.sent_ms,
+            MediaDirection::Inbound => &self.received_ms,
+        };
+        let last = last.load(Ordering::Relaxed).max(since_ms);
+        Duration::from_millis(self.now_ms().saturating_sub(last))
+    }
+}
+
+/// Receives media stall and resume notifications of the connections
+pub type LivenessHook = Arc<dyn Fn(MediaLivenessEvent) + Send + Sync>;
+
 /// WebRTC manager state
 #[derive(Clone)]
 pub struct WebRTCManager {
@@ -40,18 +155,30 @@ pub struct WebRTCManager {
     streams: Arc<AsyncMutex<HashMap<String, Arc<VideoStream>>>>, // Active video streams
     connection_to_device: Arc<AsyncMutex<HashMap<String, String>>>, // Map connection_id -> device_id
     connection_to_stream: Arc<AsyncMutex<HashMap<String, String>>>, // Map connection_id -> stream_id
+    telemetry: Telemetry,
+    /// Time without media after which a connection is reported stalled, and who to tell
+    liveness: Option<(Duration, LivenessHook)>,
 }
 
 impl WebRTCManager {
-    pub fn new() -> Self {
+    pub fn new(telemetry: Telemetry) -> Self {
         Self {
             connections: Arc::new(AsyncMutex::new(HashMap::new())),
             streams: Arc::new(AsyncMutex::new(HashMap::new())),
             connection_to_device: Arc::new(AsyncMutex::new(HashMap::new())),
             connection_to_stream: Arc::new(AsyncMutex::new(HashMap::new())),
+            telemetry,
+            liveness: None,
         }
     }
 
+    /// Watch every connection for media going quiet: no sample sent or no RTCP received
+    /// for `timeout` while connected
+    pub fn with_liveness(mut self, timeout: Duration, hook: LivenessHook) -> Self {
+        self.liveness = Some((timeout, hook));
+        self
+    }
+
     /// Register device_id for a connection (for cleanup on close)
     pub async fn register_device_for_connection(
         &self,
@@ -96,6 +223,17 @@ impl WebRTCManager {
             .cloned()
     }
 
+    /// Connections linked to a stream
+    pub async fn connections_for_stream(&self, stream_id: &str) -> Vec<String> {
+        self.connection_to_stream
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, stream)| *stream == stream_id)
+            .map(|(connection_id, _)| connection_id.clone())
+            .collect()
+    }
+
     /// Create a new peer connection
     pub async fn create_peer_connection(&self, ice_servers: Vec<RTCIceServer>) -> Result<String> {
         let id = uuid::Uuid::new_v4().to_string();
@@ -132,18 +270,104 @@ impl WebRTCManager {
                 Error::CameraError(format!("Failed to create peer connection: {}", e))
             })?);
 
+        // Report connection quality changes (disconnected, failed, reconnected) to telemetry
+        let telemetry = self.telemetry.clone();
+        let connection_id = id.clone();
+        pc.on_peer_connection_state_change(Box::new(move |state| {
+            telemetry.emit(TelemetryEvent::ConnectionStateChanged {
+                connection_id: connection_id.clone(),
+                state: state.to_string(),
+            });
+            Box::pin(async {})
+        }));
+
         let peer_conn = Arc::new(PeerConnection {
             id: id.clone(),
             pc: pc.clone(),
             video_track: AsyncMutex::new(None),
+            video_sender: AsyncMutex::new(None),
+            activity: Arc::new(MediaActivity::new()),
+            probing: AtomicBool::new(false),
+            keyframe_requests: Arc::new(Notify::new()),
         });
 
         // Store the connection
         self.connections.lock().await.insert(id.clone(), peer_conn);
+        if let Some((timeout, hook)) = &self.liveness {
+            self.spawn_liveness_monitor(id.clone(), *timeout, hook.clone());
+        }
 
         Ok(id)
     }
 
+    /// Report when media stops or resumes flowing on a connection, until it is removed
+    fn spawn_liveness_monitor(&self, connection_id: String, timeout: Duration, hook: LivenessHook) {
+        let connections = Arc::downgrade(&self.connections);
+        let telemetry = self.telemetry.clone();
+        tokio::spawn(async move {
+            let mut check = tokio::time::interval(LIVENESS_CHECK_INTERVAL);
+            // Start of the period media is expected: connected and sending
+            let mut watched_since: Option<u64> = None;
+            let mut stalled = [false; 2];
+            loop {
+                check.tick().await;
+                let Some(connections) = connections.upgrade() else {
+                    break;
+                };
+                let Some(conn) = connections.lock().await.get(&connection_id).cloned() else {
+                    break;
+                };
+                let activity = &conn.activity;
+                if conn.pc.connection_state() != RTCPeerConnectionState::Connected
+                    || !activity.sending()
+                {
+                    // Connection state changes are reported on their own
+                    watched_since = None;
+                    stalled = [false; 2];
+                    continue;
+                }
+                let since = *watched_since.get_or_insert_with(|| activity.now_ms());
+
+                for (index, direction) in [MediaDirection::Outbound, MediaDirection::Inbound]
+                    .into_iter()
+                    .enumerate()
+                {
+                    let idle = activity.idle(direction, since);
+                    let now_stalled = idle >= timeout;
+                    if now_stalled == stalled[index] {
+                        continue;
+                    }
+                    stalled[index] = now_stalled;
+                    if now_stalled {
+                        log::warn!(
+                            "No {:?} media on connection {} for {:?}",
+                            direction,
+                            connection_id,
+                            idle
+                        );
+                    } else {
+                        log::info!(
+                            "{:?} media resumed on connection {}",
+                            direction,
+                            connection_id
+                        );
+                    }
+                    telemetry.emit(TelemetryEvent::MediaLiveness {
+                        connection_id: connection_id.clone(),
+                        direction,
+                        stalled: now_stalled,
+                    });
+                    hook(MediaLivenessEvent {
+                        connection_id: connection_id.clone(),
+                        direction,
+                        stalled: now_stalled,
+                        idle_ms: idle.as_millis() as u64,
+                    });
+                }
+            }
+        });
+    }
+
     /// Get a peer connection by ID
     pub async fn get_connection(&self, id: &str) -> Result<Arc<PeerConnection>> {
         self.connections
@@ -154,6 +378,60 @@ impl WebRTCManager {
             .ok_or_else(|| Error::CameraError(format!("Peer connection not found: {}", id)))
     }
 
+    /// Number of open peer connections
+    pub async fn connection_count(&self) -> usize {
+        self.connections.lock().await.len()
+    }
+
+    /// Restart ICE with a new offer, e.g. after a network change. The offer must reach the
+    /// peer, and its answer come back through `set_remote_description`.
+    pub async fn restart_ice(&self, connection_id: &str) -> Result<SessionDescriptionData> {
+        let conn = self.get_connection(connection_id).await?;
+        let options = RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        };
+        let offer = conn.pc.create_offer(Some(options)).await.map_err(|e| {
+            Error::CameraError(format!("Failed to create ICE restart offer: {}", e))
+        })?;
+        conn.pc
+            .set_local_description(offer.clone())
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to set local description: {}", e)))?;
+
+        Ok(SessionDescriptionData {
+            sdp_type: offer.sdp_type.to_string(),
+            sdp: offer.sdp,
+        })
+    }
+
+    /// Connections whose ICE should be restarted after a network change: established
+    /// or losing connectivity, and not in the middle of a negotiation
+    pub async fn restartable_connections(&self) -> Vec<String> {
+        self.connections
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, conn)| {
+                matches!(
+                    conn.pc.connection_state(),
+                    RTCPeerConnectionState::Connected
+                        | RTCPeerConnectionState::Disconnected
+                        | RTCPeerConnectionState::Failed
+                ) && conn.pc.signaling_state() == RTCSignalingState::Stable
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Make the video of a connection resume from a keyframe, e.g. after the remote
+    /// decoder reported corruption through a side channel
+    pub async fn request_keyframe(&self, connection_id: &str) -> Result<()> {
+        let conn = self.get_connection(connection_id).await?;
+        conn.keyframe_requests.notify_one();
+        Ok(())
+    }
+
     /// Remove a peer connection
     pub async fn remove_connection(&self, id: &str) -> Result<()> {
         let device_id = self.get_device_for_connection(id).await;
@@ -176,59 +454,168 @@ impl WebRTCManager {
     /// Attach an H.264 video track to the PeerConnection.
     /// This prepares the connection to accept encoded H.264 samples.
     pub async fn attach_h264_video_track(&self, id: &str) -> Result<()> {
+        self.attach_video_track(id, VideoCodec::H264).await
+    }
+
+    /// Attach a video track of the given codec to the PeerConnection
+    /// A connection carries a single video track, attaching another codec fails.
+    pub async fn attach_video_track(&self, id: &str, codec: VideoCodec) -> Result<()> {
         let conn = self.get_connection(id).await?;
         let mut video_track_guard = conn.video_track.lock().await;
 
         // If already attached, do nothing
-        if video_track_guard.is_some() {
+        if let Some(track) = video_track_guard.as_ref() {
+            let attached = track.codec().mime_type;
+            if !attached.eq_ignore_ascii_case(codec.mime_type()) {
+                return Err(Error::InvalidConfig(format!(
+                    "Connection {} already carries a {} track",
+                    id, attached
+                )));
+            }
             return Ok(());
         }
 
-        // Create a static sample track for H.264 video
-        let track = Arc::new(TrackLocalStaticSample::new(
-            RTCRtpCodecCapability {
-                mime_type: "video/h264".to_string(),
-                ..Default::default()
-            },
-            "tauri-camera".to_string(),
-            "tauri-camera-stream".to_string(),
-        ));
+        let fmtp = match codec {
+            VideoCodec::H264 => DEFAULT_H264_FMTP,
+            _ => "",
+        };
+        let track = video_track(id, codec, fmtp);
 
         // Add to PeerConnection
-        conn.pc
+        let sender = conn
+            .pc
             .add_track(Arc::clone(&track) as Arc<dyn TrackLocal + Send + Sync>)
             .await
             .map_err(|e| Error::CameraError(format!("Failed to add video track: {}", e)))?;
+        *conn.video_sender.lock().await = Some(sender.clone());
+
+        // Receiver reports of the remote peer tell it still gets the video; reading them
+        // also lets the interceptors (NACK, reports) process them. Ends with the connection.
+        let activity = conn.activity.clone();
+        let keyframe_requests = conn.keyframe_requests.clone();
+        tokio::spawn(async move {
+            while let Ok((packets, _)) = sender.read_rtcp().await {
+                activity.received();
+                // The peer can't decode the picture anymore (loss, or it joined late)
+                if packets.iter().any(|packet| {
+                    packet.as_any().is::<PictureLossIndication>()
+                        || packet.as_any().is::<FullIntraRequest>()
+                }) {
+                    keyframe_requests.notify_one();
+                }
+                // Loss of the video, fed to bandwidth probes
+                let fraction_lost = packets
+                    .iter()
+                    .filter_map(|packet| packet.as_any().downcast_ref::<ReceiverReport>())
+                    .flat_map(|report| report.reports.iter())
+                    .map(|reception| reception.fraction_lost)
+                    .max();
+                if let Some(fraction_lost) = fraction_lost {
+                    activity.report(fraction_lost);
+                }
+                let remb = packets.iter().find_map(|packet| {
+                    packet
+                        .as_any()
+                        .downcast_ref::<ReceiverEstimatedMaximumBitrate>()
+                });
+                if let Some(remb) = remb {
+                    activity.estimate((remb.bitrate / 1000.0) as u32);
+                }
+            }
+        });
 
         *video_track_guard = Some(track);
         Ok(())
     }
 
-    /// Push an encoded H.264 access unit to the attached video track.
-    /// `data` must be an Annex B byte stream (e.g., NAL units with start codes),
-    /// already encoded as H.264 matching negotiated profile/level.
-    pub async fn push_h264_sample(&self, id: &str, data: Vec<u8>, duration_ms: u64) -> Result<()> {
+    /// Replace the video track of a connection by one of the codec the answer negotiated,
+    /// with its `fmtp` parameters; the sink feeding the old track has to be reconnected
+    #[cfg_attr(mobile, allow(dead_code))]
+    pub async fn replace_video_track(&self, id: &str, negotiated: &NegotiatedCodec) -> Result<()> {
+        let conn = self.get_connection(id).await?;
+        let sender = conn
+            .video_sender
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| Error::CameraError("No video track attached".to_string()))?;
+        let track = video_track(id, negotiated.codec, &negotiated.fmtp);
+        sender
+            .replace_track(Some(Arc::clone(&track) as Arc<dyn TrackLocal + Send + Sync>))
+            .await
+            .map_err(|e| Error::CameraError(format!("Failed to replace video track: {}", e)))?;
+        *conn.video_track.lock().await = Some(track);
+        Ok(())
+    }
+
+    /// Codec of the video track attached to a connection, if any
+    pub async fn video_codec(&self, id: &str) -> Result<Option<VideoCodec>> {
+        let conn = self.get_connection(id).await?;
+        let video_track_guard = conn.video_track.lock().await;
+        Ok(video_track_guard
+            .as_ref()
+            .and_then(|track| VideoCodec::from_mime_type(&track.codec().mime_type)))
+    }
+
+    /// Push an encoded access unit to the attached video track.
+    /// H.264 `data` must be an Annex B byte stream (e.g., NAL units with start codes)
+    /// matching the negotiated profile/level; VP8 and VP9 `data` is one compressed frame.
+    /// `duration` advances the RTP timestamp; `captured` is the frame's wall-clock capture time.
+    pub async fn push_video_sample(
+        &self,
+        id: &str,
+        data: Vec<u8>,
+        duration: Duration,
+        captured: SystemTime,
+    ) -> Result<()> {
         let conn = self.get_connection(id).await?;
         let video_track_guard = conn.video_track.lock().await;
         let track = video_track_guard
             .as_ref()
             .ok_or_else(|| Error::CameraError("No video track attached".to_string()))?;
 
+        let bytes = data.len();
         let sample = Sample {
             data: Bytes::from(data),
-            duration: Duration::from_millis(duration_ms),
-            timestamp: SystemTime::now(),
+            duration,
+            timestamp: captured,
             ..Default::default()
         };
 
         track
             .write_sample(&sample)
             .await
-            .map_err(|e| Error::CameraError(format!("Failed to write H.264 sample: {}", e)))?;
+            .map_err(|e| Error::CameraError(format!("Failed to write video sample: {}", e)))?;
+        conn.activity.sent(bytes);
 
         Ok(())
     }
 
+    /// Estimate the uplink available to a connection by padding its video track at rising
+    /// rates until the peer reports loss. Works before media flows, once connected.
+    pub async fn probe_bandwidth(
+        &self,
+        connection_id: &str,
+        max_kbps: Option<u32>,
+    ) -> Result<BandwidthEstimate> {
+        let conn = self.get_connection(connection_id).await?;
+        if conn.pc.connection_state() != RTCPeerConnectionState::Connected {
+            return Err(Error::CameraError(format!(
+                "Connection {} is not connected",
+                connection_id
+            )));
+        }
+        if conn.probing.swap(true, Ordering::AcqRel) {
+            return Err(Error::CameraError(format!(
+                "A bandwidth probe is already running on {}",
+                connection_id
+            )));
+        }
+        let estimate = crate::bandwidth::probe(&conn, max_kbps).await;
+        conn.probing.store(false, Ordering::Release);
+        estimate
+    }
+
     /// Start a video stream from a camera device, optionally tied to a WebRTC connection
     /// Returns a session ID for managing the stream
     pub async fn start_streaming(
@@ -270,11 +657,10 @@ impl WebRTCManager {
     /// This spawns a background task that consumes frames from the receiver
     /// and encodes/pushes them to the WebRTC track
     pub async fn attach_receiver_to_connection(&self, connection_id: &str) -> Result<()> {
-        // Verify connection exists
-        let _ = self.get_connection(connection_id).await?;
-
-        // Ensure track is attached
-        self.attach_h264_video_track(connection_id).await?;
+        // Ensure a track is attached, H.264 unless the connection already has one
+        if self.video_codec(connection_id).await?.is_none() {
+            self.attach_h264_video_track(connection_id).await?;
+        }
 
         // NOTE: The background task that consumes frames from the receiver
         // should be spawned by the caller, as it needs access to the receiver
@@ -284,6 +670,105 @@ impl WebRTCManager {
     }
 }
 
+/// Static sample track of `codec`, in a media stream of its own so a page receiving
+/// several cameras gets one MediaStream per connection
+fn video_track(id: &str, codec: VideoCodec, fmtp: &str) -> Arc<TrackLocalStaticSample> {
+    Arc::new(TrackLocalStaticSample::new(
+        RTCRtpCodecCapability {
+            mime_type: codec.mime_type().to_string(),
+            clock_rate: 90000,
+            sdp_fmtp_line: fmtp.to_string(),
+            ..Default::default()
+        },
+        "tauri-camera".to_string(),
+        format!("tauri-camera-{}", id),
+    ))
+}
+
+/// Video codec of an SDP answer, with its format parameters
+#[cfg_attr(mobile, allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegotiatedCodec {
+    pub codec: VideoCodec,
+    pub fmtp: String,
+}
+
+#[cfg_attr(mobile, allow(dead_code))]
+impl NegotiatedCodec {
+    /// Highest H.264 profile the peer decodes, from the `profile-level-id` of the `fmtp`
+    /// Constrained Baseline counts as Baseline; `None` for other codecs and profiles.
+    pub fn h264_profile(&self) -> Option<H264Profile> {
+        if self.codec != VideoCodec::H264 {
+            return None;
+        }
+        let profile_level_id = self
+            .fmtp
+            .split(';')
+            .find_map(|param| param.trim().strip_prefix("profile-level-id="))?;
+        match u8::from_str_radix(profile_level_id.get(..2)?, 16).ok()? {
+            0x42 => Some(H264Profile::Baseline),
+            0x4d => Some(H264Profile::Main),
+            0x64 => Some(H264Profile::High),
+            _ => None,
+        }
+    }
+}
+
+/// Video codecs the plugin can send among those of the first video section of an SDP
+/// answer, in the peer's order of preference
+/// Retransmission and FEC formats are left out. Fails when the answer has no video or
+/// rejected it.
+#[cfg_attr(mobile, allow(dead_code))]
+pub fn answer_video_codecs(sdp: &str) -> Result<Vec<NegotiatedCodec>> {
+    let mut formats: Option<Vec<&str>> = None;
+    let mut names = HashMap::new();
+    let mut fmtps = HashMap::new();
+    for line in sdp.lines().map(str::trim_end) {
+        if let Some(media) = line.strip_prefix("m=") {
+            if formats.is_some() {
+                break;
+            }
+            let fields: Vec<&str> = media.split_whitespace().collect();
+            if fields.first() == Some(&"video") {
+                if fields.get(1) == Some(&"0") {
+                    return Err(Error::CameraError(
+                        "The peer rejected the video of the connection".to_string(),
+                    ));
+                }
+                formats = Some(fields.into_iter().skip(3).collect());
+            }
+            continue;
+        }
+        if formats.is_none() {
+            continue;
+        }
+        if let Some((format, rtpmap)) = line
+            .strip_prefix("a=rtpmap:")
+            .and_then(|rtpmap| rtpmap.split_once(' '))
+        {
+            let name = rtpmap.split('/').next().unwrap_or_default();
+            names.insert(format, name);
+        } else if let Some((format, fmtp)) = line
+            .strip_prefix("a=fmtp:")
+            .and_then(|fmtp| fmtp.split_once(' '))
+        {
+            fmtps.insert(format, fmtp);
+        }
+    }
+    let formats = formats
+        .ok_or_else(|| Error::CameraError("The peer's answer has no video section".to_string()))?;
+    Ok(formats
+        .into_iter()
+        .filter_map(|format| {
+            let codec = VideoCodec::from_mime_type(&format!("video/{}", names.get(format)?))?;
+            Some(NegotiatedCodec {
+                codec,
+                fmtp: fmtps.get(format).copied().unwrap_or_default().to_string(),
+            })
+        })
+        .collect())
+}
+
 // ============================================================================
 // Data Types for WebRTC Commands
 // ============================================================================
@@ -297,7 +782,7 @@ pub struct IceServer {
     pub credential: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct VideoConfig {
     #[serde(default)]
     pub width: Option<u32>,
@@ -305,6 +790,12 @@ pub struct VideoConfig {
     pub height: Option<u32>,
     #[serde(default)]
     pub fps: Option<f64>,
+    /// Codec of the track, H.264 when unset
+    #[serde(default)]
+    pub codec: Option<VideoCodec>,
+    /// Settings of the encoder feeding the track, instead of the device profile's
+    #[serde(default)]
+    pub encoder: Option<EncoderSettings>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -340,9 +831,64 @@ pub struct SessionDescriptionData {
     pub sdp: String,
 }
 
+/// Uplink estimate returned by `probe_bandwidth`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthEstimate {
+    pub connection_id: String,
+    /// Rate the peer received without noticeable loss
+    pub available_kbps: u32,
+    /// Share of packets lost at the last rate tried
+    pub loss: f32,
+    /// The probe stopped on loss before reaching its maximum rate
+    pub limited: bool,
+    pub duration_ms: u64,
+}
+
+/// Emitted on `camera://ice-restart` with the offer to relay to the peer
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceRestartEvent {
+    pub connection_id: String,
+    pub offer: SessionDescriptionData,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IceCandidateData {
     pub candidate: String,
     pub sdp_mid: Option<String>,
     pub sdp_m_line_index: Option<u16>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_video_codecs() {
+        let answer = "v=0\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+            a=rtpmap:111 opus/48000/2\r\n\
+            m=video 9 UDP/TLS/RTP/SAVPF 98 99 102 45\r\n\
+            a=rtpmap:98 VP9/90000\r\n\
+            a=fmtp:98 profile-id=0\r\n\
+            a=rtpmap:99 rtx/90000\r\n\
+            a=fmtp:99 apt=98\r\n\
+            a=rtpmap:102 H264/90000\r\n\
+            a=fmtp:102 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=640c1f\r\n\
+            a=rtpmap:45 AV1/90000\r\n";
+        let codecs = answer_video_codecs(answer).unwrap();
+        let kinds: Vec<VideoCodec> = codecs.iter().map(|codec| codec.codec).collect();
+        assert_eq!(
+            kinds,
+            vec![VideoCodec::Vp9, VideoCodec::H264, VideoCodec::Av1]
+        );
+        assert_eq!(codecs[0].fmtp, "profile-id=0");
+        assert_eq!(codecs[1].h264_profile(), Some(H264Profile::High));
+        assert_eq!(codecs[0].h264_profile(), None);
+
+        let rejected = "v=0\r\nm=video 0 UDP/TLS/RTP/SAVPF 96\r\na=rtpmap:96 VP8/90000\r\n";
+        assert!(answer_video_codecs(rejected).is_err());
+        assert!(answer_video_codecs("v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\n").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::error::Result;
+use crate::models::Config;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Name of the plugin's own directory inside the configured temp directory,
+/// so cleanup never touches files the plugin didn't create
+const WORK_DIR_NAME: &str = "tauri-plugin-camera";
+
+/// Working directory for encoding artifacts (segment temp files, thumbnails, replay buffers)
+/// Leftovers of a previous run are removed at startup and the directory is removed on exit.
+pub struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    /// Inside `Config::temp_dir`, or the app cache directory by default
+    pub fn new<R: Runtime>(app: &AppHandle<R>, config: &Config) -> Result<Self> {
+        let parent = match &config.temp_dir {
+            Some(dir) => dir.clone(),
+            None => app.path().app_cache_dir()?,
+        };
+        let work_dir = Self {
+            path: parent.join(WORK_DIR_NAME),
+        };
+        work_dir.cleanup();
+        Ok(work_dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path for a temporary file, creating the directory if needed
+    pub fn file(&self, name: &str) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.path)?;
+        Ok(self.path.join(name))
+    }
+
+    /// Remove the directory and everything in it
+    pub fn cleanup(&self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => log::info!("Removed camera work directory {:?}", self.path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!(
+                "Failed to remove camera work directory {:?}: {}",
+                self.path,
+                e
+            ),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::models::ZoomRange;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Highest digital zoom, past which a crop is too blurry to be useful
+pub const MAX_DIGITAL_ZOOM: f32 = 8.0;
+
+/// Digital zoom factor of a device, shared with the capture callbacks of its streams
+/// 1.0 leaves frames untouched; above, the scale stage crops the center of the frame
+/// and scales it back up.
+#[derive(Clone)]
+pub struct DigitalZoom(Arc<AtomicU32>);
+
+impl Default for DigitalZoom {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl DigitalZoom {
+    pub fn new(factor: f32) -> Self {
+        Self(Arc::new(AtomicU32::new(factor.to_bits())))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, factor: f32) {
+        self.0.store(factor.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct DeviceZoom {
+    digital: DigitalZoom,
+    factor: f32,
+    /// The device zooms itself; the digital zoom stays at 1.0
+    platform: bool,
+}
+
+/// Zoom of each device, kept for the session
+#[derive(Default)]
+pub struct Zooms {
+    devices: Mutex<HashMap<String, DeviceZoom>>,
+}
+
+impl Zooms {
+    /// Digital zoom of a device, for a stream starting on it
+    pub fn digital(&self, device_id: &str) -> DigitalZoom {
+        self.devices
+            .lock()
+            .unwrap()
+            .entry(device_id.to_string())
+            .or_insert_with(DeviceZoom::unzoomed)
+            .digital
+            .clone()
+    }
+
+    /// Record the zoom of a device, done by the device itself or digitally
+    pub fn set(&self, device_id: &str, factor: f32, platform: bool) {
+        let mut devices = self.devices.lock().unwrap();
+        let zoom = devices
+            .entry(device_id.to_string())
+            .or_insert_with(DeviceZoom::unzoomed);
+        zoom.digital.set(if platform { 1.0 } else { factor });
+        zoom.factor = factor;
+        zoom.platform = platform;
+    }
+
+    pub fn range(&self, device_id: &str) -> ZoomRange {
+        let devices = self.devices.lock().unwrap();
+        let zoom = devices.get(device_id);
+        ZoomRange {
+            min: 1.0,
+            max: MAX_DIGITAL_ZOOM,
+            zoom: zoom.map_or(1.0, |zoom| zoom.factor),
+            digital: !zoom.is_some_and(|zoom| zoom.platform),
+        }
+    }
+}
+
+impl DeviceZoom {
+    fn unzoomed() -> Self {
+        Self {
+            factor: 1.0,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_zoom_resets_digital_zoom() {
+        let zooms = Zooms::default();
+        let digital = zooms.digital("0");
+        zooms.set("0", 2.5, false);
+        assert_eq!(digital.get(), 2.5);
+        assert!(zooms.range("0").digital);
+
+        zooms.set("0", 3.0, true);
+        assert_eq!(digital.get(), 1.0);
+        let range = zooms.range("0");
+        assert_eq!(range.zoom, 3.0);
+        assert!(!range.digital);
+    }
+}
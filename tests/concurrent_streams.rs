@@ -0,0 +1,195 @@
+//! Two devices streaming at once, each into its own sinks, stopped independently
+#![cfg(feature = "mock-camera")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+use tauri_plugin_camera::{CameraExt, FrameEvent, MockCamera, StreamOptions, MOCK_DEVICE_ID};
+
+const SECOND_DEVICE_ID: &str = "mock-1";
+
+fn app() -> tauri::App<MockRuntime> {
+    let app = mock_builder()
+        .plugin(tauri_plugin_camera::init())
+        .build(mock_context(noop_assets()))
+        .unwrap();
+    app.camera()
+        .register_source(Arc::new(MockCamera::new(SECOND_DEVICE_ID)));
+    app
+}
+
+/// Count the frames a consumer of `device_id` receives
+async fn count_frames(app: &tauri::App<MockRuntime>, device_id: &str) -> Arc<AtomicUsize> {
+    let frames = Arc::new(AtomicUsize::new(0));
+    let counter = frames.clone();
+    app.camera()
+        .register_consumer(
+            format!("count-{}", device_id),
+            device_id,
+            Box::new(move |_: &FrameEvent| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }),
+        )
+        .await
+        .unwrap();
+    frames
+}
+
+/// Whether `frames` keeps growing
+async fn receiving(frames: &AtomicUsize) -> bool {
+    let before = frames.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    frames.load(Ordering::Relaxed) > before
+}
+
+#[tokio::test]
+async fn test_two_devices_stream_and_stop_independently() {
+    let app = app();
+    let camera = app.camera();
+    let first = count_frames(&app, MOCK_DEVICE_ID).await;
+    let second = count_frames(&app, SECOND_DEVICE_ID).await;
+
+    let first_stream = camera.stream_id_by_device_id(MOCK_DEVICE_ID).await.unwrap();
+    let second_stream = camera
+        .stream_id_by_device_id(SECOND_DEVICE_ID)
+        .await
+        .unwrap();
+    assert_ne!(first_stream, second_stream);
+    assert!(receiving(&first).await);
+    assert!(receiving(&second).await);
+
+    camera.stop_streaming(first_stream).await.unwrap();
+    assert!(camera
+        .stream_id_by_device_id(MOCK_DEVICE_ID)
+        .await
+        .is_none());
+    assert!(!receiving(&first).await);
+    assert!(receiving(&second).await);
+
+    camera.stop_streaming(second_stream).await.unwrap();
+    assert!(!receiving(&second).await);
+}
+
+#[tokio::test]
+async fn test_switching_device_keeps_the_stream() {
+    let app = app();
+    let camera = app.camera();
+    let frames = count_frames(&app, MOCK_DEVICE_ID).await;
+    let stream_id = camera.stream_id_by_device_id(MOCK_DEVICE_ID).await.unwrap();
+
+    camera
+        .switch_stream_device(&stream_id, SECOND_DEVICE_ID.to_string())
+        .await
+        .unwrap();
+    assert!(camera
+        .stream_id_by_device_id(MOCK_DEVICE_ID)
+        .await
+        .is_none());
+    assert_eq!(
+        camera.stream_id_by_device_id(SECOND_DEVICE_ID).await,
+        Some(stream_id.clone())
+    );
+    assert!(receiving(&frames).await);
+
+    assert!(camera
+        .switch_stream_device(&stream_id, "missing".to_string())
+        .await
+        .is_err());
+    assert_eq!(
+        camera.stream_id_by_device_id(SECOND_DEVICE_ID).await,
+        Some(stream_id)
+    );
+    assert!(receiving(&frames).await);
+}
+
+#[tokio::test]
+async fn test_closing_a_connection_keeps_the_other_devices_streaming() {
+    let app = app();
+    let camera = app.camera();
+    let manager = &camera.webrtc_manager;
+
+    let mut connections = Vec::new();
+    for device_id in [MOCK_DEVICE_ID, SECOND_DEVICE_ID] {
+        let connection_id = manager.create_peer_connection(Vec::new()).await.unwrap();
+        manager
+            .register_device_for_connection(connection_id.clone(), device_id.to_string())
+            .await
+            .unwrap();
+        manager
+            .attach_h264_video_track(&connection_id)
+            .await
+            .unwrap();
+        let stream_id = camera
+            .start_streaming(device_id.to_string(), StreamOptions::default())
+            .await
+            .unwrap();
+        manager
+            .register_stream_for_connection(connection_id.clone(), stream_id)
+            .await
+            .unwrap();
+        camera
+            .connect_camera_to_webrtc(device_id.to_string(), connection_id.clone())
+            .await
+            .unwrap();
+        connections.push(connection_id);
+    }
+
+    camera.close_connection(&connections[0]).await.unwrap();
+    assert!(camera
+        .stream_id_by_device_id(MOCK_DEVICE_ID)
+        .await
+        .is_none());
+    assert!(manager.get_connection(&connections[0]).await.is_err());
+    assert!(camera
+        .stream_id_by_device_id(SECOND_DEVICE_ID)
+        .await
+        .is_some());
+    assert!(manager.get_connection(&connections[1]).await.is_ok());
+
+    camera.close_connection(&connections[1]).await.unwrap();
+    assert!(camera
+        .stream_id_by_device_id(SECOND_DEVICE_ID)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_a_stream_shared_by_two_connections_outlives_the_first() {
+    let app = app();
+    let camera = app.camera();
+    let manager = &camera.webrtc_manager;
+    let stream_id = camera
+        .start_streaming(MOCK_DEVICE_ID.to_string(), StreamOptions::default())
+        .await
+        .unwrap();
+
+    let mut connections = Vec::new();
+    for _ in 0..2 {
+        let connection_id = manager.create_peer_connection(Vec::new()).await.unwrap();
+        manager
+            .attach_h264_video_track(&connection_id)
+            .await
+            .unwrap();
+        manager
+            .register_stream_for_connection(connection_id.clone(), stream_id.clone())
+            .await
+            .unwrap();
+        camera
+            .connect_camera_to_webrtc(MOCK_DEVICE_ID.to_string(), connection_id.clone())
+            .await
+            .unwrap();
+        connections.push(connection_id);
+    }
+
+    camera.close_connection(&connections[0]).await.unwrap();
+    assert_eq!(
+        camera.stream_id_by_device_id(MOCK_DEVICE_ID).await,
+        Some(stream_id)
+    );
+    camera.close_connection(&connections[1]).await.unwrap();
+    assert!(camera
+        .stream_id_by_device_id(MOCK_DEVICE_ID)
+        .await
+        .is_none());
+}